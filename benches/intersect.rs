@@ -0,0 +1,99 @@
+#![allow(clippy::needless_return)]
+//Rays per second of the closest hit and occlusion queries the cpu tracer makes, for the linear scan, the binary bvh and the 4 and 8 wide bvhs.
+//Run with cargo bench --bench intersect, criterion keeps the previous run in target/criterion and reports regressions against it.
+//The scenes are the built in one, the golden test scenes and a generated scene with enough spheres for the trees to matter.
+
+use std::hint::black_box;
+
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+use criterion::Throughput;
+
+use mport::bvh::Bvh;
+use mport::bvh::Bvh4;
+use mport::bvh::Bvh8;
+use mport::material::Material;
+use mport::sampler::hash;
+use mport::scene::Scene;
+use mport::scene::Sphere;
+
+const WIDTH : u32 = 128;
+const HEIGHT : u32 = 72;
+const GENERATED_SPHERES : usize = 4096;
+
+fn random(state : &mut u32) -> f32{
+    *state = hash(*state);
+    return (*state >> 8) as f32 / (1 << 24) as f32;
+}
+///Small spheres scattered over the ground in front of the default camera.
+fn generated_scene() -> Scene{
+    let mut scene = Scene::default_scene();
+    scene.spheres.truncate(1);
+    let mut state = 1;
+    for _ in 0..GENERATED_SPHERES{
+        let center = Vector3::new(random(&mut state) * 20.0 - 10.0,random(&mut state) * 3.0,random(&mut state) * -20.0);
+        scene.spheres.push(Sphere{center, radius : 0.05 + random(&mut state) * 0.25, material : Material::lambert([0.5,0.5,0.5])});
+    }
+    return scene;
+}
+fn scenes() -> Vec<(String,Scene)>{
+    let mut scenes = vec!((String::from("default"),Scene::default_scene()));
+    for name in ["materials","lights","environment"]{
+        let path = format!("{}/tests/golden/scenes/{}.toml",env!("CARGO_MANIFEST_DIR"),name);
+        scenes.push((String::from(name),Scene::load(&path).expect("Failed to load a benchmark scene.")));
+    }
+    scenes.push((String::from("generated"),generated_scene()));
+    return scenes;
+}
+///A camera ray through the center of every pixel.
+fn camera_rays(scene : &Scene) -> Vec<(Vector3<f32>,Vector3<f32>)>{
+    let aspect = WIDTH as f32 / HEIGHT as f32;
+    let mut rays = vec!();
+    for y in 0..HEIGHT{
+        for x in 0..WIDTH{
+            let u = (x as f32 + 0.5) / WIDTH as f32 * 2.0 - 1.0;
+            let v = 1.0 - (y as f32 + 0.5) / HEIGHT as f32 * 2.0;
+            let ray = scene.camera.generate_ray(u, v, aspect, [0.5,0.5]);
+            rays.push((ray.origin,ray.direction));
+        }
+    }
+    return rays;
+}
+///From every camera hit towards a point above the scene, like the shadow rays of a light.
+fn shadow_rays(scene : &Scene , camera_rays : &[(Vector3<f32>,Vector3<f32>)]) -> Vec<(Vector3<f32>,Vector3<f32>,f32)>{
+    let light = Vector3::new(0.0,10.0,0.0);
+    return camera_rays.iter().filter_map(|&(origin,direction)| {
+        let (t,_) = scene.intersect(origin, direction)?;
+        let point = origin + direction * t;
+        let offset = light - point;
+        return Some((point,offset.normalize(),offset.magnitude()));
+    }).collect();
+}
+fn intersect(criterion : &mut Criterion){
+    for (name,scene) in scenes(){
+        let spheres = &scene.spheres;
+        let (binary,bvh4,bvh8) = (Bvh::new(spheres, 4),Bvh4::new(spheres),Bvh8::new(spheres));
+        let rays = camera_rays(&scene);
+        let mut group = criterion.benchmark_group(format!("closest hit/{}",name));
+        group.throughput(Throughput::Elements(rays.len() as u64));
+        group.bench_function("linear", |bencher| bencher.iter(|| rays.iter().filter_map(|&(origin,direction)| scene.intersect(black_box(origin), direction)).count()));
+        group.bench_function("binary", |bencher| bencher.iter(|| rays.iter().filter_map(|&(origin,direction)| binary.intersect(spheres, black_box(origin), direction)).count()));
+        group.bench_function("bvh4", |bencher| bencher.iter(|| rays.iter().filter_map(|&(origin,direction)| bvh4.intersect(black_box(origin), direction)).count()));
+        group.bench_function("bvh8", |bencher| bencher.iter(|| rays.iter().filter_map(|&(origin,direction)| bvh8.intersect(black_box(origin), direction)).count()));
+        group.finish();
+        let shadows = shadow_rays(&scene, &rays);
+        let mut group = criterion.benchmark_group(format!("occluded/{}",name));
+        group.throughput(Throughput::Elements(shadows.len() as u64));
+        group.bench_function("linear", |bencher| bencher.iter(|| shadows.iter().filter(|&&(origin,direction,distance)| scene.occluded(black_box(origin), direction, distance)).count()));
+        group.bench_function("binary", |bencher| bencher.iter(|| shadows.iter().filter(|&&(origin,direction,distance)| binary.occluded(spheres, black_box(origin), direction, distance)).count()));
+        group.bench_function("bvh4", |bencher| bencher.iter(|| shadows.iter().filter(|&&(origin,direction,distance)| bvh4.occluded(black_box(origin), direction, distance)).count()));
+        group.bench_function("bvh8", |bencher| bencher.iter(|| shadows.iter().filter(|&&(origin,direction,distance)| bvh8.occluded(black_box(origin), direction, distance)).count()));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, intersect);
+criterion_main!(benches);
@@ -0,0 +1,554 @@
+//Port of src/material, the bsdfs, the microfacet functions and the shading frame match their rust counterparts.
+//Directions are in the local shading frame where the normal is +z and point away from the surface.
+
+#define PI 3.14159265358979
+#define BSDF_LAMBERT 0u
+#define BSDF_CONDUCTOR 1u
+#define BSDF_DIELECTRIC 2u
+#define BSDF_THIN_DIELECTRIC 3u
+#define BSDF_PLASTIC 4u
+#define BSDF_PRINCIPLED 5u
+//Below this roughness dielectrics are treated as perfectly smooth, other lobes clamp to it to stay finite.
+#define MIN_ALPHA 1e-3
+
+//A material with its textures evaluated at one point, color is the albedo, f0, tint or base color depending on the kind.
+struct Bsdf{
+    uint kind;
+    vec3 color;
+    float alpha;
+    float eta;
+    float metallic;
+    float specular;
+    float transmission;
+};
+//A direction chosen by bsdf_sample, weight is f * |cos| / pdf, valid is false where the rust code returns None.
+struct BsdfSample{
+    bool valid;
+    vec3 wi;
+    vec3 weight;
+    float pdf;
+    bool delta;
+};
+struct Frame{
+    vec3 tangent;
+    vec3 bitangent;
+    vec3 normal;
+};
+
+//Duff et al., Building an Orthonormal Basis, Revisited, 2017.
+Frame frame_new(vec3 normal){
+    float s = (floatBitsToUint(normal.z) & 0x80000000u) != 0u ? -1.0 : 1.0;
+    float a = -1.0 / (s + normal.z);
+    float b = normal.x * normal.y * a;
+    Frame frame;
+    frame.tangent = vec3(1.0 + s * normal.x * normal.x * a, s * b, -s * normal.x);
+    frame.bitangent = vec3(b, s + normal.y * normal.y * a, -normal.y);
+    frame.normal = normal;
+    return frame;
+}
+vec3 frame_to_local(Frame frame , vec3 v){
+    return vec3(dot(v, frame.tangent), dot(v, frame.bitangent), dot(v, frame.normal));
+}
+vec3 frame_to_world(Frame frame , vec3 v){
+    return frame.tangent * v.x + frame.bitangent * v.y + frame.normal * v.z;
+}
+
+vec3 microfacet_reflect(vec3 wo , vec3 normal){
+    return -wo + normal * (2.0 * dot(wo, normal));
+}
+//The refracted direction and the relative eta seen from the side of wo, false on total internal reflection.
+bool microfacet_refract(vec3 wo , vec3 normal , float eta , out vec3 wi , out float etap){
+    float cos_i = dot(wo, normal);
+    vec3 n = normal;
+    float e = eta;
+    if(cos_i < 0.0){
+        e = 1.0 / e;
+        cos_i = -cos_i;
+        n = -n;
+    }
+    float sin2_i = max(1.0 - cos_i * cos_i, 0.0);
+    float sin2_t = sin2_i / (e * e);
+    wi = vec3(0.0);
+    etap = e;
+    if(sin2_t >= 1.0){
+        return false;
+    }
+    float cos_t = sqrt(1.0 - sin2_t);
+    wi = -wo / e + n * (cos_i / e - cos_t);
+    return true;
+}
+float fresnel_dielectric(float cos_i , float eta){
+    float c = clamp(cos_i, -1.0, 1.0);
+    float e = eta;
+    if(c < 0.0){
+        e = 1.0 / e;
+        c = -c;
+    }
+    float sin2_t = (1.0 - c * c) / (e * e);
+    if(sin2_t >= 1.0){
+        return 1.0;
+    }
+    float cos_t = sqrt(max(1.0 - sin2_t, 0.0));
+    float parallel = (e * c - cos_t) / (e * c + cos_t);
+    float perpendicular = (c - e * cos_t) / (c + e * cos_t);
+    return (parallel * parallel + perpendicular * perpendicular) / 2.0;
+}
+float pow5(float x){
+    float x2 = x * x;
+    return x2 * x2 * x;
+}
+vec3 fresnel_schlick(vec3 f0 , float cos_i){
+    float weight = pow5(clamp(1.0 - abs(cos_i), 0.0, 1.0));
+    return f0 + (vec3(1.0) - f0) * weight;
+}
+float microfacet_reflectance(float eta){
+    float r = (eta - 1.0) / (eta + 1.0);
+    return r * r;
+}
+float microfacet_distribution(vec3 normal , float alpha){
+    float alpha2 = alpha * alpha;
+    float denominator = normal.z * normal.z * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * denominator * denominator);
+}
+//Infinite at the horizon, the callers return zero masking there instead.
+float microfacet_lambda(vec3 w , float alpha){
+    float cos2 = w.z * w.z;
+    float tan2 = max(1.0 - cos2, 0.0) / cos2;
+    return (sqrt(1.0 + alpha * alpha * tan2) - 1.0) / 2.0;
+}
+//Smith masking of a single direction.
+float microfacet_masking(vec3 w , float alpha){
+    if(w.z * w.z <= 0.0){
+        return 0.0;
+    }
+    return 1.0 / (1.0 + microfacet_lambda(w, alpha));
+}
+//Height correlated Smith masking and shadowing.
+float microfacet_masking_shadowing(vec3 wo , vec3 wi , float alpha){
+    if(wo.z * wo.z <= 0.0 || wi.z * wi.z <= 0.0){
+        return 0.0;
+    }
+    return 1.0 / (1.0 + microfacet_lambda(wo, alpha) + microfacet_lambda(wi, alpha));
+}
+//Heitz, Sampling the GGX Distribution of Visible Normals, 2018.
+vec3 sample_visible_normal(vec3 wo , float alpha , vec2 u){
+    vec3 wh = normalize(vec3(alpha * wo.x, alpha * wo.y, wo.z));
+    if(wh.z < 0.0){
+        wh = -wh;
+    }
+    vec3 t1 = wh.z < 0.99999 ? normalize(cross(vec3(0.0, 0.0, 1.0), wh)) : vec3(1.0, 0.0, 0.0);
+    vec3 t2 = cross(wh, t1);
+    float radius = sqrt(u.x);
+    float phi = 2.0 * PI * u.y;
+    float px = radius * cos(phi);
+    float py = radius * sin(phi);
+    float s = (1.0 + wh.z) / 2.0;
+    py = (1.0 - s) * sqrt(max(1.0 - px * px, 0.0)) + s * py;
+    float pz = sqrt(max(1.0 - px * px - py * py, 0.0));
+    vec3 normal = t1 * px + t2 * py + wh * pz;
+    return normalize(vec3(alpha * normal.x, alpha * normal.y, max(normal.z, 1e-6)));
+}
+float visible_normal_pdf(vec3 wo , vec3 normal , float alpha){
+    if(wo.z == 0.0){
+        return 0.0;
+    }
+    return microfacet_masking(wo, alpha) / abs(wo.z) * microfacet_distribution(normal, alpha) * abs(dot(wo, normal));
+}
+
+bool same_hemisphere(vec3 a , vec3 b){
+    return a.z * b.z > 0.0;
+}
+vec3 flip_z(vec3 w){
+    return vec3(w.x, w.y, -w.z);
+}
+//The microfacet normal that turns wo into wi, facing +z, false for degenerate or back facing configurations.
+bool generalized_half(vec3 wo , vec3 wi , float etap , out vec3 half_vector){
+    vec3 h = wi * etap + wo;
+    half_vector = vec3(0.0, 0.0, 1.0);
+    if(dot(h, h) == 0.0){
+        return false;
+    }
+    h = normalize(h);
+    if(h.z < 0.0){
+        h = -h;
+    }
+    if(dot(h, wi) * wi.z < 0.0 || dot(h, wo) * wo.z < 0.0){
+        return false;
+    }
+    half_vector = h;
+    return true;
+}
+float reflection_pdf(vec3 wo , vec3 wi , float alpha){
+    vec3 h = normalize(wo + wi);
+    return visible_normal_pdf(wo, h, alpha) / (4.0 * abs(dot(wo, h)));
+}
+vec3 cosine_hemisphere(vec2 u){
+    float radius = sqrt(u.x);
+    float phi = 2.0 * PI * u.y;
+    return vec3(radius * cos(phi), radius * sin(phi), sqrt(max(1.0 - u.x, 0.0)));
+}
+//Ashikhmin and Shirley's diffuse term, it only scatters what the specular layer lets through at both directions.
+float plastic_diffuse(vec3 wo , vec3 wi , float eta , float specular){
+    float rs = min(microfacet_reflectance(eta) * specular, 1.0);
+    float through_o = 1.0 - pow5(1.0 - wo.z / 2.0);
+    float through_i = 1.0 - pow5(1.0 - wi.z / 2.0);
+    return 28.0 / (23.0 * PI) * (1.0 - rs) * through_o * through_i;
+}
+float plastic_specular_probability(vec3 wo , vec3 color , float eta , float specular){
+    float s = min(fresnel_dielectric(wo.z, eta) * specular, 1.0);
+    float diffuse = (color.x + color.y + color.z) / 3.0 * (1.0 - s);
+    if(diffuse <= 0.0){
+        return 1.0;
+    }
+    return max(s / (s + diffuse), 0.1);
+}
+
+//The weighted lobes of the principled bsdf, the weights sum to one, other kinds are their own first lobe.
+Bsdf bsdf_lobe(Bsdf bsdf , uint lobe , out float weight){
+    if(bsdf.kind != BSDF_PRINCIPLED){
+        weight = lobe == 0u ? 1.0 : 0.0;
+        return bsdf;
+    }
+    float metallic = clamp(bsdf.metallic, 0.0, 1.0);
+    float transmission = clamp(bsdf.transmission, 0.0, 1.0);
+    Bsdf result = bsdf;
+    if(lobe == 0u){
+        weight = metallic;
+        result.kind = BSDF_CONDUCTOR;
+    } else if(lobe == 1u){
+        weight = (1.0 - metallic) * transmission;
+        result.kind = BSDF_DIELECTRIC;
+    } else{
+        weight = (1.0 - metallic) * (1.0 - transmission);
+        result.kind = BSDF_PLASTIC;
+    }
+    return result;
+}
+//The value of a bsdf that is not principled without the cosine term.
+vec3 lobe_eval(Bsdf bsdf , vec3 wo , vec3 wi){
+    if(wo.z == 0.0 || wi.z == 0.0){
+        return vec3(0.0);
+    }
+    if(bsdf.kind == BSDF_LAMBERT){
+        if(!same_hemisphere(wo, wi)){
+            return vec3(0.0);
+        }
+        return bsdf.color / PI;
+    }
+    if(bsdf.kind == BSDF_CONDUCTOR){
+        if(!same_hemisphere(wo, wi)){
+            return vec3(0.0);
+        }
+        vec3 o = wo.z < 0.0 ? flip_z(wo) : wo;
+        vec3 i = wo.z < 0.0 ? flip_z(wi) : wi;
+        float alpha = max(bsdf.alpha, MIN_ALPHA);
+        vec3 h = normalize(o + i);
+        vec3 fresnel = fresnel_schlick(bsdf.color, dot(o, h));
+        return fresnel * (microfacet_distribution(h, alpha) * microfacet_masking_shadowing(o, i, alpha) / (4.0 * o.z * i.z));
+    }
+    if(bsdf.kind == BSDF_DIELECTRIC){
+        if(bsdf.alpha < MIN_ALPHA){
+            return vec3(0.0);
+        }
+        bool reflection = same_hemisphere(wo, wi);
+        float etap = reflection ? 1.0 : (wo.z > 0.0 ? bsdf.eta : 1.0 / bsdf.eta);
+        vec3 h;
+        if(!generalized_half(wo, wi, etap, h)){
+            return vec3(0.0);
+        }
+        float fresnel = fresnel_dielectric(dot(wo, h), bsdf.eta);
+        float d = microfacet_distribution(h, bsdf.alpha);
+        float g = microfacet_masking_shadowing(wo, wi, bsdf.alpha);
+        if(reflection){
+            return vec3(d * g * fresnel / abs(4.0 * wo.z * wi.z));
+        }
+        float denominator_root = dot(wi, h) + dot(wo, h) / etap;
+        float denominator = denominator_root * denominator_root * wi.z * wo.z;
+        float f = d * (1.0 - fresnel) * g * abs(dot(wi, h) * dot(wo, h) / denominator) / (etap * etap);
+        return bsdf.color * f;
+    }
+    if(bsdf.kind == BSDF_PLASTIC){
+        if(!same_hemisphere(wo, wi)){
+            return vec3(0.0);
+        }
+        vec3 o = wo.z < 0.0 ? flip_z(wo) : wo;
+        vec3 i = wo.z < 0.0 ? flip_z(wi) : wi;
+        float alpha = max(bsdf.alpha, MIN_ALPHA);
+        vec3 h = normalize(o + i);
+        float fresnel = min(fresnel_dielectric(dot(o, h), bsdf.eta) * bsdf.specular, 1.0);
+        float glossy = microfacet_distribution(h, alpha) * microfacet_masking_shadowing(o, i, alpha) * fresnel / (4.0 * o.z * i.z);
+        return vec3(glossy) + bsdf.color * plastic_diffuse(o, i, bsdf.eta, bsdf.specular);
+    }
+    //Thin dielectrics only scatter in dirac directions.
+    return vec3(0.0);
+}
+float lobe_pdf(Bsdf bsdf , vec3 wo , vec3 wi){
+    if(wo.z == 0.0 || wi.z == 0.0){
+        return 0.0;
+    }
+    if(bsdf.kind == BSDF_LAMBERT){
+        if(!same_hemisphere(wo, wi)){
+            return 0.0;
+        }
+        return abs(wi.z) / PI;
+    }
+    if(bsdf.kind == BSDF_CONDUCTOR){
+        if(!same_hemisphere(wo, wi)){
+            return 0.0;
+        }
+        vec3 o = wo.z < 0.0 ? flip_z(wo) : wo;
+        vec3 i = wo.z < 0.0 ? flip_z(wi) : wi;
+        return reflection_pdf(o, i, max(bsdf.alpha, MIN_ALPHA));
+    }
+    if(bsdf.kind == BSDF_DIELECTRIC){
+        if(bsdf.alpha < MIN_ALPHA){
+            return 0.0;
+        }
+        bool reflection = same_hemisphere(wo, wi);
+        float etap = reflection ? 1.0 : (wo.z > 0.0 ? bsdf.eta : 1.0 / bsdf.eta);
+        vec3 h;
+        if(!generalized_half(wo, wi, etap, h)){
+            return 0.0;
+        }
+        float fresnel = fresnel_dielectric(dot(wo, h), bsdf.eta);
+        if(reflection){
+            return visible_normal_pdf(wo, h, bsdf.alpha) / (4.0 * abs(dot(wo, h))) * fresnel;
+        }
+        float denominator_root = dot(wi, h) + dot(wo, h) / etap;
+        float jacobian = abs(dot(wi, h)) / (denominator_root * denominator_root);
+        return visible_normal_pdf(wo, h, bsdf.alpha) * jacobian * (1.0 - fresnel);
+    }
+    if(bsdf.kind == BSDF_PLASTIC){
+        if(!same_hemisphere(wo, wi)){
+            return 0.0;
+        }
+        vec3 o = wo.z < 0.0 ? flip_z(wo) : wo;
+        vec3 i = wo.z < 0.0 ? flip_z(wi) : wi;
+        float probability = plastic_specular_probability(o, bsdf.color, bsdf.eta, bsdf.specular);
+        return probability * reflection_pdf(o, i, max(bsdf.alpha, MIN_ALPHA)) + (1.0 - probability) * i.z / PI;
+    }
+    return 0.0;
+}
+vec3 lobe_eval_diffuse(Bsdf bsdf , vec3 wo , vec3 wi){
+    if(wo.z == 0.0 || wi.z == 0.0 || !same_hemisphere(wo, wi)){
+        return vec3(0.0);
+    }
+    if(bsdf.kind == BSDF_LAMBERT){
+        return bsdf.color / PI;
+    }
+    if(bsdf.kind == BSDF_PLASTIC){
+        vec3 o = wo.z < 0.0 ? flip_z(wo) : wo;
+        vec3 i = wo.z < 0.0 ? flip_z(wi) : wi;
+        return bsdf.color * plastic_diffuse(o, i, bsdf.eta, bsdf.specular);
+    }
+    return vec3(0.0);
+}
+BsdfSample invalid_sample(){
+    BsdfSample sample_;
+    sample_.valid = false;
+    sample_.wi = vec3(0.0, 0.0, 1.0);
+    sample_.weight = vec3(0.0);
+    sample_.pdf = 0.0;
+    sample_.delta = false;
+    return sample_;
+}
+BsdfSample make_sample(vec3 wi , vec3 weight , float pdf , bool delta){
+    BsdfSample sample_;
+    sample_.valid = true;
+    sample_.wi = wi;
+    sample_.weight = weight;
+    sample_.pdf = pdf;
+    sample_.delta = delta;
+    return sample_;
+}
+BsdfSample lobe_sample(Bsdf bsdf , vec3 wo , vec3 u){
+    if(wo.z == 0.0){
+        return invalid_sample();
+    }
+    if(bsdf.kind == BSDF_LAMBERT){
+        vec3 wi = cosine_hemisphere(u.yz);
+        if(wo.z < 0.0){
+            wi.z = -wi.z;
+        }
+        if(wi.z == 0.0){
+            return invalid_sample();
+        }
+        return make_sample(wi, bsdf.color, abs(wi.z) / PI, false);
+    }
+    if(bsdf.kind == BSDF_CONDUCTOR){
+        float alpha = max(bsdf.alpha, MIN_ALPHA);
+        bool flip = wo.z < 0.0;
+        vec3 o = flip ? flip_z(wo) : wo;
+        vec3 h = sample_visible_normal(o, alpha, u.yz);
+        vec3 wi = microfacet_reflect(o, h);
+        if(wi.z <= 0.0){
+            return invalid_sample();
+        }
+        vec3 weight = fresnel_schlick(bsdf.color, dot(o, h)) * (microfacet_masking_shadowing(o, wi, alpha) / microfacet_masking(o, alpha));
+        return make_sample(flip ? flip_z(wi) : wi, weight, reflection_pdf(o, wi, alpha), false);
+    }
+    if(bsdf.kind == BSDF_DIELECTRIC){
+        if(bsdf.alpha < MIN_ALPHA){
+            float reflectance = fresnel_dielectric(wo.z, bsdf.eta);
+            if(u.x < reflectance){
+                return make_sample(vec3(-wo.x, -wo.y, wo.z), vec3(1.0), reflectance, true);
+            }
+            vec3 wi;
+            float etap;
+            if(!microfacet_refract(wo, vec3(0.0, 0.0, 1.0), bsdf.eta, wi, etap)){
+                return invalid_sample();
+            }
+            return make_sample(wi, bsdf.color / (etap * etap), 1.0 - reflectance, true);
+        }
+        vec3 h = sample_visible_normal(wo, bsdf.alpha, u.yz);
+        float reflectance = fresnel_dielectric(dot(wo, h), bsdf.eta);
+        vec3 wi;
+        if(u.x < reflectance){
+            wi = microfacet_reflect(wo, h);
+            if(!same_hemisphere(wo, wi)){
+                return invalid_sample();
+            }
+        } else{
+            float etap;
+            if(!microfacet_refract(wo, h, bsdf.eta, wi, etap)){
+                return invalid_sample();
+            }
+            if(same_hemisphere(wo, wi) || wi.z == 0.0){
+                return invalid_sample();
+            }
+        }
+        float pdf = lobe_pdf(bsdf, wo, wi);
+        if(pdf <= 0.0){
+            return invalid_sample();
+        }
+        return make_sample(wi, lobe_eval(bsdf, wo, wi) * (abs(wi.z) / pdf), pdf, false);
+    }
+    if(bsdf.kind == BSDF_THIN_DIELECTRIC){
+        float reflectance = fresnel_dielectric(abs(wo.z), bsdf.eta);
+        if(reflectance < 1.0){
+            float transmittance = 1.0 - reflectance;
+            reflectance += transmittance * transmittance * reflectance / (1.0 - reflectance * reflectance);
+        }
+        if(u.x < reflectance){
+            return make_sample(vec3(-wo.x, -wo.y, wo.z), vec3(1.0), reflectance, true);
+        }
+        return make_sample(-wo, bsdf.color, 1.0 - reflectance, true);
+    }
+    if(bsdf.kind == BSDF_PLASTIC){
+        bool flip = wo.z < 0.0;
+        vec3 o = flip ? flip_z(wo) : wo;
+        vec3 wi;
+        if(u.x < plastic_specular_probability(o, bsdf.color, bsdf.eta, bsdf.specular)){
+            wi = microfacet_reflect(o, sample_visible_normal(o, max(bsdf.alpha, MIN_ALPHA), u.yz));
+        } else{
+            wi = cosine_hemisphere(u.yz);
+        }
+        if(wi.z <= 0.0){
+            return invalid_sample();
+        }
+        float pdf = lobe_pdf(bsdf, o, wi);
+        if(pdf <= 0.0){
+            return invalid_sample();
+        }
+        return make_sample(flip ? flip_z(wi) : wi, lobe_eval(bsdf, o, wi) * (wi.z / pdf), pdf, false);
+    }
+    return invalid_sample();
+}
+
+//The value of the bsdf without the cosine term.
+vec3 bsdf_eval(Bsdf bsdf , vec3 wo , vec3 wi){
+    if(bsdf.kind != BSDF_PRINCIPLED){
+        return lobe_eval(bsdf, wo, wi);
+    }
+    if(wo.z == 0.0 || wi.z == 0.0){
+        return vec3(0.0);
+    }
+    vec3 sum = vec3(0.0);
+    for(uint i = 0u; i < 3u; i++){
+        float weight;
+        Bsdf lobe = bsdf_lobe(bsdf, i, weight);
+        if(weight > 0.0){
+            sum = sum + lobe_eval(lobe, wo, wi) * weight;
+        }
+    }
+    return sum;
+}
+//The density of bsdf_sample with respect to solid angle, zero for dirac lobes.
+float bsdf_pdf(Bsdf bsdf , vec3 wo , vec3 wi){
+    if(bsdf.kind != BSDF_PRINCIPLED){
+        return lobe_pdf(bsdf, wo, wi);
+    }
+    if(wo.z == 0.0 || wi.z == 0.0){
+        return 0.0;
+    }
+    float sum = 0.0;
+    for(uint i = 0u; i < 3u; i++){
+        float weight;
+        Bsdf lobe = bsdf_lobe(bsdf, i, weight);
+        sum += weight > 0.0 ? lobe_pdf(lobe, wo, wi) * weight : 0.0;
+    }
+    return sum;
+}
+//Importance samples an incoming direction, u holds three uniform numbers in [0,1).
+BsdfSample bsdf_sample(Bsdf bsdf , vec3 wo , vec3 u){
+    if(bsdf.kind != BSDF_PRINCIPLED){
+        return lobe_sample(bsdf, wo, u);
+    }
+    if(wo.z == 0.0){
+        return invalid_sample();
+    }
+    float u0 = u.x;
+    bool chosen = false;
+    float chosen_weight = 0.0;
+    Bsdf chosen_lobe = bsdf;
+    for(uint i = 0u; i < 3u; i++){
+        float weight;
+        Bsdf lobe = bsdf_lobe(bsdf, i, weight);
+        if(weight <= 0.0){
+            continue;
+        }
+        chosen = true;
+        chosen_weight = weight;
+        chosen_lobe = lobe;
+        if(u0 < weight){
+            u0 /= weight;
+            break;
+        }
+        u0 -= weight;
+    }
+    if(!chosen){
+        return invalid_sample();
+    }
+    BsdfSample sample_ = lobe_sample(chosen_lobe, wo, vec3(min(u0, 0.99999994), u.y, u.z));
+    if(!sample_.valid){
+        return sample_;
+    }
+    if(sample_.delta){
+        sample_.pdf *= chosen_weight;
+        return sample_;
+    }
+    float pdf = bsdf_pdf(bsdf, wo, sample_.wi);
+    if(pdf <= 0.0){
+        return invalid_sample();
+    }
+    sample_.weight = bsdf_eval(bsdf, wo, sample_.wi) * (abs(sample_.wi.z) / pdf);
+    sample_.pdf = pdf;
+    return sample_;
+}
+//The share of bsdf_eval coming from the diffuse lobes per channel, the rest is specular.
+vec3 bsdf_diffuse_fraction(Bsdf bsdf , vec3 wo , vec3 wi){
+    vec3 f = bsdf_eval(bsdf, wo, wi);
+    vec3 diffuse = vec3(0.0);
+    if(bsdf.kind != BSDF_PRINCIPLED){
+        diffuse = lobe_eval_diffuse(bsdf, wo, wi);
+    } else if(wo.z != 0.0 && wi.z != 0.0 && same_hemisphere(wo, wi)){
+        for(uint i = 0u; i < 3u; i++){
+            float weight;
+            Bsdf lobe = bsdf_lobe(bsdf, i, weight);
+            if(weight > 0.0){
+                diffuse = diffuse + lobe_eval_diffuse(lobe, wo, wi) * weight;
+            }
+        }
+    }
+    return vec3(f.x > 0.0 ? min(diffuse.x / f.x, 1.0) : 0.0, f.y > 0.0 ? min(diffuse.y / f.y, 1.0) : 0.0, f.z > 0.0 ? min(diffuse.z / f.z, 1.0) : 0.0);
+}
@@ -0,0 +1,280 @@
+//The light sampler of src/light on the gpu, emitters are chosen with the cdf of LightSampler and sampled the way it samples them.
+//Included by trace.glsl once the scene, its textures and the intersection routines are declared.
+
+#define EMITTER_POINT 0u
+#define EMITTER_SPOT 1u
+#define EMITTER_DIRECTIONAL 2u
+#define EMITTER_SPHERE 3u
+#define EMITTER_ENVIRONMENT 4u
+//light::sampler::SMALL_CONE, below this squared sine 1 - cos is computed from the sine.
+#define SMALL_CONE 0.00068523
+#define INFINITY uintBitsToFloat(0x7f800000u)
+//f32::EPSILON, Distribution1D::sample stays below one by this much.
+#define DISTRIBUTION_EPSILON 1.1920929e-7
+
+//LightSample, valid is false where the rust code returns None.
+struct LightSample{
+    bool valid;
+    vec3 direction;
+    float distance;
+    vec3 radiance;
+    float pdf;
+    bool delta;
+};
+LightSample no_light(){
+    LightSample light;
+    light.valid = false;
+    light.direction = vec3(0.0);
+    light.distance = 0.0;
+    light.radiance = vec3(0.0);
+    light.pdf = 0.0;
+    light.delta = false;
+    return light;
+}
+
+//The distributions of the environment map are stored as the amount of buckets and the integral, then the function and the cdf.
+uint distribution_count(uint base){
+    return environment_distribution[base];
+}
+float distribution_value(uint index){
+    return uintBitsToFloat(environment_distribution[index]);
+}
+//Distribution1D::pdf.
+float distribution_pdf(uint base , uint index){
+    float integral = distribution_value(base + 1u);
+    if(integral <= 0.0){
+        return 1.0;
+    }
+    return max(distribution_value(base + 2u + index), 0.0) / integral;
+}
+//Distribution1D::sample, returns the position in [0,1) with its density and bucket.
+float distribution_sample(uint base , float u , out float pdf , out uint index){
+    uint count = distribution_count(base);
+    uint cdf = base + 2u + count;
+    //The first cdf entry above u.
+    uint low = 0u;
+    uint high = count + 1u;
+    while(low < high){
+        uint middle = (low + high) / 2u;
+        if(distribution_value(cdf + middle) <= u){
+            low = middle + 1u;
+        } else{
+            high = middle;
+        }
+    }
+    index = clamp(low, 1u, count) - 1u;
+    float start = distribution_value(cdf + index);
+    float width = distribution_value(cdf + index + 1u) - start;
+    float offset = width > 0.0 ? (u - start) / width : 0.0;
+    pdf = distribution_pdf(base, index);
+    return min((float(index) + clamp(offset, 0.0, 1.0)) / float(count), 1.0 - DISTRIBUTION_EPSILON);
+}
+//Where the distribution of the row of the environment map starts, after the distribution of the rows.
+uint environment_row(uint y){
+    uint rows = distribution_count(0u);
+    uint width = distribution_count(2u * rows + 3u);
+    return 2u * rows + 3u + y * (2u * width + 3u);
+}
+//Environment::uv.
+vec2 environment_uv(vec3 direction){
+    float phi = mod(atan(direction.z, direction.x) - parameters.environment.x, 2.0 * PI);
+    float theta = acos(clamp(direction.y, -1.0, 1.0));
+    return vec2(phi / (2.0 * PI), theta / PI);
+}
+//Environment::texel.
+ivec2 environment_texel(vec2 uv){
+    ivec2 size = textureSize(sampler2D(environment_map, material_sampler), 0);
+    return min(ivec2(uv * vec2(size)), size - 1);
+}
+//Scene::background, the environment map is read without filtering like Environment::radiance.
+vec3 scene_background(vec3 direction){
+    if(parameters.environment.z == 0.0){
+        return parameters.sky.xyz;
+    }
+    ivec2 texel = environment_texel(environment_uv(direction));
+    return texelFetch(sampler2D(environment_map, material_sampler), texel, 0).xyz * parameters.environment.y;
+}
+//Environment::pdf.
+float environment_map_pdf(vec3 direction){
+    vec2 uv = environment_uv(direction);
+    float sin_theta = sin(uv.y * PI);
+    if(sin_theta <= 0.0){
+        return 0.0;
+    }
+    ivec2 texel = environment_texel(uv);
+    return distribution_pdf(0u, uint(texel.y)) * distribution_pdf(environment_row(uint(texel.y)), uint(texel.x)) / (2.0 * PI * PI * sin_theta);
+}
+
+//The probability of choosing the emitter, its cdf minus the one before.
+float emitter_probability(uint index){
+    return emitters[index].position_cdf.w - (index == 0u ? 0.0 : emitters[index - 1u].position_cdf.w);
+}
+//LightSampler::choose, false when no emitter has any power.
+bool choose_emitter(float u , out uint index , out float probability){
+    index = 0u;
+    probability = 0.0;
+    for(uint i = 0u; i < parameters.emitter_count; i++){
+        if(u < emitters[i].position_cdf.w){
+            index = i;
+            probability = emitter_probability(i);
+            return true;
+        }
+    }
+    for(uint i = parameters.emitter_count; i > 0u; i--){
+        if(emitters[i - 1u].position_cdf.w > 0.0){
+            index = i - 1u;
+            probability = emitter_probability(index);
+            return true;
+        }
+    }
+    return false;
+}
+//light::sampler::sphere_cone, false for points inside the sphere.
+bool sphere_cone(Sphere sphere , vec3 point , out float one_minus_cos_max , out vec3 axis){
+    vec3 offset = sphere.center_radius.xyz - point;
+    float distance2 = dot(offset, offset);
+    float radius2 = sphere.center_radius.w * sphere.center_radius.w;
+    one_minus_cos_max = 0.0;
+    axis = vec3(0.0, 0.0, 1.0);
+    if(distance2 <= radius2){
+        return false;
+    }
+    float sin2_max = radius2 / distance2;
+    one_minus_cos_max = sin2_max < SMALL_CONE ? sin2_max / 2.0 : 1.0 - sqrt(1.0 - sin2_max);
+    axis = offset / sqrt(distance2);
+    return true;
+}
+//light::smoothstep, a step where the edges meet.
+float light_smoothstep(float edge0 , float edge1 , float x){
+    if(edge0 == edge1){
+        return x >= edge0 ? 1.0 : 0.0;
+    }
+    float t = clamp((x - edge0) / (edge1 - edge0), 0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+//LightSampler::sample and Light::sample.
+LightSample sample_emitter(uint index , vec3 point , vec2 u){
+    Emitter emitter = emitters[index];
+    uint kind = emitter.ids.x;
+    LightSample light = no_light();
+    if(kind == EMITTER_POINT || kind == EMITTER_SPOT){
+        vec3 offset = emitter.position_cdf.xyz - point;
+        float distance = length(offset);
+        if(distance <= 0.0){
+            return light;
+        }
+        light.direction = offset / distance;
+        light.distance = distance;
+        float falloff = 1.0;
+        if(kind == EMITTER_SPOT){
+            falloff = light_smoothstep(emitter.intensity_cos_outer.w, emitter.direction_cos_inner.w, dot(-light.direction, emitter.direction_cos_inner.xyz));
+            if(falloff <= 0.0){
+                return light;
+            }
+        }
+        light.radiance = emitter.intensity_cos_outer.xyz * (falloff / (distance * distance));
+        light.pdf = 1.0;
+        light.delta = true;
+        light.valid = true;
+        return light;
+    }
+    if(kind == EMITTER_DIRECTIONAL){
+        light.direction = -normalize(emitter.direction_cos_inner.xyz);
+        light.distance = INFINITY;
+        light.radiance = emitter.intensity_cos_outer.xyz;
+        light.pdf = 1.0;
+        light.delta = true;
+        light.valid = true;
+        return light;
+    }
+    if(kind == EMITTER_SPHERE){
+        Sphere sphere = spheres[emitter.ids.y];
+        float one_minus_cos_max;
+        vec3 axis;
+        if(!sphere_cone(sphere, point, one_minus_cos_max, axis)){
+            return light;
+        }
+        float one_minus_cos = u.x * one_minus_cos_max;
+        float cos_theta = 1.0 - one_minus_cos;
+        float sin_theta = sqrt(max(one_minus_cos * (2.0 - one_minus_cos), 0.0));
+        float phi = 2.0 * PI * u.y;
+        vec3 direction = normalize(frame_to_world(frame_new(axis), vec3(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta)));
+        float distance = sphere_intersect(sphere, point, direction);
+        if(distance <= 0.0){
+            return light;
+        }
+        SurfaceUv surface;
+        surface.uv = sphere_uv(normalize(point + direction * distance - sphere.center_radius.xyz));
+        surface.dx = vec2(0.0);
+        surface.dy = vec2(0.0);
+        light.direction = direction;
+        light.distance = distance;
+        light.radiance = texture_eval(materials[sphere.ids.x].emission, surface);
+        light.pdf = 1.0 / (2.0 * PI * one_minus_cos_max);
+        light.valid = true;
+        return light;
+    }
+    if(parameters.environment.z != 0.0){
+        //Environment::sample.
+        float row_pdf;
+        float column_pdf;
+        uint row;
+        uint column;
+        float v = distribution_sample(0u, u.x, row_pdf, row);
+        float x = distribution_sample(environment_row(row), u.y, column_pdf, column);
+        float theta = v * PI;
+        float sin_theta = sin(theta);
+        if(sin_theta <= 0.0){
+            return light;
+        }
+        float phi = x * 2.0 * PI + parameters.environment.x;
+        light.direction = vec3(sin_theta * cos(phi), cos(theta), sin_theta * sin(phi));
+        light.distance = INFINITY;
+        light.radiance = scene_background(light.direction);
+        light.pdf = row_pdf * column_pdf / (2.0 * PI * PI * sin_theta);
+        light.valid = true;
+        return light;
+    }
+    float z = 1.0 - 2.0 * u.x;
+    float radius = sqrt(max(1.0 - z * z, 0.0));
+    float phi = 2.0 * PI * u.y;
+    light.direction = vec3(radius * cos(phi), z, radius * sin(phi));
+    light.distance = INFINITY;
+    light.radiance = parameters.sky.xyz;
+    light.pdf = 1.0 / (4.0 * PI);
+    light.valid = true;
+    return light;
+}
+//LightSampler::sphere_pdf, the density of choosing and sampling the sphere from the point.
+float sphere_light_pdf(uint sphere_index , vec3 point){
+    Sphere sphere = spheres[sphere_index];
+    if(sphere.ids.y == 0u){
+        return 0.0;
+    }
+    float one_minus_cos_max;
+    vec3 axis;
+    if(!sphere_cone(sphere, point, one_minus_cos_max, axis)){
+        return 0.0;
+    }
+    return emitter_probability(sphere.ids.y - 1u) / (2.0 * PI * one_minus_cos_max);
+}
+//LightSampler::environment_pdf, the density of choosing and sampling the sky or environment map in the direction.
+float environment_light_pdf(vec3 direction){
+    if(parameters.environment_emitter == 0u){
+        return 0.0;
+    }
+    float probability = emitter_probability(parameters.environment_emitter - 1u);
+    if(parameters.environment.z != 0.0){
+        return probability * environment_map_pdf(direction);
+    }
+    return probability / (4.0 * PI);
+}
+//Veach's power heuristic with an exponent of two.
+float power_heuristic(float pdf , float other_pdf){
+    float a = pdf * pdf;
+    float b = other_pdf * other_pdf;
+    if(a + b <= 0.0){
+        return 0.0;
+    }
+    return a / (a + b);
+}
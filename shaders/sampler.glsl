@@ -0,0 +1,166 @@
+//Port of src/sampler, every function matches its rust counterpart so a gpu tracer draws the same numbers as the cpu tracer.
+//Include it after declaring `uint blue_noise_tile[BLUE_NOISE_SIZE * BLUE_NOISE_SIZE]` in a buffer filled with sampler::blue_noise_tile().
+
+#define SAMPLER_RANDOM 0u
+#define SAMPLER_SOBOL 1u
+#define SAMPLER_BLUE_NOISE 2u
+#define SAMPLER_STRATIFIED 3u
+#define BLUE_NOISE_SIZE 64u
+
+struct PixelSampler{
+    uint kind;
+    uint seed;
+    uvec2 pixel_coordinates;
+    uint pixel;
+    uint index;
+    uint dimension;
+    uint samples_per_pixel;
+    uint state;
+};
+
+uint rxs_m_xs(uint state){
+    uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+uint sampler_hash(uint value){
+    return rxs_m_xs(value * 747796405u + 2891336453u);
+}
+PixelSampler pixel_sampler(uint kind , uint seed , uint samples_per_pixel , uvec2 coordinates , uint sample_index){
+    PixelSampler stream;
+    stream.kind = kind;
+    stream.seed = sampler_hash(seed);
+    stream.pixel_coordinates = coordinates;
+    stream.pixel = sampler_hash(stream.seed ^ sampler_hash(coordinates.x ^ sampler_hash(coordinates.y)));
+    stream.index = sample_index;
+    stream.dimension = 0u;
+    stream.samples_per_pixel = max(samples_per_pixel, 1u);
+    stream.state = sampler_hash(stream.pixel ^ sampler_hash(sample_index));
+    return stream;
+}
+float to_float(uint value){
+    return float(value >> 8u) * (1.0 / 16777216.0);
+}
+uvec2 sobol(uint index){
+    uint y = 0u;
+    uint direction = 1u << 31u;
+    for(uint i = index; i != 0u; i >>= 1u){
+        if((i & 1u) != 0u){
+            y ^= direction;
+        }
+        direction ^= direction >> 1u;
+    }
+    return uvec2(bitfieldReverse(index), y);
+}
+uint laine_karras_permutation(uint x , uint seed){
+    x += seed;
+    x ^= x * 0x6c50b47cu;
+    x ^= x * 0xb82f1e52u;
+    x ^= x * 0xc7afe638u;
+    x ^= x * 0x8d22f6e6u;
+    return x;
+}
+uint nested_uniform_scramble(uint value , uint seed){
+    return bitfieldReverse(laine_karras_permutation(bitfieldReverse(value), seed));
+}
+uvec2 sobol_2d(uint index , uint seed){
+    uvec2 point = sobol(nested_uniform_scramble(index, seed));
+    return uvec2(nested_uniform_scramble(point.x, sampler_hash(seed ^ 0x2c1b3c6du)), nested_uniform_scramble(point.y, sampler_hash(seed ^ 0x297a2d39u)));
+}
+uint blue_noise(uint x , uint y){
+    uint mask = BLUE_NOISE_SIZE - 1u;
+    return blue_noise_tile[(y & mask) * BLUE_NOISE_SIZE + (x & mask)];
+}
+uint permute(uint i , uint length , uint pattern){
+    uint w = length - 1u;
+    w |= w >> 1u;
+    w |= w >> 2u;
+    w |= w >> 4u;
+    w |= w >> 8u;
+    w |= w >> 16u;
+    while(true){
+        i ^= pattern;
+        i *= 0xe170893du;
+        i ^= pattern >> 16u;
+        i ^= (i & w) >> 4u;
+        i ^= pattern >> 8u;
+        i *= 0x0929eb3fu;
+        i ^= pattern >> 23u;
+        i ^= (i & w) >> 1u;
+        i *= 1u | pattern >> 27u;
+        i *= 0x6935fa69u;
+        i ^= (i & w) >> 11u;
+        i *= 0x74dcb303u;
+        i ^= (i & w) >> 2u;
+        i *= 0x9e501cc3u;
+        i ^= (i & w) >> 2u;
+        i *= 0xc860a3dfu;
+        i &= w;
+        i ^= i >> 5u;
+        if(i < length){
+            return (i + pattern) % length;
+        }
+    }
+    return 0u;
+}
+//The upper half of the 64 bit product, built from 16 bit halves since 64 bit integers are optional.
+uint multiply_high(uint a , uint b){
+    uint a_low = a & 0xffffu;
+    uint a_high = a >> 16u;
+    uint b_low = b & 0xffffu;
+    uint b_high = b >> 16u;
+    uint cross_terms = (a_low * b_low >> 16u) + (a_high * b_low & 0xffffu) + (a_low * b_high & 0xffffu);
+    return a_high * b_high + (a_high * b_low >> 16u) + (a_low * b_high >> 16u) + (cross_terms >> 16u);
+}
+uint stratum(uint index , uint count , uint jitter){
+    uint width = max(0xffffffffu / count, 1u);
+    return index * width + multiply_high(jitter, width);
+}
+uvec2 correlated_multi_jitter(uint sample_index , uint count , uint pattern){
+    uint columns = 1u;
+    while(columns * columns < count){
+        columns += 1u;
+    }
+    uint rows = (count + columns - 1u) / columns;
+    uint s = permute(sample_index, count, pattern * 0x51633e2du);
+    uint column = s % columns;
+    uint row = s / columns;
+    uint sub_x = permute(column, columns, pattern * 0xa511e9b3u);
+    uint sub_y = permute(row, rows, pattern * 0x63d83595u);
+    uint jitter_x = sampler_hash(s ^ (pattern * 0xa399d265u));
+    uint jitter_y = sampler_hash(s ^ (pattern * 0x711ad6a5u));
+    return uvec2(stratum(column * rows + sub_y, columns * rows, jitter_x), stratum(row * columns + sub_x, columns * rows, jitter_y));
+}
+uint sampler_random(inout PixelSampler stream){
+    stream.state = stream.state * 747796405u + 2891336453u;
+    return rxs_m_xs(stream.state);
+}
+uvec2 sampler_next(inout PixelSampler stream){
+    uint dimension = stream.dimension;
+    stream.dimension += 1u;
+    if(stream.kind == SAMPLER_RANDOM){
+        uint x = sampler_random(stream);
+        uint y = sampler_random(stream);
+        return uvec2(x, y);
+    }
+    if(stream.kind == SAMPLER_SOBOL){
+        return sobol_2d(stream.index, sampler_hash(stream.pixel ^ sampler_hash(dimension)));
+    }
+    if(stream.kind == SAMPLER_BLUE_NOISE){
+        uvec2 point = sobol_2d(stream.index, sampler_hash(stream.seed ^ sampler_hash(dimension)));
+        uint offset = sampler_hash(stream.seed ^ sampler_hash(dimension ^ 0x68bc21ebu));
+        uvec2 coordinates = stream.pixel_coordinates;
+        uint rotation_x = blue_noise(coordinates.x + offset, coordinates.y + (offset >> 16u));
+        uint rotation_y = blue_noise(coordinates.x + (offset >> 8u), coordinates.y + (offset >> 24u));
+        return point + uvec2(rotation_x, rotation_y);
+    }
+    uint sample_round = stream.index / stream.samples_per_pixel;
+    uint pattern = sampler_hash(stream.pixel ^ sampler_hash(dimension ^ sampler_hash(sample_round)));
+    return correlated_multi_jitter(stream.index % stream.samples_per_pixel, stream.samples_per_pixel, pattern);
+}
+float sampler_next_1d(inout PixelSampler stream){
+    return to_float(sampler_next(stream).x);
+}
+vec2 sampler_next_2d(inout PixelSampler stream){
+    uvec2 point = sampler_next(stream);
+    return vec2(to_float(point.x), to_float(point.y));
+}
@@ -0,0 +1,368 @@
+//The path tracer of src/tracer on the gpu, one invocation traces one sample of one pixel.
+//Paths draw the same dimensions as the cpu tracer, so both trace the same paths for the same sampler settings.
+//The including shader declares the material textures and material_sampler of set 1 and sample_material_texture, which reads element image of them.
+
+#define TEXTURE_CONSTANT 0u
+#define TEXTURE_CHECKER 1u
+#define TEXTURE_IMAGE 2u
+
+layout(std140, set = 0, binding = 0) uniform Parameters{
+    //The camera position and the tangent of half the vertical field of view.
+    vec4 camera_position;
+    //The camera axes, the forward axis holds the lens radius and the right axis the focus distance.
+    vec4 camera_forward;
+    vec4 camera_right;
+    //The up axis holds the aspect ratio of the frame.
+    vec4 camera_up;
+    vec4 sky;
+    //The rotation and intensity of the environment map, z is one when the scene has one and the sky is used otherwise.
+    vec4 environment;
+    uint width;
+    uint height;
+    uint sphere_count;
+    uint max_bounces;
+    uint sampler_kind;
+    uint sampler_seed;
+    uint samples_per_pixel;
+    //The vec4 written per pixel, 1 for the radiance, 4 with the first hit and 8 with the lighting split.
+    uint outputs;
+    uint emitter_count;
+    //The emitter of the sky or environment map plus one, zero when it does not emit.
+    uint environment_emitter;
+    uint padding0;
+    uint padding1;
+} parameters;
+//The tile of sampler::blue_noise_tile, read by the blue noise sampler.
+layout(std430, set = 0, binding = 1) readonly buffer BlueNoise{
+    uint blue_noise_tile[];
+};
+struct Sphere{
+    vec4 center_radius;
+    //The material in x and the emitter plus one in y, zero for spheres that do not emit.
+    uvec4 ids;
+};
+layout(std430, set = 0, binding = 2) readonly buffer Spheres{
+    Sphere spheres[];
+};
+//Textures are indices into the texture buffer, kinds are the BSDF_ defines of bsdf.glsl.
+struct Material{
+    uint kind;
+    float ior;
+    uint emission;
+    uint color;
+    uint roughness;
+    uint metallic;
+    uint specular;
+    uint transmission;
+};
+layout(std430, set = 0, binding = 3) readonly buffer Materials{
+    Material materials[];
+};
+struct MaterialTexture{
+    uint kind;
+    //The element of the material texture array.
+    uint image;
+    float scale;
+    uint padding;
+    //The constant value or the even squares of a checker.
+    vec4 even;
+    vec4 odd;
+};
+layout(std430, set = 0, binding = 4) readonly buffer Textures{
+    MaterialTexture textures[];
+};
+//The samples every pixel already holds, the index of the sample traced for it.
+layout(std430, set = 0, binding = 5) readonly buffer SampleIndices{
+    uint sample_indices[];
+};
+//The radiance with the hit flag in w, then the albedo with the depth, the normal with the object id and the position,
+//then the diffuse direct, diffuse indirect, specular direct and specular indirect lighting.
+layout(std430, set = 0, binding = 6) buffer Outputs{
+    vec4 outputs[];
+};
+//The emitters of LightSampler in the order it chooses them.
+struct Emitter{
+    //The kind in x and the sphere of sphere emitters in y.
+    uvec4 ids;
+    //The position with the probability of choosing this emitter or one before it.
+    vec4 position_cdf;
+    //The direction of spot and directional lights with the inner cosine of spots.
+    vec4 direction_cos_inner;
+    //The intensity or irradiance with the outer cosine of spots.
+    vec4 intensity_cos_outer;
+};
+layout(std430, set = 0, binding = 7) readonly buffer Emitters{
+    Emitter emitters[];
+};
+//The distribution of the rows of the environment map, then the distribution within each row.
+layout(std430, set = 0, binding = 8) readonly buffer EnvironmentDistribution{
+    uint environment_distribution[];
+};
+//The texels of light::Environment, rows start at the top.
+layout(set = 0, binding = 9) uniform texture2D environment_map;
+
+#include "sampler.glsl"
+#include "bsdf.glsl"
+
+//The uv coordinate with its derivatives along the x and y axes of the frame, images pick their mip level and anisotropy from them.
+struct SurfaceUv{
+    vec2 uv;
+    vec2 dx;
+    vec2 dy;
+};
+vec3 texture_eval(uint index , SurfaceUv surface){
+    MaterialTexture texture_ = textures[index];
+    vec2 uv = surface.uv;
+    if(texture_.kind == TEXTURE_CHECKER){
+        int parity = int(floor(uv.x * texture_.scale) + floor(uv.y * texture_.scale));
+        return (parity & 1) == 0 ? texture_.even.xyz : texture_.odd.xyz;
+    }
+    if(texture_.kind == TEXTURE_IMAGE){
+        //Rows of the cpu images start at the top, at v = 1.
+        return sample_material_texture(texture_.image, vec2(uv.x, 1.0 - uv.y), surface.dx * vec2(1.0, -1.0), surface.dy * vec2(1.0, -1.0)).xyz;
+    }
+    return texture_.even.xyz;
+}
+//Material::bsdf, roughness is remapped to the GGX alpha.
+Bsdf material_bsdf(Material material , SurfaceUv surface){
+    float roughness = clamp(texture_eval(material.roughness, surface).x, 0.0, 1.0);
+    Bsdf bsdf;
+    bsdf.kind = material.kind;
+    bsdf.color = texture_eval(material.color, surface);
+    bsdf.alpha = roughness * roughness;
+    bsdf.eta = material.ior;
+    bsdf.metallic = texture_eval(material.metallic, surface).x;
+    //A specular of 0.5 gives the plain fresnel of the index of refraction.
+    bsdf.specular = texture_eval(material.specular, surface).x * 2.0;
+    bsdf.transmission = texture_eval(material.transmission, surface).x;
+    return bsdf;
+}
+//The distance to the nearest intersection in front of the origin, negative for a miss.
+float sphere_intersect(Sphere sphere , vec3 origin , vec3 direction){
+    vec3 oc = origin - sphere.center_radius.xyz;
+    float b = dot(oc, direction);
+    float c = dot(oc, oc) - sphere.center_radius.w * sphere.center_radius.w;
+    float discriminant = b * b - c;
+    if(discriminant < 0.0){
+        return -1.0;
+    }
+    float root = sqrt(discriminant);
+    float t = -b - root;
+    if(t > 1e-4){
+        return t;
+    }
+    t = -b + root;
+    if(t > 1e-4){
+        return t;
+    }
+    return -1.0;
+}
+vec2 sphere_uv(vec3 normal){
+    return vec2(0.5 + atan(normal.x, -normal.z) / (2.0 * PI), 0.5 + asin(clamp(normal.y, -1.0, 1.0)) / PI);
+}
+//How the uv of sphere_uv changes with the normal.
+vec2 sphere_uv_differential(vec3 normal , vec3 normal_differential){
+    float du = (normal.x * normal_differential.z - normal.z * normal_differential.x) / max(normal.x * normal.x + normal.z * normal.z, 1e-6) / (2.0 * PI);
+    float dv = normal_differential.y / (PI * sqrt(max(1.0 - normal.y * normal.y, 1e-6)));
+    return vec2(du, dv);
+}
+//Moves a differential of the ray direction to the tangent plane of the hit at distance t, the transfer of ray differentials.
+vec3 hit_differential(vec3 direction , vec3 direction_differential , float t , vec3 normal){
+    vec3 offset = direction_differential * t;
+    return offset - direction * (dot(offset, normal) / dot(direction, normal));
+}
+//The closest sphere along the ray, false when the ray escapes, ties go to the first sphere like Scene::intersect.
+bool scene_intersect(vec3 origin , vec3 direction , out float closest , out uint index){
+    closest = 0.0;
+    index = 0u;
+    bool hit = false;
+    for(uint i = 0u; i < parameters.sphere_count; i++){
+        float t = sphere_intersect(spheres[i], origin, direction);
+        if(t > 0.0 && (!hit || t < closest)){
+            closest = t;
+            index = i;
+            hit = true;
+        }
+    }
+    return hit;
+}
+//Scene::occluded, shortened a little so the light being tested does not block itself.
+bool scene_occluded(vec3 origin , vec3 direction , float distance){
+    float limit = distance * (1.0 - 1e-4);
+    for(uint i = 0u; i < parameters.sphere_count; i++){
+        float t = sphere_intersect(spheres[i], origin, direction);
+        if(t > 0.0 && t < limit){
+            return true;
+        }
+    }
+    return false;
+}
+
+#include "lights.glsl"
+
+struct Lighting{
+    vec3 diffuse_direct;
+    vec3 diffuse_indirect;
+    vec3 specular_direct;
+    vec3 specular_indirect;
+};
+//Splits the contribution by the share of the diffuse lobes at the first hit.
+void lighting_add(inout Lighting lighting , vec3 radiance , vec3 diffuse_fraction , bool direct){
+    vec3 diffuse = radiance * diffuse_fraction;
+    vec3 specular = radiance - diffuse;
+    if(direct){
+        lighting.diffuse_direct += diffuse;
+        lighting.specular_direct += specular;
+    } else{
+        lighting.diffuse_indirect += diffuse;
+        lighting.specular_indirect += specular;
+    }
+}
+
+void main(){
+    uvec2 pixel = gl_GlobalInvocationID.xy;
+    if(pixel.x >= parameters.width || pixel.y >= parameters.height){
+        return;
+    }
+    uint index = pixel.y * parameters.width + pixel.x;
+    PixelSampler stream = pixel_sampler(parameters.sampler_kind, parameters.sampler_seed, parameters.samples_per_pixel, pixel, sample_indices[index]);
+    vec2 jitter = sampler_next_2d(stream);
+    float u = (float(pixel.x) + jitter.x) / float(parameters.width) * 2.0 - 1.0;
+    float v = 1.0 - (float(pixel.y) + jitter.y) / float(parameters.height) * 2.0;
+    vec2 lens_sample = sampler_next_2d(stream);
+    //Camera::generate_ray.
+    vec3 forward = parameters.camera_forward.xyz;
+    vec3 right = parameters.camera_right.xyz;
+    vec3 up = parameters.camera_up.xyz;
+    float tan_half_fov = parameters.camera_position.w;
+    float aperture = parameters.camera_forward.w;
+    vec3 origin = parameters.camera_position.xyz;
+    vec3 pinhole = forward + right * (u * tan_half_fov * parameters.camera_up.w) + up * (v * tan_half_fov);
+    vec3 direction = normalize(pinhole);
+    //The change of the direction to the next pixel along x and y, images are filtered over the footprint of the pixel at the first hit.
+    vec3 pinhole_dx = right * (2.0 * tan_half_fov * parameters.camera_up.w / float(parameters.width));
+    vec3 pinhole_dy = up * (-2.0 * tan_half_fov / float(parameters.height));
+    vec3 direction_dx = (pinhole_dx - direction * dot(direction, pinhole_dx)) / length(pinhole);
+    vec3 direction_dy = (pinhole_dy - direction * dot(direction, pinhole_dy)) / length(pinhole);
+    vec3 camera_direction = direction;
+    if(aperture > 0.0){
+        vec3 focus_point = origin + direction * (parameters.camera_right.w / dot(direction, forward));
+        float radius = aperture * sqrt(lens_sample.x);
+        float angle = 2.0 * PI * lens_sample.y;
+        origin = origin + right * (radius * cos(angle)) + up * (radius * sin(angle));
+        direction = normalize(focus_point - origin);
+        camera_direction = direction;
+    }
+    //Tracer::trace, every non specular hit samples a light and emission found by bsdf sampling is weighted against it.
+    vec3 radiance = vec3(0.0);
+    vec3 throughput = vec3(1.0);
+    bool first_hit = false;
+    vec3 first_albedo = vec3(0.0);
+    vec3 first_normal = vec3(0.0);
+    vec3 first_position = vec3(0.0);
+    float first_distance = 0.0;
+    uint first_sphere = 0u;
+    Lighting lighting;
+    lighting.diffuse_direct = vec3(0.0);
+    lighting.diffuse_indirect = vec3(0.0);
+    lighting.specular_direct = vec3(0.0);
+    lighting.specular_indirect = vec3(0.0);
+    //The part of the first hit's bsdf the path continued through that was diffuse, zero for dirac samples.
+    vec3 diffuse_fraction = vec3(0.0);
+    //The density the last bounce was sampled with, negative for camera rays and specular bounces which lights can not sample.
+    float bounce_pdf = -1.0;
+    for(uint bounce = 0u; bounce < parameters.max_bounces; bounce++){
+        float t;
+        uint sphere_index;
+        if(!scene_intersect(origin, direction, t, sphere_index)){
+            float weight = bounce_pdf < 0.0 ? 1.0 : power_heuristic(bounce_pdf, environment_light_pdf(direction));
+            vec3 contribution = throughput * scene_background(direction) * weight;
+            radiance += contribution;
+            if(first_hit){
+                lighting_add(lighting, contribution, diffuse_fraction, bounce == 1u);
+            }
+            break;
+        }
+        Sphere sphere = spheres[sphere_index];
+        vec3 previous = origin;
+        origin += direction * t;
+        vec3 normal = normalize(origin - sphere.center_radius.xyz);
+        SurfaceUv surface;
+        surface.uv = sphere_uv(normal);
+        //Later bounces read the finest mip level.
+        surface.dx = vec2(0.0);
+        surface.dy = vec2(0.0);
+        if(bounce == 0u){
+            float radius = sphere.center_radius.w;
+            surface.dx = sphere_uv_differential(normal, hit_differential(direction, direction_dx, t, normal) / radius);
+            surface.dy = sphere_uv_differential(normal, hit_differential(direction, direction_dy, t, normal) / radius);
+        }
+        Material material = materials[sphere.ids.x];
+        Bsdf bsdf = material_bsdf(material, surface);
+        vec3 emission = texture_eval(material.emission, surface);
+        if(emission != vec3(0.0)){
+            float weight = bounce_pdf < 0.0 ? 1.0 : power_heuristic(bounce_pdf, sphere_light_pdf(sphere_index, previous));
+            vec3 contribution = throughput * emission * weight;
+            radiance += contribution;
+            if(first_hit){
+                lighting_add(lighting, contribution, diffuse_fraction, bounce == 1u);
+            }
+        }
+        if(bounce == 0u){
+            first_hit = true;
+            first_albedo = bsdf.color;
+            first_normal = normal;
+            first_position = origin;
+            first_distance = t;
+            first_sphere = sphere_index;
+        }
+        Frame frame = frame_new(normal);
+        vec3 wo = frame_to_local(frame, -direction);
+        float light_choice = sampler_next_1d(stream);
+        vec2 light_sample = sampler_next_2d(stream);
+        float lobe = sampler_next_1d(stream);
+        vec2 direction_sample = sampler_next_2d(stream);
+        uint emitter;
+        float probability;
+        if(choose_emitter(light_choice, emitter, probability)){
+            LightSample light = sample_emitter(emitter, origin, light_sample);
+            if(light.valid){
+                vec3 wi = frame_to_local(frame, light.direction);
+                vec3 f = bsdf_eval(bsdf, wo, wi) * abs(wi.z);
+                if(f != vec3(0.0) && !scene_occluded(origin, light.direction, light.distance)){
+                    float light_pdf = probability * light.pdf;
+                    float weight = light.delta ? 1.0 : power_heuristic(light_pdf, bsdf_pdf(bsdf, wo, wi));
+                    vec3 contribution = throughput * f * light.radiance * (weight / light_pdf);
+                    radiance += contribution;
+                    if(first_hit){
+                        vec3 fraction = bounce == 0u ? bsdf_diffuse_fraction(bsdf, wo, wi) : diffuse_fraction;
+                        lighting_add(lighting, contribution, fraction, bounce == 0u);
+                    }
+                }
+            }
+        }
+        BsdfSample sample_ = bsdf_sample(bsdf, wo, vec3(lobe, direction_sample));
+        if(!sample_.valid){
+            break;
+        }
+        if(bounce == 0u && !sample_.delta){
+            diffuse_fraction = bsdf_diffuse_fraction(bsdf, wo, sample_.wi);
+        }
+        throughput *= sample_.weight;
+        bounce_pdf = sample_.delta ? -1.0 : sample_.pdf;
+        direction = normalize(frame_to_world(frame, sample_.wi));
+    }
+    uint base = index * parameters.outputs;
+    outputs[base] = vec4(radiance, first_hit ? 1.0 : 0.0);
+    if(parameters.outputs >= 4u){
+        outputs[base + 1u] = vec4(first_albedo, first_distance * dot(camera_direction, forward));
+        outputs[base + 2u] = vec4(first_normal, float(first_sphere + 1u));
+        outputs[base + 3u] = vec4(first_position, 0.0);
+    }
+    if(parameters.outputs >= 8u){
+        outputs[base + 4u] = vec4(lighting.diffuse_direct, 0.0);
+        outputs[base + 5u] = vec4(lighting.diffuse_indirect, 0.0);
+        outputs[base + 6u] = vec4(lighting.specular_direct, 0.0);
+        outputs[base + 7u] = vec4(lighting.specular_indirect, 0.0);
+    }
+}
@@ -0,0 +1,29 @@
+#version 450
+//Textures are srgb images so sampling returns linear values, the result is encoded again for unorm swapchains.
+
+layout(location = 0) in vec2 uv;
+layout(location = 1) in vec4 color;
+
+layout(set = 0, binding = 0) uniform texture2D ui_texture;
+layout(set = 0, binding = 1) uniform sampler ui_sampler;
+
+layout(push_constant) uniform PushConstants{
+    vec2 screen_size;
+    uint linear_output;
+} constants;
+
+layout(location = 0) out vec4 out_color;
+
+vec3 srgb_from_linear(vec3 linear){
+    vec3 lower = linear * 12.92;
+    vec3 higher = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+    return mix(higher, lower, vec3(lessThan(linear, vec3(0.0031308))));
+}
+
+void main(){
+    vec4 result = color * texture(sampler2D(ui_texture, ui_sampler), uv);
+    if(constants.linear_output == 0u){
+        result.rgb = srgb_from_linear(result.rgb);
+    }
+    out_color = result;
+}
@@ -0,0 +1,26 @@
+#version 450
+//Debug overlay vertices from egui, positions in points and colors in premultiplied srgb.
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+layout(location = 2) in vec4 color;
+
+layout(push_constant) uniform PushConstants{
+    vec2 screen_size;
+    uint linear_output;
+} constants;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_color;
+
+vec3 linear_from_srgb(vec3 srgb){
+    vec3 lower = srgb / 12.92;
+    vec3 higher = pow((srgb + 0.055) / 1.055, vec3(2.4));
+    return mix(higher, lower, vec3(lessThan(srgb, vec3(0.04045))));
+}
+
+void main(){
+    gl_Position = vec4(2.0 * position / constants.screen_size - 1.0, 0.0, 1.0);
+    out_uv = uv;
+    out_color = vec4(linear_from_srgb(color.rgb), color.a);
+}
@@ -0,0 +1,395 @@
+//!Bounding volume hierarchies over the spheres of a scene, the cpu tracer walks these instead of testing every sphere.
+//!A binary bvh is built with the surface area heuristic and collapsed into 4 or 8 wide trees whose nodes test all their children at once.
+
+mod wide;
+
+pub use wide::WideBvh;
+pub use wide::Bvh4;
+pub use wide::Bvh8;
+
+use cgmath::Vector3;
+
+use super::scene::Sphere;
+
+///Number of buckets the centroids are sorted into when looking for the cheapest split.
+const BINS : usize = 16;
+///Below this depth nodes are split at the median instead, so clustered spheres can not make the tree arbitrarily deep.
+const MAX_SAH_DEPTH : usize = 16;
+///Entries of a traversal stack kept on the call stack, zeroing a bigger array for every ray costs more than the rare spill to the heap.
+const INLINE_STACK : usize = 32;
+
+///An axis aligned box.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Aabb{
+    pub min : Vector3<f32>,
+    pub max : Vector3<f32>,
+}
+impl Aabb{
+    ///Contains nothing, the union with it leaves any box unchanged.
+    pub fn empty() -> Self{
+        return Self{min : Vector3::new(f32::INFINITY,f32::INFINITY,f32::INFINITY), max : Vector3::new(f32::NEG_INFINITY,f32::NEG_INFINITY,f32::NEG_INFINITY)};
+    }
+    ///The bounds of a sphere, padded by the rounding error of the intersection so a ray never hits the sphere but misses its box.
+    pub fn sphere(sphere : &Sphere) -> Self{
+        let center = sphere.center;
+        let magnitude = center.x.abs().max(center.y.abs()).max(center.z.abs()) + sphere.radius;
+        let extent = sphere.radius + magnitude * 1e-5;
+        return Self{min : center - Vector3::new(extent,extent,extent), max : center + Vector3::new(extent,extent,extent)};
+    }
+    pub fn union(&self , other : &Aabb) -> Self{
+        return Self{
+            min : Vector3::new(self.min.x.min(other.min.x),self.min.y.min(other.min.y),self.min.z.min(other.min.z)),
+            max : Vector3::new(self.max.x.max(other.max.x),self.max.y.max(other.max.y),self.max.z.max(other.max.z)),
+        };
+    }
+    pub fn grow(&mut self , point : Vector3<f32>){
+        *self = self.union(&Self{min : point, max : point});
+    }
+    pub fn is_empty(&self) -> bool{
+        return self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z;
+    }
+    pub fn center(&self) -> Vector3<f32>{
+        return (self.min + self.max) * 0.5;
+    }
+    pub fn surface_area(&self) -> f32{
+        if self.is_empty(){
+            return 0.0;
+        }
+        let extent = self.max - self.min;
+        return 2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x);
+    }
+    ///The distance along the ray where it enters the box, None when it misses the box or enters it past max_t.
+    pub fn entry(&self , origin : Vector3<f32> , inverse_direction : Vector3<f32> , max_t : f32) -> Option<f32>{
+        let (x0,x1) = ((self.min.x - origin.x) * inverse_direction.x,(self.max.x - origin.x) * inverse_direction.x);
+        let (y0,y1) = ((self.min.y - origin.y) * inverse_direction.y,(self.max.y - origin.y) * inverse_direction.y);
+        let (z0,z1) = ((self.min.z - origin.z) * inverse_direction.z,(self.max.z - origin.z) * inverse_direction.z);
+        let near = x0.min(x1).max(y0.min(y1)).max(z0.min(z1)).max(0.0);
+        let far = x0.max(x1).min(y0.max(y1)).min(z0.max(z1)).min(max_t);
+        if near <= far{
+            return Some(near);
+        }
+        return None;
+    }
+}
+///A traversal stack that lives on the call stack while it is shallow and spills onto the heap for deep trees.
+struct Stack<T : Copy>{
+    inline : [T;INLINE_STACK],
+    length : usize,
+    spilled : Vec<T>,
+}
+impl<T : Copy> Stack<T>{
+    fn new(fill : T) -> Self{
+        return Self{inline : [fill;INLINE_STACK], length : 0, spilled : vec!()};
+    }
+    fn push(&mut self , item : T){
+        if self.length < INLINE_STACK{
+            self.inline[self.length] = item;
+            self.length += 1;
+        } else{
+            self.spilled.push(item);
+        }
+    }
+    fn pop(&mut self) -> Option<T>{
+        if let Some(item) = self.spilled.pop(){
+            return Some(item);
+        }
+        if self.length == 0{
+            return None;
+        }
+        self.length -= 1;
+        return Some(self.inline[self.length]);
+    }
+}
+fn inverse(direction : Vector3<f32>) -> Vector3<f32>{
+    return Vector3::new(1.0 / direction.x,1.0 / direction.y,1.0 / direction.z);
+}
+///Whether a hit at t with the given sphere index beats the closest so far, ties go to the lower index like a linear scan.
+fn closer(t : f32 , index : usize , closest : Option<(f32,usize)>) -> bool{
+    return match closest{
+        Some((closest_t,closest_index)) => t < closest_t || (t == closest_t && index < closest_index),
+        None => true,
+    };
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Node{
+    Interior{bounds : Aabb, children : [usize;2]},
+    ///The spheres are indices[first..first + count] of the tree.
+    Leaf{bounds : Aabb, first : usize, count : usize},
+}
+impl Node{
+    pub fn bounds(&self) -> Aabb{
+        match self{
+            Node::Interior{bounds,..} => return *bounds,
+            Node::Leaf{bounds,..} => return *bounds,
+        }
+    }
+}
+///A binary bvh, the root is the first node.
+pub struct Bvh{
+    nodes : Vec<Node>,
+    indices : Vec<usize>,
+    max_leaf_size : usize,
+}
+impl Bvh{
+    ///Builds the tree with at most max_leaf_size spheres per leaf.
+    pub fn new(spheres : &[Sphere] , max_leaf_size : usize) -> Self{
+        let boxes : Vec<Aabb> = spheres.iter().map(Aabb::sphere).collect();
+        let mut bvh = Self{nodes : vec!(), indices : (0..spheres.len()).collect(), max_leaf_size : max_leaf_size.max(1)};
+        bvh.build(&boxes, 0, spheres.len(), 0);
+        return bvh;
+    }
+    pub fn nodes(&self) -> &[Node]{
+        return &self.nodes;
+    }
+    ///Sphere indices in leaf order.
+    pub fn indices(&self) -> &[usize]{
+        return &self.indices;
+    }
+    fn build(&mut self , boxes : &[Aabb] , first : usize , count : usize , depth : usize) -> usize{
+        let bounds = self.indices[first..first + count].iter().fold(Aabb::empty(), |bounds,&index| bounds.union(&boxes[index]));
+        let node = self.nodes.len();
+        self.nodes.push(Node::Leaf{bounds, first, count});
+        if count <= self.max_leaf_size{
+            return node;
+        }
+        let left_count = self.split(boxes, first, count, depth);
+        let left = self.build(boxes, first, left_count, depth + 1);
+        let right = self.build(boxes, first + left_count, count - left_count, depth + 1);
+        self.nodes[node] = Node::Interior{bounds, children : [left,right]};
+        return node;
+    }
+    ///Reorders indices[first..first + count] into the two halves of the cheapest split along the widest axis of the centroids, returns the size of the first half.
+    fn split(&mut self , boxes : &[Aabb] , first : usize , count : usize , depth : usize) -> usize{
+        let indices = &mut self.indices[first..first + count];
+        let mut centroids = Aabb::empty();
+        for &index in indices.iter(){
+            centroids.grow(boxes[index].center());
+        }
+        let extent = centroids.max - centroids.min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z{0}else if extent.y >= extent.z{1}else{2};
+        let (minimum,width) = (centroids.min[axis],extent[axis]);
+        if width <= 0.0 || depth >= MAX_SAH_DEPTH{
+            indices.sort_by(|a,b| boxes[*a].center()[axis].total_cmp(&boxes[*b].center()[axis]));
+            return count / 2;
+        }
+        let bin = |index : usize| ((boxes[index].center()[axis] - minimum) / width * BINS as f32).clamp(0.0, (BINS - 1) as f32) as usize;
+        let mut bins = [(Aabb::empty(),0usize);BINS];
+        for &index in indices.iter(){
+            let (bounds,bin_count) = &mut bins[bin(index)];
+            *bounds = bounds.union(&boxes[index]);
+            *bin_count += 1;
+        }
+        //Sweeps from the right to know the cost of every right half, then from the left to find the cheapest split.
+        let mut right_costs = [0.0;BINS];
+        let (mut right_bounds,mut right_count) = (Aabb::empty(),0);
+        for split in (1..BINS).rev(){
+            right_bounds = right_bounds.union(&bins[split].0);
+            right_count += bins[split].1;
+            right_costs[split] = right_bounds.surface_area() * right_count as f32;
+        }
+        let (mut left_bounds,mut left_count) = (Aabb::empty(),0);
+        let mut best : Option<(f32,usize)> = None;
+        for split in 1..BINS{
+            left_bounds = left_bounds.union(&bins[split - 1].0);
+            left_count += bins[split - 1].1;
+            if left_count == 0 || left_count == count{
+                continue;
+            }
+            let cost = left_bounds.surface_area() * left_count as f32 + right_costs[split];
+            if best.map_or(true, |(best_cost,_)| cost < best_cost){
+                best = Some((cost,split));
+            }
+        }
+        //The extreme centroids land in the first and last bin so there always is a split with spheres on both sides.
+        let (_,split) = best.expect("Failed to find a split of distinct centroids.");
+        let mut left = 0;
+        for i in 0..count{
+            if bin(indices[i]) < split{
+                indices.swap(i, left);
+                left += 1;
+            }
+        }
+        return left;
+    }
+    ///Returns the distance to the closest sphere along the ray and its index, the same hit as Scene::intersect.
+    pub fn intersect(&self , spheres : &[Sphere] , origin : Vector3<f32> , direction : Vector3<f32>) -> Option<(f32,usize)>{
+        let inverse_direction = inverse(direction);
+        let mut closest : Option<(f32,usize)> = None;
+        let mut stack = Stack::new((0usize,0.0f32));
+        if let Some(entry) = self.nodes[0].bounds().entry(origin, inverse_direction, f32::INFINITY){
+            stack.push((0,entry));
+        }
+        while let Some((node,entry)) = stack.pop(){
+            if closest.is_some_and(|(closest_t,_)| entry > closest_t){
+                continue;
+            }
+            match self.nodes[node]{
+                Node::Leaf{first,count,..} => {
+                    for &index in self.indices[first..first + count].iter(){
+                        if let Some(t) = spheres[index].intersect(origin, direction){
+                            if closer(t, index, closest){
+                                closest = Some((t,index));
+                            }
+                        }
+                    }
+                }
+                Node::Interior{children,..} => {
+                    let max_t = closest.map_or(f32::INFINITY, |(closest_t,_)| closest_t);
+                    let mut hits = children.map(|child| self.nodes[child].bounds().entry(origin, inverse_direction, max_t).map(|entry| (child,entry)));
+                    //The nearer child goes on top of the stack.
+                    if let [Some((_,a)),Some((_,b))] = hits{
+                        if a < b{
+                            hits.swap(0, 1);
+                        }
+                    }
+                    for hit in hits.iter().flatten(){
+                        stack.push(*hit);
+                    }
+                }
+            }
+        }
+        return closest;
+    }
+    ///Whether anything blocks the ray before it travels the given distance, the same answer as Scene::occluded.
+    pub fn occluded(&self , spheres : &[Sphere] , origin : Vector3<f32> , direction : Vector3<f32> , distance : f32) -> bool{
+        let distance = distance * (1.0 - 1e-4);
+        let inverse_direction = inverse(direction);
+        let mut stack = Stack::new(0usize);
+        stack.push(0);
+        while let Some(node) = stack.pop(){
+            let node = &self.nodes[node];
+            if node.bounds().entry(origin, inverse_direction, distance).is_none(){
+                continue;
+            }
+            match *node{
+                Node::Leaf{first,count,..} => {
+                    if self.indices[first..first + count].iter().any(|&index| spheres[index].intersect(origin, direction).is_some_and(|t| t < distance)){
+                        return true;
+                    }
+                }
+                Node::Interior{children,..} => {
+                    stack.push(children[0]);
+                    stack.push(children[1]);
+                }
+            }
+        }
+        return false;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests{
+    use super::*;
+    use crate::material::Material;
+    use crate::sampler::hash;
+    use cgmath::InnerSpace;
+
+    fn random(state : &mut u32) -> f32{
+        *state = hash(*state);
+        return (*state >> 8) as f32 / (1 << 24) as f32;
+    }
+    ///Random spheres of very different sizes, some overlapping and a few sharing a center, with a ground sphere like the test scenes.
+    pub(crate) fn random_spheres(count : usize , seed : u32) -> Vec<Sphere>{
+        let mut state = seed;
+        let mut spheres = vec!(Sphere{center : Vector3::new(0.0,-1000.0,0.0), radius : 1000.0, material : Material::lambert([0.5,0.5,0.5])});
+        for i in 1..count{
+            let center = if i % 7 == 0{spheres[i - 1].center}else{Vector3::new(random(&mut state) * 20.0 - 10.0,random(&mut state) * 4.0,random(&mut state) * 20.0 - 10.0)};
+            let radius = 0.05 + random(&mut state).powi(3) * 2.0;
+            spheres.push(Sphere{center, radius, material : Material::lambert([0.5,0.5,0.5])});
+        }
+        return spheres;
+    }
+    ///Rays from inside and around the spheres in every direction, including axis aligned ones.
+    pub(crate) fn random_rays(count : usize , seed : u32) -> Vec<(Vector3<f32>,Vector3<f32>)>{
+        let mut state = seed;
+        return (0..count).map(|i| {
+            let origin = Vector3::new(random(&mut state) * 30.0 - 15.0,random(&mut state) * 8.0 - 1.0,random(&mut state) * 30.0 - 15.0);
+            let direction = match i % 5{
+                0 => Vector3::new(0.0,-1.0,0.0),
+                1 => Vector3::new(1.0,0.0,0.0),
+                _ => Vector3::new(random(&mut state) - 0.5,random(&mut state) - 0.5,random(&mut state) - 0.5).normalize(),
+            };
+            (origin,direction)
+        }).collect();
+    }
+    ///The queries every tree answers, the binary tree takes the spheres and the wide trees keep their own copy.
+    pub(crate) trait Tree{
+        fn intersect(&self , spheres : &[Sphere] , origin : Vector3<f32> , direction : Vector3<f32>) -> Option<(f32,usize)>;
+        fn occluded(&self , spheres : &[Sphere] , origin : Vector3<f32> , direction : Vector3<f32> , distance : f32) -> bool;
+    }
+    impl Tree for Bvh{
+        fn intersect(&self , spheres : &[Sphere] , origin : Vector3<f32> , direction : Vector3<f32>) -> Option<(f32,usize)>{
+            return Bvh::intersect(self, spheres, origin, direction);
+        }
+        fn occluded(&self , spheres : &[Sphere] , origin : Vector3<f32> , direction : Vector3<f32> , distance : f32) -> bool{
+            return Bvh::occluded(self, spheres, origin, direction, distance);
+        }
+    }
+    impl<const W : usize> Tree for WideBvh<W>{
+        fn intersect(&self , _ : &[Sphere] , origin : Vector3<f32> , direction : Vector3<f32>) -> Option<(f32,usize)>{
+            return WideBvh::intersect(self, origin, direction);
+        }
+        fn occluded(&self , _ : &[Sphere] , origin : Vector3<f32> , direction : Vector3<f32> , distance : f32) -> bool{
+            return WideBvh::occluded(self, origin, direction, distance);
+        }
+    }
+    ///Asks the scene's linear scan and the tree the same queries.
+    pub(crate) fn check(count : usize , seed : u32 , tree : &dyn Tree){
+        let scene = crate::scene::Scene{spheres : random_spheres(count, seed), ..crate::scene::Scene::default_scene()};
+        let mut hits = 0;
+        for (origin,direction) in random_rays(2000, seed){
+            let expected = scene.intersect(origin, direction);
+            assert_eq!(tree.intersect(&scene.spheres, origin, direction), expected);
+            hits += expected.is_some() as usize;
+            for distance in [0.5,3.0,1e6]{
+                assert_eq!(tree.occluded(&scene.spheres, origin, direction, distance), scene.occluded(origin, direction, distance));
+            }
+        }
+        assert!(hits > 500);
+    }
+
+    #[test]
+    fn binary(){
+        for (count,leaf_size) in [(1,4),(3,4),(200,1),(200,4)]{
+            let spheres = random_spheres(count, 1);
+            let bvh = Bvh::new(&spheres, leaf_size);
+            let mut indices = bvh.indices().to_vec();
+            indices.sort();
+            assert_eq!(indices, (0..count).collect::<Vec<usize>>());
+            for node in bvh.nodes(){
+                if let Node::Leaf{count,..} = node{
+                    assert!(*count <= leaf_size);
+                }
+            }
+            check(count, 1, &bvh);
+        }
+    }
+    #[test]
+    fn stack(){
+        let mut stack = Stack::new(0);
+        for i in 0..INLINE_STACK * 3{
+            stack.push(i);
+        }
+        assert_eq!(std::iter::from_fn(|| stack.pop()).collect::<Vec<usize>>(), (0..INLINE_STACK * 3).rev().collect::<Vec<usize>>());
+    }
+    #[test]
+    fn bounds(){
+        let spheres = random_spheres(50, 2);
+        let bvh = Bvh::new(&spheres, 4);
+        for node in bvh.nodes(){
+            if let Node::Interior{bounds,children} = node{
+                for child in children.iter().map(|child| bvh.nodes()[*child].bounds()){
+                    assert_eq!(bounds.union(&child), *bounds);
+                }
+            }
+        }
+        assert!(Aabb::empty().is_empty() && Aabb::empty().surface_area() == 0.0);
+        let unit = Aabb{min : Vector3::new(0.0,0.0,0.0), max : Vector3::new(1.0,1.0,1.0)};
+        assert_eq!(unit.surface_area(), 6.0);
+        assert_eq!(unit.entry(Vector3::new(-1.0,0.5,0.5), inverse(Vector3::new(1.0,0.0,0.0)), 10.0), Some(1.0));
+        assert_eq!(unit.entry(Vector3::new(-1.0,0.5,0.5), inverse(Vector3::new(1.0,0.0,0.0)), 0.5), None);
+        assert_eq!(unit.entry(Vector3::new(-1.0,2.0,0.5), inverse(Vector3::new(1.0,0.0,0.0)), 10.0), None);
+        assert_eq!(unit.entry(Vector3::new(0.5,0.5,0.5), inverse(Vector3::new(0.0,-1.0,0.0)), 10.0), Some(0.0));
+    }
+}
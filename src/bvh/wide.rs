@@ -0,0 +1,272 @@
+use cgmath::Vector3;
+
+use super::Aabb;
+use super::Bvh;
+use super::Node;
+use super::Stack;
+use super::closer;
+use super::inverse;
+use super::super::scene::Sphere;
+
+///Marks an unused child slot or packet lane.
+const EMPTY : u32 = u32::MAX;
+///Set on children that are leaves, the other bits index the packets.
+const LEAF : u32 = 1 << 31;
+
+pub type Bvh4 = WideBvh<4>;
+pub type Bvh8 = WideBvh<8>;
+
+///The bounds of up to W children, one array per coordinate so a loop over the lanes compiles to simd instructions.
+#[derive(Clone,Copy,Debug)]
+struct WideNode<const W : usize>{
+    min_x : [f32;W],
+    min_y : [f32;W],
+    min_z : [f32;W],
+    max_x : [f32;W],
+    max_y : [f32;W],
+    max_z : [f32;W],
+    children : [u32;W],
+}
+impl<const W : usize> WideNode<W>{
+    fn empty() -> Self{
+        let min = [f32::INFINITY;W];
+        let max = [f32::NEG_INFINITY;W];
+        return Self{min_x : min, min_y : min, min_z : min, max_x : max, max_y : max, max_z : max, children : [EMPTY;W]};
+    }
+    fn set(&mut self , lane : usize , bounds : &Aabb , child : u32){
+        self.min_x[lane] = bounds.min.x;
+        self.min_y[lane] = bounds.min.y;
+        self.min_z[lane] = bounds.min.z;
+        self.max_x[lane] = bounds.max.x;
+        self.max_y[lane] = bounds.max.y;
+        self.max_z[lane] = bounds.max.z;
+        self.children[lane] = child;
+    }
+    ///Slab tests of all children at once, the distance where the ray enters each box or infinity when it misses or enters past max_t.
+    fn entries(&self , ray : &Ray , max_t : f32) -> [f32;W]{
+        let mut entries = [f32::INFINITY;W];
+        for (lane,entry) in entries.iter_mut().enumerate(){
+            let x0 = (self.min_x[lane] - ray.origin.x) * ray.inverse_direction.x;
+            let x1 = (self.max_x[lane] - ray.origin.x) * ray.inverse_direction.x;
+            let y0 = (self.min_y[lane] - ray.origin.y) * ray.inverse_direction.y;
+            let y1 = (self.max_y[lane] - ray.origin.y) * ray.inverse_direction.y;
+            let z0 = (self.min_z[lane] - ray.origin.z) * ray.inverse_direction.z;
+            let z1 = (self.max_z[lane] - ray.origin.z) * ray.inverse_direction.z;
+            let near = x0.min(x1).max(y0.min(y1)).max(z0.min(z1)).max(0.0);
+            let far = x0.max(x1).min(y0.max(y1)).min(z0.max(z1)).min(max_t);
+            *entry = if near <= far{near}else{f32::INFINITY};
+        }
+        return entries;
+    }
+}
+///The spheres of a leaf, unused lanes have a nan center that never hits.
+#[derive(Clone,Copy,Debug)]
+struct SpherePacket<const W : usize>{
+    center_x : [f32;W],
+    center_y : [f32;W],
+    center_z : [f32;W],
+    radius_squared : [f32;W],
+    indices : [u32;W],
+}
+impl<const W : usize> SpherePacket<W>{
+    fn new(spheres : &[Sphere] , indices : &[usize]) -> Self{
+        let mut packet = Self{center_x : [f32::NAN;W], center_y : [f32::NAN;W], center_z : [f32::NAN;W], radius_squared : [f32::NAN;W], indices : [EMPTY;W]};
+        for (lane,&index) in indices.iter().enumerate(){
+            let sphere = &spheres[index];
+            packet.center_x[lane] = sphere.center.x;
+            packet.center_y[lane] = sphere.center.y;
+            packet.center_z[lane] = sphere.center.z;
+            packet.radius_squared[lane] = sphere.radius * sphere.radius;
+            packet.indices[lane] = index as u32;
+        }
+        return packet;
+    }
+    ///Sphere::intersect for every lane with the same operations in the same order, so the distances are bit for bit the same, infinity for misses.
+    fn intersect(&self , ray : &Ray) -> [f32;W]{
+        let mut hits = [f32::INFINITY;W];
+        for (lane,hit) in hits.iter_mut().enumerate(){
+            let x = ray.origin.x - self.center_x[lane];
+            let y = ray.origin.y - self.center_y[lane];
+            let z = ray.origin.z - self.center_z[lane];
+            let b = x * ray.direction.x + y * ray.direction.y + z * ray.direction.z;
+            let c = x * x + y * y + z * z - self.radius_squared[lane];
+            //A negative discriminant gives a nan root and both comparisons fail.
+            let root = (b * b - c).sqrt();
+            let (near,far) = (-b - root,-b + root);
+            *hit = if near > 1e-4{near}else if far > 1e-4{far}else{f32::INFINITY};
+        }
+        return hits;
+    }
+}
+struct Ray{
+    origin : Vector3<f32>,
+    direction : Vector3<f32>,
+    inverse_direction : Vector3<f32>,
+}
+impl Ray{
+    fn new(origin : Vector3<f32> , direction : Vector3<f32>) -> Self{
+        return Self{origin, direction, inverse_direction : inverse(direction)};
+    }
+}
+///A bvh with W children per node, collapsed from a binary bvh whose leaves hold at most W spheres.
+///Each node tests the boxes of all its children in one pass over the lanes and each leaf all its spheres, the root is the first node.
+pub struct WideBvh<const W : usize>{
+    nodes : Vec<WideNode<W>>,
+    packets : Vec<SpherePacket<W>>,
+}
+impl<const W : usize> WideBvh<W>{
+    pub fn new(spheres : &[Sphere]) -> Self{
+        let bvh = Bvh::new(spheres, W);
+        let mut wide = Self{nodes : vec!(), packets : vec!()};
+        let root = wide.collapse(spheres, &bvh, 0);
+        if root & LEAF != 0{
+            let mut node = WideNode::empty();
+            node.set(0, &bvh.nodes()[0].bounds(), root);
+            wide.nodes.push(node);
+        }
+        return wide;
+    }
+    ///Number of spheres the bvh was built over.
+    pub fn sphere_count(&self) -> usize{
+        return self.packets.iter().flat_map(|packet| packet.indices.iter()).filter(|index| **index != EMPTY).count();
+    }
+    ///Turns the binary node into a wide node by pulling up the children of its largest interior child until it has W children, returns the encoded child.
+    fn collapse(&mut self , spheres : &[Sphere] , bvh : &Bvh , node : usize) -> u32{
+        let nodes = bvh.nodes();
+        let children = match nodes[node]{
+            Node::Leaf{first,count,..} => {
+                self.packets.push(SpherePacket::new(spheres, &bvh.indices()[first..first + count]));
+                return LEAF | (self.packets.len() - 1) as u32;
+            }
+            Node::Interior{children,..} => children,
+        };
+        let mut children = children.to_vec();
+        while children.len() < W{
+            let largest = children.iter().enumerate()
+                .filter_map(|(slot,child)| match nodes[*child]{
+                    Node::Interior{bounds,children} => Some((slot,bounds.surface_area(),children)),
+                    Node::Leaf{..} => None,
+                })
+                .max_by(|a,b| a.1.total_cmp(&b.1));
+            match largest{
+                Some((slot,_,[left,right])) => {
+                    children[slot] = left;
+                    children.push(right);
+                }
+                None => break,
+            }
+        }
+        let index = self.nodes.len();
+        self.nodes.push(WideNode::empty());
+        for (lane,child) in children.into_iter().enumerate(){
+            let encoded = self.collapse(spheres, bvh, child);
+            self.nodes[index].set(lane, &nodes[child].bounds(), encoded);
+        }
+        return index as u32;
+    }
+    ///Returns the distance to the closest sphere along the ray and its index, the same hit as Scene::intersect.
+    pub fn intersect(&self , origin : Vector3<f32> , direction : Vector3<f32>) -> Option<(f32,usize)>{
+        let ray = Ray::new(origin, direction);
+        let mut closest : Option<(f32,usize)> = None;
+        let mut stack = Stack::new((0u32,0.0f32));
+        stack.push((0,0.0));
+        while let Some((node,entry)) = stack.pop(){
+            let max_t = closest.map_or(f32::INFINITY, |(closest_t,_)| closest_t);
+            if entry > max_t{
+                continue;
+            }
+            let node = &self.nodes[node as usize];
+            let entries = node.entries(&ray, max_t);
+            //Leaves first, a closer hit there culls more of the other children.
+            for (lane,&child) in node.children.iter().enumerate(){
+                if child != EMPTY && child & LEAF != 0 && entries[lane] != f32::INFINITY{
+                    let packet = &self.packets[(child & !LEAF) as usize];
+                    for (&t,&index) in packet.intersect(&ray).iter().zip(packet.indices.iter()){
+                        if t != f32::INFINITY && closer(t, index as usize, closest){
+                            closest = Some((t,index as usize));
+                        }
+                    }
+                }
+            }
+            //The remaining children are pushed farthest first so the nearest is visited next.
+            let max_t = closest.map_or(f32::INFINITY, |(closest_t,_)| closest_t);
+            let mut hits = [(0u32,0.0f32);W];
+            let mut count = 0;
+            for (lane,&child) in node.children.iter().enumerate(){
+                if child != EMPTY && child & LEAF == 0 && entries[lane] != f32::INFINITY && entries[lane] <= max_t{
+                    let mut slot = count;
+                    while slot > 0 && hits[slot - 1].1 < entries[lane]{
+                        hits[slot] = hits[slot - 1];
+                        slot -= 1;
+                    }
+                    hits[slot] = (child,entries[lane]);
+                    count += 1;
+                }
+            }
+            for hit in hits[..count].iter(){
+                stack.push(*hit);
+            }
+        }
+        return closest;
+    }
+    ///Whether anything blocks the ray before it travels the given distance, the same answer as Scene::occluded.
+    pub fn occluded(&self , origin : Vector3<f32> , direction : Vector3<f32> , distance : f32) -> bool{
+        let distance = distance * (1.0 - 1e-4);
+        let ray = Ray::new(origin, direction);
+        let mut stack = Stack::new(0u32);
+        stack.push(0);
+        while let Some(node) = stack.pop(){
+            let node = &self.nodes[node as usize];
+            let entries = node.entries(&ray, distance);
+            for (lane,&child) in node.children.iter().enumerate(){
+                if child == EMPTY || entries[lane] == f32::INFINITY{
+                    continue;
+                }
+                if child & LEAF == 0{
+                    stack.push(child);
+                    continue;
+                }
+                let packet = &self.packets[(child & !LEAF) as usize];
+                if packet.intersect(&ray).iter().zip(packet.indices.iter()).any(|(&t,&index)| index != EMPTY && t < distance){
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::check;
+    use super::super::tests::random_spheres;
+
+    #[test]
+    fn same_hits(){
+        for count in [1,5,300]{
+            let bvh = Bvh4::new(&random_spheres(count, 3));
+            assert_eq!(bvh.sphere_count(), count);
+            check(count, 3, &bvh);
+            let bvh = Bvh8::new(&random_spheres(count, 3));
+            assert_eq!(bvh.sphere_count(), count);
+            check(count, 3, &bvh);
+        }
+    }
+    #[test]
+    fn empty(){
+        let bvh = Bvh8::new(&[]);
+        assert_eq!(bvh.intersect(Vector3::new(0.0,0.0,0.0), Vector3::new(0.0,0.0,1.0)), None);
+        assert!(!bvh.occluded(Vector3::new(0.0,0.0,0.0), Vector3::new(0.0,0.0,1.0), 1.0));
+    }
+    #[test]
+    fn fewer_nodes(){
+        let spheres = random_spheres(300, 4);
+        let interior = Bvh::new(&spheres, 4).nodes().iter().filter(|node| matches!(node, Node::Interior{..})).count();
+        let bvh = Bvh4::new(&spheres);
+        let children = bvh.nodes.iter().flat_map(|node| node.children.iter()).filter(|child| **child != EMPTY).count();
+        //Nodes above the leaves are full, only those next to leaves can have empty slots.
+        assert!(children as f32 / bvh.nodes.len() as f32 >= 3.0, "{} children in {} nodes", children, bvh.nodes.len());
+        assert!(bvh.nodes.len() * 2 <= interior);
+    }
+}
@@ -0,0 +1,225 @@
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+
+use winit::event::DeviceEvent;
+use winit::event::ElementState;
+use winit::event::MouseButton;
+use winit::event::MouseScrollDelta;
+use winit::event::VirtualKeyCode;
+use winit::event::WindowEvent;
+
+use super::Camera;
+
+const MOUSE_SENSITIVITY : f32 = 0.003;
+const PAN_SENSITIVITY : f32 = 0.0015;
+const MAX_PITCH : f32 = 1.55;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum CameraMode{
+    ///Rotates around a target point, the wheel zooms and the middle mouse button pans.
+    Orbit,
+    ///Moves freely with WASD, space and control, the wheel changes the speed.
+    Fly,
+}
+///Turns winit input into camera movement.
+///Mouse look is active while the right mouse button is held, Tab switches between orbit and fly.
+///The brackets change the field of view, comma and period the aperture and page up and down the focus distance.
+pub struct CameraController{
+    pub mode : CameraMode,
+    ///Fly speed in scene units per second.
+    pub speed : f32,
+    target : Vector3<f32>,
+    distance : f32,
+    forward_pressed : bool,
+    backward_pressed : bool,
+    left_pressed : bool,
+    right_pressed : bool,
+    up_pressed : bool,
+    down_pressed : bool,
+    boost_pressed : bool,
+    rotating : bool,
+    panning : bool,
+    cursor_position : Option<(f64,f64)>,
+    pending_rotation : (f32,f32),
+    pending_pan : (f32,f32),
+    pending_zoom : f32,
+    pending_fov : f32,
+    pending_aperture : i32,
+    pending_focus : i32,
+    changed : bool,
+}
+impl CameraController{
+    ///Starts orbiting around the point the camera is focused on.
+    pub fn new(camera : &Camera) -> Self{
+        return Self{
+            mode : CameraMode::Orbit,
+            speed : 2.0,
+            target : camera.position + camera.forward() * camera.focus_distance,
+            distance : camera.focus_distance,
+            forward_pressed : false,
+            backward_pressed : false,
+            left_pressed : false,
+            right_pressed : false,
+            up_pressed : false,
+            down_pressed : false,
+            boost_pressed : false,
+            rotating : false,
+            panning : false,
+            cursor_position : None,
+            pending_rotation : (0.0,0.0),
+            pending_pan : (0.0,0.0),
+            pending_zoom : 0.0,
+            pending_fov : 0.0,
+            pending_aperture : 0,
+            pending_focus : 0,
+            changed : false,
+        };
+    }
+    ///Orbits around the point the camera is focused on again, used after the camera was edited directly.
+    pub fn reset(&mut self , camera : &Camera){
+        self.target = camera.position + camera.forward() * camera.focus_distance;
+        self.distance = camera.focus_distance;
+    }
+    ///Returns true when the event was consumed by the camera.
+    pub fn handle_window_event(&mut self , event : &WindowEvent) -> bool{
+        match event{
+            WindowEvent::KeyboardInput{input,..} => {
+                let pressed = input.state == ElementState::Pressed;
+                match input.virtual_keycode{
+                    Some(VirtualKeyCode::W) => self.forward_pressed = pressed,
+                    Some(VirtualKeyCode::S) => self.backward_pressed = pressed,
+                    Some(VirtualKeyCode::A) => self.left_pressed = pressed,
+                    Some(VirtualKeyCode::D) => self.right_pressed = pressed,
+                    Some(VirtualKeyCode::Space) => self.up_pressed = pressed,
+                    Some(VirtualKeyCode::LControl) => self.down_pressed = pressed,
+                    Some(VirtualKeyCode::LShift) => self.boost_pressed = pressed,
+                    Some(VirtualKeyCode::LBracket) if pressed => self.pending_fov -= 5.0,
+                    Some(VirtualKeyCode::RBracket) if pressed => self.pending_fov += 5.0,
+                    Some(VirtualKeyCode::Comma) if pressed => self.pending_aperture -= 1,
+                    Some(VirtualKeyCode::Period) if pressed => self.pending_aperture += 1,
+                    Some(VirtualKeyCode::PageDown) if pressed => self.pending_focus -= 1,
+                    Some(VirtualKeyCode::PageUp) if pressed => self.pending_focus += 1,
+                    Some(VirtualKeyCode::Tab) if pressed => {
+                        self.mode = if self.mode == CameraMode::Orbit{CameraMode::Fly}else{CameraMode::Orbit};
+                        self.changed = true;
+                    }
+                    _ => return false,
+                }
+                return true;
+            }
+            WindowEvent::MouseInput{state,button,..} => {
+                let pressed = *state == ElementState::Pressed;
+                match button{
+                    MouseButton::Right => self.rotating = pressed,
+                    MouseButton::Middle => self.panning = pressed,
+                    _ => return false,
+                }
+                return true;
+            }
+            WindowEvent::CursorMoved{position,..} => {
+                if let Some((x,y)) = self.cursor_position{
+                    if self.panning{
+                        self.pending_pan.0 += (position.x - x) as f32;
+                        self.pending_pan.1 += (position.y - y) as f32;
+                    }
+                }
+                self.cursor_position = Some((position.x,position.y));
+                return self.panning;
+            }
+            WindowEvent::MouseWheel{delta,..} => {
+                self.pending_zoom += match delta{
+                    MouseScrollDelta::LineDelta(_,y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 40.0,
+                };
+                return true;
+            }
+            WindowEvent::Focused(false) => {
+                self.release_all();
+                return false;
+            }
+            _ => return false,
+        }
+    }
+    ///Raw mouse motion is used for rotation so the view keeps turning at the window border.
+    pub fn handle_device_event(&mut self , event : &DeviceEvent){
+        if let DeviceEvent::MouseMotion{delta} = event{
+            if self.rotating{
+                self.pending_rotation.0 += delta.0 as f32;
+                self.pending_rotation.1 += delta.1 as f32;
+            }
+        }
+    }
+    ///Applies the input gathered since the last call, returns true when the camera moved and accumulation has to restart.
+    pub fn update(&mut self , camera : &mut Camera , delta_time : f32) -> bool{
+        let mut changed = std::mem::replace(&mut self.changed, false);
+        if self.pending_rotation != (0.0,0.0){
+            camera.yaw += self.pending_rotation.0 * MOUSE_SENSITIVITY;
+            camera.pitch = (camera.pitch - self.pending_rotation.1 * MOUSE_SENSITIVITY).clamp(-MAX_PITCH, MAX_PITCH);
+            self.pending_rotation = (0.0,0.0);
+            changed = true;
+        }
+        if self.pending_fov != 0.0{
+            camera.fov = (camera.fov + self.pending_fov).clamp(5.0, 150.0);
+            changed = true;
+        }
+        if self.pending_aperture != 0{
+            let aperture = if camera.aperture <= 0.0{0.005}else{camera.aperture * 1.25f32.powi(self.pending_aperture)};
+            camera.aperture = if aperture < 0.005{0.0}else{aperture.min(10.0)};
+            changed = true;
+        }
+        if self.pending_focus != 0{
+            camera.focus_distance = (camera.focus_distance * 1.1f32.powi(self.pending_focus)).max(0.01);
+            changed = true;
+        }
+        self.pending_fov = 0.0;
+        self.pending_aperture = 0;
+        self.pending_focus = 0;
+        match self.mode{
+            CameraMode::Orbit => {
+                if self.pending_zoom != 0.0{
+                    self.distance = (self.distance * 0.9f32.powf(self.pending_zoom)).max(0.01);
+                    camera.focus_distance = self.distance;
+                    changed = true;
+                }
+                if self.pending_pan != (0.0,0.0){
+                    let scale = PAN_SENSITIVITY * self.distance;
+                    self.target += camera.up() * (self.pending_pan.1 * scale) - camera.right() * (self.pending_pan.0 * scale);
+                    changed = true;
+                }
+                camera.position = self.target - camera.forward() * self.distance;
+            }
+            CameraMode::Fly => {
+                if self.pending_zoom != 0.0{
+                    self.speed = (self.speed * 1.2f32.powf(self.pending_zoom)).clamp(0.01, 1000.0);
+                }
+                let mut direction = Vector3::new(0.0,0.0,0.0);
+                if self.forward_pressed{direction += camera.forward();}
+                if self.backward_pressed{direction -= camera.forward();}
+                if self.right_pressed{direction += camera.right();}
+                if self.left_pressed{direction -= camera.right();}
+                if self.up_pressed{direction += Vector3::unit_y();}
+                if self.down_pressed{direction -= Vector3::unit_y();}
+                if direction.magnitude2() > 0.0{
+                    let speed = if self.boost_pressed{self.speed * 4.0}else{self.speed};
+                    camera.position += direction.normalize() * (speed * delta_time);
+                    changed = true;
+                }
+                self.target = camera.position + camera.forward() * self.distance;
+            }
+        }
+        self.pending_pan = (0.0,0.0);
+        self.pending_zoom = 0.0;
+        return changed;
+    }
+    fn release_all(&mut self){
+        self.forward_pressed = false;
+        self.backward_pressed = false;
+        self.left_pressed = false;
+        self.right_pressed = false;
+        self.up_pressed = false;
+        self.down_pressed = false;
+        self.boost_pressed = false;
+        self.rotating = false;
+        self.panning = false;
+    }
+}
@@ -0,0 +1,75 @@
+mod controller;
+
+pub use controller::CameraController;
+
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+
+///A thin lens camera, an aperture of zero gives a pinhole without depth of field.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Camera{
+    pub position : Vector3<f32>,
+    ///Rotation around the world up axis in radians, zero looks down -z.
+    pub yaw : f32,
+    ///Rotation above the horizon in radians.
+    pub pitch : f32,
+    ///Vertical field of view in degrees.
+    pub fov : f32,
+    ///Radius of the lens, in scene units.
+    pub aperture : f32,
+    ///Distance to the plane that is in perfect focus.
+    pub focus_distance : f32,
+}
+///A ray leaving the camera, the direction is normalized.
+pub struct CameraRay{
+    pub origin : Vector3<f32>,
+    pub direction : Vector3<f32>,
+}
+impl Camera{
+    pub fn look_at(position : Vector3<f32> , target : Vector3<f32> , fov : f32) -> Self{
+        let direction = (target - position).normalize();
+        return Self{
+            position,
+            yaw : direction.x.atan2(-direction.z),
+            pitch : direction.y.asin(),
+            fov,
+            aperture : 0.0,
+            focus_distance : (target - position).magnitude(),
+        };
+    }
+    pub fn forward(&self) -> Vector3<f32>{
+        return Vector3::new(self.yaw.sin() * self.pitch.cos(),self.pitch.sin(),-self.yaw.cos() * self.pitch.cos());
+    }
+    pub fn right(&self) -> Vector3<f32>{
+        return Vector3::new(self.yaw.cos(),0.0,self.yaw.sin());
+    }
+    pub fn up(&self) -> Vector3<f32>{
+        return self.right().cross(self.forward());
+    }
+    ///The direction through the film position (u,v) in [-1,1] from the center of the lens, v pointing up.
+    pub fn direction(&self , u : f32 , v : f32 , aspect : f32) -> Vector3<f32>{
+        let tan_half_fov = (self.fov.to_radians() * 0.5).tan();
+        return (self.forward() + self.right() * (u * tan_half_fov * aspect) + self.up() * (v * tan_half_fov)).normalize();
+    }
+    ///The film position a point is seen at through the center of the lens, None for points behind the camera.
+    pub fn project(&self , point : Vector3<f32> , aspect : f32) -> Option<(f32,f32)>{
+        let tan_half_fov = (self.fov.to_radians() * 0.5).tan();
+        let offset = point - self.position;
+        let depth = offset.dot(self.forward());
+        if depth <= 0.0{return None;}
+        return Some((offset.dot(self.right()) / (depth * tan_half_fov * aspect),offset.dot(self.up()) / (depth * tan_half_fov)));
+    }
+    ///Creates the ray through the film position (u,v) in [-1,1], v pointing up, lens_sample in [0,1)².
+    pub fn generate_ray(&self , u : f32 , v : f32 , aspect : f32 , lens_sample : [f32;2]) -> CameraRay{
+        let (forward,right,up) = (self.forward(),self.right(),self.up());
+        let direction = self.direction(u, v, aspect);
+        if self.aperture <= 0.0{
+            return CameraRay{origin : self.position, direction};
+        }
+        let focus_point = self.position + direction * (self.focus_distance / direction.dot(forward));
+        let radius = self.aperture * lens_sample[0].sqrt();
+        let angle = 2.0 * std::f32::consts::PI * lens_sample[1];
+        let origin = self.position + right * (radius * angle.cos()) + up * (radius * angle.sin());
+        return CameraRay{origin, direction : (focus_point - origin).normalize()};
+    }
+}
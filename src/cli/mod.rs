@@ -0,0 +1,228 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use clap::Parser;
+use clap::Subcommand;
+
+use mport::renderer;
+use mport::denoise;
+use mport::config::Config;
+use mport::config::parse_size;
+use mport::config::present_mode_key;
+use mport::post::Tonemapper;
+use mport::sampler::SamplerKind;
+use mport::tracer::Aov;
+use mport::window;
+use mport::window::Fullscreen;
+use mport::viewer::ViewOptions;
+use mport::headless::RenderOptions;
+
+#[derive(Parser,Debug)]
+#[command(name = "mport", version, about = "A vulkan path tracer.")]
+pub struct Cli{
+    ///Index or part of the name of the vulkan device to use, see `mport devices`.
+    #[arg(long, global = true)]
+    pub device : Option<String>,
+    ///Enable the khronos validation layer.
+    #[arg(long, global = true, conflicts_with = "no_validation")]
+    pub validation : bool,
+    ///Disable the khronos validation layer.
+    #[arg(long, global = true)]
+    pub no_validation : bool,
+    ///Requested present mode: immediate, mailbox, fifo or fifo-relaxed.
+    #[arg(long, global = true, value_parser = parse_present_mode)]
+    pub present_mode : Option<ash::vk::PresentModeKHR>,
+    ///Trace on the gpu compute queue instead of on the cpu, spheres are scanned without the bvh.
+    #[arg(long, global = true)]
+    pub gpu_tracing : bool,
+    #[command(subcommand)]
+    pub command : Option<Command>,
+}
+#[derive(Subcommand,Debug)]
+pub enum Command{
+    ///Open a scene in the interactive viewer.
+    View(ViewArgs),
+    ///Render a scene without a window and write the result to a file.
+    Render(RenderArgs),
+    ///List the vulkan devices and their queue families.
+    Devices,
+    ///Show application and vulkan loader information.
+    Info,
+    ///Inspect the layered configuration.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+#[derive(Subcommand,Debug)]
+pub enum ConfigCommand{
+    ///Print the effective configuration as toml.
+    Dump{
+        ///Scene whose directory is searched for a mport.toml.
+        scene : Option<String>,
+    },
+}
+#[derive(Args,Debug,Default)]
+pub struct ViewArgs{
+    ///Scene file or built in scene name.
+    pub scene : Option<String>,
+    ///Prefer an hdr swapchain when the display supports one.
+    #[arg(long)]
+    pub hdr : bool,
+    ///Window size in logical pixels as WIDTHxHEIGHT.
+    #[arg(long, value_parser = parse_size)]
+    pub size : Option<(u32,u32)>,
+    ///Fullscreen mode: windowed, borderless or exclusive.
+    #[arg(long, value_parser = parse_fullscreen)]
+    pub fullscreen : Option<Fullscreen>,
+    ///Tracer resolution relative to the window, between 0.25 and 2.
+    #[arg(long, value_parser = parse_render_scale)]
+    pub render_scale : Option<f32>,
+    ///Tonemapping operator: linear, reinhard, filmic, aces or agx.
+    #[arg(long, value_parser = parse_tonemapper)]
+    pub tonemapper : Option<Tonemapper>,
+    ///Comma separated aovs to record: albedo, normal, position, depth, object_id, material_id, diffuse_direct, diffuse_indirect, specular_direct and specular_indirect.
+    #[arg(long, value_delimiter = ',', value_parser = parse_aov)]
+    pub aovs : Vec<Aov>,
+    ///Denoise the accumulation, records the albedo, normal and depth aovs to guide the filter.
+    #[arg(long)]
+    pub denoise : bool,
+    ///Denoiser strength between 0 and 4, 1 unless set in a config file.
+    #[arg(long, value_parser = parse_denoise_strength)]
+    pub denoise_strength : Option<f32>,
+    ///Run the denoiser filter as a compute pass on the gpu instead of on the cpu.
+    #[arg(long)]
+    pub denoise_compute : bool,
+    ///Sample sequence: random, sobol, blue-noise or stratified.
+    #[arg(long, value_parser = parse_sampler)]
+    pub sampler : Option<SamplerKind>,
+    ///Seed of the sample sequence, the same seed and sampler give the same image.
+    #[arg(long)]
+    pub seed : Option<u32>,
+    ///Profile from the start and write a Chrome trace of the cpu and gpu passes to this file on exit, P toggles profiling.
+    #[arg(long)]
+    pub profile : Option<PathBuf>,
+}
+#[derive(Args,Debug)]
+pub struct RenderArgs{
+    ///Scene file or built in scene name.
+    pub scene : Option<String>,
+    ///Output file, .exr and .hdr keep the linear radiance, .png is tonemapped.
+    #[arg(short, long)]
+    pub output : PathBuf,
+    ///Samples per pixel, 64 unless set in a config file.
+    #[arg(long)]
+    pub spp : Option<u32>,
+    ///Image size as WIDTHxHEIGHT, 1280x720 unless set in a config file.
+    #[arg(long, value_parser = parse_size)]
+    pub size : Option<(u32,u32)>,
+    ///Comma separated aovs to write: albedo, normal, position, depth, object_id, material_id, diffuse_direct, diffuse_indirect, specular_direct and specular_indirect.
+    #[arg(long, value_delimiter = ',', value_parser = parse_aov)]
+    pub aovs : Vec<Aov>,
+    ///Write half float OpenEXR channels.
+    #[arg(long)]
+    pub half : bool,
+    ///Tonemapping operator used for png output.
+    #[arg(long, value_parser = parse_tonemapper)]
+    pub tonemapper : Option<Tonemapper>,
+    ///Exposure in stops used for png output.
+    #[arg(long, allow_hyphen_values = true)]
+    pub exposure : Option<f32>,
+    ///Denoise the accumulation, records the albedo, normal and depth aovs to guide the filter and writes them as well.
+    #[arg(long)]
+    pub denoise : bool,
+    ///Denoiser strength between 0 and 4, 1 unless set in a config file.
+    #[arg(long, value_parser = parse_denoise_strength)]
+    pub denoise_strength : Option<f32>,
+    ///Sample sequence: random, sobol, blue-noise or stratified, stratified divides each pixel into spp strata.
+    #[arg(long, value_parser = parse_sampler)]
+    pub sampler : Option<SamplerKind>,
+    ///Seed of the sample sequence, the same seed and sampler give the same image.
+    #[arg(long)]
+    pub seed : Option<u32>,
+    ///Write a Chrome trace of the render passes to this file and print their timings.
+    #[arg(long)]
+    pub profile : Option<PathBuf>,
+}
+impl ViewArgs{
+    ///The options of the viewer that are not part of the configuration.
+    pub fn options(&self) -> ViewOptions{
+        return ViewOptions{scene : self.scene.clone(), aovs : self.aovs.clone(), profile : self.profile.clone()};
+    }
+}
+impl RenderArgs{
+    ///The options of the render that are not part of the configuration.
+    pub fn options(&self) -> RenderOptions{
+        return RenderOptions{scene : self.scene.clone(), output : self.output.clone(), aovs : self.aovs.clone(), half : self.half, profile : self.profile.clone()};
+    }
+}
+impl Cli{
+    ///The validation flag if one was given on the command line.
+    pub fn validation(&self) -> Option<bool>{
+        if self.validation{return Some(true);}
+        if self.no_validation{return Some(false);}
+        return None;
+    }
+    ///The configuration layer set by command line flags, flags that were not given are left unset.
+    pub fn config(&self) -> Config{
+        let mut config = Config::default();
+        config.renderer.device = self.device.clone();
+        config.renderer.validation = self.validation();
+        config.renderer.present_mode = self.present_mode.map(present_mode_key);
+        if self.gpu_tracing{config.renderer.gpu_tracing = Some(true);}
+        match &self.command{
+            Some(Command::View(view)) => {
+                if view.hdr{config.renderer.hdr = Some(true);}
+                config.window.width = view.size.map(|(width,_)| width);
+                config.window.height = view.size.map(|(_,height)| height);
+                config.window.fullscreen = view.fullscreen.map(|mode| String::from(mode.name()));
+                config.window.render_scale = view.render_scale;
+                config.post.tonemapper = view.tonemapper.map(|tonemapper| tonemapper.name().to_lowercase());
+                if view.denoise{config.denoise.enabled = Some(true);}
+                config.denoise.strength = view.denoise_strength;
+                if view.denoise_compute{config.denoise.compute = Some(true);}
+                config.sampler.kind = view.sampler.map(|kind| kind.name().to_lowercase());
+                config.sampler.seed = view.seed;
+            }
+            Some(Command::Render(render)) => {
+                config.render.spp = render.spp;
+                config.render.width = render.size.map(|(width,_)| width);
+                config.render.height = render.size.map(|(_,height)| height);
+                config.post.tonemapper = render.tonemapper.map(|tonemapper| tonemapper.name().to_lowercase());
+                config.post.exposure = render.exposure;
+                if render.exposure.is_some(){config.post.auto_exposure = Some(false);}
+                if render.denoise{config.denoise.enabled = Some(true);}
+                config.denoise.strength = render.denoise_strength;
+                config.sampler.kind = render.sampler.map(|kind| kind.name().to_lowercase());
+                config.sampler.seed = render.seed;
+            }
+            _ => {}
+        }
+        return config;
+    }
+}
+fn parse_present_mode(name : &str) -> Result<ash::vk::PresentModeKHR,String>{
+    return renderer::parse_present_mode(name).ok_or_else(|| format!("unknown present mode {}",name));
+}
+fn parse_tonemapper(name : &str) -> Result<Tonemapper,String>{
+    return Tonemapper::parse(name).ok_or_else(|| format!("unknown tonemapper {}",name));
+}
+fn parse_sampler(name : &str) -> Result<SamplerKind,String>{
+    return SamplerKind::parse(name).ok_or_else(|| format!("unknown sampler {}",name));
+}
+fn parse_fullscreen(name : &str) -> Result<Fullscreen,String>{
+    return Fullscreen::parse(name).ok_or_else(|| format!("unknown fullscreen mode {}",name));
+}
+fn parse_render_scale(scale : &str) -> Result<f32,String>{
+    match scale.parse::<f32>(){
+        Ok(scale) if (window::MIN_RENDER_SCALE..=window::MAX_RENDER_SCALE).contains(&scale) => return Ok(scale),
+        _ => return Err(format!("invalid render scale {}, expected a value between {} and {}",scale,window::MIN_RENDER_SCALE,window::MAX_RENDER_SCALE)),
+    }
+}
+fn parse_aov(name : &str) -> Result<Aov,String>{
+    return Aov::parse(name).ok_or_else(|| format!("unknown aov {}",name));
+}
+fn parse_denoise_strength(strength : &str) -> Result<f32,String>{
+    match strength.parse::<f32>(){
+        Ok(strength) if (denoise::MIN_STRENGTH..=denoise::MAX_STRENGTH).contains(&strength) => return Ok(strength),
+        _ => return Err(format!("invalid denoise strength {}, expected a value between {} and {}",strength,denoise::MIN_STRENGTH,denoise::MAX_STRENGTH)),
+    }
+}
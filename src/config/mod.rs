@@ -0,0 +1,320 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::denoise;
+use super::post;
+use super::renderer;
+use super::sampler;
+use super::window;
+
+///Name of the per user file inside the config directory and of the per project file next to a scene.
+const USER_CONFIG : &str = "config.toml";
+const PROJECT_CONFIG : &str = "mport.toml";
+
+///Persistent settings, every value is optional so a layer only overrides what it sets.
+///Layers are merged from defaults, the user config, the scene directory config, environment variables and finally the command line.
+#[derive(Serialize,Deserialize,Clone,Debug,Default,PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config{
+    pub window : WindowConfig,
+    pub renderer : RendererConfig,
+    pub render : RenderConfig,
+    pub post : PostConfig,
+    pub denoise : DenoiseConfig,
+    pub sampler : SamplerConfig,
+}
+#[derive(Serialize,Deserialize,Clone,Debug,Default,PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig{
+    ///Window size in logical pixels.
+    pub width : Option<u32>,
+    pub height : Option<u32>,
+    ///windowed, borderless or exclusive.
+    pub fullscreen : Option<String>,
+    ///Tracer resolution relative to the window, the frame is scaled to fit when presenting.
+    pub render_scale : Option<f32>,
+}
+#[derive(Serialize,Deserialize,Clone,Debug,Default,PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RendererConfig{
+    ///Index or part of the name of the vulkan device.
+    pub device : Option<String>,
+    pub validation : Option<bool>,
+    pub present_mode : Option<String>,
+    pub hdr : Option<bool>,
+    ///Trace on the gpu compute queue instead of on the cpu.
+    pub gpu_tracing : Option<bool>,
+}
+#[derive(Serialize,Deserialize,Clone,Debug,Default,PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderConfig{
+    ///Samples per pixel for headless renders.
+    pub spp : Option<u32>,
+    pub width : Option<u32>,
+    pub height : Option<u32>,
+}
+#[derive(Serialize,Deserialize,Clone,Debug,Default,PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostConfig{
+    pub tonemapper : Option<String>,
+    ///Exposure in stops, the compensation when auto exposure is enabled.
+    pub exposure : Option<f32>,
+    pub auto_exposure : Option<bool>,
+    pub temperature : Option<f32>,
+    pub tint : Option<f32>,
+}
+#[derive(Serialize,Deserialize,Clone,Debug,Default,PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DenoiseConfig{
+    pub enabled : Option<bool>,
+    ///How strongly differences in illumination are smoothed, between 0 and 4.
+    pub strength : Option<f32>,
+    ///Filter on the gpu compute queue in the viewer.
+    pub compute : Option<bool>,
+}
+#[derive(Serialize,Deserialize,Clone,Debug,Default,PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SamplerConfig{
+    ///random, sobol, blue-noise or stratified.
+    pub kind : Option<String>,
+    ///Renders with the same seed and sampler are identical.
+    pub seed : Option<u32>,
+}
+
+macro_rules! merge_fields{
+    ($target:expr , $layer:expr , $($field:ident),*) => {
+        $(if $layer.$field.is_some(){$target.$field = $layer.$field;})*
+    };
+}
+
+impl Config{
+    ///The built in settings every other layer is merged over.
+    pub fn defaults() -> Self{
+        let post = post::PostSettings::default();
+        let denoise = denoise::DenoiseSettings::default();
+        let sampler = sampler::SamplerSettings::default();
+        return Self{
+            window : WindowConfig{
+                width : Some(1280),
+                height : Some(720),
+                fullscreen : Some(String::from(window::Fullscreen::Windowed.name())),
+                render_scale : Some(1.0),
+            },
+            renderer : RendererConfig{
+                device : None,
+                validation : Some(*super::VALIDATION_ENABLED),
+                present_mode : Some(present_mode_key(super::PRESENT_MODE)),
+                hdr : Some(*super::HDR_ENABLED),
+                gpu_tracing : Some(false),
+            },
+            render : RenderConfig{spp : Some(64), width : Some(1280), height : Some(720)},
+            post : PostConfig{
+                tonemapper : Some(post.tonemapper.name().to_lowercase()),
+                exposure : Some(0.0),
+                auto_exposure : Some(false),
+                temperature : Some(post.temperature),
+                tint : Some(post.tint),
+            },
+            denoise : DenoiseConfig{enabled : Some(denoise.enabled), strength : Some(denoise.strength), compute : Some(denoise.compute)},
+            sampler : SamplerConfig{kind : Some(sampler.kind.name().to_lowercase()), seed : Some(sampler.seed)},
+        };
+    }
+    ///Builds the effective configuration for a run, returns it with the names of the layers that were found.
+    ///The command line layer is merged last, it only sets the values given as flags.
+    pub fn load(command_line : Self , scene : Option<&str>) -> Result<(Self,Vec<String>),String>{
+        let mut config = Self::defaults();
+        let mut sources = vec!(String::from("defaults"));
+        let mut files = vec!();
+        if let Some(dir) = user_config_dir(){
+            files.push(dir.join(USER_CONFIG));
+        }
+        if let Some(dir) = scene.filter(|&name| name != "default").map(scene_dir){
+            files.push(dir.join(PROJECT_CONFIG));
+        }
+        for path in files{
+            if path.is_file(){
+                config.merge(Self::read(&path)?);
+                sources.push(path.display().to_string());
+            }
+        }
+        let env = Self::from_env()?;
+        if env != Self::default(){
+            config.merge(env);
+            sources.push(String::from("environment"));
+        }
+        if command_line != Self::default(){
+            config.merge(command_line);
+            sources.push(String::from("command line"));
+        }
+        return Ok((config,sources));
+    }
+    ///Reads a single config file.
+    pub fn read(path : &Path) -> Result<Self,String>{
+        let source = std::fs::read_to_string(path).map_err(|error| format!("Failed to read config {} : {}.",path.display(),error))?;
+        let config : Self = toml::from_str(&source).map_err(|error| format!("Failed to parse config {} : {}",path.display(),error))?;
+        config.validate().map_err(|error| format!("Invalid config {} : {}.",path.display(),error))?;
+        return Ok(config);
+    }
+    ///Overrides every value that is set in the layer.
+    pub fn merge(&mut self , layer : Self){
+        merge_fields!(self.window, layer.window, width, height, fullscreen, render_scale);
+        merge_fields!(self.renderer, layer.renderer, device, validation, present_mode, hdr, gpu_tracing);
+        merge_fields!(self.render, layer.render, spp, width, height);
+        merge_fields!(self.post, layer.post, tonemapper, exposure, auto_exposure, temperature, tint);
+        merge_fields!(self.denoise, layer.denoise, enabled, strength, compute);
+        merge_fields!(self.sampler, layer.sampler, kind, seed);
+    }
+    fn validate(&self) -> Result<(),String>{
+        if let Some(name) = &self.renderer.present_mode{
+            renderer::parse_present_mode(name).ok_or_else(|| format!("unknown present mode {}",name))?;
+        }
+        if let Some(name) = &self.post.tonemapper{
+            post::Tonemapper::parse(name).ok_or_else(|| format!("unknown tonemapper {}",name))?;
+        }
+        if let Some(name) = &self.sampler.kind{
+            sampler::SamplerKind::parse(name).ok_or_else(|| format!("unknown sampler {}",name))?;
+        }
+        if let Some(name) = &self.window.fullscreen{
+            window::Fullscreen::parse(name).ok_or_else(|| format!("unknown fullscreen mode {}",name))?;
+        }
+        if let Some(scale) = self.window.render_scale{
+            if !(window::MIN_RENDER_SCALE..=window::MAX_RENDER_SCALE).contains(&scale){
+                return Err(format!("render scale must be between {} and {}",window::MIN_RENDER_SCALE,window::MAX_RENDER_SCALE));
+            }
+        }
+        if let Some(strength) = self.denoise.strength{
+            if !(denoise::MIN_STRENGTH..=denoise::MAX_STRENGTH).contains(&strength){
+                return Err(format!("denoise strength must be between {} and {}",denoise::MIN_STRENGTH,denoise::MAX_STRENGTH));
+            }
+        }
+        let sizes = [self.window.width,self.window.height,self.render.width,self.render.height];
+        if sizes.contains(&Some(0)){
+            return Err(String::from("sizes must be larger than zero"));
+        }
+        return Ok(());
+    }
+    ///The layer set by MPORT_* environment variables.
+    fn from_env() -> Result<Self,String>{
+        let var = |name : &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let mut config = Self::default();
+        config.renderer.device = var("MPORT_DEVICE");
+        config.renderer.validation = var("MPORT_VALIDATION").map(|value| parse_env("MPORT_VALIDATION", &value, parse_bool)).transpose()?;
+        config.renderer.present_mode = var("MPORT_PRESENT_MODE");
+        config.renderer.hdr = var("MPORT_HDR").map(|value| parse_env("MPORT_HDR", &value, parse_bool)).transpose()?;
+        config.renderer.gpu_tracing = var("MPORT_GPU_TRACING").map(|value| parse_env("MPORT_GPU_TRACING", &value, parse_bool)).transpose()?;
+        if let Some(value) = var("MPORT_WINDOW_SIZE"){
+            let (width,height) = parse_env("MPORT_WINDOW_SIZE", &value, |value| parse_size(value).ok())?;
+            config.window.width = Some(width);
+            config.window.height = Some(height);
+        }
+        config.window.fullscreen = var("MPORT_FULLSCREEN");
+        config.window.render_scale = var("MPORT_RENDER_SCALE").map(|value| parse_env("MPORT_RENDER_SCALE", &value, |value| value.parse().ok())).transpose()?;
+        config.render.spp = var("MPORT_SPP").map(|value| parse_env("MPORT_SPP", &value, |value| value.parse().ok())).transpose()?;
+        if let Some(value) = var("MPORT_SIZE"){
+            let (width,height) = parse_env("MPORT_SIZE", &value, |value| parse_size(value).ok())?;
+            config.render.width = Some(width);
+            config.render.height = Some(height);
+        }
+        config.post.tonemapper = var("MPORT_TONEMAPPER");
+        config.post.exposure = var("MPORT_EXPOSURE").map(|value| parse_env("MPORT_EXPOSURE", &value, |value| value.parse().ok())).transpose()?;
+        config.denoise.enabled = var("MPORT_DENOISE").map(|value| parse_env("MPORT_DENOISE", &value, parse_bool)).transpose()?;
+        config.denoise.strength = var("MPORT_DENOISE_STRENGTH").map(|value| parse_env("MPORT_DENOISE_STRENGTH", &value, |value| value.parse().ok())).transpose()?;
+        config.denoise.compute = var("MPORT_DENOISE_COMPUTE").map(|value| parse_env("MPORT_DENOISE_COMPUTE", &value, parse_bool)).transpose()?;
+        config.sampler.kind = var("MPORT_SAMPLER");
+        config.sampler.seed = var("MPORT_SEED").map(|value| parse_env("MPORT_SEED", &value, |value| value.parse().ok())).transpose()?;
+        config.validate().map_err(|error| format!("Invalid environment : {}.",error))?;
+        return Ok(config);
+    }
+    pub fn renderer_settings(&self) -> renderer::RendererSettings{
+        return renderer::RendererSettings{
+            present_mode : self.renderer.present_mode.as_deref().and_then(renderer::parse_present_mode).unwrap_or(super::PRESENT_MODE),
+            hdr : self.renderer.hdr.unwrap_or(*super::HDR_ENABLED),
+            validation : self.renderer.validation.unwrap_or(*super::VALIDATION_ENABLED),
+            device : self.renderer.device.clone(),
+            app_info : renderer::AppInfo::default(),
+        };
+    }
+    pub fn gpu_tracing(&self) -> bool{
+        return self.renderer.gpu_tracing.unwrap_or(false);
+    }
+    pub fn post_settings(&self) -> post::PostSettings{
+        let mut settings = post::PostSettings::default();
+        if let Some(tonemapper) = self.post.tonemapper.as_deref().and_then(post::Tonemapper::parse){
+            settings.tonemapper = tonemapper;
+        }
+        let exposure = self.post.exposure.unwrap_or(0.0);
+        settings.exposure = if self.post.auto_exposure.unwrap_or(false){post::Exposure::Auto(exposure)}else{post::Exposure::Manual(exposure)};
+        settings.temperature = self.post.temperature.unwrap_or(settings.temperature);
+        settings.tint = self.post.tint.unwrap_or(settings.tint);
+        return settings;
+    }
+    pub fn denoise_settings(&self) -> denoise::DenoiseSettings{
+        let defaults = denoise::DenoiseSettings::default();
+        return denoise::DenoiseSettings{
+            enabled : self.denoise.enabled.unwrap_or(defaults.enabled),
+            strength : self.denoise.strength.unwrap_or(defaults.strength),
+            compute : self.denoise.compute.unwrap_or(defaults.compute),
+        };
+    }
+    ///The stratified sampler divides each pixel into the configured samples per pixel.
+    pub fn sampler_settings(&self) -> sampler::SamplerSettings{
+        let defaults = sampler::SamplerSettings::default();
+        return sampler::SamplerSettings{
+            kind : self.sampler.kind.as_deref().and_then(sampler::SamplerKind::parse).unwrap_or(defaults.kind),
+            seed : self.sampler.seed.unwrap_or(defaults.seed),
+            samples_per_pixel : self.spp(),
+        };
+    }
+    pub fn window_size(&self) -> (u32,u32){
+        return (self.window.width.unwrap_or(1280),self.window.height.unwrap_or(720));
+    }
+    pub fn fullscreen(&self) -> window::Fullscreen{
+        return self.window.fullscreen.as_deref().and_then(window::Fullscreen::parse).unwrap_or(window::Fullscreen::Windowed);
+    }
+    pub fn render_scale(&self) -> f32{
+        return self.window.render_scale.unwrap_or(1.0);
+    }
+    pub fn render_size(&self) -> (u32,u32){
+        return (self.render.width.unwrap_or(1280),self.render.height.unwrap_or(720));
+    }
+    pub fn spp(&self) -> u32{
+        return self.render.spp.unwrap_or(64);
+    }
+    ///The configuration as a toml document.
+    pub fn to_toml(&self) -> String{
+        return toml::to_string(self).expect("Failed to serialize config.");
+    }
+}
+///Where the per user config lives, $XDG_CONFIG_HOME/mport on linux, the application support or roaming app data folder elsewhere.
+pub fn user_config_dir() -> Option<PathBuf>{
+    return dirs::config_dir().map(|dir| dir.join("mport"));
+}
+fn scene_dir(scene : &str) -> PathBuf{
+    match Path::new(scene).parent(){
+        Some(dir) if !dir.as_os_str().is_empty() => return dir.to_path_buf(),
+        _ => return PathBuf::from("."),
+    }
+}
+pub fn parse_size(size : &str) -> Result<(u32,u32),String>{
+    match size.split_once('x').map(|(width,height)| (width.trim().parse::<u32>(),height.trim().parse::<u32>())){
+        Some((Ok(width),Ok(height))) if width > 0 && height > 0 => return Ok((width,height)),
+        _ => return Err(format!("invalid size {}, expected WIDTHxHEIGHT",size)),
+    }
+}
+///The name present modes are stored under, such as fifo-relaxed.
+pub fn present_mode_key(mode : ash::vk::PresentModeKHR) -> String{
+    return renderer::present_mode_name(mode).to_lowercase().replace(' ', "-");
+}
+fn parse_bool(value : &str) -> Option<bool>{
+    match value.trim().to_lowercase().as_str(){
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+fn parse_env<T>(name : &str , value : &str , parse : impl Fn(&str) -> Option<T>) -> Result<T,String>{
+    return parse(value).ok_or_else(|| format!("Invalid value {} for {}.",value,name));
+}
@@ -0,0 +1,104 @@
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+
+use super::Frame;
+use super::pixel_luminance;
+
+///Each iteration doubles the distance between the taps, five reach 16 pixels away for a 61 pixel wide footprint.
+pub const ITERATIONS : u32 = 5;
+///B3 spline weights of the 5x5 kernel.
+const KERNEL : [f32;5] = [1.0 / 16.0,1.0 / 4.0,3.0 / 8.0,1.0 / 4.0,1.0 / 16.0];
+///Edge stopping parameters from the SVGF paper.
+const SIGMA_LUMINANCE : f32 = 4.0;
+const SIGMA_NORMAL : i32 = 128;
+const SIGMA_DEPTH : f32 = 1.0;
+
+///Filters the illumination, pixels without a surface are left untouched and never used as neighbours.
+///shaders/atrous.comp is the same filter for the compute queue.
+pub fn filter(frame : &Frame , illumination : &[[f32;3]] , strength : f32) -> Vec<[f32;3]>{
+    let (width,height) = (frame.width as i64,frame.height as i64);
+    let depth : Vec<f32> = frame.depth.iter().map(|depth| depth[0]).collect();
+    let depth_gradient = depth_gradients(width, height, &depth);
+    let mut color = illumination.to_vec();
+    let mut variance = luminance_variance(width, height, &color, &depth);
+    for iteration in 0..ITERATIONS{
+        let step = 1i64 << iteration;
+        let mut next_color = color.clone();
+        let mut next_variance = variance.clone();
+        for y in 0..height{
+            for x in 0..width{
+                let index = (y * width + x) as usize;
+                if depth[index] <= 0.0{continue;}
+                let luminance = pixel_luminance(color[index]);
+                let normal = Vector3::from(frame.normal[index]);
+                let luminance_scale = SIGMA_LUMINANCE * strength * variance[index].max(0.0).sqrt() + 1e-4;
+                let mut sum = [0.0;3];
+                let mut sum_variance = 0.0;
+                let mut total = 0.0;
+                for (j,kernel_y) in KERNEL.iter().enumerate(){
+                    for (i,kernel_x) in KERNEL.iter().enumerate(){
+                        let (offset_x,offset_y) = ((i as i64 - 2) * step,(j as i64 - 2) * step);
+                        let (qx,qy) = (x + offset_x,y + offset_y);
+                        if qx < 0 || qy < 0 || qx >= width || qy >= height{continue;}
+                        let q = (qy * width + qx) as usize;
+                        if depth[q] <= 0.0{continue;}
+                        let distance = ((offset_x * offset_x + offset_y * offset_y) as f32).sqrt();
+                        let depth_weight = -(depth[index] - depth[q]).abs() / (SIGMA_DEPTH * depth_gradient[index] * distance + 1e-3 * depth[index]);
+                        let normal_weight = normal.dot(Vector3::from(frame.normal[q])).max(0.0).powi(SIGMA_NORMAL);
+                        let luminance_weight = -(luminance - pixel_luminance(color[q])).abs() / luminance_scale;
+                        let weight = kernel_x * kernel_y * normal_weight * (depth_weight + luminance_weight).exp();
+                        for c in 0..3{
+                            sum[c] += color[q][c] * weight;
+                        }
+                        sum_variance += variance[q] * weight * weight;
+                        total += weight;
+                    }
+                }
+                if total > 0.0{
+                    next_color[index] = [sum[0] / total,sum[1] / total,sum[2] / total];
+                    next_variance[index] = sum_variance / (total * total);
+                }
+            }
+        }
+        color = next_color;
+        variance = next_variance;
+    }
+    return color;
+}
+///The variance of the luminance in the 3x3 neighbourhood of each pixel, an estimate of the noise as the illumination is otherwise smooth.
+fn luminance_variance(width : i64 , height : i64 , color : &[[f32;3]] , depth : &[f32]) -> Vec<f32>{
+    let mut variance = vec!(0.0;color.len());
+    for y in 0..height{
+        for x in 0..width{
+            let (mut sum,mut sum_squares,mut count) = (0.0,0.0,0.0);
+            for qy in (y - 1).max(0)..(y + 2).min(height){
+                for qx in (x - 1).max(0)..(x + 2).min(width){
+                    let q = (qy * width + qx) as usize;
+                    if depth[q] <= 0.0{continue;}
+                    let luminance = pixel_luminance(color[q]);
+                    sum += luminance;
+                    sum_squares += luminance * luminance;
+                    count += 1.0;
+                }
+            }
+            if count > 0.0{
+                let mean = sum / count;
+                variance[(y * width + x) as usize] = (sum_squares / count - mean * mean).max(0.0);
+            }
+        }
+    }
+    return variance;
+}
+///How much the depth changes per pixel, so slanted surfaces are not mistaken for edges.
+fn depth_gradients(width : i64 , height : i64 , depth : &[f32]) -> Vec<f32>{
+    let at = |x : i64 , y : i64| depth[(y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize];
+    let mut gradients = vec!(0.0;depth.len());
+    for y in 0..height{
+        for x in 0..width{
+            let dx = (at(x + 1, y) - at(x - 1, y)).abs() / 2.0;
+            let dy = (at(x, y + 1) - at(x, y - 1)).abs() / 2.0;
+            gradients[(y * width + x) as usize] = dx.max(dy);
+        }
+    }
+    return gradients;
+}
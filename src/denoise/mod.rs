@@ -0,0 +1,107 @@
+mod temporal;
+mod atrous;
+
+use super::camera::Camera;
+use super::tracer::Accumulator;
+use super::tracer::Tracer;
+use super::tracer::Aov;
+use super::light::luminance;
+
+use temporal::History;
+
+pub use atrous::ITERATIONS;
+
+pub const MIN_STRENGTH : f32 = 0.0;
+pub const MAX_STRENGTH : f32 = 4.0;
+///The aovs that guide the filter, the tracer has to record them for the denoiser to run.
+pub const GUIDE_AOVS : [Aov;3] = [Aov::Albedo,Aov::Normal,Aov::Depth];
+///Albedos darker than this are not divided out, it would only amplify the noise.
+const MIN_ALBEDO : f32 = 0.01;
+
+///Records the aovs the denoiser is guided by.
+pub fn enable_guides(tracer : &mut Tracer){
+    for &aov in GUIDE_AOVS.iter(){
+        tracer.enable_aov(aov);
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct DenoiseSettings{
+    pub enabled : bool,
+    ///Scales how different neighbouring pixels may be and still be averaged, zero disables the filter.
+    pub strength : f32,
+    ///Filter on the gpu compute queue in the viewer, renders without a window always filter on the cpu.
+    pub compute : bool,
+}
+impl Default for DenoiseSettings{
+    fn default() -> Self{
+        return Self{enabled : false, strength : 1.0, compute : false};
+    }
+}
+///The accumulated image and its guides, row major starting at the top left.
+pub struct Frame<'a>{
+    pub width : u32,
+    pub height : u32,
+    pub radiance : &'a [[f32;3]],
+    pub albedo : &'a [[f32;3]],
+    pub normal : &'a [[f32;3]],
+    pub depth : &'a [[f32;3]],
+    ///Samples per pixel in the radiance.
+    pub samples : u32,
+}
+///Edge avoiding à-trous wavelet filter after Dammertz et al. 2010, with the temporal reprojection and variance guided weights of SVGF, Schied et al. 2017.
+///The filter works on illumination, the albedo is divided out before and multiplied back after so textures stay sharp.
+pub struct Denoiser{
+    pub settings : DenoiseSettings,
+    history : Option<History>,
+}
+impl Denoiser{
+    pub fn new(settings : DenoiseSettings) -> Self{
+        return Self{settings, history : None};
+    }
+    ///Forgets the previous frames, for changes reprojection can not follow such as a new scene.
+    pub fn reset(&mut self){
+        self.history = None;
+    }
+    ///Denoises the accumulation seen through the camera, returns the plain estimate when disabled or when the guides are not recorded.
+    pub fn process(&mut self , accumulator : &Accumulator , camera : &Camera) -> Vec<[f32;3]>{
+        return self.process_with(accumulator, camera, atrous::filter);
+    }
+    ///Like process, with the spatial filter run by the closure, such as the compute pass of the renderer.
+    pub fn process_with<F : FnOnce(&Frame,&[[f32;3]],f32) -> Vec<[f32;3]>>(&mut self , accumulator : &Accumulator , camera : &Camera , filter : F) -> Vec<[f32;3]>{
+        let radiance = accumulator.resolve();
+        if !self.settings.enabled || self.settings.strength <= 0.0 || accumulator.samples() == 0{
+            return radiance;
+        }
+        let (albedo,normal,depth) = match (accumulator.resolve_aov(Aov::Albedo),accumulator.resolve_aov(Aov::Normal),accumulator.resolve_aov(Aov::Depth)){
+            (Some(albedo),Some(normal),Some(depth)) => (albedo,normal,depth),
+            _ => return radiance,
+        };
+        let frame = Frame{width : accumulator.width(), height : accumulator.height(), radiance : &radiance, albedo : &albedo, normal : &normal, depth : &depth, samples : accumulator.samples()};
+        return self.denoise_with(&frame, camera, filter);
+    }
+    pub fn denoise(&mut self , frame : &Frame , camera : &Camera) -> Vec<[f32;3]>{
+        return self.denoise_with(frame, camera, atrous::filter);
+    }
+    fn denoise_with<F : FnOnce(&Frame,&[[f32;3]],f32) -> Vec<[f32;3]>>(&mut self , frame : &Frame , camera : &Camera , filter : F) -> Vec<[f32;3]>{
+        let albedo : Vec<[f32;3]> = frame.albedo.iter().map(|albedo| {
+            let mut factor = [1.0;3];
+            for c in 0..3{
+                if albedo[c] > MIN_ALBEDO{factor[c] = albedo[c];}
+            }
+            factor
+        }).collect();
+        let illumination : Vec<[f32;3]> = frame.radiance.iter().zip(albedo.iter()).map(|(radiance,albedo)| [radiance[0] / albedo[0],radiance[1] / albedo[1],radiance[2] / albedo[2]]).collect();
+        let history = History::update(self.history.take(), frame, &illumination, camera);
+        let filtered = filter(frame, &history.color, self.settings.strength);
+        self.history = Some(history);
+        return filtered.iter().zip(albedo.iter()).enumerate().map(|(index,(illumination,albedo))| {
+            //Pixels that saw the background have nothing to guide the filter and keep their value.
+            if frame.depth[index][0] <= 0.0{return frame.radiance[index];}
+            [illumination[0] * albedo[0],illumination[1] * albedo[1],illumination[2] * albedo[2]]
+        }).collect();
+    }
+}
+fn pixel_luminance(pixel : [f32;3]) -> f32{
+    return luminance(pixel.into());
+}
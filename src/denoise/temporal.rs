@@ -0,0 +1,94 @@
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+
+use super::Frame;
+use super::super::camera::Camera;
+
+///The most samples reprojected history counts as, so lighting that changes with the view still converges to the new estimate.
+const MAX_HISTORY_SAMPLES : f32 = 16.0;
+///A reprojected pixel is only reused when its depth is within this fraction of the expected depth.
+const DEPTH_TOLERANCE : f32 = 0.05;
+///And when its normal is within about 25 degrees.
+const NORMAL_TOLERANCE : f32 = 0.9;
+
+///The illumination of the last frame and what it was built from.
+///The accumulator restarts whenever the camera moves, the history then carries the previous estimate over as a prior that fades as new samples arrive.
+pub struct History{
+    width : u32,
+    height : u32,
+    camera : Camera,
+    samples : u32,
+    ///Prior and accumulated samples combined.
+    pub color : Vec<[f32;3]>,
+    ///The amount of samples the color is worth.
+    weight : Vec<f32>,
+    depth : Vec<f32>,
+    normal : Vec<[f32;3]>,
+    ///Reprojected from before the last restart of the accumulation.
+    prior : Vec<[f32;3]>,
+    prior_weight : Vec<f32>,
+}
+impl History{
+    pub fn update(previous : Option<History> , frame : &Frame , illumination : &[[f32;3]] , camera : &Camera) -> History{
+        let size = (frame.width * frame.height) as usize;
+        let depth : Vec<f32> = frame.depth.iter().map(|depth| depth[0]).collect();
+        let normal = frame.normal.to_vec();
+        let (prior,prior_weight) = match previous{
+            Some(previous) if previous.width == frame.width && previous.height == frame.height => {
+                if frame.samples > previous.samples{
+                    //The accumulation continued, the prior still stands.
+                    (previous.prior,previous.prior_weight)
+                } else{
+                    previous.reproject(frame, &depth, camera)
+                }
+            }
+            _ => (vec!([0.0;3];size),vec!(0.0;size)),
+        };
+        let samples = frame.samples as f32;
+        let mut color = Vec::with_capacity(size);
+        let mut weight = Vec::with_capacity(size);
+        for index in 0..size{
+            let total = prior_weight[index] + samples;
+            let mut pixel = [0.0;3];
+            for c in 0..3{
+                pixel[c] = (prior[index][c] * prior_weight[index] + illumination[index][c] * samples) / total;
+            }
+            color.push(pixel);
+            weight.push(total);
+        }
+        return History{width : frame.width, height : frame.height, camera : *camera, samples : frame.samples, color, weight, depth, normal, prior, prior_weight};
+    }
+    ///Finds where every pixel of the new frame was in this one and takes over its color when the surface matches.
+    fn reproject(&self , frame : &Frame , depth : &[f32] , camera : &Camera) -> (Vec<[f32;3]>,Vec<f32>){
+        let size = (frame.width * frame.height) as usize;
+        let mut prior = vec!([0.0;3];size);
+        let mut prior_weight = vec!(0.0;size);
+        let aspect = frame.width as f32 / frame.height.max(1) as f32;
+        for y in 0..frame.height{
+            for x in 0..frame.width{
+                let index = (y * frame.width + x) as usize;
+                if depth[index] <= 0.0{continue;}
+                let u = (x as f32 + 0.5) / frame.width as f32 * 2.0 - 1.0;
+                let v = 1.0 - (y as f32 + 0.5) / frame.height as f32 * 2.0;
+                let direction = camera.direction(u, v, aspect);
+                let point = camera.position + direction * (depth[index] / direction.dot(camera.forward()));
+                let (previous_u,previous_v) = match self.camera.project(point, aspect){
+                    Some(film) => film,
+                    None => continue,
+                };
+                let previous_x = ((previous_u + 1.0) / 2.0 * self.width as f32).floor();
+                let previous_y = ((1.0 - previous_v) / 2.0 * self.height as f32).floor();
+                if previous_x < 0.0 || previous_y < 0.0 || previous_x >= self.width as f32 || previous_y >= self.height as f32{continue;}
+                let previous_index = (previous_y as u32 * self.width + previous_x as u32) as usize;
+                let expected_depth = (point - self.camera.position).dot(self.camera.forward());
+                let depth_matches = (self.depth[previous_index] - expected_depth).abs() <= DEPTH_TOLERANCE * expected_depth;
+                let normal_matches = Vector3::from(self.normal[previous_index]).dot(Vector3::from(frame.normal[index])) >= NORMAL_TOLERANCE;
+                if depth_matches && normal_matches{
+                    prior[index] = self.color[previous_index];
+                    prior_weight[index] = self.weight[previous_index].min(MAX_HISTORY_SAMPLES);
+                }
+            }
+        }
+        return (prior,prior_weight);
+    }
+}
@@ -0,0 +1,90 @@
+//!Renders a scene without a window and writes the accumulation to a file.
+
+use std::path::PathBuf;
+
+use super::tracer;
+use super::output;
+use super::denoise;
+use super::profiler;
+use super::renderer;
+use super::config::Config;
+use super::scene::Scene;
+
+///What a render needs besides the configuration.
+#[derive(Clone,Debug)]
+pub struct RenderOptions{
+    ///Scene file or built in scene name, the default scene when unset.
+    pub scene : Option<String>,
+    ///Output file, .exr and .hdr keep the linear radiance, .png is tonemapped.
+    pub output : PathBuf,
+    ///Aovs written next to the beauty.
+    pub aovs : Vec<tracer::Aov>,
+    ///Write half float OpenEXR channels.
+    pub half : bool,
+    ///Write a Chrome trace of the render passes to this file and print their timings.
+    pub profile : Option<PathBuf>,
+}
+
+///Renders without a window and writes the result to the output file.
+pub fn run(config : &Config , options : &RenderOptions) -> Result<(),String>{
+    if !output::is_supported(&options.output){
+        return Err(format!("Unsupported output format for {}, use .exr, .hdr or .png.",options.output.display()));
+    }
+    let scene = Scene::load(options.scene.as_deref().unwrap_or("default"))?;
+    let (width,height) = config.render_size();
+    let spp = config.spp();
+    let mut tracer = tracer::Tracer::new(width, height);
+    tracer.set_sampler(config.sampler_settings());
+    for &aov in options.aovs.iter(){
+        tracer.enable_aov(aov);
+    }
+    let mut denoiser = denoise::Denoiser::new(config.denoise_settings());
+    if denoiser.settings.enabled{
+        denoise::enable_guides(&mut tracer);
+    }
+    let mut profiler = profiler::Profiler::new(options.profile.is_some());
+    //Ctrl-C stops after the tiles being traced and saves what is done, pixels of the unfinished pass hold one sample more.
+    let cancel = tracer.cancel_flag();
+    if let Err(error) = ctrlc::set_handler(move || cancel.store(true, std::sync::atomic::Ordering::Relaxed)){
+        eprintln!("Failed to handle Ctrl-C : {}.",error);
+    }
+    //Gpu passes are traced in one dispatch, a cancel takes effect between them.
+    let mut compute = if config.gpu_tracing(){Some(renderer::ComputeDevice::new(&config.renderer_settings())?)}else{None};
+    if let Some(compute) = compute.as_mut(){
+        if let Some(fallback) = compute.device_fallback(){
+            println!("{}",fallback);
+        }
+        println!("Tracing on {}.",compute.device_name());
+        if let Err(error) = compute.set_scene(&scene){
+            println!("{}",error);
+        }
+    }
+    for _ in 0..spp{
+        let traced = profiler.scope("trace", || {
+            match compute.as_mut(){
+                Some(compute) if !tracer.is_cancelled() => {
+                    compute.trace_pass(&scene.camera, &mut tracer);
+                    return true;
+                }
+                Some(_) => return false,
+                None => return tracer.render_pass(&scene),
+            }
+        });
+        if !traced{
+            println!("Cancelled after {} of {} samples per pixel.",tracer.accumulator().samples(),spp);
+            break;
+        }
+    }
+    let output_options = output::OutputOptions{
+        precision : if options.half{output::Precision::Half}else{output::Precision::Full},
+        post : config.post_settings(),
+    };
+    let beauty = profiler.scope("denoise", || denoiser.process(tracer.accumulator(), &scene.camera));
+    profiler.scope("save", || output::save(&options.output, tracer.accumulator(), &beauty, &output_options))?;
+    println!("Saved {} with {} samples per pixel.",options.output.display(),tracer.accumulator().samples());
+    if let Some(path) = &options.profile{
+        print!("{}",profiler.report());
+        profiler.save_chrome_trace(path)?;
+    }
+    return Ok(());
+}
@@ -0,0 +1,27 @@
+#![allow(clippy::needless_return,clippy::too_many_arguments,clippy::if_same_then_else)]
+//!A vulkan path tracer, the mport binary is a thin command line around this library.
+
+pub mod renderer;
+pub mod scene;
+pub mod bvh;
+pub mod tracer;
+pub mod sampler;
+pub mod post;
+pub mod output;
+pub mod camera;
+pub mod material;
+pub mod light;
+pub mod config;
+pub mod window;
+pub mod denoise;
+pub mod profiler;
+pub mod ui;
+pub mod viewer;
+pub mod headless;
+
+const APP_NAME : &str = "Mport";
+const ENGINE_NAME : &str = "Mport Engine";
+const VERSION : &u32 = &1;
+const VALIDATION_ENABLED : &bool = &true; //This severely hurts performance, only use for debugging!!
+const HDR_ENABLED : &bool = &false; //Only used when the display exposes an hdr color space.
+const PRESENT_MODE : ash::vk::PresentModeKHR = ash::vk::PresentModeKHR::MAILBOX; //Falls back to the closest supported mode.
@@ -0,0 +1,45 @@
+///A piecewise constant distribution over [0,1) built from non negative weights.
+pub struct Distribution1D{
+    function : Vec<f32>,
+    cdf : Vec<f32>,
+    integral : f32,
+}
+impl Distribution1D{
+    pub fn new(function : Vec<f32>) -> Self{
+        let count = function.len() as f32;
+        let mut cdf = Vec::with_capacity(function.len() + 1);
+        cdf.push(0.0);
+        for (index,&value) in function.iter().enumerate(){
+            cdf.push(cdf[index] + value.max(0.0) / count);
+        }
+        let integral = *cdf.last().unwrap();
+        for (index,value) in cdf.iter_mut().enumerate(){
+            //An all zero function falls back to uniform sampling.
+            *value = if integral > 0.0{*value / integral}else{index as f32 / count};
+        }
+        return Self{function, cdf, integral};
+    }
+    pub fn function(&self) -> &[f32]{
+        return &self.function;
+    }
+    ///The normalized running sum of the function, one entry longer than it and starting at zero.
+    pub fn cdf(&self) -> &[f32]{
+        return &self.cdf;
+    }
+    ///The average of the function, zero when every weight is zero.
+    pub fn integral(&self) -> f32{
+        return self.integral;
+    }
+    ///Returns a position in [0,1), its density and the bucket it falls in.
+    pub fn sample(&self , u : f32) -> (f32,f32,usize){
+        let index = self.cdf.partition_point(|&value| value <= u).clamp(1, self.function.len()) - 1;
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0{(u - self.cdf[index]) / width}else{0.0};
+        let x = ((index as f32 + offset.clamp(0.0, 1.0)) / self.function.len() as f32).min(1.0 - f32::EPSILON);
+        return (x,self.pdf(index),index);
+    }
+    pub fn pdf(&self , index : usize) -> f32{
+        if self.integral <= 0.0{return 1.0;}
+        return self.function[index].max(0.0) / self.integral;
+    }
+}
@@ -0,0 +1,129 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use cgmath::Vector3;
+
+use super::distribution::Distribution1D;
+use super::luminance;
+
+///An equirectangular hdr image surrounding the scene, used as the background and importance sampled as a light.
+///The top row looks straight up, u increases counter clockwise seen from above starting at +x.
+pub struct Environment{
+    width : usize,
+    height : usize,
+    texels : Arc<Vec<[f32;3]>>,
+    ///Rotation around the up axis in radians.
+    pub rotation : f32,
+    pub intensity : f32,
+    ///Picks a row, weighted by the total luminance of its texels.
+    marginal : Distribution1D,
+    ///Picks a texel within each row.
+    conditionals : Vec<Distribution1D>,
+}
+impl Environment{
+    ///Loads a radiance .hdr file through image or an OpenEXR file, rotation is in degrees.
+    pub fn load(path : &Path , rotation : f32 , intensity : f32) -> Result<Self,String>{
+        let error = |error : String| format!("Failed to load environment {} : {}.",path.display(),error);
+        let extension = path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase());
+        let (width,height,texels) = match extension.as_deref(){
+            Some("hdr") => {
+                let file = std::fs::File::open(path).map_err(|e| error(e.to_string()))?;
+                let decoder = image::codecs::hdr::HdrDecoder::new(std::io::BufReader::new(file)).map_err(|e| error(e.to_string()))?;
+                let metadata = decoder.metadata();
+                let pixels = decoder.read_image_hdr().map_err(|e| error(e.to_string()))?;
+                (metadata.width as usize,metadata.height as usize,pixels.into_iter().map(|pixel| pixel.0).collect())
+            }
+            Some("exr") => {
+                let image = exr::prelude::read_first_rgba_layer_from_file(
+                    path,
+                    |resolution,_| (resolution.width(),vec!([0.0;3];resolution.width() * resolution.height())),
+                    |(width,texels) : &mut (usize,Vec<[f32;3]>),position,(r,g,b,_) : (f32,f32,f32,f32)| texels[position.y() * *width + position.x()] = [r,g,b],
+                ).map_err(|e| error(e.to_string()))?;
+                let size = image.layer_data.size;
+                (size.width(),size.height(),image.layer_data.channel_data.pixels.1)
+            }
+            _ => return Err(error(String::from("expected a .hdr or .exr file"))),
+        };
+        if width == 0 || height == 0{
+            return Err(error(String::from("the image is empty")));
+        }
+        return Ok(Self::new(width, height, texels, rotation, intensity));
+    }
+    pub fn new(width : usize , height : usize , texels : Vec<[f32;3]> , rotation : f32 , intensity : f32) -> Self{
+        let mut conditionals = Vec::with_capacity(height);
+        let mut rows = Vec::with_capacity(height);
+        for y in 0..height{
+            //Rows near the poles cover less solid angle.
+            let sin_theta = (std::f32::consts::PI * (y as f32 + 0.5) / height as f32).sin();
+            let row = Distribution1D::new(texels[y * width..(y + 1) * width].iter().map(|&texel| luminance(Vector3::from(texel)) * sin_theta).collect());
+            rows.push(row.integral());
+            conditionals.push(row);
+        }
+        return Self{
+            width,
+            height,
+            texels : Arc::new(texels),
+            rotation : rotation.to_radians(),
+            intensity,
+            marginal : Distribution1D::new(rows),
+            conditionals,
+        };
+    }
+    pub fn width(&self) -> usize{
+        return self.width;
+    }
+    pub fn height(&self) -> usize{
+        return self.height;
+    }
+    ///Linear rgb texels, row major starting at the top left.
+    ///They are shared so the gpu tracer can hold on to them and tell whether it already uploaded this map.
+    pub fn texels(&self) -> &Arc<Vec<[f32;3]>>{
+        return &self.texels;
+    }
+    ///Picks a row, weighted by the total luminance of its texels.
+    pub fn marginal(&self) -> &Distribution1D{
+        return &self.marginal;
+    }
+    ///Picks a texel within each row.
+    pub fn conditionals(&self) -> &[Distribution1D]{
+        return &self.conditionals;
+    }
+    ///The radiance arriving from the direction, without filtering so it matches the sampling density exactly.
+    pub fn radiance(&self , direction : Vector3<f32>) -> Vector3<f32>{
+        let (x,y) = self.texel(self.uv(direction));
+        return Vector3::from(self.texels[y * self.width + x]) * self.intensity;
+    }
+    ///The average luminance over the sphere of directions.
+    pub fn average_luminance(&self) -> f32{
+        return self.marginal.integral() * self.intensity * std::f32::consts::PI / 2.0;
+    }
+    ///Returns a direction, the radiance from it and the density with respect to solid angle.
+    pub fn sample(&self , u : [f32;2]) -> Option<(Vector3<f32>,Vector3<f32>,f32)>{
+        let (v,row_pdf,y) = self.marginal.sample(u[0]);
+        let (u,column_pdf,_) = self.conditionals[y].sample(u[1]);
+        let theta = v * std::f32::consts::PI;
+        let sin_theta = theta.sin();
+        if sin_theta <= 0.0{return None;}
+        let phi = u * 2.0 * std::f32::consts::PI + self.rotation;
+        let direction = Vector3::new(sin_theta * phi.cos(),theta.cos(),sin_theta * phi.sin());
+        let pdf = row_pdf * column_pdf / (2.0 * std::f32::consts::PI * std::f32::consts::PI * sin_theta);
+        return Some((direction,self.radiance(direction),pdf));
+    }
+    pub fn pdf(&self , direction : Vector3<f32>) -> f32{
+        let uv = self.uv(direction);
+        let sin_theta = (uv[1] * std::f32::consts::PI).sin();
+        if sin_theta <= 0.0{return 0.0;}
+        let (x,y) = self.texel(uv);
+        return self.marginal.pdf(y) * self.conditionals[y].pdf(x) / (2.0 * std::f32::consts::PI * std::f32::consts::PI * sin_theta);
+    }
+    fn uv(&self , direction : Vector3<f32>) -> [f32;2]{
+        let phi = (direction.z.atan2(direction.x) - self.rotation).rem_euclid(2.0 * std::f32::consts::PI);
+        let theta = direction.y.clamp(-1.0, 1.0).acos();
+        return [phi / (2.0 * std::f32::consts::PI),theta / std::f32::consts::PI];
+    }
+    fn texel(&self , uv : [f32;2]) -> (usize,usize){
+        let x = ((uv[0] * self.width as f32) as usize).min(self.width - 1);
+        let y = ((uv[1] * self.height as f32) as usize).min(self.height - 1);
+        return (x,y);
+    }
+}
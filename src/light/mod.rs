@@ -0,0 +1,87 @@
+//!Punctual, emitter and environment lights with a power weighted light sampler for next event estimation.
+//!shaders/lights.glsl samples the same emitters with the same probabilities in the gpu tracer.
+
+mod sampler;
+mod distribution;
+mod environment;
+
+pub use sampler::LightSampler;
+pub use sampler::Emitter;
+pub use distribution::Distribution1D;
+pub use environment::Environment;
+
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+
+///Lights without geometry, emissive spheres, the sky and environment maps are lights as well but live in the scene itself.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Light{
+    ///Radiant intensity in every direction.
+    Point{position : Vector3<f32>, intensity : Vector3<f32>},
+    ///A point light restricted to a cone, the intensity falls off smoothly between the inner and outer cosines.
+    Spot{position : Vector3<f32>, direction : Vector3<f32>, intensity : Vector3<f32>, cos_inner : f32, cos_outer : f32},
+    ///Parallel light such as the sun, direction is the way the light travels.
+    Directional{direction : Vector3<f32>, irradiance : Vector3<f32>},
+}
+///A direction towards a light from a shading point.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct LightSample{
+    pub direction : Vector3<f32>,
+    ///Distance to the light, infinite for lights that are infinitely far away.
+    pub distance : f32,
+    pub radiance : Vector3<f32>,
+    ///Density with respect to solid angle, one for dirac lights.
+    pub pdf : f32,
+    ///The light can not be hit by a ray, bsdf samples never find it so no multiple importance sampling is needed.
+    pub delta : bool,
+}
+impl Light{
+    pub fn spot(position : Vector3<f32> , direction : Vector3<f32> , intensity : Vector3<f32> , inner_angle : f32 , outer_angle : f32) -> Self{
+        return Light::Spot{
+            position,
+            direction : direction.normalize(),
+            intensity,
+            cos_inner : inner_angle.min(outer_angle).to_radians().cos(),
+            cos_outer : outer_angle.to_radians().cos(),
+        };
+    }
+    pub fn sample(&self , point : Vector3<f32>) -> Option<LightSample>{
+        match *self{
+            Light::Point{position,intensity} => {
+                let (direction,distance) = towards(point, position)?;
+                return Some(LightSample{direction, distance, radiance : intensity / (distance * distance), pdf : 1.0, delta : true});
+            }
+            Light::Spot{position,direction : axis,intensity,cos_inner,cos_outer} => {
+                let (direction,distance) = towards(point, position)?;
+                let falloff = smoothstep(cos_outer, cos_inner, (-direction).dot(axis));
+                if falloff <= 0.0{return None;}
+                return Some(LightSample{direction, distance, radiance : intensity * (falloff / (distance * distance)), pdf : 1.0, delta : true});
+            }
+            Light::Directional{direction,irradiance} => {
+                return Some(LightSample{direction : -direction.normalize(), distance : f32::INFINITY, radiance : irradiance, pdf : 1.0, delta : true});
+            }
+        }
+    }
+    ///Total emitted power, infinitely distant lights are measured over a disc of the scene radius.
+    pub fn power(&self , scene_radius : f32) -> f32{
+        match *self{
+            Light::Point{intensity,..} => 4.0 * std::f32::consts::PI * luminance(intensity),
+            Light::Spot{intensity,cos_inner,cos_outer,..} => 2.0 * std::f32::consts::PI * luminance(intensity) * (1.0 - (cos_inner + cos_outer) / 2.0),
+            Light::Directional{irradiance,..} => std::f32::consts::PI * scene_radius * scene_radius * luminance(irradiance),
+        }
+    }
+}
+pub fn luminance(color : Vector3<f32>) -> f32{
+    return 0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z;
+}
+fn towards(point : Vector3<f32> , position : Vector3<f32>) -> Option<(Vector3<f32>,f32)>{
+    let offset = position - point;
+    let distance = offset.magnitude();
+    if distance <= 0.0{return None;}
+    return Some((offset / distance,distance));
+}
+fn smoothstep(edge0 : f32 , edge1 : f32 , x : f32) -> f32{
+    if edge0 == edge1{return if x >= edge0{1.0}else{0.0};}
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
@@ -0,0 +1,158 @@
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+
+use super::LightSample;
+use super::luminance;
+use super::super::scene::Scene;
+use super::super::material::Frame;
+
+///Spheres larger than this stand in for ground planes and are left out of the scene radius.
+const MAX_OBJECT_RADIUS : f32 = 100.0;
+///Below this squared sine the cone of a sphere is too narrow for 1 - cos to be computed directly in f32.
+const SMALL_CONE : f32 = 0.000_685_23;
+
+///Something next event estimation can choose, the index of a light or a sphere of the scene.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Emitter{
+    Light(usize),
+    Sphere(usize),
+    Environment,
+}
+///Picks lights for next event estimation with a probability proportional to their power.
+pub struct LightSampler{
+    emitters : Vec<Emitter>,
+    cdf : Vec<f32>,
+    ///The emitter index of every sphere that emits light.
+    sphere_emitters : Vec<Option<usize>>,
+    environment : Option<usize>,
+}
+impl LightSampler{
+    pub fn new(scene : &Scene) -> Self{
+        let scene_radius = scene_radius(scene);
+        let mut emitters = vec!();
+        let mut powers = vec!();
+        for (index,light) in scene.lights.iter().enumerate(){
+            emitters.push(Emitter::Light(index));
+            powers.push(light.power(scene_radius));
+        }
+        let mut sphere_emitters = vec!(None;scene.spheres.len());
+        for (index,sphere) in scene.spheres.iter().enumerate(){
+            //Textured emission is approximated by its value at the center of the texture.
+            let radiance = luminance(sphere.material.emission([0.5,0.5]));
+            if radiance > 0.0{
+                sphere_emitters[index] = Some(emitters.len());
+                emitters.push(Emitter::Sphere(index));
+                powers.push(std::f32::consts::PI * 4.0 * std::f32::consts::PI * sphere.radius * sphere.radius * radiance);
+            }
+        }
+        let mut environment = None;
+        let sky = match &scene.environment{
+            Some(environment) => environment.average_luminance(),
+            None => luminance(scene.sky),
+        };
+        if sky > 0.0{
+            environment = Some(emitters.len());
+            emitters.push(Emitter::Environment);
+            powers.push(std::f32::consts::PI * 4.0 * std::f32::consts::PI * scene_radius * scene_radius * sky);
+        }
+        let total : f32 = powers.iter().sum();
+        let mut cdf = Vec::with_capacity(powers.len());
+        let mut sum = 0.0;
+        for power in powers{
+            sum += if total > 0.0{power / total}else{0.0};
+            cdf.push(sum);
+        }
+        return Self{emitters, cdf, sphere_emitters, environment};
+    }
+    ///Chooses an emitter, returns its index and the probability it had of being chosen.
+    pub fn choose(&self , u : f32) -> Option<(usize,f32)>{
+        let index = self.cdf.iter().position(|&sum| u < sum).or_else(|| self.cdf.iter().rposition(|&sum| sum > 0.0))?;
+        return Some((index,self.probability(index)));
+    }
+    ///The emitters in the order they are chosen in.
+    pub fn emitters(&self) -> &[Emitter]{
+        return &self.emitters;
+    }
+    ///The probability of choosing each emitter or one before it, the gpu tracer chooses with the same sums.
+    pub fn cdf(&self) -> &[f32]{
+        return &self.cdf;
+    }
+    fn probability(&self , index : usize) -> f32{
+        return self.cdf[index] - if index == 0{0.0}else{self.cdf[index - 1]};
+    }
+    ///Samples a direction from the point towards the emitter.
+    pub fn sample(&self , scene : &Scene , index : usize , point : Vector3<f32> , u : [f32;2]) -> Option<LightSample>{
+        match self.emitters[index]{
+            Emitter::Light(light) => return scene.lights[light].sample(point),
+            Emitter::Sphere(sphere_index) => {
+                let sphere = &scene.spheres[sphere_index];
+                let (one_minus_cos_max,frame) = sphere_cone(sphere.center, sphere.radius, point)?;
+                let one_minus_cos = u[0] * one_minus_cos_max;
+                let cos_theta = 1.0 - one_minus_cos;
+                let sin_theta = (one_minus_cos * (2.0 - one_minus_cos)).max(0.0).sqrt();
+                let phi = 2.0 * std::f32::consts::PI * u[1];
+                let direction = frame.to_world(Vector3::new(sin_theta * phi.cos(),sin_theta * phi.sin(),cos_theta)).normalize();
+                let distance = sphere.intersect(point, direction)?;
+                let normal = (point + direction * distance - sphere.center).normalize();
+                let radiance = sphere.material.emission(sphere.uv(normal));
+                return Some(LightSample{direction, distance, radiance, pdf : 1.0 / (2.0 * std::f32::consts::PI * one_minus_cos_max), delta : false});
+            }
+            Emitter::Environment => {
+                if let Some(environment) = &scene.environment{
+                    let (direction,radiance,pdf) = environment.sample(u)?;
+                    return Some(LightSample{direction, distance : f32::INFINITY, radiance, pdf, delta : false});
+                }
+                let z = 1.0 - 2.0 * u[0];
+                let radius = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * std::f32::consts::PI * u[1];
+                let direction = Vector3::new(radius * phi.cos(),z,radius * phi.sin());
+                return Some(LightSample{direction, distance : f32::INFINITY, radiance : scene.sky, pdf : 1.0 / (4.0 * std::f32::consts::PI), delta : false});
+            }
+        }
+    }
+    ///The density of choosing and sampling the sphere from the point, for rays that hit it.
+    pub fn sphere_pdf(&self , scene : &Scene , sphere_index : usize , point : Vector3<f32>) -> f32{
+        let index = match self.sphere_emitters[sphere_index]{
+            Some(index) => index,
+            None => return 0.0,
+        };
+        let sphere = &scene.spheres[sphere_index];
+        match sphere_cone(sphere.center, sphere.radius, point){
+            Some((one_minus_cos_max,_)) => return self.probability(index) / (2.0 * std::f32::consts::PI * one_minus_cos_max),
+            None => return 0.0,
+        }
+    }
+    ///The density of choosing and sampling the sky or environment map in the direction, for rays that escape the scene.
+    pub fn environment_pdf(&self , scene : &Scene , direction : Vector3<f32>) -> f32{
+        let index = match self.environment{
+            Some(index) => index,
+            None => return 0.0,
+        };
+        match &scene.environment{
+            Some(environment) => return self.probability(index) * environment.pdf(direction),
+            None => return self.probability(index) / (4.0 * std::f32::consts::PI),
+        }
+    }
+}
+///The cone of directions from the point that hit the sphere, as 1 - cos of its half angle and a frame around its axis.
+///Points inside the sphere see it in every direction and are not sampled.
+fn sphere_cone(center : Vector3<f32> , radius : f32 , point : Vector3<f32>) -> Option<(f32,Frame)>{
+    let offset = center - point;
+    let distance2 = offset.magnitude2();
+    if distance2 <= radius * radius{return None;}
+    let sin2_max = radius * radius / distance2;
+    let one_minus_cos_max = if sin2_max < SMALL_CONE{sin2_max / 2.0}else{1.0 - (1.0 - sin2_max).sqrt()};
+    return Some((one_minus_cos_max,Frame::new(offset / distance2.sqrt())));
+}
+///Radius of a sphere around the camera and every object, used to give infinitely distant lights a power.
+fn scene_radius(scene : &Scene) -> f32{
+    let objects = scene.spheres.iter().filter(|sphere| sphere.radius < MAX_OBJECT_RADIUS);
+    let (mut min,mut max) = (scene.camera.position,scene.camera.position);
+    for sphere in objects{
+        for axis in 0..3{
+            min[axis] = min[axis].min(sphere.center[axis] - sphere.radius);
+            max[axis] = max[axis].max(sphere.center[axis] + sphere.radius);
+        }
+    }
+    return ((max - min).magnitude() / 2.0).max(1.0);
+}
@@ -1,37 +1,46 @@
-mod renderer;
+#![allow(clippy::needless_return,clippy::too_many_arguments,clippy::if_same_then_else)]
 
-use winit::event_loop::EventLoop;
-use winit::event_loop::ControlFlow;
-use winit::window::Window;
-use winit::event::Event;
-use winit::event::WindowEvent;
+mod cli;
 
-const APP_NAME : &'static str = "Mport";
-const ENGINE_NAME : &'static str = "Mport Engine";
-const VERSION : &'static u32 = &1;
-const VALIDATION_ENABLED : &'static bool = &true; //This severely hurts performance, only use for debugging!!
+use clap::Parser;
+
+use mport::renderer;
+use mport::config;
+use mport::viewer;
+use mport::headless;
 
 fn main(){
-    let event_loop = EventLoop::new();
-    let window = Window::new(&event_loop).expect("Failed to create window.");
-    let mut renderer = renderer::Renderer::new(&window);
-    let mut first_loop = true;
-    event_loop.run(move |event,_,control_flow|{
-        *control_flow = ControlFlow::Poll;
-        if first_loop{
-            first_loop = false;
-            renderer.show_create_info();
-            //Remove this when the renderer is finished.
-            renderer.recreate_swapchain();
-        }
-        match event{
-            Event::WindowEvent{
-                event : WindowEvent::CloseRequested,
-                ..
-            } => {
-                *control_flow = ControlFlow::Exit;
-            }
-            _ => {}
+    let cli = cli::Cli::parse();
+    match cli.command{
+        Some(cli::Command::View(ref args)) => exit_on_error(viewer::run(&load_config(&cli, args.scene.as_deref()), &args.options())),
+        Some(cli::Command::Render(ref args)) => exit_on_error(headless::run(&load_config(&cli, args.scene.as_deref()), &args.options())),
+        Some(cli::Command::Devices) => renderer::print_devices(&load_config(&cli, None).renderer_settings()),
+        Some(cli::Command::Info) => renderer::print_info(&renderer::AppInfo::default()),
+        Some(cli::Command::Config(cli::ConfigCommand::Dump{ref scene})) => exit_on_error(dump_config(&cli, scene.as_deref())),
+        None => exit_on_error(viewer::run(&load_config(&cli, None), &viewer::ViewOptions::default())),
+    }
+}
+fn exit_on_error(result : Result<(),String>){
+    if let Err(error) = result{
+        eprintln!("{}",error);
+        std::process::exit(1);
+    }
+}
+fn load_config(cli : &cli::Cli , scene : Option<&str>) -> config::Config{
+    match config::Config::load(cli.config(), scene){
+        Ok((config,_)) => return config,
+        Err(error) => {
+            eprintln!("{}",error);
+            std::process::exit(1);
         }
-    })
-}
\ No newline at end of file
+    }
+}
+///Prints the merged configuration, preceded by the layers it was built from.
+fn dump_config(cli : &cli::Cli , scene : Option<&str>) -> Result<(),String>{
+    let (config,sources) = config::Config::load(cli.config(), scene)?;
+    for source in sources{
+        println!("# {}",source);
+    }
+    print!("{}",config.to_toml());
+    return Ok(());
+}
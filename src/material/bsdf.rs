@@ -0,0 +1,443 @@
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+
+use super::microfacet;
+
+///Below this roughness dielectrics are treated as perfectly smooth, other lobes clamp to it to stay finite.
+pub const MIN_ALPHA : f32 = 1e-3;
+
+///A direction chosen by Bsdf::sample, weight is f * |cos| / pdf.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct BsdfSample{
+    pub wi : Vector3<f32>,
+    pub weight : Vector3<f32>,
+    pub pdf : f32,
+    ///Sampled from a dirac lobe, eval and pdf are zero for such directions.
+    pub delta : bool,
+}
+///A material with its textures evaluated at one point, directions are in the local shading frame and point away from the surface.
+///Radiance is transported from wi towards wo, refraction scales it by the squared relative index of refraction.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Bsdf{
+    Lambert{albedo : Vector3<f32>},
+    ///GGX microfacet metal with a Schlick fresnel starting at f0.
+    Conductor{f0 : Vector3<f32>, alpha : f32},
+    ///GGX microfacet boundary between two media, eta is the inside over the outside index of refraction.
+    Dielectric{eta : f32, alpha : f32, tint : Vector3<f32>},
+    ///An infinitely thin sheet such as a window, rays pass without bending and inter reflections are summed.
+    ThinDielectric{eta : f32, tint : Vector3<f32>},
+    ///GGX specular with dielectric fresnel over an energy conserving diffuse base, the opaque part of the principled bsdf.
+    Plastic{color : Vector3<f32>, alpha : f32, eta : f32, specular : f32},
+    ///A blend of metal, glass and plastic in the spirit of the Disney and OpenPBR surfaces.
+    Principled{base_color : Vector3<f32>, metallic : f32, alpha : f32, eta : f32, specular : f32, transmission : f32},
+}
+impl Bsdf{
+    ///The value of the bsdf without the cosine term.
+    pub fn eval(&self , wo : Vector3<f32> , wi : Vector3<f32>) -> Vector3<f32>{
+        let zero = Vector3::new(0.0,0.0,0.0);
+        if wo.z == 0.0 || wi.z == 0.0{return zero;}
+        match *self{
+            Bsdf::Lambert{albedo} => {
+                if !same_hemisphere(wo, wi){return zero;}
+                return albedo / std::f32::consts::PI;
+            }
+            Bsdf::Conductor{f0,alpha} => {
+                if !same_hemisphere(wo, wi){return zero;}
+                let (wo,wi) = upper(wo, wi);
+                let alpha = alpha.max(MIN_ALPHA);
+                let half = (wo + wi).normalize();
+                let fresnel = microfacet::fresnel_schlick(f0, wo.dot(half));
+                return fresnel * (microfacet::distribution(half, alpha) * microfacet::masking_shadowing(wo, wi, alpha) / (4.0 * wo.z * wi.z));
+            }
+            Bsdf::Dielectric{eta,alpha,tint} => {
+                if alpha < MIN_ALPHA{return zero;}
+                let reflection = same_hemisphere(wo, wi);
+                let etap = if reflection{1.0}else if wo.z > 0.0{eta}else{1.0 / eta};
+                let half = match generalized_half(wo, wi, etap){
+                    Some(half) => half,
+                    None => return zero,
+                };
+                let fresnel = microfacet::fresnel_dielectric(wo.dot(half), eta);
+                let d = microfacet::distribution(half, alpha);
+                let g = microfacet::masking_shadowing(wo, wi, alpha);
+                if reflection{
+                    let f = d * g * fresnel / (4.0 * wo.z * wi.z).abs();
+                    return Vector3::new(f,f,f);
+                }
+                let denominator = (wi.dot(half) + wo.dot(half) / etap).powi(2) * wi.z * wo.z;
+                let f = d * (1.0 - fresnel) * g * (wi.dot(half) * wo.dot(half) / denominator).abs() / (etap * etap);
+                return tint * f;
+            }
+            Bsdf::ThinDielectric{..} => return zero,
+            Bsdf::Plastic{color,alpha,eta,specular} => {
+                if !same_hemisphere(wo, wi){return zero;}
+                let (wo,wi) = upper(wo, wi);
+                let alpha = alpha.max(MIN_ALPHA);
+                let half = (wo + wi).normalize();
+                let fresnel = (microfacet::fresnel_dielectric(wo.dot(half), eta) * specular).min(1.0);
+                let glossy = microfacet::distribution(half, alpha) * microfacet::masking_shadowing(wo, wi, alpha) * fresnel / (4.0 * wo.z * wi.z);
+                return Vector3::new(glossy,glossy,glossy) + color * plastic_diffuse(wo, wi, eta, specular);
+            }
+            Bsdf::Principled{..} => {
+                return self.lobes().iter().fold(zero, |sum,(weight,lobe)| if *weight > 0.0{sum + lobe.eval(wo, wi) * *weight}else{sum});
+            }
+        }
+    }
+    ///The density of sample with respect to solid angle, zero for dirac lobes.
+    pub fn pdf(&self , wo : Vector3<f32> , wi : Vector3<f32>) -> f32{
+        if wo.z == 0.0 || wi.z == 0.0{return 0.0;}
+        match *self{
+            Bsdf::Lambert{..} => {
+                if !same_hemisphere(wo, wi){return 0.0;}
+                return wi.z.abs() / std::f32::consts::PI;
+            }
+            Bsdf::Conductor{alpha,..} => {
+                if !same_hemisphere(wo, wi){return 0.0;}
+                let (wo,wi) = upper(wo, wi);
+                return reflection_pdf(wo, wi, alpha.max(MIN_ALPHA));
+            }
+            Bsdf::Dielectric{eta,alpha,..} => {
+                if alpha < MIN_ALPHA{return 0.0;}
+                let reflection = same_hemisphere(wo, wi);
+                let etap = if reflection{1.0}else if wo.z > 0.0{eta}else{1.0 / eta};
+                let half = match generalized_half(wo, wi, etap){
+                    Some(half) => half,
+                    None => return 0.0,
+                };
+                let fresnel = microfacet::fresnel_dielectric(wo.dot(half), eta);
+                if reflection{
+                    return microfacet::visible_normal_pdf(wo, half, alpha) / (4.0 * wo.dot(half).abs()) * fresnel;
+                }
+                let jacobian = wi.dot(half).abs() / (wi.dot(half) + wo.dot(half) / etap).powi(2);
+                return microfacet::visible_normal_pdf(wo, half, alpha) * jacobian * (1.0 - fresnel);
+            }
+            Bsdf::ThinDielectric{..} => return 0.0,
+            Bsdf::Plastic{color,alpha,eta,specular} => {
+                if !same_hemisphere(wo, wi){return 0.0;}
+                let (wo,wi) = upper(wo, wi);
+                let probability = plastic_specular_probability(wo, color, eta, specular);
+                return probability * reflection_pdf(wo, wi, alpha.max(MIN_ALPHA)) + (1.0 - probability) * wi.z / std::f32::consts::PI;
+            }
+            Bsdf::Principled{..} => {
+                return self.lobes().iter().map(|(weight,lobe)| if *weight > 0.0{lobe.pdf(wo, wi) * weight}else{0.0}).sum();
+            }
+        }
+    }
+    ///Importance samples an incoming direction, u holds three uniform numbers in [0,1).
+    pub fn sample(&self , wo : Vector3<f32> , u : [f32;3]) -> Option<BsdfSample>{
+        if wo.z == 0.0{return None;}
+        match *self{
+            Bsdf::Lambert{albedo} => {
+                let mut wi = cosine_hemisphere([u[1],u[2]]);
+                if wo.z < 0.0{wi.z = -wi.z;}
+                if wi.z == 0.0{return None;}
+                return Some(BsdfSample{wi, weight : albedo, pdf : wi.z.abs() / std::f32::consts::PI, delta : false});
+            }
+            Bsdf::Conductor{f0,alpha} => {
+                let alpha = alpha.max(MIN_ALPHA);
+                let flip = wo.z < 0.0;
+                let wo = if flip{flip_z(wo)}else{wo};
+                let half = microfacet::sample_visible_normal(wo, alpha, [u[1],u[2]]);
+                let wi = microfacet::reflect(wo, half);
+                if wi.z <= 0.0{return None;}
+                let weight = microfacet::fresnel_schlick(f0, wo.dot(half)) * (microfacet::masking_shadowing(wo, wi, alpha) / microfacet::masking(wo, alpha));
+                return Some(BsdfSample{wi : if flip{flip_z(wi)}else{wi}, weight, pdf : reflection_pdf(wo, wi, alpha), delta : false});
+            }
+            Bsdf::Dielectric{eta,alpha,tint} => {
+                if alpha < MIN_ALPHA{
+                    let reflectance = microfacet::fresnel_dielectric(wo.z, eta);
+                    if u[0] < reflectance{
+                        let wi = Vector3::new(-wo.x,-wo.y,wo.z);
+                        return Some(BsdfSample{wi, weight : Vector3::new(1.0,1.0,1.0), pdf : reflectance, delta : true});
+                    }
+                    let (wi,etap) = microfacet::refract(wo, Vector3::unit_z(), eta)?;
+                    return Some(BsdfSample{wi, weight : tint / (etap * etap), pdf : 1.0 - reflectance, delta : true});
+                }
+                let half = microfacet::sample_visible_normal(wo, alpha, [u[1],u[2]]);
+                let reflectance = microfacet::fresnel_dielectric(wo.dot(half), eta);
+                let wi = if u[0] < reflectance{
+                    let wi = microfacet::reflect(wo, half);
+                    if !same_hemisphere(wo, wi){return None;}
+                    wi
+                } else{
+                    let (wi,_) = microfacet::refract(wo, half, eta)?;
+                    if same_hemisphere(wo, wi) || wi.z == 0.0{return None;}
+                    wi
+                };
+                let pdf = self.pdf(wo, wi);
+                if pdf <= 0.0{return None;}
+                return Some(BsdfSample{wi, weight : self.eval(wo, wi) * (wi.z.abs() / pdf), pdf, delta : false});
+            }
+            Bsdf::ThinDielectric{eta,tint} => {
+                let mut reflectance = microfacet::fresnel_dielectric(wo.z.abs(), eta);
+                if reflectance < 1.0{
+                    let transmittance = 1.0 - reflectance;
+                    reflectance += transmittance * transmittance * reflectance / (1.0 - reflectance * reflectance);
+                }
+                if u[0] < reflectance{
+                    return Some(BsdfSample{wi : Vector3::new(-wo.x,-wo.y,wo.z), weight : Vector3::new(1.0,1.0,1.0), pdf : reflectance, delta : true});
+                }
+                return Some(BsdfSample{wi : -wo, weight : tint, pdf : 1.0 - reflectance, delta : true});
+            }
+            Bsdf::Plastic{color,alpha,eta,specular} => {
+                let flip = wo.z < 0.0;
+                let wo = if flip{flip_z(wo)}else{wo};
+                let wi = if u[0] < plastic_specular_probability(wo, color, eta, specular){
+                    microfacet::reflect(wo, microfacet::sample_visible_normal(wo, alpha.max(MIN_ALPHA), [u[1],u[2]]))
+                } else{
+                    cosine_hemisphere([u[1],u[2]])
+                };
+                if wi.z <= 0.0{return None;}
+                let pdf = self.pdf(wo, wi);
+                if pdf <= 0.0{return None;}
+                return Some(BsdfSample{wi : if flip{flip_z(wi)}else{wi}, weight : self.eval(wo, wi) * (wi.z / pdf), pdf, delta : false});
+            }
+            Bsdf::Principled{..} => {
+                let lobes = self.lobes();
+                let mut u0 = u[0];
+                let mut chosen = None;
+                for &(weight,lobe) in lobes.iter().filter(|(weight,_)| *weight > 0.0){
+                    chosen = Some((weight,lobe));
+                    if u0 < weight{
+                        u0 /= weight;
+                        break;
+                    }
+                    u0 -= weight;
+                }
+                let (weight,lobe) = chosen?;
+                let sample = lobe.sample(wo, [u0.min(0.99999994),u[1],u[2]])?;
+                if sample.delta{
+                    return Some(BsdfSample{pdf : sample.pdf * weight, ..sample});
+                }
+                let pdf = self.pdf(wo, sample.wi);
+                if pdf <= 0.0{return None;}
+                return Some(BsdfSample{weight : self.eval(wo, sample.wi) * (sample.wi.z.abs() / pdf), pdf, ..sample});
+            }
+        }
+    }
+    ///The share of eval coming from the diffuse lobes per channel, the rest is specular.
+    pub fn diffuse_fraction(&self , wo : Vector3<f32> , wi : Vector3<f32>) -> Vector3<f32>{
+        let f = self.eval(wo, wi);
+        let diffuse = self.eval_diffuse(wo, wi);
+        let fraction = |diffuse : f32 , f : f32| if f > 0.0{(diffuse / f).min(1.0)}else{0.0};
+        return Vector3::new(fraction(diffuse.x, f.x),fraction(diffuse.y, f.y),fraction(diffuse.z, f.z));
+    }
+    ///The color used for the albedo aov and for denoising guides.
+    pub fn albedo(&self) -> Vector3<f32>{
+        match *self{
+            Bsdf::Lambert{albedo} => albedo,
+            Bsdf::Conductor{f0,..} => f0,
+            Bsdf::Dielectric{tint,..} | Bsdf::ThinDielectric{tint,..} => tint,
+            Bsdf::Plastic{color,..} => color,
+            Bsdf::Principled{base_color,..} => base_color,
+        }
+    }
+    fn eval_diffuse(&self , wo : Vector3<f32> , wi : Vector3<f32>) -> Vector3<f32>{
+        let zero = Vector3::new(0.0,0.0,0.0);
+        if wo.z == 0.0 || wi.z == 0.0 || !same_hemisphere(wo, wi){return zero;}
+        match *self{
+            Bsdf::Lambert{albedo} => return albedo / std::f32::consts::PI,
+            Bsdf::Plastic{color,eta,specular,..} => {
+                let (wo,wi) = upper(wo, wi);
+                return color * plastic_diffuse(wo, wi, eta, specular);
+            }
+            Bsdf::Principled{..} => {
+                return self.lobes().iter().fold(zero, |sum,(weight,lobe)| if *weight > 0.0{sum + lobe.eval_diffuse(wo, wi) * *weight}else{sum});
+            }
+            _ => return zero,
+        }
+    }
+    ///The weighted lobes of the principled bsdf, the weights sum to one.
+    fn lobes(&self) -> [(f32,Bsdf);3]{
+        match *self{
+            Bsdf::Principled{base_color,metallic,alpha,eta,specular,transmission} => {
+                let metallic = metallic.clamp(0.0, 1.0);
+                let transmission = transmission.clamp(0.0, 1.0);
+                return [
+                    (metallic,Bsdf::Conductor{f0 : base_color, alpha}),
+                    ((1.0 - metallic) * transmission,Bsdf::Dielectric{eta, alpha, tint : base_color}),
+                    ((1.0 - metallic) * (1.0 - transmission),Bsdf::Plastic{color : base_color, alpha, eta, specular}),
+                ];
+            }
+            _ => return [(1.0,*self),(0.0,*self),(0.0,*self)],
+        }
+    }
+}
+fn same_hemisphere(a : Vector3<f32> , b : Vector3<f32>) -> bool{
+    return a.z * b.z > 0.0;
+}
+fn flip_z(w : Vector3<f32>) -> Vector3<f32>{
+    return Vector3::new(w.x,w.y,-w.z);
+}
+///Mirrors both directions into the upper hemisphere, opaque lobes look the same from both sides.
+fn upper(wo : Vector3<f32> , wi : Vector3<f32>) -> (Vector3<f32>,Vector3<f32>){
+    if wo.z < 0.0{return (flip_z(wo),flip_z(wi));}
+    return (wo,wi);
+}
+///The microfacet normal that turns wo into wi, facing +z, None for degenerate or back facing configurations.
+fn generalized_half(wo : Vector3<f32> , wi : Vector3<f32> , etap : f32) -> Option<Vector3<f32>>{
+    let half = wi * etap + wo;
+    if half.magnitude2() == 0.0{return None;}
+    let half = half.normalize();
+    let half = if half.z < 0.0{-half}else{half};
+    if half.dot(wi) * wi.z < 0.0 || half.dot(wo) * wo.z < 0.0{return None;}
+    return Some(half);
+}
+fn reflection_pdf(wo : Vector3<f32> , wi : Vector3<f32> , alpha : f32) -> f32{
+    let half = (wo + wi).normalize();
+    return microfacet::visible_normal_pdf(wo, half, alpha) / (4.0 * wo.dot(half).abs());
+}
+fn cosine_hemisphere(u : [f32;2]) -> Vector3<f32>{
+    let radius = u[0].sqrt();
+    let phi = 2.0 * std::f32::consts::PI * u[1];
+    return Vector3::new(radius * phi.cos(),radius * phi.sin(),(1.0 - u[0]).max(0.0).sqrt());
+}
+///Ashikhmin and Shirley's diffuse term, it only scatters what the specular layer lets through at both directions.
+fn plastic_diffuse(wo : Vector3<f32> , wi : Vector3<f32> , eta : f32 , specular : f32) -> f32{
+    let rs = (microfacet::reflectance(eta) * specular).min(1.0);
+    let through = |cos : f32| 1.0 - (1.0 - cos / 2.0).powi(5);
+    return 28.0 / (23.0 * std::f32::consts::PI) * (1.0 - rs) * through(wo.z) * through(wi.z);
+}
+fn plastic_specular_probability(wo : Vector3<f32> , color : Vector3<f32> , eta : f32 , specular : f32) -> f32{
+    let specular = (microfacet::fresnel_dielectric(wo.z, eta) * specular).min(1.0);
+    let diffuse = (color.x + color.y + color.z) / 3.0 * (1.0 - specular);
+    if diffuse <= 0.0{return 1.0;}
+    return (specular / (specular + diffuse)).max(0.1);
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const SAMPLES : usize = 200_000;
+
+    struct Rng(u64);
+    impl Rng{
+        fn next(&mut self) -> f32{
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            return (self.0 >> 40) as f32 / (1u64 << 24) as f32;
+        }
+        fn u3(&mut self) -> [f32;3]{
+            return [self.next(),self.next(),self.next()];
+        }
+        fn sphere(&mut self) -> Vector3<f32>{
+            let z = 1.0 - 2.0 * self.next();
+            let radius = (1.0 - z * z).max(0.0).sqrt();
+            let phi = 2.0 * std::f32::consts::PI * self.next();
+            return Vector3::new(radius * phi.cos(),radius * phi.sin(),z);
+        }
+    }
+    fn direction(cos_theta : f32 , phi : f32) -> Vector3<f32>{
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        return Vector3::new(sin_theta * phi.cos(),sin_theta * phi.sin(),cos_theta);
+    }
+    fn white() -> Vector3<f32>{
+        return Vector3::new(1.0,1.0,1.0);
+    }
+    ///Bsdfs that can not gain energy, seen from outside for the ones that refract.
+    fn bsdfs() -> Vec<Bsdf>{
+        return vec!(
+            Bsdf::Lambert{albedo : white()},
+            Bsdf::Conductor{f0 : white(), alpha : 0.05},
+            Bsdf::Conductor{f0 : Vector3::new(0.9,0.6,0.3), alpha : 0.5},
+            Bsdf::Conductor{f0 : white(), alpha : 1.0},
+            Bsdf::Dielectric{eta : 1.5, alpha : 0.0, tint : white()},
+            Bsdf::Dielectric{eta : 1.5, alpha : 0.3, tint : white()},
+            Bsdf::Dielectric{eta : 1.33, alpha : 0.8, tint : white()},
+            Bsdf::ThinDielectric{eta : 1.5, tint : white()},
+            Bsdf::Plastic{color : white(), alpha : 0.2, eta : 1.5, specular : 1.0},
+            Bsdf::Plastic{color : white(), alpha : 0.7, eta : 1.5, specular : 2.0},
+            Bsdf::Principled{base_color : white(), metallic : 0.5, alpha : 0.3, eta : 1.5, specular : 1.0, transmission : 0.5},
+            Bsdf::Principled{base_color : white(), metallic : 0.0, alpha : 0.6, eta : 1.45, specular : 1.0, transmission : 0.0},
+        );
+    }
+    ///Integrates f * |cos| over the sphere by importance sampling, the directional albedo.
+    fn albedo(bsdf : &Bsdf , wo : Vector3<f32> , rng : &mut Rng) -> Vector3<f32>{
+        let mut sum = Vector3::new(0.0,0.0,0.0);
+        for _ in 0..SAMPLES{
+            if let Some(sample) = bsdf.sample(wo, rng.u3()){
+                sum += sample.weight;
+            }
+        }
+        return sum / SAMPLES as f32;
+    }
+    #[test]
+    fn energy_conservation(){
+        let mut rng = Rng(1);
+        for bsdf in bsdfs(){
+            for &cos_theta in [1.0,0.7,0.3,0.05].iter(){
+                let albedo = albedo(&bsdf, direction(cos_theta, 0.3), &mut rng);
+                for c in 0..3{
+                    assert!(albedo[c] <= 1.01, "{:?} reflects {} at cos {}",bsdf,albedo[c],cos_theta);
+                }
+            }
+        }
+        //A white lambert and a smooth glass boundary lose nothing.
+        let lambert = albedo(&Bsdf::Lambert{albedo : white()}, direction(0.5, 0.0), &mut rng);
+        assert!((lambert.x - 1.0).abs() < 1e-3);
+        let glass = albedo(&Bsdf::Dielectric{eta : 1.0, alpha : 0.0, tint : white()}, direction(0.5, 0.0), &mut rng);
+        assert!((glass.x - 1.0).abs() < 1e-3);
+    }
+    #[test]
+    fn uniform_integration_matches_sampling(){
+        let mut rng = Rng(2);
+        for bsdf in bsdfs().into_iter().filter(|bsdf| !matches!(bsdf, Bsdf::Dielectric{alpha,..} if *alpha < MIN_ALPHA) && !matches!(bsdf, Bsdf::ThinDielectric{..})){
+            if let Bsdf::Conductor{alpha,..} = bsdf{
+                if alpha < 0.3{continue;}
+            }
+            let wo = direction(0.6, 1.0);
+            let mut uniform = Vector3::new(0.0,0.0,0.0);
+            let mut pdf_integral = 0.0;
+            for _ in 0..SAMPLES{
+                let wi = rng.sphere();
+                uniform += bsdf.eval(wo, wi) * (wi.z.abs() * 4.0 * std::f32::consts::PI);
+                pdf_integral += bsdf.pdf(wo, wi) * 4.0 * std::f32::consts::PI;
+            }
+            uniform /= SAMPLES as f32;
+            pdf_integral /= SAMPLES as f32;
+            let sampled = albedo(&bsdf, wo, &mut rng);
+            assert!((uniform.x - sampled.x).abs() < 0.03, "{:?} integrates to {} but samples {}",bsdf,uniform.x,sampled.x);
+            //Microfacet reflections that end up below the horizon are discarded, the density only covers the samples that succeed.
+            let succeeded = (0..SAMPLES).filter(|_| bsdf.sample(wo, rng.u3()).is_some()).count() as f32 / SAMPLES as f32;
+            assert!((pdf_integral - succeeded).abs() < 0.03, "{:?} pdf integrates to {} but {} of the samples succeed",bsdf,pdf_integral,succeeded);
+        }
+    }
+    #[test]
+    fn sample_matches_eval_and_pdf(){
+        let mut rng = Rng(3);
+        for bsdf in bsdfs(){
+            let wo = direction(0.4, 2.0);
+            for _ in 0..1000{
+                let sample = match bsdf.sample(wo, rng.u3()){
+                    Some(sample) => sample,
+                    None => continue,
+                };
+                assert!((sample.wi.magnitude() - 1.0).abs() < 1e-3);
+                if sample.delta{continue;}
+                let pdf = bsdf.pdf(wo, sample.wi);
+                assert!((pdf - sample.pdf).abs() <= 1e-3 * pdf.max(1.0), "{:?} pdf {} != {}",bsdf,pdf,sample.pdf);
+                let weight = bsdf.eval(wo, sample.wi) * (sample.wi.z.abs() / pdf);
+                assert!((weight - sample.weight).magnitude() <= 1e-3 * weight.magnitude().max(1.0));
+            }
+        }
+    }
+    #[test]
+    fn reciprocity(){
+        let mut rng = Rng(4);
+        for bsdf in bsdfs(){
+            for _ in 0..1000{
+                let (wo,wi) = (rng.sphere(),rng.sphere());
+                let forward = bsdf.eval(wo, wi);
+                let backward = bsdf.eval(wi, wo);
+                //Radiance is scaled by the squared relative index of refraction when it crosses into a denser medium.
+                let scale = match bsdf{
+                    Bsdf::Dielectric{eta,..} if !same_hemisphere(wo, wi) => if wo.z > 0.0{1.0 / (eta * eta)}else{eta * eta},
+                    Bsdf::Principled{..} if !same_hemisphere(wo, wi) => continue,
+                    _ => 1.0,
+                };
+                let expected = backward * scale;
+                assert!((forward - expected).magnitude() <= 1e-3 * forward.magnitude().max(1.0), "{:?} {:?} {:?}",bsdf,forward,expected);
+            }
+        }
+    }
+}
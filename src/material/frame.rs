@@ -0,0 +1,29 @@
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+
+///Orthonormal basis around a shading normal, bsdfs work in this frame with the normal along +z.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Frame{
+    pub tangent : Vector3<f32>,
+    pub bitangent : Vector3<f32>,
+    pub normal : Vector3<f32>,
+}
+impl Frame{
+    ///Duff et al., Building an Orthonormal Basis, Revisited, 2017.
+    pub fn new(normal : Vector3<f32>) -> Self{
+        let sign = 1f32.copysign(normal.z);
+        let a = -1.0 / (sign + normal.z);
+        let b = normal.x * normal.y * a;
+        return Self{
+            tangent : Vector3::new(1.0 + sign * normal.x * normal.x * a,sign * b,-sign * normal.x),
+            bitangent : Vector3::new(b,sign + normal.y * normal.y * a,-normal.y),
+            normal,
+        };
+    }
+    pub fn to_local(self , v : Vector3<f32>) -> Vector3<f32>{
+        return Vector3::new(v.dot(self.tangent),v.dot(self.bitangent),v.dot(self.normal));
+    }
+    pub fn to_world(self , v : Vector3<f32>) -> Vector3<f32>{
+        return self.tangent * v.x + self.bitangent * v.y + self.normal * v.z;
+    }
+}
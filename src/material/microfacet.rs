@@ -0,0 +1,88 @@
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+
+///Directions are in the local shading frame where the normal is +z.
+pub fn reflect(wo : Vector3<f32> , normal : Vector3<f32>) -> Vector3<f32>{
+    return -wo + normal * (2.0 * wo.dot(normal));
+}
+///Refracts wo through a surface with the relative index of refraction eta, the normal may face either side.
+///Returns the refracted direction and the relative eta seen from the side of wo, None on total internal reflection.
+pub fn refract(wo : Vector3<f32> , mut normal : Vector3<f32> , mut eta : f32) -> Option<(Vector3<f32>,f32)>{
+    let mut cos_i = wo.dot(normal);
+    if cos_i < 0.0{
+        eta = 1.0 / eta;
+        cos_i = -cos_i;
+        normal = -normal;
+    }
+    let sin2_i = (1.0 - cos_i * cos_i).max(0.0);
+    let sin2_t = sin2_i / (eta * eta);
+    if sin2_t >= 1.0{return None;}
+    let cos_t = (1.0 - sin2_t).sqrt();
+    return Some((-wo / eta + normal * (cos_i / eta - cos_t),eta));
+}
+///Unpolarized fresnel reflectance of a dielectric interface, a negative cosine means the ray arrives from inside.
+pub fn fresnel_dielectric(cos_i : f32 , mut eta : f32) -> f32{
+    let mut cos_i = cos_i.clamp(-1.0, 1.0);
+    if cos_i < 0.0{
+        eta = 1.0 / eta;
+        cos_i = -cos_i;
+    }
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0{return 1.0;}
+    let cos_t = (1.0 - sin2_t).max(0.0).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    return (parallel * parallel + perpendicular * perpendicular) / 2.0;
+}
+pub fn fresnel_schlick(f0 : Vector3<f32> , cos_i : f32) -> Vector3<f32>{
+    let weight = (1.0 - cos_i.abs()).clamp(0.0, 1.0).powi(5);
+    return f0 + (Vector3::new(1.0,1.0,1.0) - f0) * weight;
+}
+///Reflectance at normal incidence of an interface with the given index of refraction.
+pub fn reflectance(eta : f32) -> f32{
+    return ((eta - 1.0) / (eta + 1.0)).powi(2);
+}
+///The GGX normal distribution.
+pub fn distribution(normal : Vector3<f32> , alpha : f32) -> f32{
+    let alpha2 = alpha * alpha;
+    let denominator = normal.z * normal.z * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (std::f32::consts::PI * denominator * denominator);
+}
+fn lambda(w : Vector3<f32> , alpha : f32) -> f32{
+    let cos2 = w.z * w.z;
+    if cos2 <= 0.0{return f32::INFINITY;}
+    let tan2 = (1.0 - cos2).max(0.0) / cos2;
+    return ((1.0 + alpha * alpha * tan2).sqrt() - 1.0) / 2.0;
+}
+///Smith masking of a single direction.
+pub fn masking(w : Vector3<f32> , alpha : f32) -> f32{
+    return 1.0 / (1.0 + lambda(w, alpha));
+}
+///Height correlated Smith masking and shadowing.
+pub fn masking_shadowing(wo : Vector3<f32> , wi : Vector3<f32> , alpha : f32) -> f32{
+    return 1.0 / (1.0 + lambda(wo, alpha) + lambda(wi, alpha));
+}
+///Samples a microfacet normal visible from wo, the result always faces +z.
+///Heitz, Sampling the GGX Distribution of Visible Normals, 2018.
+pub fn sample_visible_normal(wo : Vector3<f32> , alpha : f32 , u : [f32;2]) -> Vector3<f32>{
+    let mut wh = Vector3::new(alpha * wo.x,alpha * wo.y,wo.z).normalize();
+    if wh.z < 0.0{
+        wh = -wh;
+    }
+    let t1 = if wh.z < 0.99999{Vector3::unit_z().cross(wh).normalize()}else{Vector3::unit_x()};
+    let t2 = wh.cross(t1);
+    let radius = u[0].sqrt();
+    let phi = 2.0 * std::f32::consts::PI * u[1];
+    let px = radius * phi.cos();
+    let py = radius * phi.sin();
+    let s = (1.0 + wh.z) / 2.0;
+    let py = (1.0 - s) * (1.0 - px * px).max(0.0).sqrt() + s * py;
+    let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+    let normal = t1 * px + t2 * py + wh * pz;
+    return Vector3::new(alpha * normal.x,alpha * normal.y,normal.z.max(1e-6)).normalize();
+}
+///Density of sample_visible_normal with respect to the microfacet normal.
+pub fn visible_normal_pdf(wo : Vector3<f32> , normal : Vector3<f32> , alpha : f32) -> f32{
+    if wo.z == 0.0{return 0.0;}
+    return masking(wo, alpha) / wo.z.abs() * distribution(normal, alpha) * wo.dot(normal).abs();
+}
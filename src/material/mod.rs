@@ -0,0 +1,62 @@
+//!Surface models of the tracers, lambert, ggx conductors and dielectrics, thin dielectrics and a principled bsdf with sampling and pdf evaluation.
+//!shaders/bsdf.glsl mirrors them for the gpu tracer, the renderer's tests compare both.
+
+mod bsdf;
+mod microfacet;
+mod texture;
+mod frame;
+
+pub use bsdf::Bsdf;
+pub use bsdf::BsdfSample;
+pub use texture::Texture;
+pub use texture::Image;
+pub use frame::Frame;
+
+use cgmath::Vector3;
+
+///The surface models, every parameter can be textured, indices of refraction are constant.
+#[derive(Clone,Debug,PartialEq)]
+pub enum MaterialKind{
+    Lambert{albedo : Texture},
+    Conductor{color : Texture, roughness : Texture},
+    Dielectric{ior : f32, roughness : Texture, tint : Texture},
+    ThinDielectric{ior : f32, tint : Texture},
+    Principled{base_color : Texture, metallic : Texture, roughness : Texture, specular : Texture, transmission : Texture, ior : f32},
+}
+#[derive(Clone,Debug,PartialEq)]
+pub struct Material{
+    pub kind : MaterialKind,
+    ///Radiance leaving the surface in every direction.
+    pub emission : Texture,
+}
+impl Material{
+    pub fn lambert(albedo : [f32;3]) -> Self{
+        return Self{kind : MaterialKind::Lambert{albedo : Texture::color(albedo)}, emission : Texture::value(0.0)};
+    }
+    ///A black surface that only emits light.
+    pub fn emissive(emission : [f32;3]) -> Self{
+        return Self{kind : MaterialKind::Lambert{albedo : Texture::value(0.0)}, emission : Texture::color(emission)};
+    }
+    ///Evaluates the textures at the uv coordinate, roughness is remapped to the GGX alpha.
+    pub fn bsdf(&self , uv : [f32;2]) -> Bsdf{
+        let alpha = |roughness : &Texture| roughness.eval_scalar(uv).clamp(0.0, 1.0).powi(2);
+        match &self.kind{
+            MaterialKind::Lambert{albedo} => Bsdf::Lambert{albedo : albedo.eval(uv)},
+            MaterialKind::Conductor{color,roughness} => Bsdf::Conductor{f0 : color.eval(uv), alpha : alpha(roughness)},
+            MaterialKind::Dielectric{ior,roughness,tint} => Bsdf::Dielectric{eta : *ior, alpha : alpha(roughness), tint : tint.eval(uv)},
+            MaterialKind::ThinDielectric{ior,tint} => Bsdf::ThinDielectric{eta : *ior, tint : tint.eval(uv)},
+            MaterialKind::Principled{base_color,metallic,roughness,specular,transmission,ior} => Bsdf::Principled{
+                base_color : base_color.eval(uv),
+                metallic : metallic.eval_scalar(uv),
+                alpha : alpha(roughness),
+                eta : *ior,
+                //A specular of 0.5 gives the plain fresnel of the index of refraction.
+                specular : specular.eval_scalar(uv) * 2.0,
+                transmission : transmission.eval_scalar(uv),
+            },
+        }
+    }
+    pub fn emission(&self , uv : [f32;2]) -> Vector3<f32>{
+        return self.emission.eval(uv);
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use cgmath::Vector3;
+
+///Linear rgb texels, row major starting at the top left.
+#[derive(Debug)]
+pub struct Image{
+    pub width : u32,
+    pub height : u32,
+    pub texels : Vec<[f32;3]>,
+    ///The file the image was loaded from so the renderer can upload it in its own format.
+    pub path : PathBuf,
+    ///The file is sRGB encoded.
+    pub srgb : bool,
+}
+///A material parameter, scalar parameters read the first channel.
+#[derive(Clone,Debug)]
+pub enum Texture{
+    Constant(Vector3<f32>),
+    ///Alternates between two values, scale is the amount of squares per unit of uv.
+    Checker{even : Vector3<f32>, odd : Vector3<f32>, scale : f32},
+    ///Bilinearly filtered and repeated outside [0,1].
+    Image(Arc<Image>),
+}
+///Images are equal when they come from the same file, the texels are not compared.
+impl PartialEq for Texture{
+    fn eq(&self , other : &Self) -> bool{
+        match (self,other){
+            (Texture::Constant(a),Texture::Constant(b)) => return a == b,
+            (Texture::Checker{even,odd,scale},Texture::Checker{even : other_even, odd : other_odd, scale : other_scale}) => return even == other_even && odd == other_odd && scale == other_scale,
+            (Texture::Image(a),Texture::Image(b)) => return Arc::ptr_eq(a, b) || (a.path == b.path && a.srgb == b.srgb),
+            _ => return false,
+        }
+    }
+}
+impl Texture{
+    pub fn value(value : f32) -> Self{
+        return Texture::Constant(Vector3::new(value,value,value));
+    }
+    pub fn color(color : [f32;3]) -> Self{
+        return Texture::Constant(Vector3::from(color));
+    }
+    ///Loads an 8 or 16 bit image, color textures are sRGB encoded while data such as roughness is linear.
+    pub fn load(path : &Path , srgb : bool) -> Result<Self,String>{
+        let image = image::open(path).map_err(|error| format!("Failed to load texture {} : {}.",path.display(),error))?.to_rgb16();
+        let decode = |c : u16| {
+            let c = c as f32 / 65535.0;
+            if !srgb{return c;}
+            if c <= 0.04045{c / 12.92}else{((c + 0.055) / 1.055).powf(2.4)}
+        };
+        return Ok(Texture::Image(Arc::new(Image{
+            width : image.width(),
+            height : image.height(),
+            texels : image.pixels().map(|pixel| [decode(pixel[0]),decode(pixel[1]),decode(pixel[2])]).collect(),
+            path : path.to_owned(),
+            srgb,
+        })));
+    }
+    pub fn eval(&self , uv : [f32;2]) -> Vector3<f32>{
+        match self{
+            Texture::Constant(value) => *value,
+            Texture::Checker{even,odd,scale} => {
+                let parity = ((uv[0] * scale).floor() + (uv[1] * scale).floor()) as i64;
+                if parity.rem_euclid(2) == 0{*even}else{*odd}
+            }
+            Texture::Image(image) => image.sample(uv),
+        }
+    }
+    pub fn eval_scalar(&self , uv : [f32;2]) -> f32{
+        return self.eval(uv).x;
+    }
+}
+impl Image{
+    fn texel(&self , x : i64 , y : i64) -> Vector3<f32>{
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        return Vector3::from(self.texels[y * self.width as usize + x]);
+    }
+    fn sample(&self , uv : [f32;2]) -> Vector3<f32>{
+        let x = uv[0] * self.width as f32 - 0.5;
+        let y = (1.0 - uv[1]) * self.height as f32 - 0.5;
+        let (x0,y0) = (x.floor(),y.floor());
+        let (fx,fy) = (x - x0,y - y0);
+        let (x0,y0) = (x0 as i64,y0 as i64);
+        let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+        let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+        return top * (1.0 - fy) + bottom * fy;
+    }
+}
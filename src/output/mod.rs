@@ -0,0 +1,96 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use exr::prelude::*;
+
+use super::tracer::Accumulator;
+use super::tracer::Aov;
+use super::post;
+use super::post::PostProcess;
+use super::post::PostSettings;
+
+///Sample type of the channels written to OpenEXR files.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Precision{
+    Half,
+    Full,
+}
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct OutputOptions{
+    pub precision : Precision,
+    ///Used to tonemap the beauty for 8 bit formats.
+    pub post : PostSettings,
+}
+///Writes the beauty, usually the resolved or denoised accumulation, to a file, the format is picked by the extension.
+///OpenEXR files hold every recorded aov as extra channels, Radiance files cannot so each aov gets a file of its own next to the beauty.
+///8 bit formats such as png are tonemapped and write the aovs to separate files as well.
+pub fn save(path : &Path , accumulator : &Accumulator , beauty : &[[f32;3]] , options : &OutputOptions) -> std::result::Result<(),String>{
+    match extension(path).as_deref(){
+        Some("exr") => return save_exr(path, accumulator, beauty, options.precision),
+        Some("hdr") => return save_hdr(path, accumulator, beauty),
+        Some("png") | Some("jpg") | Some("jpeg") | Some("bmp") | Some("tga") => return save_ldr(path, accumulator, beauty, &options.post),
+        _ => return Err(format!("Unsupported output format for {}, use .exr, .hdr or .png.",path.display())),
+    }
+}
+///Checks the extension up front so long renders do not fail at the end.
+pub fn is_supported(path : &Path) -> bool{
+    return matches!(extension(path).as_deref(),Some("exr") | Some("hdr") | Some("png") | Some("jpg") | Some("jpeg") | Some("bmp") | Some("tga"));
+}
+fn extension(path : &Path) -> Option<String>{
+    return path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase());
+}
+///Lighting aovs are tonemapped like the beauty, data aovs are written as their previews.
+pub fn save_ldr(path : &Path , accumulator : &Accumulator , beauty : &[[f32;3]] , settings : &PostSettings) -> std::result::Result<(),String>{
+    write_ldr(path, accumulator.width(), accumulator.height(), &PostProcess::new(*settings).process_rgb8(beauty))?;
+    for aov in accumulator.enabled_aovs(){
+        let pixels = accumulator.resolve_aov(aov).expect("Enabled aov has no data.");
+        let bytes = if aov.is_radiance(){PostProcess::new(*settings).process_rgb8(&pixels)}else{post::encode_rgb8(&aov.preview(&pixels))};
+        write_ldr(&aov_path(path, aov), accumulator.width(), accumulator.height(), &bytes)?;
+    }
+    return Ok(());
+}
+fn write_ldr(path : &Path , width : u32 , height : u32 , pixels : &[u8]) -> std::result::Result<(),String>{
+    return image::save_buffer(path, pixels, width, height, image::ColorType::Rgb8).map_err(|error| format!("Failed to write {} : {}.",path.display(),error));
+}
+pub fn save_exr(path : &Path , accumulator : &Accumulator , beauty : &[[f32;3]] , precision : Precision) -> std::result::Result<(),String>{
+    let mut channels = vec!();
+    push_channels(&mut channels, &["R","G","B"], beauty, precision);
+    for aov in accumulator.enabled_aovs(){
+        let pixels = accumulator.resolve_aov(aov).expect("Enabled aov has no data.");
+        //Positions, depth and ids are written at full precision regardless, half floats lose too much at a distance and can not count past 2048.
+        let precision = if matches!(aov, Aov::Position | Aov::Depth) || aov.is_id(){Precision::Full}else{precision};
+        push_channels(&mut channels, aov.channels(), &pixels, precision);
+    }
+    let size = (accumulator.width() as usize,accumulator.height() as usize);
+    let image = Image::from_channels(size, AnyChannels::sort(SmallVec::from_vec(channels)));
+    return image.write().to_file(path).map_err(|error| format!("Failed to write {} : {}.",path.display(),error));
+}
+pub fn save_hdr(path : &Path , accumulator : &Accumulator , beauty : &[[f32;3]]) -> std::result::Result<(),String>{
+    write_hdr(path, accumulator.width(), accumulator.height(), beauty)?;
+    for aov in accumulator.enabled_aovs(){
+        let pixels = accumulator.resolve_aov(aov).expect("Enabled aov has no data.");
+        write_hdr(&aov_path(path, aov), accumulator.width(), accumulator.height(), &pixels)?;
+    }
+    return Ok(());
+}
+///The path an aov is written to when the format has no layers, image.hdr becomes image.normal.hdr.
+pub fn aov_path(path : &Path , aov : Aov) -> PathBuf{
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("output");
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("hdr");
+    return path.with_file_name(format!("{}.{}.{}",stem,aov.name(),extension));
+}
+fn write_hdr(path : &Path , width : u32 , height : u32 , pixels : &[[f32;3]]) -> std::result::Result<(),String>{
+    let file = std::fs::File::create(path).map_err(|error| format!("Failed to create {} : {}.",path.display(),error))?;
+    //Radiance files store shared exponents, negative values such as normals are clamped to zero.
+    let data = pixels.iter().map(|pixel| image::Rgb([pixel[0].max(0.0),pixel[1].max(0.0),pixel[2].max(0.0)])).collect::<Vec<_>>();
+    return image::codecs::hdr::HdrEncoder::new(std::io::BufWriter::new(file)).encode(&data, width as usize, height as usize).map_err(|error| format!("Failed to write {} : {}.",path.display(),error));
+}
+fn push_channels(channels : &mut Vec<AnyChannel<FlatSamples>> , names : &[&str] , pixels : &[[f32;3]] , precision : Precision){
+    for (index,name) in names.iter().enumerate(){
+        let samples = match precision{
+            Precision::Half => FlatSamples::F16(pixels.iter().map(|pixel| f16::from_f32(pixel[index])).collect()),
+            Precision::Full => FlatSamples::F32(pixels.iter().map(|pixel| pixel[index]).collect()),
+        };
+        channels.push(AnyChannel::new(*name, samples));
+    }
+}
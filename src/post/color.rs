@@ -0,0 +1,239 @@
+use super::tonemap::mul_matrix;
+
+///How the final display values are encoded into the swapchain image.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Encoding{
+    ///sRGB transfer function, buffer copies bypass the hardware encoder so this is used for both SRGB and UNORM formats.
+    Srgb,
+    ///scRGB, linear Rec.709 where 1.0 is 80 nits.
+    ExtendedSrgbLinear,
+    ///Rec.2020 primaries with the ST2084 perceptual quantizer.
+    Hdr10,
+}
+impl Encoding{
+    pub fn from_color_space(color_space : ash::vk::ColorSpaceKHR) -> Self{
+        match color_space{
+            ash::vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT => Encoding::ExtendedSrgbLinear,
+            ash::vk::ColorSpaceKHR::HDR10_ST2084_EXT => Encoding::Hdr10,
+            _ => Encoding::Srgb,
+        }
+    }
+    pub fn is_hdr(self) -> bool{
+        return self != Encoding::Srgb;
+    }
+}
+pub fn srgb_encode(c : f32) -> f32{
+    if c <= 0.0031308{return 12.92 * c;}
+    return 1.055 * c.powf(1.0 / 2.4) - 0.055;
+}
+///ST2084 inverse EOTF, takes absolute luminance in nits.
+pub fn pq_encode(nits : f32) -> f32{
+    let (m1,m2) = (2610.0 / 16384.0,2523.0 / 4096.0 * 128.0);
+    let (c1,c2,c3) = (3424.0 / 4096.0,2413.0 / 4096.0 * 32.0,2392.0 / 4096.0 * 32.0);
+    let y = (nits / 10000.0).clamp(0.0, 1.0).powf(m1);
+    return ((c1 + c2 * y) / (1.0 + c3 * y)).powf(m2);
+}
+pub fn rec709_to_rec2020(color : [f32;3]) -> [f32;3]{
+    const M : [[f32;3];3] = [[0.627404,0.329282,0.0433136],[0.069097,0.91954,0.0113612],[0.0163916,0.0880132,0.895595]];
+    return mul_matrix(&M, color);
+}
+///Chromaticity of a daylight / planckian white point with the given correlated color temperature.
+fn white_point(temperature : f32 , tint : f32) -> [f32;2]{
+    let t = temperature.clamp(1667.0, 25000.0);
+    let x = if t <= 4000.0{
+        -0.2661239e9 / (t * t * t) - 0.2343589e6 / (t * t) + 0.8776956e3 / t + 0.179910
+    } else{
+        -3.025847e9 / (t * t * t) + 2.107038e6 / (t * t) + 0.2226347e3 / t + 0.240390
+    };
+    let y = if t <= 2222.0{
+        -1.1063814 * x * x * x - 1.3481102 * x * x + 2.1855583 * x - 0.20219683
+    } else if t <= 4000.0{
+        -0.9549476 * x * x * x - 1.3741859 * x * x + 2.09137 * x - 0.16748867
+    } else{
+        3.081758 * x * x * x - 5.873387 * x * x + 3.75113 * x - 0.37001483
+    };
+    return [x,y + tint * 0.01];
+}
+const BRADFORD : [[f32;3];3] = [[0.8951,0.2664,-0.1614],[-0.7502,1.7135,0.0367],[0.0389,-0.0685,1.0296]];
+const REC709_TO_XYZ : [[f32;3];3] = [[0.4124,0.3576,0.1805],[0.2126,0.7152,0.0722],[0.0193,0.1192,0.9505]];
+
+fn xy_to_lms(xy : [f32;2]) -> [f32;3]{
+    let xyz = [xy[0] / xy[1],1.0,(1.0 - xy[0] - xy[1]) / xy[1]];
+    return mul_matrix(&BRADFORD, xyz);
+}
+///Bradford chromatic adaptation matrix that makes a surface lit by the given white appear neutral.
+///A temperature of 6500 with no tint leaves the image unchanged.
+pub fn white_balance_matrix(temperature : f32 , tint : f32) -> [[f32;3];3]{
+    let source = xy_to_lms(white_point(temperature, tint));
+    let target = xy_to_lms(white_point(6500.0, 0.0));
+    let scale = [[target[0] / source[0],0.0,0.0],[0.0,target[1] / source[1],0.0],[0.0,0.0,target[2] / source[2]]];
+    let rgb_to_lms = mul_matrices(&BRADFORD, &REC709_TO_XYZ);
+    return mul_matrices(&invert(&rgb_to_lms), &mul_matrices(&scale, &rgb_to_lms));
+}
+fn mul_matrices(a : &[[f32;3];3] , b : &[[f32;3];3]) -> [[f32;3];3]{
+    let mut m = [[0.0;3];3];
+    for row in 0..3{
+        for column in 0..3{
+            m[row][column] = (0..3).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    return m;
+}
+fn invert(m : &[[f32;3];3]) -> [[f32;3];3]{
+    let determinant = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0]) + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    let d = 1.0 / determinant;
+    return [
+        [(m[1][1] * m[2][2] - m[1][2] * m[2][1]) * d,(m[0][2] * m[2][1] - m[0][1] * m[2][2]) * d,(m[0][1] * m[1][2] - m[0][2] * m[1][1]) * d],
+        [(m[1][2] * m[2][0] - m[1][0] * m[2][2]) * d,(m[0][0] * m[2][2] - m[0][2] * m[2][0]) * d,(m[0][2] * m[1][0] - m[0][0] * m[1][2]) * d],
+        [(m[1][0] * m[2][1] - m[1][1] * m[2][0]) * d,(m[0][1] * m[2][0] - m[0][0] * m[2][1]) * d,(m[0][0] * m[1][1] - m[0][1] * m[1][0]) * d],
+    ];
+}
+///Converts to an IEEE half float, rounding to nearest.
+pub fn f32_to_f16(value : f32) -> u16{
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff{
+        return sign | 0x7c00 | if mantissa != 0{0x200}else{0};
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f{
+        return sign | 0x7c00;
+    }
+    if exponent <= 0{
+        if exponent < -10{return sign;}
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = (mantissa >> shift) as u16;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | (half + round as u16);
+    }
+    let half = sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16;
+    return half + ((mantissa >> 12) & 1) as u16;
+}
+///Writes one encoded pixel with all channels in [0,1], or linear values for float formats.
+///Fails for formats the swapchain is never created with, see renderer::format_size.
+pub fn pack_pixel(format : ash::vk::Format , c : [f32;3] , out : &mut [u8]) -> Result<(),String>{
+    let unorm8 = |v : f32| (v.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+    let unorm10 = |v : f32| (v.clamp(0.0, 1.0) * 1023.0 + 0.5) as u32;
+    match format{
+        ash::vk::Format::B8G8R8A8_SRGB | ash::vk::Format::B8G8R8A8_UNORM => {
+            out.copy_from_slice(&[unorm8(c[2]),unorm8(c[1]),unorm8(c[0]),255]);
+        }
+        ash::vk::Format::R8G8B8A8_SRGB | ash::vk::Format::R8G8B8A8_UNORM | ash::vk::Format::A8B8G8R8_SRGB_PACK32 | ash::vk::Format::A8B8G8R8_UNORM_PACK32 => {
+            out.copy_from_slice(&[unorm8(c[0]),unorm8(c[1]),unorm8(c[2]),255]);
+        }
+        ash::vk::Format::A2B10G10R10_UNORM_PACK32 => {
+            out.copy_from_slice(&(3 << 30 | unorm10(c[2]) << 20 | unorm10(c[1]) << 10 | unorm10(c[0])).to_le_bytes());
+        }
+        ash::vk::Format::A2R10G10B10_UNORM_PACK32 => {
+            out.copy_from_slice(&(3 << 30 | unorm10(c[0]) << 20 | unorm10(c[1]) << 10 | unorm10(c[2])).to_le_bytes());
+        }
+        ash::vk::Format::R16G16B16A16_SFLOAT => {
+            for (i,&v) in [c[0],c[1],c[2],1.0].iter().enumerate(){
+                out[i * 2..i * 2 + 2].copy_from_slice(&f32_to_f16(v).to_le_bytes());
+            }
+        }
+        _ => return Err(format!("Unsupported swapchain format {:?} for the post process pass.",format)),
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn close(a : [f32;3] , b : [f32;3] , tolerance : f32) -> bool{
+        return a.iter().zip(b.iter()).all(|(a,b)| (a - b).abs() <= tolerance);
+    }
+    #[test]
+    fn srgb(){
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_encode(0.18) - 0.4614).abs() < 1e-3);
+        //Both pieces meet at the threshold.
+        assert!((srgb_encode(0.0031308) - (1.055 * 0.0031308f32.powf(1.0 / 2.4) - 0.055)).abs() < 1e-6);
+    }
+    #[test]
+    fn pq(){
+        //Reference values of the ST2084 curve.
+        assert!(pq_encode(0.0) < 1e-6);
+        assert!((pq_encode(100.0) - 0.5081).abs() < 1e-3);
+        assert!((pq_encode(1000.0) - 0.7518).abs() < 1e-3);
+        assert!((pq_encode(10000.0) - 1.0).abs() < 1e-5);
+        assert_eq!(pq_encode(20000.0), pq_encode(10000.0));
+        assert!(pq_encode(-5.0) < 1e-6);
+    }
+    #[test]
+    fn rec2020(){
+        assert!(close(rec709_to_rec2020([1.0,1.0,1.0]), [1.0,1.0,1.0], 1e-4));
+        //Pure Rec.709 red is inside the wider Rec.2020 gamut.
+        let red = rec709_to_rec2020([1.0,0.0,0.0]);
+        assert!(red[0] < 1.0 && red[1] > 0.0 && red[2] > 0.0);
+    }
+    #[test]
+    fn white_balance(){
+        let identity = white_balance_matrix(6500.0, 0.0);
+        for (row,values) in identity.iter().enumerate(){
+            for (column,value) in values.iter().enumerate(){
+                assert!((value - if row == column{1.0}else{0.0}).abs() < 1e-4);
+            }
+        }
+        //The white of a warm light turns into the 6500 kelvin white, and neutral turns blue.
+        let rgb = |xy : [f32;2]| mul_matrix(&invert(&REC709_TO_XYZ), [xy[0] / xy[1],1.0,(1.0 - xy[0] - xy[1]) / xy[1]]);
+        let balanced = mul_matrix(&white_balance_matrix(3000.0, 0.0), rgb(white_point(3000.0, 0.0)));
+        assert!(close(balanced, rgb(white_point(6500.0, 0.0)), 1e-3), "{:?}", balanced);
+        let gray = mul_matrix(&white_balance_matrix(3000.0, 0.0), [0.5,0.5,0.5]);
+        assert!(gray[2] > gray[1] && gray[1] > gray[0]);
+        let gray = mul_matrix(&white_balance_matrix(9000.0, 0.0), [0.5,0.5,0.5]);
+        assert!(gray[0] > gray[2]);
+    }
+    #[test]
+    fn half_floats(){
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        //Rounds to the nearest half, overflows to infinity and keeps nan a nan.
+        assert_eq!(f32_to_f16(1.0 + 0.75 / 1024.0), 0x3c01);
+        assert_eq!(f32_to_f16(1.0 + 0.25 / 1024.0), 0x3c00);
+        assert_eq!(f32_to_f16(1e6), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+        assert_eq!(f32_to_f16(f32::NAN) & 0x7c00, 0x7c00);
+        assert_ne!(f32_to_f16(f32::NAN) & 0x3ff, 0);
+        //Subnormals down to the smallest one.
+        assert_eq!(f32_to_f16(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(2f32.powi(-15)), 0x0200);
+        assert_eq!(f32_to_f16(2f32.powi(-30)), 0x0000);
+    }
+    #[test]
+    fn pack(){
+        let mut out = [0u8;4];
+        pack_pixel(ash::vk::Format::B8G8R8A8_SRGB, [1.0,0.5,0.0], &mut out).expect("Failed to pack a pixel.");
+        assert_eq!(out, [0,128,255,255]);
+        pack_pixel(ash::vk::Format::R8G8B8A8_UNORM, [1.0,0.5,-1.0], &mut out).expect("Failed to pack a pixel.");
+        assert_eq!(out, [255,128,0,255]);
+        pack_pixel(ash::vk::Format::A2B10G10R10_UNORM_PACK32, [1.0,0.0,0.5], &mut out).expect("Failed to pack a pixel.");
+        assert_eq!(u32::from_le_bytes(out), 3 << 30 | 512 << 20 | 1023);
+        pack_pixel(ash::vk::Format::A2R10G10B10_UNORM_PACK32, [1.0,0.0,0.5], &mut out).expect("Failed to pack a pixel.");
+        assert_eq!(u32::from_le_bytes(out), 3 << 30 | 1023 << 20 | 512);
+        let mut wide = [0u8;8];
+        pack_pixel(ash::vk::Format::R16G16B16A16_SFLOAT, [2.0,0.5,-1.0], &mut wide).expect("Failed to pack a pixel.");
+        assert_eq!(wide, [0x00,0x40,0x00,0x38,0x00,0xbc,0x00,0x3c]);
+        assert!(pack_pixel(ash::vk::Format::R5G6B5_UNORM_PACK16, [1.0,1.0,1.0], &mut out).is_err());
+    }
+    #[test]
+    fn swapchain_formats(){
+        //Every format the swapchain may be created with can be packed.
+        let formats = [ash::vk::Format::B8G8R8A8_SRGB,ash::vk::Format::B8G8R8A8_UNORM,ash::vk::Format::R8G8B8A8_SRGB,ash::vk::Format::R8G8B8A8_UNORM,ash::vk::Format::A8B8G8R8_SRGB_PACK32,ash::vk::Format::A8B8G8R8_UNORM_PACK32,ash::vk::Format::A2B10G10R10_UNORM_PACK32,ash::vk::Format::A2R10G10B10_UNORM_PACK32,ash::vk::Format::R16G16B16A16_SFLOAT,ash::vk::Format::R5G6B5_UNORM_PACK16,ash::vk::Format::R32G32B32A32_SFLOAT];
+        for format in formats{
+            match crate::renderer::format_size(format){
+                Some(size) => assert!(pack_pixel(format, [0.5;3], &mut vec!(0u8;size)).is_ok(), "{:?}", format),
+                None => assert!(pack_pixel(format, [0.5;3], &mut [0u8;16]).is_err(), "{:?}", format),
+            }
+        }
+    }
+}
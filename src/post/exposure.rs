@@ -0,0 +1,44 @@
+const HISTOGRAM_BINS : usize = 64;
+const MIN_LOG_LUMINANCE : f32 = -10.0;
+const MAX_LOG_LUMINANCE : f32 = 10.0;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Exposure{
+    ///A fixed exposure in stops.
+    Manual(f32),
+    ///Exposure derived from the luminance histogram, offset by a compensation in stops.
+    Auto(f32),
+}
+pub fn luminance(color : [f32;3]) -> f32{
+    return 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+}
+///Builds a log2 luminance histogram and returns the exposure in stops that maps the average to middle grey.
+///The darkest half and the brightest five percent of the pixels are ignored so small highlights do not dim the image.
+pub fn auto_exposure(pixels : &[[f32;3]]) -> f32{
+    let mut histogram = [0u32;HISTOGRAM_BINS];
+    for &pixel in pixels.iter(){
+        let log_luminance = luminance(pixel).max(1e-6).log2();
+        let bin = ((log_luminance - MIN_LOG_LUMINANCE) / (MAX_LOG_LUMINANCE - MIN_LOG_LUMINANCE) * HISTOGRAM_BINS as f32) as i32;
+        histogram[bin.clamp(0, HISTOGRAM_BINS as i32 - 1) as usize] += 1;
+    }
+    let total = pixels.len() as f32;
+    let (low,high) = (total * 0.5,total * 0.95);
+    let mut seen = 0.0;
+    let mut sum = 0.0;
+    let mut weight = 0.0;
+    for (bin,&count) in histogram.iter().enumerate(){
+        let count = count as f32;
+        let in_range = (seen + count).min(high) - seen.max(low);
+        if in_range > 0.0{
+            sum += bin_log_luminance(bin) * in_range;
+            weight += in_range;
+        }
+        seen += count;
+    }
+    if weight == 0.0{return 0.0;}
+    let average = sum / weight;
+    return 0.18f32.log2() - average;
+}
+fn bin_log_luminance(bin : usize) -> f32{
+    return MIN_LOG_LUMINANCE + (bin as f32 + 0.5) / HISTOGRAM_BINS as f32 * (MAX_LOG_LUMINANCE - MIN_LOG_LUMINANCE);
+}
@@ -0,0 +1,183 @@
+mod tonemap;
+mod exposure;
+mod color;
+
+pub use tonemap::Tonemapper;
+pub use tonemap::TONEMAPPERS;
+pub use exposure::Exposure;
+pub use color::Encoding;
+
+///How quickly auto exposure follows the scene, the fraction of the remaining difference covered each frame.
+const ADAPTATION_RATE : f32 = 0.2;
+///scRGB defines 1.0 as 80 nits.
+const SCRGB_NITS : f32 = 80.0;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct PostSettings{
+    pub tonemapper : Tonemapper,
+    pub exposure : Exposure,
+    ///White balance temperature in kelvin, 6500 is neutral.
+    pub temperature : f32,
+    ///Green magenta shift applied on top of the temperature.
+    pub tint : f32,
+    ///Luminance in nits that scene white maps to on hdr displays.
+    pub paper_white : f32,
+    ///Peak luminance of the hdr display in nits.
+    pub peak_luminance : f32,
+}
+impl Default for PostSettings{
+    fn default() -> Self{
+        return Self{
+            tonemapper : Tonemapper::Aces,
+            exposure : Exposure::Manual(0.0),
+            temperature : 6500.0,
+            tint : 0.0,
+            paper_white : 203.0,
+            peak_luminance : 1000.0,
+        };
+    }
+}
+///The pass between accumulation and presentation, exposes, white balances, tonemaps and encodes linear radiance.
+pub struct PostProcess{
+    pub settings : PostSettings,
+    adapted_exposure : Option<f32>,
+}
+impl PostProcess{
+    pub fn new(settings : PostSettings) -> Self{
+        return Self{
+            settings,
+            adapted_exposure : None,
+        };
+    }
+    ///The exposure in stops used for the last processed frame.
+    pub fn exposure(&self) -> f32{
+        match self.settings.exposure{
+            Exposure::Manual(stops) => stops,
+            Exposure::Auto(compensation) => self.adapted_exposure.unwrap_or(0.0) + compensation,
+        }
+    }
+    ///Applies exposure and white balance, the result is still linear and unbounded.
+    pub fn expose(&mut self , radiance : &[[f32;3]]) -> Vec<[f32;3]>{
+        if let Exposure::Auto(_) = self.settings.exposure{
+            let target = exposure::auto_exposure(radiance);
+            let adapted = self.adapted_exposure.map_or(target, |adapted| adapted + (target - adapted) * ADAPTATION_RATE);
+            self.adapted_exposure = Some(adapted);
+        }
+        let scale = 2f32.powf(self.exposure());
+        let white_balance = color::white_balance_matrix(self.settings.temperature, self.settings.tint);
+        return radiance.iter().map(|&pixel| {
+            let c = tonemap::mul_matrix(&white_balance, pixel);
+            [c[0] * scale,c[1] * scale,c[2] * scale]
+        }).collect();
+    }
+    ///Tonemaps to linear display values in [0,1], used for sdr output and 8 bit image files.
+    pub fn tonemap(&mut self , radiance : &[[f32;3]]) -> Vec<[f32;3]>{
+        let tonemapper = self.settings.tonemapper;
+        return self.expose(radiance).into_iter().map(|pixel| tonemapper.apply(pixel)).collect();
+    }
+    ///Tonemaps and sRGB encodes the radiance into 8 bit rgb triplets, used for image files.
+    pub fn process_rgb8(&mut self , radiance : &[[f32;3]]) -> Vec<u8>{
+        return encode_rgb8(&self.tonemap(radiance));
+    }
+    ///Produces the bytes of a full swapchain image in the given surface format, fails for formats it can't encode.
+    pub fn process(&mut self , radiance : &[[f32;3]] , surface_format : ash::vk::SurfaceFormatKHR) -> Result<Vec<u8>,String>{
+        let encoding = Encoding::from_color_space(surface_format.color_space);
+        let display = if encoding.is_hdr(){
+            let peak = self.settings.peak_luminance / self.settings.paper_white;
+            self.expose(radiance).into_iter().map(|pixel| hdr_rolloff(pixel, peak)).collect::<Vec<_>>()
+        } else{
+            self.tonemap(radiance)
+        };
+        return self.encode(&display, surface_format);
+    }
+    ///Encodes display values, where 1 is paper white, into a swapchain image without exposing or tonemapping them.
+    pub fn encode(&self , display : &[[f32;3]] , surface_format : ash::vk::SurfaceFormatKHR) -> Result<Vec<u8>,String>{
+        let encoding = Encoding::from_color_space(surface_format.color_space);
+        let bytes_per_pixel = super::renderer::format_size(surface_format.format)
+            .ok_or_else(|| format!("Unsupported swapchain format {:?} for the post process pass.",surface_format.format))?;
+        let mut bytes = vec!(0u8;display.len() * bytes_per_pixel);
+        for (pixel,out) in display.iter().zip(bytes.chunks_exact_mut(bytes_per_pixel)){
+            let encoded = match encoding{
+                Encoding::Srgb => [color::srgb_encode(pixel[0]),color::srgb_encode(pixel[1]),color::srgb_encode(pixel[2])],
+                Encoding::ExtendedSrgbLinear => {
+                    let scale = self.settings.paper_white / SCRGB_NITS;
+                    [pixel[0] * scale,pixel[1] * scale,pixel[2] * scale]
+                }
+                Encoding::Hdr10 => {
+                    let c = color::rec709_to_rec2020(*pixel);
+                    let nits = self.settings.paper_white;
+                    [color::pq_encode(c[0] * nits),color::pq_encode(c[1] * nits),color::pq_encode(c[2] * nits)]
+                }
+            };
+            color::pack_pixel(surface_format.format, encoded, out)?;
+        }
+        return Ok(bytes);
+    }
+}
+///sRGB encodes display values in [0,1] into 8 bit rgb triplets.
+pub fn encode_rgb8(display : &[[f32;3]]) -> Vec<u8>{
+    return display.iter().flat_map(|pixel| pixel.iter().map(|&c| (color::srgb_encode(c) * 255.0 + 0.5) as u8).collect::<Vec<_>>()).collect();
+}
+///Compresses luminance above paper white towards the display peak while keeping the hue, nothing is clamped at 1.
+fn hdr_rolloff(pixel : [f32;3] , peak : f32) -> [f32;3]{
+    let luminance = exposure::luminance(pixel);
+    if luminance <= 0.0{return [0.0;3];}
+    let scale = 1.0 / (1.0 + luminance / peak);
+    return [pixel[0].max(0.0) * scale,pixel[1].max(0.0) * scale,pixel[2].max(0.0) * scale];
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn surface_format(format : ash::vk::Format , color_space : ash::vk::ColorSpaceKHR) -> ash::vk::SurfaceFormatKHR{
+        return ash::vk::SurfaceFormatKHR{format, color_space};
+    }
+    fn half(bytes : &[u8]) -> u16{
+        return u16::from_le_bytes([bytes[0],bytes[1]]);
+    }
+    #[test]
+    fn srgb_output(){
+        let post_process = PostProcess::new(PostSettings::default());
+        let format = surface_format(ash::vk::Format::B8G8R8A8_SRGB, ash::vk::ColorSpaceKHR::SRGB_NONLINEAR);
+        let bytes = post_process.encode(&[[1.0,0.0,0.0],[0.18,0.18,0.18]], format).expect("Failed to encode a frame.");
+        assert_eq!(bytes, vec!(0,0,255,255,118,118,118,255));
+    }
+    #[test]
+    fn scrgb_output(){
+        //Paper white lands at paper_white / 80 nits in the linear float image.
+        let post_process = PostProcess::new(PostSettings{paper_white : 160.0, ..PostSettings::default()});
+        let format = surface_format(ash::vk::Format::R16G16B16A16_SFLOAT, ash::vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT);
+        let bytes = post_process.encode(&[[1.0,0.5,0.0]], format).expect("Failed to encode a frame.");
+        assert_eq!(bytes.len(), 8);
+        assert_eq!([half(&bytes[0..2]),half(&bytes[2..4]),half(&bytes[4..6]),half(&bytes[6..8])], [0x4000,0x3c00,0x0000,0x3c00]);
+    }
+    #[test]
+    fn hdr10_output(){
+        let post_process = PostProcess::new(PostSettings::default());
+        let format = surface_format(ash::vk::Format::A2B10G10R10_UNORM_PACK32, ash::vk::ColorSpaceKHR::HDR10_ST2084_EXT);
+        let bytes = post_process.encode(&[[1.0,1.0,1.0],[0.0,0.0,0.0]], format).expect("Failed to encode a frame.");
+        let white = u32::from_le_bytes([bytes[0],bytes[1],bytes[2],bytes[3]]);
+        //203 nits paper white is 0.5807 on the PQ curve.
+        let expected = (color::pq_encode(203.0) * 1023.0 + 0.5) as u32;
+        assert!((expected as i32 - 594).abs() <= 1);
+        assert_eq!(white, 3 << 30 | expected << 20 | expected << 10 | expected);
+        assert_eq!(u32::from_le_bytes([bytes[4],bytes[5],bytes[6],bytes[7]]), 3 << 30);
+    }
+    #[test]
+    fn unsupported_format(){
+        let mut post_process = PostProcess::new(PostSettings::default());
+        let format = surface_format(ash::vk::Format::R5G6B5_UNORM_PACK16, ash::vk::ColorSpaceKHR::SRGB_NONLINEAR);
+        assert!(post_process.encode(&[[1.0;3]], format).is_err());
+        assert!(post_process.process(&[[1.0;3]], format).is_err());
+    }
+    #[test]
+    fn rolloff(){
+        //Keeps the hue and never exceeds the peak.
+        let peak = 1000.0 / 203.0;
+        let rolled = hdr_rolloff([100.0,50.0,25.0], peak);
+        assert!((rolled[0] / rolled[1] - 2.0).abs() < 1e-5 && (rolled[1] / rolled[2] - 2.0).abs() < 1e-5);
+        assert!(exposure::luminance(rolled) < peak);
+        assert_eq!(hdr_rolloff([0.0;3], peak), [0.0;3]);
+    }
+}
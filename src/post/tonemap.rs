@@ -0,0 +1,130 @@
+///Operators that compress linear scene radiance into the displayable [0,1] range.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Tonemapper{
+    Linear,
+    Reinhard,
+    Filmic,
+    Aces,
+    Agx,
+}
+pub const TONEMAPPERS : [Tonemapper;5] = [Tonemapper::Linear,Tonemapper::Reinhard,Tonemapper::Filmic,Tonemapper::Aces,Tonemapper::Agx];
+
+impl Tonemapper{
+    pub fn name(self) -> &'static str{
+        match self{
+            Tonemapper::Linear => "Linear",
+            Tonemapper::Reinhard => "Reinhard",
+            Tonemapper::Filmic => "Filmic",
+            Tonemapper::Aces => "ACES",
+            Tonemapper::Agx => "AgX",
+        }
+    }
+    pub fn parse(name : &str) -> Option<Self>{
+        return TONEMAPPERS.iter().copied().find(|tonemapper| tonemapper.name().eq_ignore_ascii_case(name));
+    }
+    pub fn next(self) -> Self{
+        let index = TONEMAPPERS.iter().position(|&tonemapper| tonemapper == self).unwrap_or(0);
+        return TONEMAPPERS[(index + 1) % TONEMAPPERS.len()];
+    }
+    ///Maps exposed linear Rec.709 radiance to linear display values in [0,1].
+    pub fn apply(self , color : [f32;3]) -> [f32;3]{
+        let mapped = match self{
+            Tonemapper::Linear => color,
+            Tonemapper::Reinhard => map(color, |c| c / (1.0 + c)),
+            Tonemapper::Filmic => filmic(color),
+            Tonemapper::Aces => aces(color),
+            Tonemapper::Agx => agx(color),
+        };
+        return map(mapped, |c| c.clamp(0.0, 1.0));
+    }
+}
+fn map(color : [f32;3] , f : impl Fn(f32) -> f32) -> [f32;3]{
+    return [f(color[0]),f(color[1]),f(color[2])];
+}
+pub fn mul_matrix(m : &[[f32;3];3] , c : [f32;3]) -> [f32;3]{
+    return [
+        m[0][0] * c[0] + m[0][1] * c[1] + m[0][2] * c[2],
+        m[1][0] * c[0] + m[1][1] * c[1] + m[1][2] * c[2],
+        m[2][0] * c[0] + m[2][1] * c[1] + m[2][2] * c[2],
+    ];
+}
+///John Hable's Uncharted 2 curve, normalized so the linear white point maps to 1.
+fn filmic(color : [f32;3]) -> [f32;3]{
+    fn curve(x : f32) -> f32{
+        let (a,b,c,d,e,f) = (0.15,0.50,0.10,0.20,0.02,0.30);
+        return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+    }
+    let white = curve(11.2);
+    return map(color, |c| curve(c * 2.0) / white);
+}
+///Stephen Hill's fit of the ACES RRT and sRGB ODT.
+fn aces(color : [f32;3]) -> [f32;3]{
+    const INPUT : [[f32;3];3] = [[0.59719,0.35458,0.04823],[0.07600,0.90834,0.01566],[0.02840,0.13383,0.83777]];
+    const OUTPUT : [[f32;3];3] = [[1.60475,-0.53108,-0.07367],[-0.10208,1.10813,-0.00605],[-0.00327,-0.07276,1.07602]];
+    let v = mul_matrix(&INPUT, color);
+    let v = map(v, |c| (c * (c + 0.0245786) - 9.0537e-05) / (c * (0.983729 * c + 0.432951) + 0.238081));
+    return mul_matrix(&OUTPUT, v);
+}
+///Minimal AgX with the polynomial sigmoid approximation, the result is linearized again for the shared encoder.
+fn agx(color : [f32;3]) -> [f32;3]{
+    const INSET : [[f32;3];3] = [[0.84247906,0.0784336,0.079223745],[0.042328242,0.87846864,0.07916613],[0.042375655,0.0784336,0.879143]];
+    const OUTSET : [[f32;3];3] = [[1.196879,-0.09802088,-0.09902974],[-0.052896852,1.1519031,-0.098961177],[-0.052971636,-0.09804345,1.1510737]];
+    const MIN_EV : f32 = -12.47393;
+    const MAX_EV : f32 = 4.026069;
+    let v = mul_matrix(&INSET, color);
+    let v = map(v, |c| ((c.max(1e-10).log2() - MIN_EV) / (MAX_EV - MIN_EV)).clamp(0.0, 1.0));
+    let v = map(v, |x| {
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+    });
+    let v = mul_matrix(&OUTSET, v);
+    return map(v, |c| c.max(0.0).powf(2.2));
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn reinhard(){
+        assert_eq!(Tonemapper::Reinhard.apply([1.0,3.0,0.0]), [0.5,0.75,0.0]);
+    }
+    #[test]
+    fn filmic_white(){
+        //The linear white point of 11.2 is reached at half the input because of the exposure bias.
+        let white = Tonemapper::Filmic.apply([5.6;3]);
+        assert!(white.iter().all(|c| (c - 1.0).abs() < 1e-5), "{:?}", white);
+        assert_eq!(Tonemapper::Filmic.apply([100.0;3]), [1.0;3]);
+    }
+    #[test]
+    fn mid_gray(){
+        for (tonemapper,min,max) in [(Tonemapper::Aces,0.1,0.35),(Tonemapper::Agx,0.1,0.35),(Tonemapper::Filmic,0.05,0.35)]{
+            let gray = tonemapper.apply([0.18;3])[1];
+            assert!(gray > min && gray < max, "{:?} maps mid gray to {}", tonemapper, gray);
+        }
+    }
+    #[test]
+    fn all_operators(){
+        for tonemapper in TONEMAPPERS{
+            assert!(tonemapper.apply([0.0;3]).iter().all(|c| c.abs() < 1e-3), "{:?}", tonemapper);
+            let mut previous = -1.0;
+            for step in 0..200{
+                let v = 2f32.powf(step as f32 * 0.1 - 10.0);
+                let c = tonemapper.apply([v;3]);
+                assert!(c.iter().all(|c| (0.0..=1.0).contains(c)), "{:?} {:?}", tonemapper, c);
+                //Gray stays gray and brighter input never gets darker.
+                assert!((c[0] - c[1]).abs() < 1e-3 && (c[1] - c[2]).abs() < 1e-3, "{:?} {:?}", tonemapper, c);
+                assert!(c[1] >= previous - 1e-6, "{:?} at {}", tonemapper, v);
+                previous = c[1];
+            }
+        }
+    }
+    #[test]
+    fn names(){
+        for tonemapper in TONEMAPPERS{
+            assert_eq!(Tonemapper::parse(&tonemapper.name().to_lowercase()), Some(tonemapper));
+        }
+        assert_eq!(Tonemapper::Agx.next(), Tonemapper::Linear);
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::renderer::GpuFrame;
+
+///How many recent frames the rolling statistics cover.
+pub const STATS_WINDOW : usize = 120;
+///Events past this are dropped so long sessions do not grow without bound, the statistics keep updating.
+pub const MAX_EVENTS : usize = 1 << 20;
+
+///The timeline an event is shown on, gpu work overlaps the cpu work of later frames.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Track{
+    Cpu,
+    Gpu,
+}
+impl Track{
+    pub fn name(self) -> &'static str{
+        match self{
+            Track::Cpu => "cpu",
+            Track::Gpu => "gpu",
+        }
+    }
+    fn thread_id(self) -> u32{
+        match self{
+            Track::Cpu => 1,
+            Track::Gpu => 2,
+        }
+    }
+}
+///A timed scope, start is relative to the creation of the profiler.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Event{
+    pub name : &'static str,
+    pub track : Track,
+    pub start : Duration,
+    pub duration : Duration,
+}
+///Timings of the last STATS_WINDOW samples in milliseconds.
+#[derive(Clone,Debug,Default)]
+pub struct RollingStats{
+    samples : VecDeque<f32>,
+}
+impl RollingStats{
+    pub fn push(&mut self , milliseconds : f32){
+        if self.samples.len() == STATS_WINDOW{
+            self.samples.pop_front();
+        }
+        self.samples.push_back(milliseconds);
+    }
+    pub fn last(&self) -> f32{
+        return self.samples.back().copied().unwrap_or(0.0);
+    }
+    pub fn mean(&self) -> f32{
+        return self.samples.iter().sum::<f32>() / self.samples.len().max(1) as f32;
+    }
+    pub fn min(&self) -> f32{
+        if self.samples.is_empty(){return 0.0;}
+        return self.samples.iter().copied().fold(f32::INFINITY, f32::min);
+    }
+    pub fn max(&self) -> f32{
+        return self.samples.iter().copied().fold(0.0, f32::max);
+    }
+    pub fn len(&self) -> usize{
+        return self.samples.len();
+    }
+    pub fn is_empty(&self) -> bool{
+        return self.samples.is_empty();
+    }
+}
+///Collects cpu scopes and gpu timestamps, keeps rolling statistics per scope and exports everything as a Chrome trace.
+pub struct Profiler{
+    ///Scopes are only measured while enabled.
+    pub enabled : bool,
+    epoch : Instant,
+    events : Vec<Event>,
+    stats : Vec<(&'static str,Track,RollingStats)>,
+}
+impl Profiler{
+    pub fn new(enabled : bool) -> Self{
+        return Self{enabled, epoch : Instant::now(), events : vec!(), stats : vec!()};
+    }
+    ///Runs the closure and records how long it took on the cpu.
+    pub fn scope<T , F : FnOnce() -> T>(&mut self , name : &'static str , f : F) -> T{
+        if !self.enabled{return f();}
+        let start = Instant::now();
+        let result = f();
+        self.add(name, Track::Cpu, start.saturating_duration_since(self.epoch), start.elapsed());
+        return result;
+    }
+    ///Adds the gpu scopes of a finished frame, they are placed on the gpu track starting when the frame was submitted.
+    pub fn add_gpu_frame(&mut self , frame : &GpuFrame){
+        let submitted = frame.submitted.saturating_duration_since(self.epoch);
+        for scope in frame.scopes.iter(){
+            self.add(scope.name, Track::Gpu, submitted + scope.begin, scope.end.saturating_sub(scope.begin));
+        }
+    }
+    pub fn add(&mut self , name : &'static str , track : Track , start : Duration , duration : Duration){
+        if self.events.len() < MAX_EVENTS{
+            self.events.push(Event{name, track, start, duration});
+        }
+        let milliseconds = duration.as_secs_f32() * 1000.0;
+        match self.stats.iter_mut().find(|(other,other_track,_)| *other == name && *other_track == track){
+            Some((_,_,stats)) => stats.push(milliseconds),
+            None => {
+                let mut stats = RollingStats::default();
+                stats.push(milliseconds);
+                self.stats.push((name,track,stats));
+            }
+        }
+    }
+    pub fn events(&self) -> &[Event]{
+        return &self.events;
+    }
+    pub fn stats(&self , name : &str , track : Track) -> Option<&RollingStats>{
+        return self.stats.iter().find(|(other,other_track,_)| *other == name && *other_track == track).map(|(_,_,stats)| stats);
+    }
+    ///The statistics of every scope in the order they were first seen.
+    pub fn scopes(&self) -> Vec<(&'static str,Track,&RollingStats)>{
+        return self.stats.iter().map(|(name,track,stats)| (*name,*track,stats)).collect();
+    }
+    ///One line per scope in the order they were first seen.
+    pub fn report(&self) -> String{
+        let mut report = String::new();
+        for (name,track,stats) in self.stats.iter(){
+            report += &format!("{} {} : {:.3} ms mean, {:.3} min, {:.3} max over {} samples.\n",track.name(),name,stats.mean(),stats.min(),stats.max(),stats.len());
+        }
+        return report;
+    }
+    ///The events in the Chrome trace event format, open it in chrome://tracing or Perfetto.
+    pub fn chrome_trace(&self) -> String{
+        let mut events = vec!();
+        for &track in [Track::Cpu,Track::Gpu].iter(){
+            events.push(format!("{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",track.thread_id(),track.name()));
+        }
+        for event in self.events.iter(){
+            events.push(format!(
+                "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":{}}}",
+                escape(event.name),event.track.name(),event.start.as_secs_f64() * 1e6,event.duration.as_secs_f64() * 1e6,event.track.thread_id(),
+            ));
+        }
+        return format!("{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}\n",events.join(",\n"));
+    }
+    pub fn save_chrome_trace(&self , path : &Path) -> Result<(),String>{
+        return std::fs::write(path, self.chrome_trace()).map_err(|error| format!("Failed to write {} : {}.",path.display(),error));
+    }
+}
+fn escape(text : &str) -> String{
+    return text.chars().flat_map(|c| match c{
+        '"' | '\\' => vec!('\\',c),
+        c if c.is_control() => format!("\\u{:04x}",c as u32).chars().collect(),
+        c => vec!(c),
+    }).collect();
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::renderer::GpuScope;
+
+    #[test]
+    fn rolling_stats(){
+        let mut stats = RollingStats::default();
+        assert_eq!((stats.mean(),stats.min(),stats.max()), (0.0,0.0,0.0));
+        for i in 0..STATS_WINDOW + 10{
+            stats.push(i as f32);
+        }
+        //Only the last STATS_WINDOW samples count.
+        assert_eq!(stats.len(), STATS_WINDOW);
+        assert_eq!(stats.min(), 10.0);
+        assert_eq!(stats.max(), (STATS_WINDOW + 9) as f32);
+        assert_eq!(stats.last(), (STATS_WINDOW + 9) as f32);
+        assert_eq!(stats.mean(), (10 + STATS_WINDOW + 9) as f32 / 2.0);
+    }
+    #[test]
+    fn scopes(){
+        let mut profiler = Profiler::new(false);
+        assert_eq!(profiler.scope("trace", || 1 + 1), 2);
+        assert!(profiler.events().is_empty());
+        profiler.enabled = true;
+        profiler.scope("trace", || std::thread::sleep(Duration::from_millis(2)));
+        profiler.scope("trace", || {});
+        let stats = profiler.stats("trace", Track::Cpu).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert!(stats.max() >= 2.0);
+        assert!(profiler.stats("trace", Track::Gpu).is_none());
+        let submitted = Instant::now();
+        profiler.add_gpu_frame(&GpuFrame{submitted, scopes : vec!(GpuScope{name : "upload", begin : Duration::ZERO, end : Duration::from_micros(250)},GpuScope{name : "present blit", begin : Duration::from_micros(300), end : Duration::from_micros(400)})});
+        let blit = &profiler.events()[3];
+        assert_eq!((blit.name,blit.track,blit.duration), ("present blit",Track::Gpu,Duration::from_micros(100)));
+        assert_eq!(blit.start, submitted.duration_since(profiler.epoch) + Duration::from_micros(300));
+        assert_eq!(profiler.report().lines().count(), 3);
+    }
+    #[test]
+    fn chrome_trace(){
+        let mut profiler = Profiler::new(true);
+        profiler.add("denoise", Track::Cpu, Duration::from_micros(1500), Duration::from_micros(250));
+        profiler.add("upload", Track::Gpu, Duration::from_millis(2), Duration::from_nanos(12_345));
+        let trace = profiler.chrome_trace();
+        assert!(trace.contains("{\"name\":\"denoise\",\"cat\":\"cpu\",\"ph\":\"X\",\"ts\":1500.000,\"dur\":250.000,\"pid\":1,\"tid\":1}"));
+        assert!(trace.contains("{\"name\":\"upload\",\"cat\":\"gpu\",\"ph\":\"X\",\"ts\":2000.000,\"dur\":12.345,\"pid\":1,\"tid\":2}"));
+        assert!(trace.contains("\"args\":{\"name\":\"gpu\"}"));
+        assert_eq!(escape("a\"b\\c\n"), "a\\\"b\\\\c\\u000a");
+    }
+}
@@ -0,0 +1,186 @@
+use ash::Device;
+use ash::Instance;
+use ash::version::DeviceV1_0;
+
+use super::memory;
+use super::commands;
+use super::pipeline;
+use super::descriptors::DescriptorLayout;
+use super::descriptors::DescriptorAllocator;
+use super::descriptors::LayoutBinding;
+
+//Rebuild the shader with glslc shaders/atrous.comp -o shaders/atrous.comp.spv.
+const COMPUTE_SHADER : &[u8] = include_bytes!("../../shaders/atrous.comp.spv");
+///Width, height, step and strength.
+const PUSH_CONSTANTS_SIZE : u32 = 16;
+///The workgroup size of the shader in both dimensions.
+const GROUP_SIZE : u32 = 8;
+///One vec4 per pixel in every buffer.
+const PIXEL_SIZE : u64 = 16;
+
+///The steps between the taps of each dispatch, zero for the variance pass that comes first.
+pub fn dispatch_steps(iterations : u32) -> Vec<u32>{
+    return std::iter::once(0).chain((0..iterations).map(|iteration| 1 << iteration)).collect();
+}
+///The color buffer holding the result after the dispatches, they alternate between the two starting with the first.
+pub fn result_buffer(iterations : u32) -> usize{
+    return (iterations as usize + 1) % 2;
+}
+///The push constants of one dispatch as the shader lays them out.
+pub fn push_constants(width : u32 , height : u32 , step : u32 , strength : f32) -> [u8;PUSH_CONSTANTS_SIZE as usize]{
+    let mut bytes = [0u8;PUSH_CONSTANTS_SIZE as usize];
+    bytes[0..4].copy_from_slice(&width.to_ne_bytes());
+    bytes[4..8].copy_from_slice(&height.to_ne_bytes());
+    bytes[8..12].copy_from_slice(&step.to_ne_bytes());
+    bytes[12..16].copy_from_slice(&strength.to_ne_bytes());
+    return bytes;
+}
+///The à-trous filter of the denoiser as a compute pass on its own queue, the results match denoise::filter.
+///The buffers are host visible so the frame goes in and out without staging, the pass waits for its fence before returning.
+pub struct AtrousPass{
+    descriptor_layout : DescriptorLayout,
+    pipeline_layout : ash::vk::PipelineLayout,
+    pipeline : ash::vk::Pipeline,
+    queue : ash::vk::Queue,
+    command_pool : ash::vk::CommandPool,
+    command_buffer : ash::vk::CommandBuffer,
+    fence : ash::vk::Fence,
+    ///The first set reads the first color buffer and writes the second, the other set the reverse.
+    sets : [ash::vk::DescriptorSet;2],
+    ///The guides followed by the two color buffers.
+    buffers : Vec<ash::vk::Buffer>,
+    memories : Vec<ash::vk::DeviceMemory>,
+    pixels : usize,
+}
+impl AtrousPass{
+    pub fn new(device : &Device , queue_family : u32 , descriptor_allocator : &mut DescriptorAllocator) -> Self{
+        let descriptor_layout = DescriptorLayout::new(device, &[
+            LayoutBinding::new(0, ash::vk::DescriptorType::STORAGE_BUFFER, ash::vk::ShaderStageFlags::COMPUTE),
+            LayoutBinding::new(1, ash::vk::DescriptorType::STORAGE_BUFFER, ash::vk::ShaderStageFlags::COMPUTE),
+            LayoutBinding::new(2, ash::vk::DescriptorType::STORAGE_BUFFER, ash::vk::ShaderStageFlags::COMPUTE),
+        ]);
+        let pipeline_layout = pipeline::create_compute_pipeline_layout(device, &[descriptor_layout.layout], PUSH_CONSTANTS_SIZE, "denoiser");
+        let pipeline = pipeline::create_compute_pipeline(device, pipeline_layout, COMPUTE_SHADER, "denoiser");
+        let command_pool = commands::create_command_pool(device, queue_family);
+        let command_buffer = commands::create_command_buffers(device, &command_pool, 1)[0];
+        let fence_create_info = ash::vk::FenceCreateInfo{
+            s_type : ash::vk::StructureType::FENCE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : ash::vk::FenceCreateFlags::empty(),
+        };
+        let fence = unsafe{device.create_fence(&fence_create_info, None)}.expect("Failed to create denoiser fence.");
+        let sets = [descriptor_allocator.allocate(device, &descriptor_layout),descriptor_allocator.allocate(device, &descriptor_layout)];
+        return Self{
+            descriptor_layout,
+            pipeline_layout,
+            pipeline,
+            queue : unsafe{device.get_device_queue(queue_family, 0)},
+            command_pool,
+            command_buffer,
+            fence,
+            sets,
+            buffers : vec!(),
+            memories : vec!(),
+            pixels : 0,
+        };
+    }
+    ///Filters the illumination guided by the normal and depth of every pixel, packed as the xyz and w of the guides.
+    pub fn filter(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , width : u32 , height : u32 , guides : &[[f32;4]] , illumination : &[[f32;3]] , strength : f32 , iterations : u32) -> Vec<[f32;3]>{
+        let pixels = (width * height) as usize;
+        assert!(guides.len() == pixels && illumination.len() == pixels, "The denoiser guides and illumination must cover the frame.");
+        if pixels == 0{return vec!();}
+        self.update_buffers(instance, device, physical_device, pixels);
+        memory::write_memory(device, &self.memories[0], &guides.iter().flatten().flat_map(|value| value.to_ne_bytes()).collect::<Vec<u8>>());
+        memory::write_memory(device, &self.memories[1], &illumination.iter().flat_map(|pixel| [pixel[0],pixel[1],pixel[2],0.0]).flat_map(f32::to_ne_bytes).collect::<Vec<u8>>());
+        let command_buffer = self.command_buffer;
+        commands::begin_command_buffer(device, command_buffer);
+        unsafe{device.cmd_bind_pipeline(command_buffer, ash::vk::PipelineBindPoint::COMPUTE, self.pipeline)};
+        let barrier = ash::vk::MemoryBarrier{
+            s_type : ash::vk::StructureType::MEMORY_BARRIER,
+            p_next : std::ptr::null(),
+            src_access_mask : ash::vk::AccessFlags::SHADER_WRITE,
+            dst_access_mask : ash::vk::AccessFlags::SHADER_READ | ash::vk::AccessFlags::SHADER_WRITE,
+        };
+        for (dispatch,&step) in dispatch_steps(iterations).iter().enumerate(){
+            if dispatch > 0{
+                unsafe{device.cmd_pipeline_barrier(command_buffer, ash::vk::PipelineStageFlags::COMPUTE_SHADER, ash::vk::PipelineStageFlags::COMPUTE_SHADER, ash::vk::DependencyFlags::empty(), &[barrier], &[], &[])};
+            }
+            unsafe{
+                device.cmd_bind_descriptor_sets(command_buffer, ash::vk::PipelineBindPoint::COMPUTE, self.pipeline_layout, 0, &[self.sets[dispatch % 2]], &[]);
+                device.cmd_push_constants(command_buffer, self.pipeline_layout, ash::vk::ShaderStageFlags::COMPUTE, 0, &push_constants(width, height, step, strength));
+                device.cmd_dispatch(command_buffer, width.div_ceil(GROUP_SIZE), height.div_ceil(GROUP_SIZE), 1);
+            }
+        }
+        commands::record_host_barrier(device, command_buffer);
+        unsafe{device.end_command_buffer(command_buffer)}.expect("Failed to record denoiser command buffer.");
+        commands::submit_and_wait(device, self.queue, command_buffer, self.fence);
+        let bytes = memory::read_memory(device, &self.memories[1 + result_buffer(iterations)], pixels * PIXEL_SIZE as usize);
+        return bytes.chunks_exact(PIXEL_SIZE as usize).map(|pixel| {
+            let channel = |c : usize| f32::from_ne_bytes([pixel[c * 4],pixel[c * 4 + 1],pixel[c * 4 + 2],pixel[c * 4 + 3]]);
+            [channel(0),channel(1),channel(2)]
+        }).collect();
+    }
+    ///Makes sure the buffers hold the pixels and points both sets at them, the pass is idle between filters.
+    fn update_buffers(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , pixels : usize){
+        if self.pixels == pixels{return;}
+        self.destroy_buffers(device);
+        let size = pixels as u64 * PIXEL_SIZE;
+        for _ in 0..3{
+            let (buffer,memory) = memory::create_buffer(instance, device, physical_device, size, ash::vk::BufferUsageFlags::STORAGE_BUFFER, ash::vk::MemoryPropertyFlags::HOST_VISIBLE | ash::vk::MemoryPropertyFlags::HOST_COHERENT);
+            self.buffers.push(buffer);
+            self.memories.push(memory);
+        }
+        let info = |buffer : ash::vk::Buffer| ash::vk::DescriptorBufferInfo{buffer, offset : 0, range : size};
+        for (set,(source,destination)) in self.sets.iter().zip([(1,2),(2,1)]){
+            self.descriptor_layout.write(device, *set, 0, 0, &[info(self.buffers[0])]);
+            self.descriptor_layout.write(device, *set, 1, 0, &[info(self.buffers[source])]);
+            self.descriptor_layout.write(device, *set, 2, 0, &[info(self.buffers[destination])]);
+        }
+        self.pixels = pixels;
+    }
+    fn destroy_buffers(&mut self , device : &Device){
+        for (&buffer,&memory) in self.buffers.iter().zip(self.memories.iter()){
+            unsafe{device.destroy_buffer(buffer, None)};
+            unsafe{device.free_memory(memory, None)};
+        }
+        self.buffers.clear();
+        self.memories.clear();
+        self.pixels = 0;
+    }
+    ///The sets are freed with the pools of the descriptor allocator.
+    pub fn destroy(&mut self , device : &Device){
+        self.destroy_buffers(device);
+        unsafe{device.destroy_fence(self.fence, None)};
+        unsafe{device.destroy_command_pool(self.command_pool, None)};
+        unsafe{device.destroy_pipeline(self.pipeline, None)};
+        unsafe{device.destroy_pipeline_layout(self.pipeline_layout, None)};
+        self.descriptor_layout.destroy(device);
+    }
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn dispatches(){
+        assert_eq!(dispatch_steps(5), vec!(0,1,2,4,8,16));
+        assert_eq!(dispatch_steps(0), vec!(0));
+        //The variance pass writes the second buffer and every iteration swaps.
+        assert_eq!(result_buffer(0), 1);
+        assert_eq!(result_buffer(1), 0);
+        assert_eq!(result_buffer(5), 0);
+    }
+    #[test]
+    fn push_constant_layout(){
+        let bytes = push_constants(640, 480, 4, 1.5);
+        assert_eq!(u32::from_ne_bytes([bytes[0],bytes[1],bytes[2],bytes[3]]), 640);
+        assert_eq!(u32::from_ne_bytes([bytes[4],bytes[5],bytes[6],bytes[7]]), 480);
+        assert_eq!(u32::from_ne_bytes([bytes[8],bytes[9],bytes[10],bytes[11]]), 4);
+        assert_eq!(f32::from_ne_bytes([bytes[12],bytes[13],bytes[14],bytes[15]]), 1.5);
+    }
+    #[test]
+    fn shader_is_spirv(){
+        let code = ash::util::read_spv(&mut std::io::Cursor::new(COMPUTE_SHADER)).expect("Failed to read denoiser shader.");
+        assert_eq!(code[0], 0x0723_0203);
+    }
+}
@@ -0,0 +1,264 @@
+use ash::Device;
+use ash::Instance;
+use ash::version::DeviceV1_0;
+
+use cgmath::Vector3;
+
+use super::memory;
+use super::commands;
+use super::pipeline;
+use super::descriptors::DescriptorLayout;
+use super::descriptors::DescriptorAllocator;
+use super::descriptors::LayoutBinding;
+use super::super::material::Bsdf;
+use super::super::material::BsdfSample;
+
+//Rebuild the shader with glslc shaders/bsdf.comp -o shaders/bsdf.comp.spv, it includes shaders/bsdf.glsl.
+const COMPUTE_SHADER : &[u8] = include_bytes!("../../shaders/bsdf.comp.spv");
+///The amount of cases.
+const PUSH_CONSTANTS_SIZE : u32 = 4;
+///The workgroup size of the shader.
+const GROUP_SIZE : u32 = 64;
+///Six vec4 per case.
+const CASE_SIZE : usize = 96;
+///Four vec4 per result.
+const RESULT_SIZE : usize = 64;
+
+///Directions in the local shading frame the gpu port evaluates a bsdf for, wi is evaluated and u is drawn from.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct BsdfCase{
+    pub bsdf : Bsdf,
+    pub wo : Vector3<f32>,
+    pub wi : Vector3<f32>,
+    pub u : [f32;3],
+}
+///What shaders/bsdf.glsl returns for a case, the same as Bsdf::eval, pdf, sample and diffuse_fraction.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct BsdfResult{
+    pub eval : Vector3<f32>,
+    pub pdf : f32,
+    pub sample : Option<BsdfSample>,
+    pub diffuse_fraction : Vector3<f32>,
+}
+///The kind and parameters of a bsdf as struct Bsdf in shaders/bsdf.glsl, color, alpha, eta, metallic, specular and transmission.
+pub fn shader_bsdf(bsdf : &Bsdf) -> (u32,[f32;8]){
+    let color = |color : Vector3<f32> , alpha : f32 , eta : f32 , metallic : f32 , specular : f32 , transmission : f32| [color.x,color.y,color.z,alpha,eta,metallic,specular,transmission];
+    match *bsdf{
+        Bsdf::Lambert{albedo} => (0,color(albedo, 0.0, 1.0, 0.0, 0.0, 0.0)),
+        Bsdf::Conductor{f0,alpha} => (1,color(f0, alpha, 1.0, 0.0, 0.0, 0.0)),
+        Bsdf::Dielectric{eta,alpha,tint} => (2,color(tint, alpha, eta, 0.0, 0.0, 0.0)),
+        Bsdf::ThinDielectric{eta,tint} => (3,color(tint, 0.0, eta, 0.0, 0.0, 0.0)),
+        Bsdf::Plastic{color : plastic,alpha,eta,specular} => (4,color(plastic, alpha, eta, 0.0, specular, 0.0)),
+        Bsdf::Principled{base_color,metallic,alpha,eta,specular,transmission} => (5,color(base_color, alpha, eta, metallic, specular, transmission)),
+    }
+}
+///A case as struct BsdfCase in shaders/bsdf.comp.
+pub fn case_bytes(case : &BsdfCase) -> [u8;CASE_SIZE]{
+    let (kind,parameters) = shader_bsdf(&case.bsdf);
+    let mut words = [0u32;CASE_SIZE / 4];
+    words[0] = kind;
+    let floats = parameters.iter().copied().chain([case.wo.x,case.wo.y,case.wo.z,0.0,case.wi.x,case.wi.y,case.wi.z,0.0,case.u[0],case.u[1],case.u[2],0.0]);
+    for (word,value) in words[4..].iter_mut().zip(floats){
+        *word = value.to_bits();
+    }
+    let mut bytes = [0u8;CASE_SIZE];
+    for (chunk,word) in bytes.chunks_exact_mut(4).zip(words){
+        chunk.copy_from_slice(&word.to_ne_bytes());
+    }
+    return bytes;
+}
+fn result(bytes : &[u8]) -> BsdfResult{
+    let value = |index : usize| f32::from_ne_bytes([bytes[index * 4],bytes[index * 4 + 1],bytes[index * 4 + 2],bytes[index * 4 + 3]]);
+    let vector = |index : usize| Vector3::new(value(index),value(index + 1),value(index + 2));
+    let sample = if value(7) != 0.0{
+        Some(BsdfSample{wi : vector(4), weight : vector(8), pdf : value(11), delta : value(15) != 0.0})
+    } else{
+        None
+    };
+    return BsdfResult{eval : vector(0), pdf : value(3), sample, diffuse_fraction : vector(12)};
+}
+///Runs the gpu port of the bsdfs in shaders/bsdf.glsl, so the gpu tracer's materials can be checked against src/material.
+///The buffers are host visible, the pass waits for its fence before returning.
+pub struct BsdfPass{
+    descriptor_layout : DescriptorLayout,
+    pipeline_layout : ash::vk::PipelineLayout,
+    pipeline : ash::vk::Pipeline,
+    queue : ash::vk::Queue,
+    command_pool : ash::vk::CommandPool,
+    command_buffer : ash::vk::CommandBuffer,
+    fence : ash::vk::Fence,
+    set : ash::vk::DescriptorSet,
+    ///The cases followed by the results.
+    buffers : Vec<ash::vk::Buffer>,
+    memories : Vec<ash::vk::DeviceMemory>,
+    count : usize,
+}
+impl BsdfPass{
+    pub fn new(device : &Device , queue_family : u32 , descriptor_allocator : &mut DescriptorAllocator) -> Self{
+        let descriptor_layout = DescriptorLayout::new(device, &[
+            LayoutBinding::new(0, ash::vk::DescriptorType::STORAGE_BUFFER, ash::vk::ShaderStageFlags::COMPUTE),
+            LayoutBinding::new(1, ash::vk::DescriptorType::STORAGE_BUFFER, ash::vk::ShaderStageFlags::COMPUTE),
+        ]);
+        let pipeline_layout = pipeline::create_compute_pipeline_layout(device, &[descriptor_layout.layout], PUSH_CONSTANTS_SIZE, "bsdf");
+        let pipeline = pipeline::create_compute_pipeline(device, pipeline_layout, COMPUTE_SHADER, "bsdf");
+        let command_pool = commands::create_command_pool(device, queue_family);
+        let command_buffer = commands::create_command_buffers(device, &command_pool, 1)[0];
+        let fence_create_info = ash::vk::FenceCreateInfo{
+            s_type : ash::vk::StructureType::FENCE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : ash::vk::FenceCreateFlags::empty(),
+        };
+        let fence = unsafe{device.create_fence(&fence_create_info, None)}.expect("Failed to create bsdf fence.");
+        let set = descriptor_allocator.allocate(device, &descriptor_layout);
+        return Self{
+            descriptor_layout,
+            pipeline_layout,
+            pipeline,
+            queue : unsafe{device.get_device_queue(queue_family, 0)},
+            command_pool,
+            command_buffer,
+            fence,
+            set,
+            buffers : vec!(),
+            memories : vec!(),
+            count : 0,
+        };
+    }
+    pub fn evaluate(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , cases : &[BsdfCase]) -> Vec<BsdfResult>{
+        if cases.is_empty(){return vec!();}
+        self.update_buffers(instance, device, physical_device, cases.len());
+        memory::write_memory(device, &self.memories[0], &cases.iter().flat_map(case_bytes).collect::<Vec<u8>>());
+        let command_buffer = self.command_buffer;
+        commands::begin_command_buffer(device, command_buffer);
+        unsafe{
+            device.cmd_bind_pipeline(command_buffer, ash::vk::PipelineBindPoint::COMPUTE, self.pipeline);
+            device.cmd_bind_descriptor_sets(command_buffer, ash::vk::PipelineBindPoint::COMPUTE, self.pipeline_layout, 0, &[self.set], &[]);
+            device.cmd_push_constants(command_buffer, self.pipeline_layout, ash::vk::ShaderStageFlags::COMPUTE, 0, &(cases.len() as u32).to_ne_bytes());
+            device.cmd_dispatch(command_buffer, (cases.len() as u32).div_ceil(GROUP_SIZE), 1, 1);
+        }
+        commands::record_host_barrier(device, command_buffer);
+        unsafe{device.end_command_buffer(command_buffer)}.expect("Failed to record bsdf command buffer.");
+        commands::submit_and_wait(device, self.queue, command_buffer, self.fence);
+        let bytes = memory::read_memory(device, &self.memories[1], cases.len() * RESULT_SIZE);
+        return bytes.chunks_exact(RESULT_SIZE).map(result).collect();
+    }
+    ///Makes sure the buffers hold the cases and points the set at them, the pass is idle between dispatches.
+    fn update_buffers(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , count : usize){
+        if self.count == count{return;}
+        self.destroy_buffers(device);
+        for (binding,size) in [(0,count * CASE_SIZE),(1,count * RESULT_SIZE)]{
+            let (buffer,memory) = memory::create_buffer(instance, device, physical_device, size as u64, ash::vk::BufferUsageFlags::STORAGE_BUFFER, ash::vk::MemoryPropertyFlags::HOST_VISIBLE | ash::vk::MemoryPropertyFlags::HOST_COHERENT);
+            self.descriptor_layout.write(device, self.set, binding, 0, &[ash::vk::DescriptorBufferInfo{buffer, offset : 0, range : size as u64}]);
+            self.buffers.push(buffer);
+            self.memories.push(memory);
+        }
+        self.count = count;
+    }
+    fn destroy_buffers(&mut self , device : &Device){
+        for (&buffer,&memory) in self.buffers.iter().zip(self.memories.iter()){
+            unsafe{device.destroy_buffer(buffer, None)};
+            unsafe{device.free_memory(memory, None)};
+        }
+        self.buffers.clear();
+        self.memories.clear();
+        self.count = 0;
+    }
+    ///The set is freed with the pools of the descriptor allocator.
+    pub fn destroy(&mut self , device : &Device){
+        self.destroy_buffers(device);
+        unsafe{device.destroy_fence(self.fence, None)};
+        unsafe{device.destroy_command_pool(self.command_pool, None)};
+        unsafe{device.destroy_pipeline(self.pipeline, None)};
+        unsafe{device.destroy_pipeline_layout(self.pipeline_layout, None)};
+        self.descriptor_layout.destroy(device);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use cgmath::InnerSpace;
+    use super::super::ComputeDevice;
+    use super::super::RendererSettings;
+    use super::super::AppInfo;
+
+    fn white() -> Vector3<f32>{
+        return Vector3::new(1.0,1.0,1.0);
+    }
+    fn bsdfs() -> Vec<Bsdf>{
+        return vec!(
+            Bsdf::Lambert{albedo : Vector3::new(0.8,0.5,0.2)},
+            Bsdf::Conductor{f0 : Vector3::new(0.9,0.6,0.3), alpha : 0.05},
+            Bsdf::Conductor{f0 : white(), alpha : 0.5},
+            Bsdf::Dielectric{eta : 1.5, alpha : 0.0, tint : white()},
+            Bsdf::Dielectric{eta : 1.5, alpha : 0.3, tint : Vector3::new(0.8,0.85,1.0)},
+            Bsdf::ThinDielectric{eta : 1.5, tint : white()},
+            Bsdf::Plastic{color : Vector3::new(0.2,0.4,0.8), alpha : 0.2, eta : 1.5, specular : 1.0},
+            Bsdf::Principled{base_color : white(), metallic : 0.5, alpha : 0.3, eta : 1.5, specular : 1.0, transmission : 0.5},
+            Bsdf::Principled{base_color : Vector3::new(0.7,0.3,0.3), metallic : 0.0, alpha : 0.6, eta : 1.45, specular : 1.0, transmission : 0.0},
+        );
+    }
+    fn close(a : f32 , b : f32) -> bool{
+        return (a - b).abs() <= 1e-3 * a.abs().max(b.abs()).max(1.0);
+    }
+    fn close_vector(a : Vector3<f32> , b : Vector3<f32>) -> bool{
+        return close(a.x, b.x) && close(a.y, b.y) && close(a.z, b.z);
+    }
+    #[test]
+    fn case_layout(){
+        let case = BsdfCase{bsdf : Bsdf::Principled{base_color : Vector3::new(0.1,0.2,0.3), metallic : 0.4, alpha : 0.5, eta : 1.5, specular : 0.6, transmission : 0.7}, wo : Vector3::new(1.0,2.0,3.0), wi : Vector3::new(4.0,5.0,6.0), u : [0.25,0.5,0.75]};
+        let bytes = case_bytes(&case);
+        let word = |index : usize| u32::from_ne_bytes([bytes[index * 4],bytes[index * 4 + 1],bytes[index * 4 + 2],bytes[index * 4 + 3]]);
+        assert_eq!(word(0), 5);
+        let floats : Vec<f32> = (4..24).map(|index| f32::from_bits(word(index))).collect();
+        assert_eq!(floats, vec!(0.1,0.2,0.3,0.5,1.5,0.4,0.6,0.7,1.0,2.0,3.0,0.0,4.0,5.0,6.0,0.0,0.25,0.5,0.75,0.0));
+        assert_eq!(shader_bsdf(&Bsdf::ThinDielectric{eta : 1.3, tint : white()}), (3,[1.0,1.0,1.0,0.0,1.3,0.0,0.0,0.0]));
+    }
+    #[test]
+    fn shader_is_spirv(){
+        let code = ash::util::read_spv(&mut std::io::Cursor::new(COMPUTE_SHADER)).expect("Failed to read bsdf shader.");
+        assert_eq!(code[0], 0x0723_0203);
+    }
+    //Returns early on machines without a vulkan device.
+    #[test]
+    fn matches_cpu_bsdfs(){
+        let settings = RendererSettings{present_mode : ash::vk::PresentModeKHR::FIFO, hdr : false, validation : false, device : None, app_info : AppInfo::default()};
+        let mut compute = match ComputeDevice::new(&settings){
+            Ok(compute) => compute,
+            Err(error) => {
+                println!("Skipped, {}",error);
+                return;
+            }
+        };
+        let mut state = 7u64;
+        let mut next = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 40) as f32 / (1u64 << 24) as f32
+        };
+        let mut cases = vec!();
+        for bsdf in bsdfs(){
+            for _ in 0..200{
+                let mut direction = || Vector3::new(next() * 2.0 - 1.0,next() * 2.0 - 1.0,next() * 2.0 - 1.0).normalize();
+                let (wo,wi) = (direction(),direction());
+                cases.push(BsdfCase{bsdf, wo, wi, u : [next(),next(),next()]});
+            }
+        }
+        let results = compute.evaluate_bsdfs(&cases);
+        //Sin, cos and sqrt are not correctly rounded on every gpu, a number landing right on a lobe threshold may pick the other lobe.
+        let mut mismatches = 0;
+        for (case,result) in cases.iter().zip(results.iter()){
+            assert!(close_vector(case.bsdf.eval(case.wo, case.wi), result.eval), "{:?} evaluates to {:?} on the gpu",case,result.eval);
+            assert!(close(case.bsdf.pdf(case.wo, case.wi), result.pdf), "{:?} has the pdf {} on the gpu",case,result.pdf);
+            assert!(close_vector(case.bsdf.diffuse_fraction(case.wo, case.wi), result.diffuse_fraction), "{:?} has the diffuse fraction {:?} on the gpu",case,result.diffuse_fraction);
+            let matches = match (case.bsdf.sample(case.wo, case.u),result.sample){
+                (Some(cpu),Some(gpu)) => close_vector(cpu.wi, gpu.wi) && close_vector(cpu.weight, gpu.weight) && close(cpu.pdf, gpu.pdf) && cpu.delta == gpu.delta,
+                (None,None) => true,
+                _ => false,
+            };
+            if !matches{
+                mismatches += 1;
+            }
+        }
+        assert!(mismatches * 100 <= cases.len(), "{} of {} gpu samples differ from the cpu",mismatches,cases.len());
+    }
+}
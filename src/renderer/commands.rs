@@ -0,0 +1,243 @@
+use ash::Device;
+use ash::version::DeviceV1_0;
+
+const COLOR_SUBRESOURCE_RANGE : ash::vk::ImageSubresourceRange = ash::vk::ImageSubresourceRange{
+    aspect_mask : ash::vk::ImageAspectFlags::COLOR,
+    base_mip_level : 0,
+    level_count : 1,
+    base_array_layer : 0,
+    layer_count : 1,
+};
+const COLOR_SUBRESOURCE_LAYERS : ash::vk::ImageSubresourceLayers = ash::vk::ImageSubresourceLayers{
+    aspect_mask : ash::vk::ImageAspectFlags::COLOR,
+    mip_level : 0,
+    base_array_layer : 0,
+    layer_count : 1,
+};
+
+pub fn create_command_pool(device : &Device , queue_family : u32) -> ash::vk::CommandPool{
+    let command_pool_create_info = ash::vk::CommandPoolCreateInfo{
+        s_type : ash::vk::StructureType::COMMAND_POOL_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        queue_family_index : queue_family,
+    };
+    return unsafe{device.create_command_pool(&command_pool_create_info, None)}.expect("Failed to create command pool.");
+}
+pub fn create_command_buffers(device : &Device , command_pool : &ash::vk::CommandPool , count : u32) -> Vec<ash::vk::CommandBuffer>{
+    let command_buffer_allocate_info = ash::vk::CommandBufferAllocateInfo{
+        s_type : ash::vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+        p_next : std::ptr::null(),
+        command_pool : *command_pool,
+        level : ash::vk::CommandBufferLevel::PRIMARY,
+        command_buffer_count : count,
+    };
+    return unsafe{device.allocate_command_buffers(&command_buffer_allocate_info)}.expect("Failed to allocate command buffers.");
+}
+pub fn begin_command_buffer(device : &Device , command_buffer : ash::vk::CommandBuffer){
+    unsafe{device.reset_command_buffer(command_buffer, ash::vk::CommandBufferResetFlags::empty())}.expect("Failed to reset command buffer.");
+    let command_buffer_begin_info = ash::vk::CommandBufferBeginInfo{
+        s_type : ash::vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        p_inheritance_info : std::ptr::null(),
+    };
+    unsafe{device.begin_command_buffer(command_buffer, &command_buffer_begin_info)}.expect("Failed to begin command buffer.");
+}
+///Begins the render pass, records its only subpass with the closure and ends it.
+pub fn record_render_pass<F : FnOnce(ash::vk::CommandBuffer)>(device : &Device , command_buffer : ash::vk::CommandBuffer , render_pass : &ash::vk::RenderPass , framebuffer : &ash::vk::Framebuffer , extent : &ash::vk::Extent2D , record : F){
+    let render_pass_begin_info = ash::vk::RenderPassBeginInfo{
+        s_type : ash::vk::StructureType::RENDER_PASS_BEGIN_INFO,
+        p_next : std::ptr::null(),
+        render_pass : *render_pass,
+        framebuffer : *framebuffer,
+        render_area : ash::vk::Rect2D{offset : ash::vk::Offset2D{x : 0, y : 0}, extent : *extent},
+        clear_value_count : 0,
+        p_clear_values : std::ptr::null(),
+    };
+    unsafe{device.cmd_begin_render_pass(command_buffer, &render_pass_begin_info, ash::vk::SubpassContents::INLINE)};
+    record(command_buffer);
+    unsafe{device.cmd_end_render_pass(command_buffer)};
+}
+///Moves the image to a new layout, transfer work before the barrier finishes before transfer work after it starts.
+pub fn record_transition(device : &Device , command_buffer : ash::vk::CommandBuffer , image : ash::vk::Image , old_layout : ash::vk::ImageLayout , new_layout : ash::vk::ImageLayout , src_access_mask : ash::vk::AccessFlags , dst_access_mask : ash::vk::AccessFlags){
+    let barrier = ash::vk::ImageMemoryBarrier{
+        s_type : ash::vk::StructureType::IMAGE_MEMORY_BARRIER,
+        p_next : std::ptr::null(),
+        src_access_mask,
+        dst_access_mask,
+        old_layout,
+        new_layout,
+        src_queue_family_index : ash::vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index : ash::vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range : COLOR_SUBRESOURCE_RANGE,
+    };
+    unsafe{device.cmd_pipeline_barrier(command_buffer, ash::vk::PipelineStageFlags::TRANSFER, ash::vk::PipelineStageFlags::TRANSFER, ash::vk::DependencyFlags::empty(), &[], &[], &[barrier])};
+}
+///Copies the frame from the staging buffer into the image, or clears it when there is no frame.
+///The image is left in TRANSFER_DST_OPTIMAL which the render pass expects as its initial layout.
+pub fn record_upload(device : &Device , command_buffer : ash::vk::CommandBuffer , image : ash::vk::Image , staging_buffer : Option<ash::vk::Buffer> , extent : &ash::vk::Extent2D){
+    record_transition(device, command_buffer, image, ash::vk::ImageLayout::UNDEFINED, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, ash::vk::AccessFlags::empty(), ash::vk::AccessFlags::TRANSFER_WRITE);
+    match staging_buffer{
+        Some(buffer) => {
+            let region = ash::vk::BufferImageCopy{
+                buffer_offset : 0,
+                buffer_row_length : 0,
+                buffer_image_height : 0,
+                image_subresource : COLOR_SUBRESOURCE_LAYERS,
+                image_offset : ash::vk::Offset3D{x : 0, y : 0, z : 0},
+                image_extent : ash::vk::Extent3D{width : extent.width, height : extent.height, depth : 1},
+            };
+            unsafe{device.cmd_copy_buffer_to_image(command_buffer, buffer, image, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, &[region])};
+        }
+        None => {
+            let clear_color = ash::vk::ClearColorValue{float32 : [0.0,0.0,0.0,1.0]};
+            unsafe{device.cmd_clear_color_image(command_buffer, image, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, &clear_color, &[COLOR_SUBRESOURCE_RANGE])};
+        }
+    }
+}
+///Copies the regions of the staging buffer, laid out like an image of the given extent, into the same regions of the image in GENERAL.
+pub fn record_copy_regions(device : &Device , command_buffer : ash::vk::CommandBuffer , staging_buffer : ash::vk::Buffer , image : ash::vk::Image , extent : &ash::vk::Extent2D , pixel_size : usize , regions : &[ash::vk::Rect2D]){
+    let copies : Vec<ash::vk::BufferImageCopy> = regions.iter().map(|region| ash::vk::BufferImageCopy{
+        buffer_offset : ((region.offset.y as u64 * extent.width as u64) + region.offset.x as u64) * pixel_size as u64,
+        buffer_row_length : extent.width,
+        buffer_image_height : 0,
+        image_subresource : COLOR_SUBRESOURCE_LAYERS,
+        image_offset : ash::vk::Offset3D{x : region.offset.x, y : region.offset.y, z : 0},
+        image_extent : ash::vk::Extent3D{width : region.extent.width, height : region.extent.height, depth : 1},
+    }).collect();
+    if copies.is_empty(){return;}
+    unsafe{device.cmd_copy_buffer_to_image(command_buffer, staging_buffer, image, ash::vk::ImageLayout::GENERAL, &copies)};
+}
+///Copies a frame of the same extent onto the swapchain image.
+///The source must be in GENERAL, the destination is left in TRANSFER_DST_OPTIMAL for the render pass.
+pub fn record_copy(device : &Device , command_buffer : ash::vk::CommandBuffer , src_image : ash::vk::Image , dst_image : ash::vk::Image , extent : &ash::vk::Extent2D){
+    record_transition(device, command_buffer, dst_image, ash::vk::ImageLayout::UNDEFINED, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, ash::vk::AccessFlags::empty(), ash::vk::AccessFlags::TRANSFER_WRITE);
+    let region = ash::vk::ImageCopy{
+        src_subresource : COLOR_SUBRESOURCE_LAYERS,
+        src_offset : ash::vk::Offset3D{x : 0, y : 0, z : 0},
+        dst_subresource : COLOR_SUBRESOURCE_LAYERS,
+        dst_offset : ash::vk::Offset3D{x : 0, y : 0, z : 0},
+        extent : ash::vk::Extent3D{width : extent.width, height : extent.height, depth : 1},
+    };
+    unsafe{device.cmd_copy_image(command_buffer, src_image, ash::vk::ImageLayout::GENERAL, dst_image, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, &[region])};
+}
+///Scales a frame onto the swapchain image.
+///The source must be in GENERAL, the destination is left in TRANSFER_DST_OPTIMAL for the render pass.
+pub fn record_blit(device : &Device , command_buffer : ash::vk::CommandBuffer , src_image : ash::vk::Image , src_extent : &ash::vk::Extent2D , dst_image : ash::vk::Image , dst_extent : &ash::vk::Extent2D , filter : ash::vk::Filter){
+    record_transition(device, command_buffer, dst_image, ash::vk::ImageLayout::UNDEFINED, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, ash::vk::AccessFlags::empty(), ash::vk::AccessFlags::TRANSFER_WRITE);
+    let region = ash::vk::ImageBlit{
+        src_subresource : COLOR_SUBRESOURCE_LAYERS,
+        src_offsets : [ash::vk::Offset3D{x : 0, y : 0, z : 0},ash::vk::Offset3D{x : src_extent.width as i32, y : src_extent.height as i32, z : 1}],
+        dst_subresource : COLOR_SUBRESOURCE_LAYERS,
+        dst_offsets : [ash::vk::Offset3D{x : 0, y : 0, z : 0},ash::vk::Offset3D{x : dst_extent.width as i32, y : dst_extent.height as i32, z : 1}],
+    };
+    unsafe{device.cmd_blit_image(command_buffer, src_image, ash::vk::ImageLayout::GENERAL, dst_image, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, &[region], filter)};
+}
+///Records commands into a temporary command buffer, submits them and waits for them to finish.
+pub fn submit_once<F : FnOnce(ash::vk::CommandBuffer)>(device : &Device , command_pool : &ash::vk::CommandPool , queue : ash::vk::Queue , record : F){
+    let command_buffer = create_command_buffers(device, command_pool, 1)[0];
+    begin_command_buffer(device, command_buffer);
+    record(command_buffer);
+    unsafe{device.end_command_buffer(command_buffer)}.expect("Failed to record command buffer.");
+    let command_buffers = [command_buffer];
+    let submit_info = ash::vk::SubmitInfo{
+        s_type : ash::vk::StructureType::SUBMIT_INFO,
+        p_next : std::ptr::null(),
+        wait_semaphore_count : 0,
+        p_wait_semaphores : std::ptr::null(),
+        p_wait_dst_stage_mask : std::ptr::null(),
+        command_buffer_count : command_buffers.len() as u32,
+        p_command_buffers : command_buffers.as_ptr(),
+        signal_semaphore_count : 0,
+        p_signal_semaphores : std::ptr::null(),
+    };
+    unsafe{device.queue_submit(queue, &[submit_info], ash::vk::Fence::null())}.expect("Failed to submit command buffer.");
+    unsafe{device.queue_wait_idle(queue)}.expect("Failed to wait for the queue to become idle.");
+    unsafe{device.free_command_buffers(*command_pool, &command_buffers)};
+}
+///Makes an image filled by record_upload readable by shaders, the submission has to be waited for before they read it.
+///Transfer only queues have no shader stages to wait for, so the barrier only moves the image to SHADER_READ_ONLY_OPTIMAL.
+pub fn record_shader_read(device : &Device , command_buffer : ash::vk::CommandBuffer , image : ash::vk::Image){
+    let barrier = mip_barrier(image, 0, 1, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, ash::vk::AccessFlags::TRANSFER_WRITE, ash::vk::AccessFlags::empty());
+    unsafe{device.cmd_pipeline_barrier(command_buffer, ash::vk::PipelineStageFlags::TRANSFER, ash::vk::PipelineStageFlags::BOTTOM_OF_PIPE, ash::vk::DependencyFlags::empty(), &[], &[], &[barrier])};
+}
+///Makes the writes of the compute shaders before it visible to the host once the fence of the submission is signaled.
+pub fn record_host_barrier(device : &Device , command_buffer : ash::vk::CommandBuffer){
+    let barrier = ash::vk::MemoryBarrier{
+        s_type : ash::vk::StructureType::MEMORY_BARRIER,
+        p_next : std::ptr::null(),
+        src_access_mask : ash::vk::AccessFlags::SHADER_WRITE,
+        dst_access_mask : ash::vk::AccessFlags::HOST_READ,
+    };
+    unsafe{device.cmd_pipeline_barrier(command_buffer, ash::vk::PipelineStageFlags::COMPUTE_SHADER, ash::vk::PipelineStageFlags::HOST, ash::vk::DependencyFlags::empty(), &[barrier], &[], &[])};
+}
+///Submits a recorded command buffer, waits for the fence and resets it for the next submission.
+pub fn submit_and_wait(device : &Device , queue : ash::vk::Queue , command_buffer : ash::vk::CommandBuffer , fence : ash::vk::Fence){
+    submit(device, queue, command_buffer, fence);
+    wait_for_fence(device, fence);
+}
+///Submits a recorded command buffer that signals the fence when done.
+pub fn submit(device : &Device , queue : ash::vk::Queue , command_buffer : ash::vk::CommandBuffer , fence : ash::vk::Fence){
+    let command_buffers = [command_buffer];
+    let submit_info = ash::vk::SubmitInfo{
+        s_type : ash::vk::StructureType::SUBMIT_INFO,
+        p_next : std::ptr::null(),
+        wait_semaphore_count : 0,
+        p_wait_semaphores : std::ptr::null(),
+        p_wait_dst_stage_mask : std::ptr::null(),
+        command_buffer_count : command_buffers.len() as u32,
+        p_command_buffers : command_buffers.as_ptr(),
+        signal_semaphore_count : 0,
+        p_signal_semaphores : std::ptr::null(),
+    };
+    unsafe{device.queue_submit(queue, &[submit_info], fence)}.expect("Failed to submit command buffer.");
+}
+///Waits for a submission to finish and resets its fence for the next one.
+pub fn wait_for_fence(device : &Device , fence : ash::vk::Fence){
+    unsafe{device.wait_for_fences(&[fence], true, u64::MAX)}.expect("Failed to wait for the fence.");
+    unsafe{device.reset_fences(&[fence])}.expect("Failed to reset the fence.");
+}
+fn mip_barrier(image : ash::vk::Image , base_mip_level : u32 , level_count : u32 , old_layout : ash::vk::ImageLayout , new_layout : ash::vk::ImageLayout , src_access_mask : ash::vk::AccessFlags , dst_access_mask : ash::vk::AccessFlags) -> ash::vk::ImageMemoryBarrier{
+    return ash::vk::ImageMemoryBarrier{
+        s_type : ash::vk::StructureType::IMAGE_MEMORY_BARRIER,
+        p_next : std::ptr::null(),
+        src_access_mask,
+        dst_access_mask,
+        old_layout,
+        new_layout,
+        src_queue_family_index : ash::vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index : ash::vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range : ash::vk::ImageSubresourceRange{base_mip_level, level_count, ..COLOR_SUBRESOURCE_RANGE},
+    };
+}
+///Fills every mip level below the first by repeatedly halving the previous one with linear blits, then makes the image readable by shaders.
+///The first level must be in TRANSFER_DST_OPTIMAL as left by record_upload, every level is left in SHADER_READ_ONLY_OPTIMAL.
+pub fn record_mipmaps(device : &Device , command_buffer : ash::vk::CommandBuffer , image : ash::vk::Image , extent : &ash::vk::Extent2D , mip_levels : u32){
+    let shader_stages = ash::vk::PipelineStageFlags::COMPUTE_SHADER | ash::vk::PipelineStageFlags::FRAGMENT_SHADER;
+    if mip_levels > 1{
+        let barrier = mip_barrier(image, 1, mip_levels - 1, ash::vk::ImageLayout::UNDEFINED, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, ash::vk::AccessFlags::empty(), ash::vk::AccessFlags::TRANSFER_WRITE);
+        unsafe{device.cmd_pipeline_barrier(command_buffer, ash::vk::PipelineStageFlags::TOP_OF_PIPE, ash::vk::PipelineStageFlags::TRANSFER, ash::vk::DependencyFlags::empty(), &[], &[], &[barrier])};
+    }
+    let (mut width,mut height) = (extent.width as i32,extent.height as i32);
+    for level in 1..mip_levels{
+        let barrier = mip_barrier(image, level - 1, 1, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL, ash::vk::AccessFlags::TRANSFER_WRITE, ash::vk::AccessFlags::TRANSFER_READ);
+        unsafe{device.cmd_pipeline_barrier(command_buffer, ash::vk::PipelineStageFlags::TRANSFER, ash::vk::PipelineStageFlags::TRANSFER, ash::vk::DependencyFlags::empty(), &[], &[], &[barrier])};
+        let (next_width,next_height) = ((width / 2).max(1),(height / 2).max(1));
+        let region = ash::vk::ImageBlit{
+            src_subresource : ash::vk::ImageSubresourceLayers{mip_level : level - 1, ..COLOR_SUBRESOURCE_LAYERS},
+            src_offsets : [ash::vk::Offset3D{x : 0, y : 0, z : 0},ash::vk::Offset3D{x : width, y : height, z : 1}],
+            dst_subresource : ash::vk::ImageSubresourceLayers{mip_level : level, ..COLOR_SUBRESOURCE_LAYERS},
+            dst_offsets : [ash::vk::Offset3D{x : 0, y : 0, z : 0},ash::vk::Offset3D{x : next_width, y : next_height, z : 1}],
+        };
+        unsafe{device.cmd_blit_image(command_buffer, image, ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL, image, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, &[region], ash::vk::Filter::LINEAR)};
+        let barrier = mip_barrier(image, level - 1, 1, ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL, ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, ash::vk::AccessFlags::TRANSFER_READ, ash::vk::AccessFlags::SHADER_READ);
+        unsafe{device.cmd_pipeline_barrier(command_buffer, ash::vk::PipelineStageFlags::TRANSFER, shader_stages, ash::vk::DependencyFlags::empty(), &[], &[], &[barrier])};
+        width = next_width;
+        height = next_height;
+    }
+    let barrier = mip_barrier(image, mip_levels - 1, 1, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL, ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, ash::vk::AccessFlags::TRANSFER_WRITE, ash::vk::AccessFlags::SHADER_READ);
+    unsafe{device.cmd_pipeline_barrier(command_buffer, ash::vk::PipelineStageFlags::TRANSFER, shader_stages, ash::vk::DependencyFlags::empty(), &[], &[], &[barrier])};
+}
@@ -0,0 +1,127 @@
+use ash::version::DeviceV1_0;
+use ash::version::InstanceV1_0;
+
+use super::device;
+use super::instance;
+use super::descriptors;
+use super::sequence;
+use super::bsdf;
+use super::trace;
+use super::upload;
+use super::RendererSettings;
+use super::super::sampler::SamplerSettings;
+use super::super::scene::Scene;
+use super::super::camera::Camera;
+use super::super::tracer::Tracer;
+
+///A vulkan device without a window, for the gpu work of headless runs and of the tests comparing the gpu with the cpu.
+///It takes a device with a graphics queue like the viewer, the passes run on its compute queue and environment maps are uploaded on its transfer queue.
+pub struct ComputeDevice{
+    _entry : ash::Entry,
+    instance : ash::Instance,
+    physical_device : ash::vk::PhysicalDevice,
+    ///Why the device asked for in the settings is not used.
+    device_fallback : Option<String>,
+    device_features : device::DeviceFeatures,
+    graphics_queue_family : u32,
+    compute_queue_family : u32,
+    device : ash::Device,
+    descriptor_allocator : descriptors::DescriptorAllocator,
+    ///Only its transfer queue is used, there are no frames to upload.
+    upload : upload::FrameUpload,
+    ///Created on first use.
+    sequence : Option<sequence::SequencePass>,
+    bsdf : Option<bsdf::BsdfPass>,
+    trace : Option<trace::TracePass>,
+}
+impl ComputeDevice{
+    ///An error when there is no vulkan loader or no device with a graphics queue, the window settings are ignored.
+    pub fn new(settings : &RendererSettings) -> Result<Self,String>{
+        let entry = instance::load_entry()?;
+        let instance = instance::create_instance(&entry, &settings.app_info, &[], settings.validation);
+        let device_choice = match device::choose_headless_device(&instance, settings.device.as_deref()){
+            Some(choice) => choice,
+            None => {
+                unsafe{instance.destroy_instance(None)};
+                return Err(String::from("No vulkan device has a graphics queue."));
+            }
+        };
+        let physical_device = device_choice.device;
+        let graphics_queue_family = device::get_graphics_queue_family(&instance, &physical_device);
+        let transfer_queue_family = device::get_transfer_queue_family(&instance, &physical_device);
+        let compute_queue_family = device::get_compute_queue_family(&instance, &physical_device);
+        let device_features = device::get_device_features(&instance, &physical_device);
+        let device = device::create_device(&instance, &physical_device, &device_features, graphics_queue_family, transfer_queue_family, compute_queue_family, None);
+        let upload = upload::FrameUpload::new(&device, transfer_queue_family, graphics_queue_family, 1);
+        return Ok(Self{
+            _entry : entry,
+            instance,
+            physical_device,
+            device_fallback : device_choice.fallback,
+            device_features,
+            graphics_queue_family,
+            compute_queue_family,
+            device,
+            descriptor_allocator : descriptors::DescriptorAllocator::new(device_features.descriptor_indexing),
+            upload,
+            sequence : None,
+            bsdf : None,
+            trace : None,
+        });
+    }
+    pub fn device_name(&self) -> String{
+        return device::get_device_name(&self.instance, &self.physical_device);
+    }
+    ///Why the device asked for in the settings is not used, None when it is or when there was no preference.
+    pub fn device_fallback(&self) -> Option<&str>{
+        return self.device_fallback.as_deref();
+    }
+    ///The numbers shaders/sampler.glsl draws for the first dimensions of samples of a width by height block of pixels at the origin.
+    ///They are ordered by pixel, then sample, then dimension, and match what sampler::Sampler draws for the same settings.
+    pub fn sample_sequences(&mut self , settings : &SamplerSettings , width : u32 , height : u32 , first_sample : u32 , samples : u32 , dimensions : u32) -> Vec<[f32;2]>{
+        let (instance,device,physical_device) = (&self.instance,&self.device,&self.physical_device);
+        let (compute_queue_family,descriptor_allocator) = (self.compute_queue_family,&mut self.descriptor_allocator);
+        let pass = self.sequence.get_or_insert_with(|| sequence::SequencePass::new(instance, device, physical_device, compute_queue_family, descriptor_allocator));
+        return pass.sample(instance, device, physical_device, settings, width, height, first_sample, samples, dimensions);
+    }
+    ///What shaders/bsdf.glsl evaluates and samples for every case, in the order of the cases.
+    pub fn evaluate_bsdfs(&mut self , cases : &[bsdf::BsdfCase]) -> Vec<bsdf::BsdfResult>{
+        let (instance,device,physical_device) = (&self.instance,&self.device,&self.physical_device);
+        let (compute_queue_family,descriptor_allocator) = (self.compute_queue_family,&mut self.descriptor_allocator);
+        let pass = self.bsdf.get_or_insert_with(|| bsdf::BsdfPass::new(device, compute_queue_family, descriptor_allocator));
+        return pass.evaluate(instance, device, physical_device, cases);
+    }
+    ///Uploads the scene for trace_pass, call it again whenever the spheres or materials change.
+    ///The error names the images that are traced with their mean color, the scene is uploaded either way.
+    pub fn set_scene(&mut self , scene : &Scene) -> Result<(),String>{
+        let (instance,device,physical_device,device_features) = (&self.instance,&self.device,&self.physical_device,&self.device_features);
+        let (graphics_queue_family,compute_queue_family,upload,descriptor_allocator) = (self.graphics_queue_family,self.compute_queue_family,&self.upload,&mut self.descriptor_allocator);
+        let pass = self.trace.get_or_insert_with(|| trace::TracePass::new(instance, device, physical_device, device_features, graphics_queue_family, compute_queue_family, 1, upload, descriptor_allocator));
+        return pass.set_scene(instance, device, physical_device, upload, scene);
+    }
+    ///Adds one sample traced on the gpu to every pixel of the tracer, with the camera and the scene of the last set_scene.
+    pub fn trace_pass(&mut self , camera : &Camera , tracer : &mut Tracer){
+        let (instance,device,physical_device,device_features) = (&self.instance,&self.device,&self.physical_device,&self.device_features);
+        let (graphics_queue_family,compute_queue_family,upload,descriptor_allocator) = (self.graphics_queue_family,self.compute_queue_family,&self.upload,&mut self.descriptor_allocator);
+        let pass = self.trace.get_or_insert_with(|| trace::TracePass::new(instance, device, physical_device, device_features, graphics_queue_family, compute_queue_family, 1, upload, descriptor_allocator));
+        pass.trace(instance, device, physical_device, camera, tracer);
+    }
+}
+impl Drop for ComputeDevice{
+    fn drop(&mut self){
+        unsafe{self.device.device_wait_idle()}.expect("Failed to wait for the device to become idle.");
+        if let Some(sequence) = self.sequence.as_mut(){
+            sequence.destroy(&self.device);
+        }
+        if let Some(bsdf) = self.bsdf.as_mut(){
+            bsdf.destroy(&self.device);
+        }
+        if let Some(trace) = self.trace.as_mut(){
+            trace.destroy(&self.device);
+        }
+        self.descriptor_allocator.destroy(&self.device);
+        self.upload.destroy(&self.device);
+        unsafe{self.device.destroy_device(None)};
+        unsafe{self.instance.destroy_instance(None)};
+    }
+}
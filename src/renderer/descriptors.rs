@@ -0,0 +1,294 @@
+use ash::Device;
+use ash::version::DeviceV1_0;
+
+///The sets the first pool holds, every pool created after it holds twice as many as the last up to MAX_POOL_SETS.
+const INITIAL_POOL_SETS : u32 = 16;
+const MAX_POOL_SETS : u32 = 1024;
+///Descriptors of each type a pool reserves per set, layouts needing more get a pool sized for them.
+const DESCRIPTORS_PER_SET : u32 = 4;
+///Every pool has room for these, other types only when the layout that needed a new pool uses them.
+const POOL_DESCRIPTOR_TYPES : [ash::vk::DescriptorType;5] = [
+    ash::vk::DescriptorType::UNIFORM_BUFFER,
+    ash::vk::DescriptorType::STORAGE_BUFFER,
+    ash::vk::DescriptorType::STORAGE_IMAGE,
+    ash::vk::DescriptorType::SAMPLED_IMAGE,
+    ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+];
+
+///One binding of a descriptor set layout.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct LayoutBinding{
+    pub binding : u32,
+    pub descriptor_type : ash::vk::DescriptorType,
+    ///Array size, the upper bound for bindless arrays.
+    pub count : u32,
+    pub stages : ash::vk::ShaderStageFlags,
+    ///A partially bound array with a variable size that can be updated while in use, needs descriptor indexing.
+    ///Only the binding with the highest number may be bindless.
+    pub bindless : bool,
+}
+impl LayoutBinding{
+    pub fn new(binding : u32 , descriptor_type : ash::vk::DescriptorType , stages : ash::vk::ShaderStageFlags) -> Self{
+        return Self{binding, descriptor_type, count : 1, stages, bindless : false};
+    }
+    pub fn array(binding : u32 , descriptor_type : ash::vk::DescriptorType , count : u32 , stages : ash::vk::ShaderStageFlags , bindless : bool) -> Self{
+        return Self{binding, descriptor_type, count, stages, bindless};
+    }
+    fn flags(&self) -> ash::vk::DescriptorBindingFlags{
+        if !self.bindless{
+            return ash::vk::DescriptorBindingFlags::empty();
+        }
+        return ash::vk::DescriptorBindingFlags::PARTIALLY_BOUND | ash::vk::DescriptorBindingFlags::UPDATE_AFTER_BIND | ash::vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT;
+    }
+}
+///A descriptor set layout that remembers its bindings so writes can be checked against them.
+pub struct DescriptorLayout{
+    pub layout : ash::vk::DescriptorSetLayout,
+    bindings : Vec<LayoutBinding>,
+}
+impl DescriptorLayout{
+    pub fn new(device : &Device , bindings : &[LayoutBinding]) -> Self{
+        let mut bindings = bindings.to_vec();
+        bindings.sort_by_key(|binding| binding.binding);
+        assert!(bindings.iter().rev().skip(1).all(|binding| !binding.bindless), "Only the last binding of a descriptor set layout can be bindless.");
+        let vk_bindings : Vec<ash::vk::DescriptorSetLayoutBinding> = bindings.iter().map(|binding| ash::vk::DescriptorSetLayoutBinding{
+            binding : binding.binding,
+            descriptor_type : binding.descriptor_type,
+            descriptor_count : binding.count,
+            stage_flags : binding.stages,
+            p_immutable_samplers : std::ptr::null(),
+        }).collect();
+        let binding_flags : Vec<ash::vk::DescriptorBindingFlags> = bindings.iter().map(LayoutBinding::flags).collect();
+        let binding_flags_create_info = ash::vk::DescriptorSetLayoutBindingFlagsCreateInfo{
+            s_type : ash::vk::StructureType::DESCRIPTOR_SET_LAYOUT_BINDING_FLAGS_CREATE_INFO,
+            p_next : std::ptr::null(),
+            binding_count : binding_flags.len() as u32,
+            p_binding_flags : binding_flags.as_ptr(),
+        };
+        let bindless = bindings.iter().any(|binding| binding.bindless);
+        let layout_create_info = ash::vk::DescriptorSetLayoutCreateInfo{
+            s_type : ash::vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            p_next : if bindless{&binding_flags_create_info as *const _ as *const std::ffi::c_void}else{std::ptr::null()},
+            flags : if bindless{ash::vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL}else{ash::vk::DescriptorSetLayoutCreateFlags::empty()},
+            binding_count : vk_bindings.len() as u32,
+            p_bindings : vk_bindings.as_ptr(),
+        };
+        let layout = unsafe{device.create_descriptor_set_layout(&layout_create_info, None)}.expect("Failed to create descriptor set layout.");
+        return Self{layout, bindings};
+    }
+    fn binding(&self , binding : u32) -> &LayoutBinding{
+        return self.bindings.iter().find(|layout_binding| layout_binding.binding == binding).expect("No such binding in the descriptor set layout.");
+    }
+    ///The size of the bindless array, sets of this layout are allocated with it as their variable descriptor count.
+    fn variable_count(&self) -> Option<u32>{
+        return self.bindings.last().filter(|binding| binding.bindless).map(|binding| binding.count);
+    }
+    ///Writes resources into consecutive array elements of a binding, the resources must match the type of the binding.
+    pub fn write<R : DescriptorResource>(&self , device : &Device , set : ash::vk::DescriptorSet , binding : u32 , first_element : u32 , resources : &[R]){
+        let layout_binding = self.binding(binding);
+        assert!(R::accepts(layout_binding.descriptor_type), "Resource does not match descriptor type {:?} of binding {}.",layout_binding.descriptor_type,binding);
+        assert!(first_element + resources.len() as u32 <= layout_binding.count, "Descriptor write past the end of binding {}.",binding);
+        let write = ash::vk::WriteDescriptorSet{
+            s_type : ash::vk::StructureType::WRITE_DESCRIPTOR_SET,
+            p_next : std::ptr::null(),
+            dst_set : set,
+            dst_binding : binding,
+            dst_array_element : first_element,
+            descriptor_count : resources.len() as u32,
+            descriptor_type : layout_binding.descriptor_type,
+            p_image_info : std::ptr::null(),
+            p_buffer_info : std::ptr::null(),
+            p_texel_buffer_view : std::ptr::null(),
+        };
+        R::update(device, write, resources);
+    }
+    pub fn destroy(&self , device : &Device){
+        unsafe{device.destroy_descriptor_set_layout(self.layout, None)};
+    }
+}
+///Something that can be written into a descriptor set.
+pub trait DescriptorResource : Sized{
+    fn accepts(descriptor_type : ash::vk::DescriptorType) -> bool;
+    ///Points the write at the resources and submits it.
+    fn update(device : &Device , write : ash::vk::WriteDescriptorSet , resources : &[Self]);
+}
+impl DescriptorResource for ash::vk::DescriptorBufferInfo{
+    fn accepts(descriptor_type : ash::vk::DescriptorType) -> bool{
+        return [ash::vk::DescriptorType::UNIFORM_BUFFER,ash::vk::DescriptorType::STORAGE_BUFFER,ash::vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,ash::vk::DescriptorType::STORAGE_BUFFER_DYNAMIC].contains(&descriptor_type);
+    }
+    fn update(device : &Device , write : ash::vk::WriteDescriptorSet , resources : &[Self]){
+        unsafe{device.update_descriptor_sets(&[ash::vk::WriteDescriptorSet{p_buffer_info : resources.as_ptr(), ..write}], &[])};
+    }
+}
+impl DescriptorResource for ash::vk::DescriptorImageInfo{
+    fn accepts(descriptor_type : ash::vk::DescriptorType) -> bool{
+        return [ash::vk::DescriptorType::SAMPLED_IMAGE,ash::vk::DescriptorType::STORAGE_IMAGE,ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,ash::vk::DescriptorType::SAMPLER].contains(&descriptor_type);
+    }
+    fn update(device : &Device , write : ash::vk::WriteDescriptorSet , resources : &[Self]){
+        unsafe{device.update_descriptor_sets(&[ash::vk::WriteDescriptorSet{p_image_info : resources.as_ptr(), ..write}], &[])};
+    }
+}
+impl DescriptorResource for ash::vk::AccelerationStructureKHR{
+    fn accepts(descriptor_type : ash::vk::DescriptorType) -> bool{
+        return descriptor_type == ash::vk::DescriptorType::ACCELERATION_STRUCTURE_KHR;
+    }
+    fn update(device : &Device , write : ash::vk::WriteDescriptorSet , resources : &[Self]){
+        let acceleration_structures = ash::vk::WriteDescriptorSetAccelerationStructureKHR{
+            s_type : ash::vk::StructureType::WRITE_DESCRIPTOR_SET_ACCELERATION_STRUCTURE_KHR,
+            p_next : std::ptr::null(),
+            acceleration_structure_count : resources.len() as u32,
+            p_acceleration_structures : resources.as_ptr(),
+        };
+        let write = ash::vk::WriteDescriptorSet{p_next : &acceleration_structures as *const _ as *const std::ffi::c_void, ..write};
+        unsafe{device.update_descriptor_sets(&[write], &[])};
+    }
+}
+///Hands out descriptor sets, creating a new pool whenever the current one runs out.
+pub struct DescriptorAllocator{
+    pools : Vec<ash::vk::DescriptorPool>,
+    pool_sets : u32,
+    ///Pools are created with UPDATE_AFTER_BIND so bindless layouts can be allocated from them.
+    update_after_bind : bool,
+}
+impl DescriptorAllocator{
+    ///Pass whether the device supports descriptor indexing, only then can bindless layouts be allocated.
+    pub fn new(update_after_bind : bool) -> Self{
+        return Self{pools : vec!(), pool_sets : INITIAL_POOL_SETS, update_after_bind};
+    }
+    pub fn allocate(&mut self , device : &Device , layout : &DescriptorLayout) -> ash::vk::DescriptorSet{
+        if let Some(&pool) = self.pools.last(){
+            match allocate_set(device, pool, layout){
+                Ok(set) => return set,
+                Err(ash::vk::Result::ERROR_OUT_OF_POOL_MEMORY) | Err(ash::vk::Result::ERROR_FRAGMENTED_POOL) => {}
+                Err(_) => panic!("Failed to allocate descriptor set."),
+            }
+        }
+        let pool = self.create_pool(device, layout);
+        self.pools.push(pool);
+        return allocate_set(device, pool, layout).expect("Failed to allocate descriptor set.");
+    }
+    ///One set for each frame in flight, so a frame can update its sets while the gpu still reads those of the previous frame.
+    pub fn allocate_per_frame(&mut self , device : &Device , layout : &DescriptorLayout , frames : usize) -> Vec<ash::vk::DescriptorSet>{
+        return (0..frames).map(|_| self.allocate(device, layout)).collect();
+    }
+    fn create_pool(&mut self , device : &Device , layout : &DescriptorLayout) -> ash::vk::DescriptorPool{
+        let sets = self.pool_sets;
+        self.pool_sets = next_pool_sets(sets);
+        let pool_sizes = pool_sizes(sets, &layout.bindings);
+        let pool_create_info = ash::vk::DescriptorPoolCreateInfo{
+            s_type : ash::vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : if self.update_after_bind{ash::vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND}else{ash::vk::DescriptorPoolCreateFlags::empty()},
+            max_sets : sets,
+            pool_size_count : pool_sizes.len() as u32,
+            p_pool_sizes : pool_sizes.as_ptr(),
+        };
+        return unsafe{device.create_descriptor_pool(&pool_create_info, None)}.expect("Failed to create descriptor pool.");
+    }
+    pub fn destroy(&mut self , device : &Device){
+        for pool in self.pools.drain(..){
+            unsafe{device.destroy_descriptor_pool(pool, None)};
+        }
+    }
+}
+///The sets the pool after one holding the given amount holds.
+fn next_pool_sets(sets : u32) -> u32{
+    return (sets * 2).min(MAX_POOL_SETS);
+}
+///The descriptors a pool for the given amount of sets reserves, always enough for at least one set of the layout with the bindings.
+fn pool_sizes(sets : u32 , bindings : &[LayoutBinding]) -> Vec<ash::vk::DescriptorPoolSize>{
+    let mut types = POOL_DESCRIPTOR_TYPES.to_vec();
+    for binding in bindings.iter(){
+        if !types.contains(&binding.descriptor_type){
+            types.push(binding.descriptor_type);
+        }
+    }
+    return types.into_iter().map(|ty| {
+        let layout_count = bindings.iter().filter(|binding| binding.descriptor_type == ty).map(|binding| binding.count).sum::<u32>();
+        ash::vk::DescriptorPoolSize{ty, descriptor_count : (sets * DESCRIPTORS_PER_SET).max(layout_count)}
+    }).collect();
+}
+fn allocate_set(device : &Device , pool : ash::vk::DescriptorPool , layout : &DescriptorLayout) -> Result<ash::vk::DescriptorSet,ash::vk::Result>{
+    let counts = [layout.variable_count().unwrap_or(0)];
+    let variable_count_allocate_info = ash::vk::DescriptorSetVariableDescriptorCountAllocateInfo{
+        s_type : ash::vk::StructureType::DESCRIPTOR_SET_VARIABLE_DESCRIPTOR_COUNT_ALLOCATE_INFO,
+        p_next : std::ptr::null(),
+        descriptor_set_count : counts.len() as u32,
+        p_descriptor_counts : counts.as_ptr(),
+    };
+    let layouts = [layout.layout];
+    let allocate_info = ash::vk::DescriptorSetAllocateInfo{
+        s_type : ash::vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+        p_next : if layout.variable_count().is_some(){&variable_count_allocate_info as *const _ as *const std::ffi::c_void}else{std::ptr::null()},
+        descriptor_pool : pool,
+        descriptor_set_count : layouts.len() as u32,
+        p_set_layouts : layouts.as_ptr(),
+    };
+    return unsafe{device.allocate_descriptor_sets(&allocate_info)}.map(|sets| sets[0]);
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn size(sizes : &[ash::vk::DescriptorPoolSize] , ty : ash::vk::DescriptorType) -> Option<u32>{
+        return sizes.iter().find(|size| size.ty == ty).map(|size| size.descriptor_count);
+    }
+    #[test]
+    fn pool_growth(){
+        let mut sets = INITIAL_POOL_SETS;
+        let mut pools = vec!(sets);
+        for _ in 0..8{
+            sets = next_pool_sets(sets);
+            pools.push(sets);
+        }
+        assert_eq!(pools, vec!(16,32,64,128,256,512,1024,1024,1024));
+    }
+    #[test]
+    fn default_pool_sizes(){
+        let sizes = pool_sizes(16, &[LayoutBinding::new(0, ash::vk::DescriptorType::SAMPLED_IMAGE, ash::vk::ShaderStageFlags::FRAGMENT)]);
+        assert_eq!(sizes.len(), POOL_DESCRIPTOR_TYPES.len());
+        for ty in POOL_DESCRIPTOR_TYPES{
+            assert_eq!(size(&sizes, ty), Some(16 * DESCRIPTORS_PER_SET));
+        }
+    }
+    #[test]
+    fn layout_pool_sizes(){
+        //Types outside the defaults are added for the layout, and layouts needing more than a pool reserves still fit one set.
+        let large = LayoutBinding{count : 200, ..LayoutBinding::new(1, ash::vk::DescriptorType::STORAGE_IMAGE, ash::vk::ShaderStageFlags::COMPUTE)};
+        let bindings = [
+            LayoutBinding::new(0, ash::vk::DescriptorType::SAMPLER, ash::vk::ShaderStageFlags::FRAGMENT),
+            large,
+        ];
+        let sizes = pool_sizes(16, &bindings);
+        assert_eq!(size(&sizes, ash::vk::DescriptorType::SAMPLER), Some(16 * DESCRIPTORS_PER_SET));
+        assert_eq!(size(&sizes, ash::vk::DescriptorType::STORAGE_IMAGE), Some(200));
+        assert_eq!(size(&sizes, ash::vk::DescriptorType::UNIFORM_BUFFER), Some(16 * DESCRIPTORS_PER_SET));
+        assert_eq!(sizes.len(), POOL_DESCRIPTOR_TYPES.len() + 1);
+    }
+    #[test]
+    fn bindless_flags(){
+        let stages = ash::vk::ShaderStageFlags::COMPUTE;
+        assert_eq!(LayoutBinding::new(0, ash::vk::DescriptorType::SAMPLER, stages).flags(), ash::vk::DescriptorBindingFlags::empty());
+        assert_eq!(LayoutBinding::array(1, ash::vk::DescriptorType::SAMPLED_IMAGE, 16, stages, false).flags(), ash::vk::DescriptorBindingFlags::empty());
+        let flags = LayoutBinding::array(1, ash::vk::DescriptorType::SAMPLED_IMAGE, 4096, stages, true).flags();
+        assert!(flags.contains(ash::vk::DescriptorBindingFlags::PARTIALLY_BOUND | ash::vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT));
+        //The array is written while the sets using it are bound.
+        assert!(flags.contains(ash::vk::DescriptorBindingFlags::UPDATE_AFTER_BIND));
+    }
+    #[test]
+    fn typed_resources(){
+        let storage = ash::vk::DescriptorType::STORAGE_BUFFER;
+        let sampled = ash::vk::DescriptorType::SAMPLED_IMAGE;
+        let acceleration_structure = ash::vk::DescriptorType::ACCELERATION_STRUCTURE_KHR;
+        assert!(ash::vk::DescriptorBufferInfo::accepts(storage));
+        assert!(!ash::vk::DescriptorBufferInfo::accepts(sampled));
+        assert!(ash::vk::DescriptorImageInfo::accepts(sampled));
+        assert!(!ash::vk::DescriptorImageInfo::accepts(acceleration_structure));
+        assert!(ash::vk::AccelerationStructureKHR::accepts(acceleration_structure));
+        assert!(!ash::vk::AccelerationStructureKHR::accepts(storage));
+        //Pools get room for the acceleration structures of the layout that needed them.
+        let sizes = pool_sizes(16, &[LayoutBinding::new(0, acceleration_structure, ash::vk::ShaderStageFlags::COMPUTE)]);
+        assert_eq!(size(&sizes, acceleration_structure), Some(16 * DESCRIPTORS_PER_SET));
+    }
+}
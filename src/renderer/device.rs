@@ -5,72 +5,205 @@ use ash::vk::SurfaceKHR;
 
 use ash::Instance;
 use ash::version::InstanceV1_0;
+use ash::version::InstanceV1_1;
 
 use ash::Device;
 
-pub fn choose_physical_device(instance : &Instance , surface_loader : &Surface , surface : &SurfaceKHR) -> PhysicalDevice{
-    let physical_devices = unsafe{instance.enumerate_physical_devices()}.expect("No devices that support vulkan found.");
-    let mut prefered_device = None;
-    for &device in physical_devices.iter(){
-        let device_type = unsafe{instance.get_physical_device_properties(device).device_type};
-        for (queue_index,properties) in unsafe{instance.get_physical_device_queue_family_properties(device)}.iter().enumerate(){
-            if prefered_device.is_none() && unsafe{surface_loader.get_physical_device_surface_support(device, queue_index as u32, *surface)}.expect("Failed to query surface support.") && properties.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS){
-                prefered_device = Some(device);
-            }
-            else if unsafe{surface_loader.get_physical_device_surface_support(device, queue_index as u32, *surface)}.expect("Failed to query device surface support.") && properties.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS) && device_type == ash::vk::PhysicalDeviceType::DISCRETE_GPU{
-                prefered_device = Some(device);
-            }
-        }
+use super::portability;
+
+///Optional features that are enabled when the device supports them.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct DeviceFeatures{
+    ///The device is a portability implementation such as MoltenVK and has to enable the subset extension.
+    pub portability_subset : bool,
+    ///The largest anisotropy samplers may use, None when anisotropic filtering is not supported.
+    pub max_anisotropy : Option<f32>,
+    ///Large partially bound arrays of sampled images indexed with non uniform indices in shaders.
+    pub descriptor_indexing : bool,
+    ///Descriptor indexing comes from VK_EXT_descriptor_indexing rather than vulkan 1.2.
+    pub descriptor_indexing_extension : bool,
+}
+///Returns the device name as reported by the driver.
+pub fn get_device_name(instance : &Instance , physical_device : &PhysicalDevice) -> String{
+    let properties = unsafe{instance.get_physical_device_properties(*physical_device)};
+    let raw_string = unsafe{std::ffi::CStr::from_ptr(properties.device_name.as_ptr())};
+    return raw_string.to_str().expect("Failed to convert vulkan raw string.").to_owned();
+}
+///What device selection looks at, gathered from the driver so the choice itself can be tested against made up devices.
+#[derive(Clone,Debug)]
+pub struct DeviceProfile{
+    pub name : String,
+    pub device_type : ash::vk::PhysicalDeviceType,
+    pub queue_families : Vec<ash::vk::QueueFamilyProperties>,
+    ///Whether each queue family can present to the surface, empty without a surface.
+    pub present_support : Vec<bool>,
+}
+impl DeviceProfile{
+    ///Whether a graphics queue family can present, families that can only present are not used.
+    pub fn can_present(&self) -> bool{
+        return self.queue_families.iter().zip(self.present_support.iter()).any(|(family,&present)| present && family.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS));
+    }
+    pub fn has_graphics(&self) -> bool{
+        return choose_graphics_queue_family(&self.queue_families).is_some();
     }
-    return prefered_device.unwrap();
 }
-pub fn get_graphics_queue_family(instance : &Instance , physical_device : &PhysicalDevice) -> u32{
+pub fn get_device_profile(instance : &Instance , physical_device : &PhysicalDevice , surface : Option<(&Surface,&SurfaceKHR)>) -> DeviceProfile{
     let queue_families = unsafe{instance.get_physical_device_queue_family_properties(*physical_device)};
-    let mut queue_family_index = None;
-    for (index,queue_family) in queue_families.iter().enumerate(){
-        if queue_family_index.is_none() && queue_family.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS){
-            queue_family_index = Some(index as u32);
+    let present_support = match surface{
+        Some((surface_loader,surface)) => (0..queue_families.len()).map(|index| unsafe{surface_loader.get_physical_device_surface_support(*physical_device, index as u32, *surface)}.expect("Failed to query surface support.")).collect(),
+        None => vec!(),
+    };
+    return DeviceProfile{
+        name : get_device_name(instance, physical_device),
+        device_type : unsafe{instance.get_physical_device_properties(*physical_device)}.device_type,
+        queue_families,
+        present_support,
+    };
+}
+fn get_device_profiles(instance : &Instance , surface : Option<(&Surface,&SurfaceKHR)>) -> (Vec<PhysicalDevice>,Vec<DeviceProfile>){
+    let physical_devices = unsafe{instance.enumerate_physical_devices()}.expect("No devices that support vulkan found.");
+    let profiles = physical_devices.iter().map(|device| get_device_profile(instance, device, surface)).collect();
+    return (physical_devices,profiles);
+}
+///Finds the device matching a preference, either an index in enumeration order or part of the device name.
+pub fn find_device(profiles : &[DeviceProfile] , preference : &str) -> Option<usize>{
+    if let Ok(index) = preference.parse::<usize>(){
+        return if index < profiles.len(){Some(index)}else{None};
+    }
+    let preference = preference.to_lowercase();
+    return profiles.iter().position(|profile| profile.name.to_lowercase().contains(&preference));
+}
+///The chosen device, with the reason the preferred device was passed over when there was a preference that could not be used.
+#[derive(Clone,Debug,PartialEq)]
+pub struct DeviceChoice<T>{
+    pub device : T,
+    pub fallback : Option<String>,
+}
+impl<T> DeviceChoice<T>{
+    fn map<U , F : FnOnce(T) -> U>(self , f : F) -> DeviceChoice<U>{
+        return DeviceChoice{device : f(self.device), fallback : self.fallback};
+    }
+}
+///Picks the preferred device when usable, otherwise the first of the usable devices that is a discrete gpu, otherwise the first usable one.
+fn choose_usable<F : Fn(&DeviceProfile) -> bool>(profiles : &[DeviceProfile] , preference : Option<&str> , usable : F , unusable : &str) -> Option<DeviceChoice<usize>>{
+    let mut fallback = None;
+    if let Some(preference) = preference{
+        match find_device(profiles, preference){
+            Some(index) if usable(&profiles[index]) => return Some(DeviceChoice{device : index, fallback : None}),
+            Some(index) => fallback = Some(format!("Device {} {}, choosing another device.",profiles[index].name,unusable)),
+            None => fallback = Some(format!("No device matches {}, choosing another device.",preference)),
         }
     }
-    return queue_family_index.unwrap();
+    let discrete = profiles.iter().position(|profile| usable(profile) && profile.device_type == ash::vk::PhysicalDeviceType::DISCRETE_GPU);
+    return discrete.or_else(|| profiles.iter().position(&usable)).map(|device| DeviceChoice{device, fallback});
 }
-pub fn get_presentation_queue_family(instance : &Instance , physical_device : &PhysicalDevice , surface_loader : &Surface , surface : &SurfaceKHR) -> u32{
-    let queue_families = unsafe{instance.get_physical_device_queue_family_properties(*physical_device)};
-    let mut queue_family_index = None;
-    for (index,_) in queue_families.iter().enumerate(){
-        if unsafe{surface_loader.get_physical_device_surface_support(*physical_device, index as u32, *surface)}.expect("Failed to query presentation support."){
-            queue_family_index = Some(index as u32);
-            break;
+///Picks the preferred device if it can present, otherwise the first discrete gpu that can present, otherwise the first device that can.
+pub fn choose_device(profiles : &[DeviceProfile] , preference : Option<&str>) -> Option<DeviceChoice<usize>>{
+    return choose_usable(profiles, preference, DeviceProfile::can_present, "can not present to the window");
+}
+///Picks a device without a surface to present to, the preferred device if it has a graphics queue, otherwise the first discrete gpu with one.
+pub fn choose_headless(profiles : &[DeviceProfile] , preference : Option<&str>) -> Option<DeviceChoice<usize>>{
+    return choose_usable(profiles, preference, DeviceProfile::has_graphics, "has no graphics queue");
+}
+pub fn choose_physical_device(instance : &Instance , surface_loader : &Surface , surface : &SurfaceKHR , preference : Option<&str>) -> DeviceChoice<PhysicalDevice>{
+    let (physical_devices,profiles) = get_device_profiles(instance, Some((surface_loader,surface)));
+    return choose_device(&profiles, preference).expect("No device can present to the window.").map(|index| physical_devices[index]);
+}
+pub fn choose_headless_device(instance : &Instance , preference : Option<&str>) -> Option<DeviceChoice<PhysicalDevice>>{
+    let (physical_devices,profiles) = get_device_profiles(instance, None);
+    return choose_headless(&profiles, preference).map(|choice| choice.map(|index| physical_devices[index]));
+}
+///The first queue family with graphics, every device that is used has one.
+pub fn choose_graphics_queue_family(queue_families : &[ash::vk::QueueFamilyProperties]) -> Option<u32>{
+    return queue_families.iter().position(|family| family.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS)).map(|index| index as u32);
+}
+///Prefers presenting from the graphics family so no ownership transfer is needed, otherwise the first family that can present.
+pub fn choose_presentation_queue_family(queue_families : &[ash::vk::QueueFamilyProperties] , present_support : &[bool]) -> Option<u32>{
+    if let Some(graphics) = choose_graphics_queue_family(queue_families){
+        if present_support.get(graphics as usize) == Some(&true){
+            return Some(graphics);
         }
     }
-    return queue_family_index.unwrap();
+    return present_support.iter().position(|&present| present).map(|index| index as u32);
+}
+///Prefers a dedicated transfer family, those are usually backed by dma engines, otherwise the first family that can transfer.
+///Graphics and compute families can always transfer even when they do not report it.
+pub fn choose_transfer_queue_family(queue_families : &[ash::vk::QueueFamilyProperties]) -> Option<u32>{
+    let transfer = |family : &ash::vk::QueueFamilyProperties| family.queue_flags.intersects(ash::vk::QueueFlags::TRANSFER | ash::vk::QueueFlags::GRAPHICS | ash::vk::QueueFlags::COMPUTE);
+    let dedicated = queue_families.iter().position(|family| family.queue_flags.contains(ash::vk::QueueFlags::TRANSFER) && !family.queue_flags.intersects(ash::vk::QueueFlags::GRAPHICS | ash::vk::QueueFlags::COMPUTE));
+    return dedicated.or_else(|| queue_families.iter().position(transfer)).map(|index| index as u32);
+}
+///Prefers an async compute family without graphics, otherwise the first family with compute.
+pub fn choose_compute_queue_family(queue_families : &[ash::vk::QueueFamilyProperties]) -> Option<u32>{
+    let dedicated = queue_families.iter().position(|family| family.queue_flags.contains(ash::vk::QueueFlags::COMPUTE) && !family.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS));
+    return dedicated.or_else(|| queue_families.iter().position(|family| family.queue_flags.contains(ash::vk::QueueFlags::COMPUTE))).map(|index| index as u32);
+}
+pub fn get_graphics_queue_family(instance : &Instance , physical_device : &PhysicalDevice) -> u32{
+    let queue_families = unsafe{instance.get_physical_device_queue_family_properties(*physical_device)};
+    return choose_graphics_queue_family(&queue_families).expect("The device has no graphics queue.");
+}
+pub fn get_presentation_queue_family(instance : &Instance , physical_device : &PhysicalDevice , surface_loader : &Surface , surface : &SurfaceKHR) -> u32{
+    let profile = get_device_profile(instance, physical_device, Some((surface_loader,surface)));
+    return choose_presentation_queue_family(&profile.queue_families, &profile.present_support).expect("The device can not present to the window.");
 }
 pub fn get_transfer_queue_family(instance : &Instance , physical_device : &PhysicalDevice) -> u32{
     let queue_families = unsafe{instance.get_physical_device_queue_family_properties(*physical_device)};
-    let mut queue_family_index = None;
-    for (index,queue_family) in queue_families.iter().enumerate(){
-        if queue_family_index.is_none() && queue_family.queue_flags.contains(ash::vk::QueueFlags::TRANSFER){
-            queue_family_index = Some(index as u32);
-        } else if queue_family.queue_flags.contains(ash::vk::QueueFlags::TRANSFER) && !queue_family.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS) && !queue_family.queue_flags.contains(ash::vk::QueueFlags::COMPUTE){
-            queue_family_index = Some(index as u32);
-        }
-    }
-    return queue_family_index.unwrap();
+    return choose_transfer_queue_family(&queue_families).expect("The device has no transfer queue.");
 }
 pub fn get_compute_queue_family(instance : &Instance , physical_device : &PhysicalDevice) -> u32{
     let queue_families = unsafe{instance.get_physical_device_queue_family_properties(*physical_device)};
-    let mut queue_family_index = None;
-    for (index,queue_family) in queue_families.iter().enumerate(){
-        if queue_family_index.is_none() && queue_family.queue_flags.contains(ash::vk::QueueFlags::COMPUTE){
-            queue_family_index = Some(index as u32);
-        } else if queue_family.queue_flags.contains(ash::vk::QueueFlags::COMPUTE) && !queue_family.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS){
-            queue_family_index = Some(index as u32);
-        }
+    return choose_compute_queue_family(&queue_families).expect("The device has no compute queue.");
+}
+fn device_extensions(instance : &Instance , physical_device : &PhysicalDevice) -> Vec<std::ffi::CString>{
+    let extensions = unsafe{instance.enumerate_device_extension_properties(*physical_device)}.expect("Failed to enumerate device extensions.");
+    return extensions.iter().map(|extension| unsafe{std::ffi::CStr::from_ptr(extension.extension_name.as_ptr())}.to_owned()).collect();
+}
+///The descriptor indexing features bindless arrays need, get_device_features checks the same ones.
+fn descriptor_indexing_features() -> ash::vk::PhysicalDeviceDescriptorIndexingFeatures{
+    return ash::vk::PhysicalDeviceDescriptorIndexingFeatures{
+        shader_sampled_image_array_non_uniform_indexing : ash::vk::TRUE,
+        descriptor_binding_sampled_image_update_after_bind : ash::vk::TRUE,
+        descriptor_binding_partially_bound : ash::vk::TRUE,
+        descriptor_binding_variable_descriptor_count : ash::vk::TRUE,
+        runtime_descriptor_array : ash::vk::TRUE,
+        ..Default::default()
+    };
+}
+pub fn get_device_features(instance : &Instance , physical_device : &PhysicalDevice) -> DeviceFeatures{
+    let properties = unsafe{instance.get_physical_device_properties(*physical_device)};
+    let features = unsafe{instance.get_physical_device_features(*physical_device)};
+    let version = (ash::vk::version_major(properties.api_version),ash::vk::version_minor(properties.api_version));
+    let extensions = device_extensions(instance, physical_device);
+    let extensions = extensions.iter().map(|extension| extension.as_c_str()).collect::<Vec<_>>();
+    let core = version >= (1,2);
+    let extension = !core && version >= (1,1) && extensions.contains(&ash::vk::ExtDescriptorIndexingFn::name());
+    let mut indexing_features = ash::vk::PhysicalDeviceDescriptorIndexingFeatures::default();
+    if core || extension{
+        let mut features2 = ash::vk::PhysicalDeviceFeatures2{
+            s_type : ash::vk::StructureType::PHYSICAL_DEVICE_FEATURES_2,
+            p_next : &mut indexing_features as *mut _ as *mut std::ffi::c_void,
+            features : ash::vk::PhysicalDeviceFeatures::default(),
+        };
+        unsafe{instance.get_physical_device_features2(*physical_device, &mut features2)};
     }
-    return queue_family_index.unwrap();
+    let descriptor_indexing = (core || extension) && [
+        indexing_features.shader_sampled_image_array_non_uniform_indexing,
+        indexing_features.descriptor_binding_sampled_image_update_after_bind,
+        indexing_features.descriptor_binding_partially_bound,
+        indexing_features.descriptor_binding_variable_descriptor_count,
+        indexing_features.runtime_descriptor_array,
+    ].iter().all(|&feature| feature == ash::vk::TRUE);
+    return DeviceFeatures{
+        portability_subset : portability::is_portability_subset(&extensions),
+        max_anisotropy : if features.sampler_anisotropy == ash::vk::TRUE{Some(properties.limits.max_sampler_anisotropy)}else{None},
+        descriptor_indexing,
+        descriptor_indexing_extension : descriptor_indexing && extension,
+    };
 }
-pub fn create_device(instance : &Instance , physical_device : &PhysicalDevice, graphics_queue_family : u32, transfer_queue_family : u32, compute_queue_family : u32, presentation_queue_family : u32) -> Device{
-    let mut queues = vec!(graphics_queue_family,transfer_queue_family,compute_queue_family,presentation_queue_family);
+///Creates the device with a queue of every family, the swapchain extension is only enabled with a presentation family.
+pub fn create_device(instance : &Instance , physical_device : &PhysicalDevice , device_features : &DeviceFeatures, graphics_queue_family : u32, transfer_queue_family : u32, compute_queue_family : u32, presentation_queue_family : Option<u32>) -> Device{
+    let mut queues = vec!(graphics_queue_family,transfer_queue_family,compute_queue_family);
+    queues.extend(presentation_queue_family);
     queues.sort();
     queues.dedup();
     let mut queue_infos = Vec::new();
@@ -85,13 +218,24 @@ pub fn create_device(instance : &Instance , physical_device : &PhysicalDevice, g
             p_queue_priorities : priorities.as_ptr(),
         })
     }
-    let extensions = [ash::extensions::khr::Swapchain::name().as_ptr()];
+    let mut extensions = vec!();
+    if presentation_queue_family.is_some(){
+        extensions.push(ash::extensions::khr::Swapchain::name().as_ptr());
+    }
+    if device_features.portability_subset{
+        extensions.push(portability::subset_extension_name().as_ptr());
+    }
+    if device_features.descriptor_indexing_extension{
+        extensions.push(ash::vk::ExtDescriptorIndexingFn::name().as_ptr());
+    }
     let features = ash::vk::PhysicalDeviceFeatures{
+        sampler_anisotropy : if device_features.max_anisotropy.is_some(){ash::vk::TRUE}else{ash::vk::FALSE},
         ..Default::default()
     };
+    let indexing_features = descriptor_indexing_features();
     let device_create_info = ash::vk::DeviceCreateInfo{
         s_type : ash::vk::StructureType::DEVICE_CREATE_INFO,
-        p_next : std::ptr::null(),
+        p_next : if device_features.descriptor_indexing{&indexing_features as *const _ as *const std::ffi::c_void}else{std::ptr::null()},
         flags : ash::vk::DeviceCreateFlags::empty(),
         enabled_extension_count : extensions.len() as u32,
         pp_enabled_extension_names : extensions.as_ptr(),
@@ -102,4 +246,98 @@ pub fn create_device(instance : &Instance , physical_device : &PhysicalDevice, g
         p_enabled_features : &features,
     };
     return unsafe{instance.create_device(*physical_device, &device_create_info, None)}.expect("Failed to create logical device.");
+}
+//Selection against synthetic gpu profiles, loosely modeled on what the drivers report.
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use ash::vk::PhysicalDeviceType;
+    use ash::vk::QueueFlags;
+
+    fn family(queue_flags : QueueFlags) -> ash::vk::QueueFamilyProperties{
+        return ash::vk::QueueFamilyProperties{queue_flags, queue_count : 1, ..Default::default()};
+    }
+    fn profile(name : &str , device_type : PhysicalDeviceType , families : &[(QueueFlags,bool)]) -> DeviceProfile{
+        return DeviceProfile{
+            name : String::from(name),
+            device_type,
+            queue_families : families.iter().map(|&(flags,_)| family(flags)).collect(),
+            present_support : families.iter().map(|&(_,present)| present).collect(),
+        };
+    }
+    ///A universal family plus dedicated transfer and async compute families.
+    fn discrete() -> DeviceProfile{
+        let all = QueueFlags::GRAPHICS | QueueFlags::COMPUTE | QueueFlags::TRANSFER;
+        return profile("Discrete GPU", PhysicalDeviceType::DISCRETE_GPU, &[(all,true),(QueueFlags::TRANSFER,false),(QueueFlags::COMPUTE | QueueFlags::TRANSFER,false)]);
+    }
+    ///A single family that does everything.
+    fn integrated() -> DeviceProfile{
+        return profile("Integrated GPU", PhysicalDeviceType::INTEGRATED_GPU, &[(QueueFlags::GRAPHICS | QueueFlags::COMPUTE | QueueFlags::TRANSFER,true)]);
+    }
+    fn software() -> DeviceProfile{
+        //Graphics and compute families do not have to report transfer.
+        return profile("llvmpipe", PhysicalDeviceType::CPU, &[(QueueFlags::GRAPHICS | QueueFlags::COMPUTE,true)]);
+    }
+    #[test]
+    fn queue_families(){
+        let discrete = discrete();
+        assert_eq!(choose_graphics_queue_family(&discrete.queue_families), Some(0));
+        assert_eq!(choose_transfer_queue_family(&discrete.queue_families), Some(1));
+        assert_eq!(choose_compute_queue_family(&discrete.queue_families), Some(2));
+        assert_eq!(choose_presentation_queue_family(&discrete.queue_families, &discrete.present_support), Some(0));
+        let software = software();
+        assert_eq!(choose_transfer_queue_family(&software.queue_families), Some(0));
+        assert_eq!(choose_compute_queue_family(&software.queue_families), Some(0));
+        assert_eq!(choose_graphics_queue_family(&[family(QueueFlags::COMPUTE)]), None);
+    }
+    #[test]
+    fn presentation_from_another_family(){
+        let device = profile("Split", PhysicalDeviceType::DISCRETE_GPU, &[(QueueFlags::GRAPHICS,false),(QueueFlags::TRANSFER,true),(QueueFlags::GRAPHICS | QueueFlags::COMPUTE,true)]);
+        //The first graphics family can not present, so the first family that can is used.
+        assert_eq!(choose_presentation_queue_family(&device.queue_families, &device.present_support), Some(1));
+        assert_eq!(choose_presentation_queue_family(&device.queue_families, &[]), None);
+        //Presenting from a transfer only family does not make a device usable, the graphics family at 2 can present though.
+        assert!(device.can_present());
+        assert!(!profile("Transfer", PhysicalDeviceType::DISCRETE_GPU, &[(QueueFlags::GRAPHICS,false),(QueueFlags::TRANSFER,true)]).can_present());
+    }
+    fn index(choice : Option<DeviceChoice<usize>>) -> Option<usize>{
+        return choice.map(|choice| choice.device);
+    }
+    #[test]
+    fn prefers_discrete_gpus(){
+        assert_eq!(index(choose_device(&[software(),integrated(),discrete()], None)), Some(2));
+        assert_eq!(index(choose_device(&[software(),integrated()], None)), Some(0));
+        assert_eq!(index(choose_headless(&[integrated(),discrete()], None)), Some(1));
+        let mut headless = discrete();
+        headless.present_support = vec!(false;3);
+        assert_eq!(index(choose_device(&[integrated(),headless.clone()], None)), Some(0));
+        assert_eq!(index(choose_device(&[headless.clone()], None)), None);
+        assert_eq!(index(choose_headless(&[headless], None)), Some(0));
+        let compute_only = profile("Accelerator", PhysicalDeviceType::DISCRETE_GPU, &[(QueueFlags::COMPUTE | QueueFlags::TRANSFER,false)]);
+        assert_eq!(index(choose_headless(&[software(),compute_only.clone()], None)), Some(0));
+        assert_eq!(index(choose_headless(&[compute_only], None)), None);
+        assert_eq!(index(choose_device(&[], None)), None);
+    }
+    #[test]
+    fn preferences(){
+        let profiles = [integrated(),discrete(),software()];
+        assert_eq!(find_device(&profiles, "2"), Some(2));
+        assert_eq!(find_device(&profiles, "3"), None);
+        assert_eq!(find_device(&profiles, "LLVM"), Some(2));
+        assert_eq!(index(choose_device(&profiles, Some("integrated"))), Some(0));
+        //A preference that does not match falls back to the usual choice.
+        assert_eq!(index(choose_device(&profiles, Some("missing"))), Some(1));
+        let mut headless = software();
+        headless.present_support = vec!(false);
+        assert_eq!(index(choose_device(&[integrated(),discrete(),headless.clone()], Some("llvmpipe"))), Some(1));
+        assert_eq!(index(choose_headless(&[integrated(),discrete(),headless], Some("llvmpipe"))), Some(2));
+        //The reason the preference was passed over goes back to the caller.
+        assert_eq!(choose_device(&profiles, Some("integrated")).and_then(|choice| choice.fallback), None);
+        assert_eq!(choose_device(&profiles, Some("missing")).and_then(|choice| choice.fallback), Some(String::from("No device matches missing, choosing another device.")));
+        //A preferred device without graphics would make the graphics queue lookup fail.
+        let compute_only = profile("Accelerator", PhysicalDeviceType::DISCRETE_GPU, &[(QueueFlags::COMPUTE | QueueFlags::TRANSFER,false)]);
+        let choice = choose_headless(&[integrated(),compute_only], Some("accelerator")).expect("Failed to fall back to a device with graphics.");
+        assert_eq!(choice.device, 0);
+        assert_eq!(choice.fallback, Some(String::from("Device Accelerator has no graphics queue, choosing another device.")));
+    }
 }
\ No newline at end of file
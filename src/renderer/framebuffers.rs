@@ -1,7 +1,7 @@
 use ash::Device;
 use ash::version::DeviceV1_0;
 
-pub fn create_framebuffers(image_views : &Vec<ash::vk::ImageView> , device : &Device , extent : &ash::vk::Extent2D , render_pass : &ash::vk::RenderPass) -> Vec<ash::vk::Framebuffer>{
+pub fn create_framebuffers(image_views : &[ash::vk::ImageView] , device : &Device , extent : &ash::vk::Extent2D , render_pass : &ash::vk::RenderPass) -> Vec<ash::vk::Framebuffer>{
     let mut framebuffers = vec!();
     for &image_view in image_views.iter(){
         let attachments = [image_view];
@@ -0,0 +1,82 @@
+use ash::version::EntryV1_0;
+use ash::version::InstanceV1_0;
+
+use std::ffi::CStr;
+
+use super::device;
+use super::instance;
+use super::platform;
+
+pub fn device_type_name(device_type : ash::vk::PhysicalDeviceType) -> &'static str{
+    match device_type {
+        ash::vk::PhysicalDeviceType::CPU => "Cpu",
+        ash::vk::PhysicalDeviceType::INTEGRATED_GPU => "Integrated GPU",
+        ash::vk::PhysicalDeviceType::DISCRETE_GPU => "Discrete GPU",
+        ash::vk::PhysicalDeviceType::VIRTUAL_GPU => "Virtual GPU",
+        _ => "Unknown",
+    }
+}
+pub fn version_string(version : u32) -> String{
+    return format!("{}.{}.{}",ash::vk::version_major(version),ash::vk::version_minor(version),ash::vk::version_patch(version));
+}
+fn queue_flags_string(flags : ash::vk::QueueFlags) -> String{
+    let names = [(ash::vk::QueueFlags::GRAPHICS,"graphics"),(ash::vk::QueueFlags::COMPUTE,"compute"),(ash::vk::QueueFlags::TRANSFER,"transfer"),(ash::vk::QueueFlags::SPARSE_BINDING,"sparse binding")];
+    return names.iter().filter(|(flag,_)| flags.contains(*flag)).map(|(_,name)| *name).collect::<Vec<_>>().join(", ");
+}
+///Lists every physical device and its queue families, no window or surface is needed.
+pub fn print_devices(settings : &super::RendererSettings){
+    let entry = match instance::load_entry(){
+        Ok(entry) => entry,
+        Err(error) => {
+            println!("{}",error);
+            return;
+        }
+    };
+    let instance = instance::create_instance(&entry, &settings.app_info, &[], settings.validation);
+    let physical_devices = unsafe{instance.enumerate_physical_devices()}.expect("No devices that support vulkan found.");
+    if physical_devices.is_empty(){
+        println!("No devices that support vulkan found.");
+    }
+    for (index,physical_device) in physical_devices.iter().enumerate(){
+        let properties = unsafe{instance.get_physical_device_properties(*physical_device)};
+        println!("Device {} : {} of type {}.",index,device::get_device_name(&instance, physical_device),device_type_name(properties.device_type));
+        println!("Vulkan version : {}, driver version : {}.",version_string(properties.api_version),properties.driver_version);
+        for (queue_index,queue_family) in unsafe{instance.get_physical_device_queue_family_properties(*physical_device)}.iter().enumerate(){
+            println!("Queue family {} : {} queues, supports {}.",queue_index,queue_family.queue_count,queue_flags_string(queue_family.queue_flags));
+        }
+        println!();
+    }
+    if let Some(choice) = device::choose_headless_device(&instance, settings.device.as_deref()){
+        if let Some(fallback) = choice.fallback{
+            println!("{}",fallback);
+        }
+        println!("Selected without a window : {}.",device::get_device_name(&instance, &choice.device));
+    }
+    unsafe{instance.destroy_instance(None)};
+}
+///Prints the application and vulkan loader information.
+pub fn print_info(app_info : &super::AppInfo){
+    println!("Name : {}, version : {}.",app_info.name,app_info.version);
+    println!("Using engine : {}.",app_info.engine_name);
+    println!("Target : {}.",platform::target());
+    println!("Platform code paths : {}.",platform::code_paths().join(", "));
+    println!();
+    let entry = match instance::load_entry(){
+        Ok(entry) => entry,
+        Err(error) => {
+            println!("{}",error);
+            return;
+        }
+    };
+    let api_version = match entry.try_enumerate_instance_version(){
+        Ok(Some(version)) => version,
+        _ => ash::vk::make_version(1, 0, 0),
+    };
+    println!("Vulkan loader version : {}.",version_string(api_version));
+    println!("Runtime code paths : {}.",platform::runtime_code_paths(&instance::get_instance_portability(&entry, &[])).join(", "));
+    println!("Validation layer installed : {}.",if instance::supports_layer(&entry, instance::validation_layer_name()){"yes"}else{"no"});
+    println!("Instance extensions : ");
+    for extension in entry.enumerate_instance_extension_properties().expect("Failed to enumerate instance extensions.").iter(){
+        println!("    {}",unsafe{CStr::from_ptr(extension.extension_name.as_ptr())}.to_string_lossy());
+    }
+}
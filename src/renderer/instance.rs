@@ -4,36 +4,76 @@ use ash::Instance;
 
 use ash::vk;
 
+use super::portability;
+
 use std::ffi::CString;
+use std::ffi::CStr;
 
+///Loads the vulkan loader, fails on machines that do not have one installed.
+pub fn load_entry() -> Result<Entry,String>{
+    return Entry::new().map_err(|error| format!("Vulkan is not supported on your device : {}.",error));
+}
 pub fn create_entry() -> Entry{
-    return Entry::new().expect("Vulkan is not supported on your device.");
+    return load_entry().expect("Failed to load vulkan.");
+}
+pub fn instance_extensions(entry : &Entry) -> Vec<CString>{
+    let extensions = entry.enumerate_instance_extension_properties().expect("Failed to enumerate instance extensions.");
+    return extensions.iter().map(|ext| unsafe{CStr::from_ptr(ext.extension_name.as_ptr())}.to_owned()).collect();
+}
+pub fn supports_instance_extension(entry : &Entry , name : &CStr) -> bool{
+    return instance_extensions(entry).iter().any(|ext| ext.as_c_str() == name);
+}
+pub fn supports_layer(entry : &Entry , name : &CStr) -> bool{
+    let layers = entry.enumerate_instance_layer_properties().expect("Failed to enumerate instance layers.");
+    return layers.iter().any(|layer| unsafe{CStr::from_ptr(layer.layer_name.as_ptr())} == name);
 }
-pub fn create_instance(entry : &Entry , window : &winit::window::Window) -> Instance{
-    let exts = ash_window::enumerate_required_extensions(window).expect("Failed to enumerate window extensionns.").iter().map(|ext| ext.as_ptr()).collect::<Vec<_>>();
+pub fn validation_layer_name() -> &'static CStr{
+    return CStr::from_bytes_with_nul(b"VK_LAYER_KHRONOS_validation\0").unwrap();
+}
+///The extensions needed to present to the window, the swapchain colorspace extension is added when hdr is requested and available.
+pub fn window_extensions(entry : &Entry , window : &winit::window::Window , hdr : bool) -> Vec<&'static CStr>{
+    let mut exts = ash_window::enumerate_required_extensions(window).expect("Failed to enumerate window extensionns.");
+    if hdr && supports_instance_extension(entry, vk::ExtSwapchainColorspaceFn::name()){
+        exts.push(vk::ExtSwapchainColorspaceFn::name());
+    }
+    return exts;
+}
+///What the instance adds to the requested extensions to enumerate portability implementations such as MoltenVK, nothing when the loader does not support it.
+pub fn get_instance_portability(entry : &Entry , extensions : &[&CStr]) -> portability::InstancePortability{
+    let available = instance_extensions(entry);
+    let available = available.iter().map(|ext| ext.as_c_str()).collect::<Vec<_>>();
+    return portability::instance_portability(&available, extensions);
+}
+///Creates the instance, validation is skipped with a warning when the layer is not installed.
+///Portability implementations such as MoltenVK are enumerated when the loader supports it.
+pub fn create_instance(entry : &Entry , app_info : &super::AppInfo , extensions : &[&CStr] , validation : bool) -> Instance{
+    let portability = get_instance_portability(entry, extensions);
+    let exts = extensions.iter().chain(portability.extensions.iter()).map(|ext| ext.as_ptr()).collect::<Vec<_>>();
+    let layer_installed = supports_layer(entry, validation_layer_name());
+    if validation && !layer_installed{
+        println!("Validation was requested but {} is not installed.",validation_layer_name().to_string_lossy());
+    }
+    let validation = validation && layer_installed;
+    let application_name = CString::new(app_info.name.as_str()).expect("Application name contains a nul byte.");
+    let engine_name = CString::new(app_info.engine_name.as_str()).expect("Engine name contains a nul byte.");
     let application_info = vk::ApplicationInfo{
         s_type : vk::StructureType::APPLICATION_INFO,
         p_next : std::ptr::null(),
         api_version : vk::make_version(1, 2, 0),
-        p_application_name : CString::new(super::super::APP_NAME).unwrap().as_ptr(),
-        p_engine_name : CString::new(super::super::ENGINE_NAME).unwrap().as_ptr(),
-        application_version : *super::super::VERSION,
-        engine_version : *super::super::VERSION,
-    };
-    let validation = if *super::super::VALIDATION_ENABLED{
-        vec!(CString::new("VK_LAYER_KHRONOS_validation").unwrap())
-    } else{
-        vec!()
+        p_application_name : application_name.as_ptr(),
+        p_engine_name : engine_name.as_ptr(),
+        application_version : app_info.version,
+        engine_version : app_info.engine_version,
     };
-    let validation_raw : Vec<*const i8> = validation.iter().map(|name| name.as_ptr()).collect();
+    let validation_raw : Vec<*const i8> = if validation{vec!(validation_layer_name().as_ptr())}else{vec!()};
     let instance_create_info = vk::InstanceCreateInfo{
         s_type : vk::StructureType::INSTANCE_CREATE_INFO,
         p_next : std::ptr::null(),
-        flags : vk::InstanceCreateFlags::empty(),
+        flags : portability.flags,
         p_application_info : &application_info,
         pp_enabled_extension_names : exts.as_ptr(),
         enabled_extension_count : exts.len() as u32,
-        enabled_layer_count : if *super::super::VALIDATION_ENABLED{1}else{0},
+        enabled_layer_count : validation_raw.len() as u32,
         pp_enabled_layer_names : validation_raw.as_ptr(),
     };
     return unsafe{entry.create_instance(&instance_create_info,None)}.expect("Failed to create vulkan instance, are your drivers up to date?");
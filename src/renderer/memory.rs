@@ -0,0 +1,86 @@
+use ash::Device;
+use ash::Instance;
+use ash::version::DeviceV1_0;
+use ash::version::InstanceV1_0;
+
+pub fn find_memory_type(instance : &Instance , physical_device : &ash::vk::PhysicalDevice , type_bits : u32 , properties : ash::vk::MemoryPropertyFlags) -> u32{
+    let memory_properties = unsafe{instance.get_physical_device_memory_properties(*physical_device)};
+    for index in 0..memory_properties.memory_type_count{
+        if type_bits & (1 << index) != 0 && memory_properties.memory_types[index as usize].property_flags.contains(properties){
+            return index;
+        }
+    }
+    panic!("No suitable memory type found.");
+}
+pub fn create_buffer(instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , size : u64 , usage : ash::vk::BufferUsageFlags , properties : ash::vk::MemoryPropertyFlags) -> (ash::vk::Buffer,ash::vk::DeviceMemory){
+    let buffer_create_info = ash::vk::BufferCreateInfo{
+        s_type : ash::vk::StructureType::BUFFER_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::BufferCreateFlags::empty(),
+        size,
+        usage,
+        sharing_mode : ash::vk::SharingMode::EXCLUSIVE,
+        queue_family_index_count : 0,
+        p_queue_family_indices : std::ptr::null(),
+    };
+    let buffer = unsafe{device.create_buffer(&buffer_create_info, None)}.expect("Failed to create buffer.");
+    let requirements = unsafe{device.get_buffer_memory_requirements(buffer)};
+    let memory_allocate_info = ash::vk::MemoryAllocateInfo{
+        s_type : ash::vk::StructureType::MEMORY_ALLOCATE_INFO,
+        p_next : std::ptr::null(),
+        allocation_size : requirements.size,
+        memory_type_index : find_memory_type(instance, physical_device, requirements.memory_type_bits, properties),
+    };
+    let memory = unsafe{device.allocate_memory(&memory_allocate_info, None)}.expect("Failed to allocate buffer memory.");
+    unsafe{device.bind_buffer_memory(buffer, memory, 0)}.expect("Failed to bind buffer memory.");
+    return (buffer,memory);
+}
+///Creates a 2d device local image with optimal tiling, it starts in the UNDEFINED layout.
+pub fn create_image(instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , extent : &ash::vk::Extent2D , mip_levels : u32 , format : ash::vk::Format , usage : ash::vk::ImageUsageFlags) -> (ash::vk::Image,ash::vk::DeviceMemory){
+    return create_shared_image(instance, device, physical_device, extent, mip_levels, format, usage, &[]);
+}
+///Like create_image, an image used by more than one queue family is shared concurrently so it needs no ownership transfers.
+pub fn create_shared_image(instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , extent : &ash::vk::Extent2D , mip_levels : u32 , format : ash::vk::Format , usage : ash::vk::ImageUsageFlags , queue_families : &[u32]) -> (ash::vk::Image,ash::vk::DeviceMemory){
+    let concurrent = queue_families.len() > 1;
+    let image_create_info = ash::vk::ImageCreateInfo{
+        s_type : ash::vk::StructureType::IMAGE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::ImageCreateFlags::empty(),
+        image_type : ash::vk::ImageType::TYPE_2D,
+        format,
+        extent : ash::vk::Extent3D{width : extent.width, height : extent.height, depth : 1},
+        mip_levels,
+        array_layers : 1,
+        samples : ash::vk::SampleCountFlags::TYPE_1,
+        tiling : ash::vk::ImageTiling::OPTIMAL,
+        usage,
+        sharing_mode : if concurrent{ash::vk::SharingMode::CONCURRENT}else{ash::vk::SharingMode::EXCLUSIVE},
+        queue_family_index_count : if concurrent{queue_families.len() as u32}else{0},
+        p_queue_family_indices : if concurrent{queue_families.as_ptr()}else{std::ptr::null()},
+        initial_layout : ash::vk::ImageLayout::UNDEFINED,
+    };
+    let image = unsafe{device.create_image(&image_create_info, None)}.expect("Failed to create image.");
+    let requirements = unsafe{device.get_image_memory_requirements(image)};
+    let memory_allocate_info = ash::vk::MemoryAllocateInfo{
+        s_type : ash::vk::StructureType::MEMORY_ALLOCATE_INFO,
+        p_next : std::ptr::null(),
+        allocation_size : requirements.size,
+        memory_type_index : find_memory_type(instance, physical_device, requirements.memory_type_bits, ash::vk::MemoryPropertyFlags::DEVICE_LOCAL),
+    };
+    let memory = unsafe{device.allocate_memory(&memory_allocate_info, None)}.expect("Failed to allocate image memory.");
+    unsafe{device.bind_image_memory(image, memory, 0)}.expect("Failed to bind image memory.");
+    return (image,memory);
+}
+///Copies the data into host visible and coherent memory.
+pub fn write_memory(device : &Device , memory : &ash::vk::DeviceMemory , data : &[u8]){
+    let pointer = unsafe{device.map_memory(*memory, 0, data.len() as u64, ash::vk::MemoryMapFlags::empty())}.expect("Failed to map memory.");
+    unsafe{std::ptr::copy_nonoverlapping(data.as_ptr(), pointer as *mut u8, data.len())};
+    unsafe{device.unmap_memory(*memory)};
+}
+///Copies the first bytes out of host visible and coherent memory.
+pub fn read_memory(device : &Device , memory : &ash::vk::DeviceMemory , size : usize) -> Vec<u8>{
+    let pointer = unsafe{device.map_memory(*memory, 0, size as u64, ash::vk::MemoryMapFlags::empty())}.expect("Failed to map memory.");
+    let data = unsafe{std::slice::from_raw_parts(pointer as *const u8, size)}.to_vec();
+    unsafe{device.unmap_memory(*memory)};
+    return data;
+}
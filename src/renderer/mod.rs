@@ -4,127 +4,481 @@ mod device;
 mod swapchain;
 mod render_pass;
 mod framebuffers;
+mod commands;
+mod sync;
+mod memory;
+mod texture;
+mod descriptors;
+mod info;
+mod portability;
+mod timestamps;
+mod overlay;
+mod platform;
+mod atrous;
+mod upload;
+mod pipeline;
+mod sequence;
+mod compute;
+mod bsdf;
+mod trace;
+
+pub use swapchain::next_present_mode;
+pub use swapchain::parse_present_mode;
+pub use swapchain::present_mode_name;
+pub use swapchain::format_size;
+pub use swapchain::PRESENT_MODES;
+pub use info::print_devices;
+pub use info::print_info;
+pub use timestamps::GpuFrame;
+pub use timestamps::GpuScope;
+pub use compute::ComputeDevice;
 
 use winit::window::Window;
 
 use ash::version::DeviceV1_0;
 use ash::version::InstanceV1_0;
 
+///The amount of frames the cpu is allowed to record ahead of the gpu.
+const MAX_FRAMES_IN_FLIGHT : usize = 2;
+
+///Options chosen before the renderer is created.
+#[derive(Clone,Debug,PartialEq)]
+pub struct RendererSettings{
+    pub present_mode : ash::vk::PresentModeKHR,
+    ///Prefer an hdr swapchain when the display supports one.
+    pub hdr : bool,
+    ///Enables the khronos validation layer, this severely hurts performance.
+    pub validation : bool,
+    ///Index or part of the name of the device to use.
+    pub device : Option<String>,
+    pub app_info : AppInfo,
+}
+///Identifies the application and engine to the driver, tools embedding the renderer pass their own.
+#[derive(Clone,Debug,PartialEq)]
+pub struct AppInfo{
+    pub name : String,
+    pub version : u32,
+    pub engine_name : String,
+    pub engine_version : u32,
+}
+impl Default for AppInfo{
+    fn default() -> Self{
+        return Self{
+            name : String::from(super::APP_NAME),
+            version : *super::VERSION,
+            engine_name : String::from(super::ENGINE_NAME),
+            engine_version : *super::VERSION,
+        };
+    }
+}
 pub struct Renderer{
+    app_info : AppInfo,
     _entry : ash::Entry,
     instance : ash::Instance,
     surface_loader : ash::extensions::khr::Surface,
     surface : ash::vk::SurfaceKHR,
     physical_device : ash::vk::PhysicalDevice,
+    ///Why the device asked for in the settings is not used.
+    device_fallback : Option<String>,
     graphics_queue_family : u32,
     presentation_queue_family : u32,
     transfer_queue_family : u32,
     compute_queue_family : u32,
     device : ash::Device,
+    ///The optional features the device was created with.
+    device_features : device::DeviceFeatures,
+    graphics_queue : ash::vk::Queue,
+    presentation_queue : ash::vk::Queue,
+    requested_present_mode : ash::vk::PresentModeKHR,
     present_mode : ash::vk::PresentModeKHR,
     swapchain_format : ash::vk::SurfaceFormatKHR,
     swapchain_extent : ash::vk::Extent2D,
     swapchain_image_count : u32,
     swapchain_loader : ash::extensions::khr::Swapchain,
     swapchain : ash::vk::SwapchainKHR,
-    _swapchain_images : Vec<ash::vk::Image>,
+    swapchain_images : Vec<ash::vk::Image>,
     swapchain_image_views : Vec<ash::vk::ImageView>,
     render_pass : ash::vk::RenderPass,
     framebuffers : Vec<ash::vk::Framebuffer>,
+    command_pool : ash::vk::CommandPool,
+    command_buffers : Vec<ash::vk::CommandBuffer>,
+    image_available_semaphores : Vec<ash::vk::Semaphore>,
+    render_finished_semaphores : Vec<ash::vk::Semaphore>,
+    in_flight_fences : Vec<ash::vk::Fence>,
+    ///Uploads the frames on the transfer queue, the draw copies or blits them onto the swapchain.
+    upload : upload::FrameUpload,
+    ///The filter used to scale frames, None when the swapchain format can not be blitted.
+    scaling_filter : Option<ash::vk::Filter>,
+    descriptor_allocator : descriptors::DescriptorAllocator,
+    ///The filter of the denoiser on the compute queue.
+    atrous : atrous::AtrousPass,
+    ///The path tracer on the compute queue, created by the first set_scene.
+    trace : Option<trace::TracePass>,
+    ///None when the graphics queue has no timestamps.
+    timestamps : Option<timestamps::TimestampQueries>,
+    ///Record gpu timestamps around the passes of each frame.
+    profiling : bool,
+    ///Finished frames that were not taken yet.
+    gpu_frames : Vec<GpuFrame>,
+    overlay : overlay::Overlay,
+    current_frame : usize,
+    swapchain_outdated : bool,
 }
 impl Renderer{
-    pub fn new(window : &Window)->Self{
+    ///Sets up presenting to the window, an error when its surface can not show the frames.
+    pub fn new(window : &Window , settings : &RendererSettings)->Result<Self,String>{
+        let requested_present_mode = settings.present_mode;
         let entry = instance::create_entry();
-        let instance = instance::create_instance(&entry,window);
+        let hdr = settings.hdr && instance::supports_instance_extension(&entry, ash::vk::ExtSwapchainColorspaceFn::name());
+        let instance = instance::create_instance(&entry, &settings.app_info, &instance::window_extensions(&entry, window, hdr), settings.validation);
         let (surface_loader,surface) = surface::create_surface(&entry, &instance, window);
-        let physical_device = device::choose_physical_device(&instance, &surface_loader, &surface);
+        let device_choice = device::choose_physical_device(&instance, &surface_loader, &surface, settings.device.as_deref());
+        let physical_device = device_choice.device;
         let graphics_queue_family = device::get_graphics_queue_family(&instance, &physical_device);
         let presentation_queue_family = device::get_presentation_queue_family(&instance, &physical_device, &surface_loader, &surface);
         let transfer_queue_family = device::get_transfer_queue_family(&instance, &physical_device);
         let compute_queue_family = device::get_compute_queue_family(&instance, &physical_device);
-        let device = device::create_device(&instance, &physical_device, graphics_queue_family, transfer_queue_family, compute_queue_family, presentation_queue_family);
-        let present_mode = swapchain::get_swapchain_present_mode(&surface_loader, &surface, &physical_device);
-        let format = swapchain::get_swapchain_surface_format(&surface_loader, &surface, &physical_device);
+        let device_features = device::get_device_features(&instance, &physical_device);
+        let device = device::create_device(&instance, &physical_device, &device_features, graphics_queue_family, transfer_queue_family, compute_queue_family, Some(presentation_queue_family));
+        let graphics_queue = unsafe{device.get_device_queue(graphics_queue_family, 0)};
+        let presentation_queue = unsafe{device.get_device_queue(presentation_queue_family, 0)};
+        let present_mode = swapchain::get_swapchain_present_mode(&surface_loader, &surface, &physical_device, requested_present_mode);
+        let format = swapchain::get_swapchain_surface_format(&surface_loader, &surface, &physical_device, hdr)?;
         let extent = swapchain::get_swapchain_extent(&surface_loader, &surface, &physical_device);
         let min_image_count = swapchain::get_min_image_count(&surface_loader, &surface, &physical_device);
-        let (swapchain_loader,swapchain) = swapchain::create_swapchain(&instance, &device, &surface, &present_mode, &extent, &format, min_image_count, graphics_queue_family, presentation_queue_family, &surface_loader, &physical_device);
+        let (swapchain_loader,swapchain) = swapchain::create_swapchain(&instance, &device, &surface, &present_mode, &extent, &format, min_image_count, graphics_queue_family, presentation_queue_family, &surface_loader, &physical_device)?;
         let swapchain_images = swapchain::create_swapchain_images(&swapchain_loader, &swapchain);
         let swapchain_image_views = swapchain::create_swapchain_image_views(&swapchain_images, &device, format.format);
         let render_pass = render_pass::create_render_pass(&device, format.format);
         let framebuffers = framebuffers::create_framebuffers(&swapchain_image_views, &device, &extent, &render_pass);
-        return Self{
+        let command_pool = commands::create_command_pool(&device, graphics_queue_family);
+        let command_buffers = commands::create_command_buffers(&device, &command_pool, MAX_FRAMES_IN_FLIGHT as u32);
+        let image_available_semaphores = sync::create_semaphores(&device, MAX_FRAMES_IN_FLIGHT);
+        let render_finished_semaphores = sync::create_semaphores(&device, MAX_FRAMES_IN_FLIGHT);
+        let in_flight_fences = sync::create_fences(&device, MAX_FRAMES_IN_FLIGHT);
+        let upload = upload::FrameUpload::new(&device, transfer_queue_family, graphics_queue_family, MAX_FRAMES_IN_FLIGHT);
+        let scaling_filter = swapchain::get_scaling_filter(&instance, &physical_device, format.format);
+        let mut descriptor_allocator = descriptors::DescriptorAllocator::new(device_features.descriptor_indexing);
+        let atrous = atrous::AtrousPass::new(&device, compute_queue_family, &mut descriptor_allocator);
+        let timestamp_period = unsafe{instance.get_physical_device_properties(physical_device)}.limits.timestamp_period;
+        let timestamp_valid_bits = unsafe{instance.get_physical_device_queue_family_properties(physical_device)}[graphics_queue_family as usize].timestamp_valid_bits;
+        let timestamps = timestamps::TimestampQueries::new(&device, MAX_FRAMES_IN_FLIGHT, timestamp_period, timestamp_valid_bits);
+        let overlay = overlay::Overlay::new(&device, render_pass, format.format, MAX_FRAMES_IN_FLIGHT);
+        return Ok(Self{
+            app_info : settings.app_info.clone(),
             _entry : entry,
             instance,
             surface_loader,
             surface,
             physical_device,
+            device_fallback : device_choice.fallback,
             graphics_queue_family,
             compute_queue_family,
             transfer_queue_family,
             presentation_queue_family,
             device,
+            device_features,
+            graphics_queue,
+            presentation_queue,
+            requested_present_mode,
             present_mode,
             swapchain_format : format,
             swapchain_extent : extent,
             swapchain_image_count : min_image_count,
             swapchain_loader,
             swapchain,
-            _swapchain_images : swapchain_images,
+            swapchain_images,
             swapchain_image_views,
             render_pass,
             framebuffers,
-        }
+            command_pool,
+            command_buffers,
+            image_available_semaphores,
+            render_finished_semaphores,
+            in_flight_fences,
+            upload,
+            scaling_filter,
+            descriptor_allocator,
+            atrous,
+            trace : None,
+            timestamps,
+            profiling : false,
+            gpu_frames : vec!(),
+            overlay,
+            current_frame : 0,
+            swapchain_outdated : false,
+        })
+    }
+    ///Why the device asked for in the settings is not used, None when it is or when there was no preference.
+    pub fn device_fallback(&self) -> Option<&str>{
+        return self.device_fallback.as_deref();
     }
     pub fn show_create_info(&self){
+        for line in self.create_info(){
+            println!("{}",line);
+        }
+    }
+    ///What show_create_info prints, empty lines separate the sections.
+    pub fn create_info(&self) -> Vec<String>{
         let device_properties = unsafe{self.instance.get_physical_device_properties(self.physical_device)};
-        let raw_string = unsafe {
-            let pointer = device_properties.device_name.as_ptr();
-            std::ffi::CStr::from_ptr(pointer)
-        };
-        let device_type = match device_properties.device_type {
-            ash::vk::PhysicalDeviceType::CPU => "Cpu",
-            ash::vk::PhysicalDeviceType::INTEGRATED_GPU => "Integrated GPU",
-            ash::vk::PhysicalDeviceType::DISCRETE_GPU => "Discrete GPU",
-            ash::vk::PhysicalDeviceType::VIRTUAL_GPU => "Virtual GPU",
-            ash::vk::PhysicalDeviceType::OTHER => "Unknown",
-            _ => panic!(),
-        };
-        let device_name = raw_string.to_str().expect("Failed to convert vulkan raw string.").to_owned();
-        println!("Name : {}, version : {}.",super::APP_NAME,super::VERSION);
-        println!("Using engine : {}.",super::ENGINE_NAME);
-        println!("");
-        println!("Vulkan info : ");
-        println!("Using device : {} of type {}.",device_name,device_type);
-        println!("");
-        println!("Graphics queue family : {}.",self.graphics_queue_family);
-        println!("Presentation queue family : {}.",self.presentation_queue_family);
-        println!("Transfer queue family : {}.",self.transfer_queue_family);
-        println!("Compute queue family : {}.",self.compute_queue_family);
-        println!("");
-        println!("Using Swapchain with {} images.",self.swapchain_image_count);
-        println!("Using Swapchain present mode : {}." , if self.present_mode == ash::vk::PresentModeKHR::MAILBOX{"Mailbox"}else{"FIFO"});
-        println!("Using Swapchain Extent : x : {} , y : {}.",self.swapchain_extent.width,self.swapchain_extent.height);
-        println!("Using Swapchain Format : {:?}, and Color space : {:?}.",self.swapchain_format.format,self.swapchain_format.color_space);
-        println!("Using Render pass with 1 Subpass.");
-        println!("");
+        let device_type = info::device_type_name(device_properties.device_type);
+        let device_name = device::get_device_name(&self.instance, &self.physical_device);
+        let mut lines = vec!();
+        lines.push(format!("Name : {}, version : {}.",self.app_info.name,self.app_info.version));
+        lines.push(format!("Using engine : {}.",self.app_info.engine_name));
+        lines.push(String::new());
+        lines.push(String::from("Vulkan info : "));
+        lines.push(format!("Using device : {} of type {}.",device_name,device_type));
+        if self.device_features.portability_subset{
+            lines.push(String::from("Device is a portability implementation."));
+        }
+        match self.device_features.max_anisotropy{
+            Some(max_anisotropy) => lines.push(format!("Anisotropic filtering up to {}x.",max_anisotropy)),
+            None => lines.push(String::from("No anisotropic filtering.")),
+        }
+        lines.push(format!("Material textures : {}.",if self.device_features.descriptor_indexing{"bindless"}else{"bound"}));
+        lines.push(String::new());
+        lines.push(format!("Graphics queue family : {}.",self.graphics_queue_family));
+        lines.push(format!("Presentation queue family : {}.",self.presentation_queue_family));
+        lines.push(format!("Transfer queue family : {}.",self.transfer_queue_family));
+        lines.push(format!("Compute queue family : {}.",self.compute_queue_family));
+        lines.push(String::new());
+        lines.push(format!("Using Swapchain with {} images.",self.swapchain_image_count));
+        if self.present_mode == self.requested_present_mode{
+            lines.push(format!("Using Swapchain present mode : {}.",swapchain::present_mode_name(self.present_mode)));
+        } else{
+            lines.push(format!("Using Swapchain present mode : {}, {} is not supported.",swapchain::present_mode_name(self.present_mode),swapchain::present_mode_name(self.requested_present_mode)));
+        }
+        lines.push(format!("Using Swapchain Extent : x : {} , y : {}.",self.swapchain_extent.width,self.swapchain_extent.height));
+        lines.push(format!("Using Swapchain Format : {:?}, and Color space : {:?}.",self.swapchain_format.format,self.swapchain_format.color_space));
+        lines.push(format!("Using {} output.",if self.is_hdr(){"HDR"}else{"SDR"}));
+        lines.push(String::from("Using Render pass with 1 Subpass."));
+        lines.push(String::new());
+        return lines;
+    }
+    ///Whether the swapchain presents in an hdr color space, radiance should then not be clamped to [0,1].
+    pub fn is_hdr(&self) -> bool{
+        return swapchain::is_hdr_color_space(self.swapchain_format.color_space);
+    }
+    pub fn swapchain_format(&self) -> ash::vk::SurfaceFormatKHR{
+        return self.swapchain_format;
+    }
+    pub fn swapchain_extent(&self) -> ash::vk::Extent2D{
+        return self.swapchain_extent;
+    }
+    ///Whether frames of a different size than the swapchain can be presented, see draw_frame.
+    pub fn supports_scaling(&self) -> bool{
+        return self.scaling_filter.is_some();
+    }
+    ///The present mode the swapchain is actually using, this may differ from the requested mode.
+    pub fn present_mode(&self) -> ash::vk::PresentModeKHR{
+        return self.present_mode;
+    }
+    pub fn requested_present_mode(&self) -> ash::vk::PresentModeKHR{
+        return self.requested_present_mode;
+    }
+    ///Requests a new present mode, the swapchain is only recreated when the resolved mode changes.
+    pub fn set_present_mode(&mut self , requested_present_mode : ash::vk::PresentModeKHR){
+        self.requested_present_mode = requested_present_mode;
+        let present_mode = swapchain::get_swapchain_present_mode(&self.surface_loader, &self.surface, &self.physical_device, requested_present_mode);
+        if present_mode != self.present_mode{
+            self.present_mode = present_mode;
+            self.recreate_swapchain();
+        }
+    }
+    ///Whether passes can be timed on the gpu.
+    pub fn supports_timestamps(&self) -> bool{
+        return self.timestamps.is_some();
+    }
+    ///Starts or stops recording gpu timestamps, frames still in flight are read back either way.
+    pub fn set_profiling(&mut self , profiling : bool){
+        self.profiling = profiling;
+    }
+    ///The timings of the frames that finished on the gpu since the last call.
+    pub fn take_gpu_frames(&mut self) -> Vec<GpuFrame>{
+        return std::mem::take(&mut self.gpu_frames);
+    }
+    ///Replaces what the overlay draws over the following frames, textures are updated right away.
+    ///The primitives are in points, pixels_per_point scales them to the swapchain.
+    pub fn update_overlay(&mut self , textures_delta : &egui::TexturesDelta , primitives : Vec<egui::ClippedPrimitive> , pixels_per_point : f32){
+        if self.overlay.needs_idle(textures_delta){
+            unsafe{self.device.device_wait_idle()}.expect("Failed to wait for the device to become idle.");
+        }
+        self.overlay.update(&self.instance, &self.device, &self.physical_device, &self.command_pool, self.graphics_queue, &mut self.descriptor_allocator, textures_delta, primitives, pixels_per_point);
+    }
+    ///Marks the swapchain as outdated, it will be recreated before the next frame.
+    pub fn resize(&mut self){
+        self.swapchain_outdated = true;
     }
+    ///Rebuilds the swapchain for the current surface, a minimized window has a zero sized surface so the old swapchain is kept and stays outdated until it is restored.
     pub fn recreate_swapchain(&mut self){
-        unsafe{self.swapchain_loader.destroy_swapchain(self.swapchain, None)};
-        let swapchain_tupple = swapchain::create_swapchain(&self.instance, &self.device, &self.surface, &self.present_mode, &self.swapchain_extent, &self.swapchain_format, self.swapchain_image_count, self.graphics_queue_family, self.presentation_queue_family, &self.surface_loader, &self.physical_device);
+        let extent = swapchain::get_swapchain_extent(&self.surface_loader, &self.surface, &self.physical_device);
+        if extent.width == 0 || extent.height == 0{
+            self.swapchain_outdated = true;
+            return;
+        }
+        unsafe{self.device.device_wait_idle()}.expect("Failed to wait for the device to become idle.");
+        self.destroy_swapchain();
+        self.swapchain_extent = extent;
+        self.swapchain_image_count = swapchain::get_min_image_count(&self.surface_loader, &self.surface, &self.physical_device);
+        let swapchain_tupple = swapchain::create_swapchain(&self.instance, &self.device, &self.surface, &self.present_mode, &self.swapchain_extent, &self.swapchain_format, self.swapchain_image_count, self.graphics_queue_family, self.presentation_queue_family, &self.surface_loader, &self.physical_device).expect("Failed to recreate the swapchain.");
         self.swapchain = swapchain_tupple.1; self.swapchain_loader = swapchain_tupple.0;
+        self.swapchain_images = swapchain::create_swapchain_images(&self.swapchain_loader, &self.swapchain);
+        self.swapchain_image_views = swapchain::create_swapchain_image_views(&self.swapchain_images, &self.device, self.swapchain_format.format);
+        self.framebuffers = framebuffers::create_framebuffers(&self.swapchain_image_views, &self.device, &self.swapchain_extent, &self.render_pass);
+        self.swapchain_outdated = false;
     }
-}
-impl Drop for Renderer{
-    fn drop(&mut self){
-        unsafe{self.device.device_wait_idle()}.expect("Oh no! The renderer crashed.");
+    ///Presents a frame of the given extent encoded in the swapchain format.
+    ///Frames that do not match the swapchain extent are scaled to fit when supported and replaced by black otherwise.
+    pub fn draw_frame(&mut self , frame : &[u8] , frame_extent : ash::vk::Extent2D){
+        if self.swapchain_outdated{
+            self.recreate_swapchain();
+        }
+        //Still outdated when the window is minimized, there is nothing to present to.
+        if self.swapchain_outdated{
+            return;
+        }
+        let fence = self.in_flight_fences[self.current_frame];
+        unsafe{self.device.wait_for_fences(&[fence], true, u64::MAX)}.expect("Failed to wait for frame fence.");
+        if let Some(timestamps) = self.timestamps.as_mut(){
+            self.gpu_frames.extend(timestamps.read(&self.device, self.current_frame));
+        }
+        let image_index = match unsafe{self.swapchain_loader.acquire_next_image(self.swapchain, u64::MAX, self.image_available_semaphores[self.current_frame], ash::vk::Fence::null())}{
+            Ok((image_index,_)) => image_index,
+            Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.swapchain_outdated = true;
+                return;
+            }
+            Err(_) => panic!("Failed to acquire swapchain image."),
+        };
+        unsafe{self.device.reset_fences(&[fence])}.expect("Failed to reset frame fence.");
+        let pixel_size = swapchain::format_size(self.swapchain_format.format).expect("Failed to find the pixel size of the swapchain format.");
+        let frame_size = frame_extent.width as usize * frame_extent.height as usize * pixel_size;
+        let scaled = frame_extent != self.swapchain_extent;
+        //The changed tiles go up on the transfer queue while the graphics queue is still busy with the previous frame.
+        let uploaded = if frame.len() == frame_size && (!scaled || self.supports_scaling()){
+            Some(self.upload.upload(&self.instance, &self.device, &self.physical_device, self.current_frame, frame, frame_extent, self.swapchain_format.format, pixel_size))
+        } else{
+            None
+        };
+        let swapchain_image = self.swapchain_images[image_index as usize];
+        let command_buffer = self.command_buffers[self.current_frame];
+        self.overlay.prepare(&self.instance, &self.device, &self.physical_device, self.current_frame, self.swapchain_extent);
+        commands::begin_command_buffer(&self.device, command_buffer);
+        let device = &self.device;
+        let frame_index = self.current_frame;
+        let (render_pass,framebuffer,frame_image,swapchain_extent) = (self.render_pass,self.framebuffers[image_index as usize],self.upload.image(),self.swapchain_extent);
+        let overlay = &self.overlay;
+        let mut timestamps = if self.profiling{self.timestamps.as_mut()}else{None};
+        if let Some(timestamps) = timestamps.as_deref_mut(){
+            timestamps.record_reset(device, command_buffer, frame_index);
+        }
+        match (uploaded,self.scaling_filter){
+            (Some(_),Some(filter)) if scaled => {
+                timestamps::record_scope(timestamps.as_deref_mut(), device, command_buffer, frame_index, "present blit", || commands::record_blit(device, command_buffer, frame_image, &frame_extent, swapchain_image, &swapchain_extent, filter));
+            }
+            (Some(_),_) => {
+                timestamps::record_scope(timestamps.as_deref_mut(), device, command_buffer, frame_index, "present copy", || commands::record_copy(device, command_buffer, frame_image, swapchain_image, &swapchain_extent));
+            }
+            (None,_) => {
+                timestamps::record_scope(timestamps.as_deref_mut(), device, command_buffer, frame_index, "clear", || commands::record_upload(device, command_buffer, swapchain_image, None, &swapchain_extent));
+            }
+        }
+        timestamps::record_scope(timestamps.as_deref_mut(), device, command_buffer, frame_index, "overlay", || commands::record_render_pass(device, command_buffer, &render_pass, &framebuffer, &swapchain_extent, |command_buffer| overlay.record(device, command_buffer, frame_index, swapchain_extent)));
+        unsafe{self.device.end_command_buffer(command_buffer)}.expect("Failed to record command buffer.");
+        let mut wait_semaphores = vec!(self.image_available_semaphores[self.current_frame]);
+        let mut signal_semaphores = vec!(self.render_finished_semaphores[self.current_frame]);
+        if let Some(uploaded) = uploaded{
+            wait_semaphores.push(uploaded);
+            signal_semaphores.push(self.upload.released(self.current_frame));
+        }
+        let wait_stages = [ash::vk::PipelineStageFlags::TRANSFER,ash::vk::PipelineStageFlags::TRANSFER];
+        let present_semaphores = [self.render_finished_semaphores[self.current_frame]];
+        let command_buffers = [command_buffer];
+        let submit_info = ash::vk::SubmitInfo{
+            s_type : ash::vk::StructureType::SUBMIT_INFO,
+            p_next : std::ptr::null(),
+            wait_semaphore_count : wait_semaphores.len() as u32,
+            p_wait_semaphores : wait_semaphores.as_ptr(),
+            p_wait_dst_stage_mask : wait_stages.as_ptr(),
+            command_buffer_count : command_buffers.len() as u32,
+            p_command_buffers : command_buffers.as_ptr(),
+            signal_semaphore_count : signal_semaphores.len() as u32,
+            p_signal_semaphores : signal_semaphores.as_ptr(),
+        };
+        unsafe{self.device.queue_submit(self.graphics_queue, &[submit_info], fence)}.expect("Failed to submit draw command buffer.");
+        if let Some(timestamps) = timestamps{
+            timestamps.submitted(frame_index);
+        }
+        let swapchains = [self.swapchain];
+        let image_indices = [image_index];
+        let present_info = ash::vk::PresentInfoKHR{
+            s_type : ash::vk::StructureType::PRESENT_INFO_KHR,
+            p_next : std::ptr::null(),
+            wait_semaphore_count : present_semaphores.len() as u32,
+            p_wait_semaphores : present_semaphores.as_ptr(),
+            swapchain_count : swapchains.len() as u32,
+            p_swapchains : swapchains.as_ptr(),
+            p_image_indices : image_indices.as_ptr(),
+            p_results : std::ptr::null_mut(),
+        };
+        match unsafe{self.swapchain_loader.queue_present(self.presentation_queue, &present_info)}{
+            Ok(false) => {}
+            Ok(true) | Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR) => self.swapchain_outdated = true,
+            Err(_) => panic!("Failed to present swapchain image."),
+        }
+        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+    }
+    ///Runs the à-trous filter of the denoiser on the compute queue, the gpu side of Denoiser::process_with.
+    pub fn filter_illumination(&mut self , frame : &super::denoise::Frame , illumination : &[[f32;3]] , strength : f32) -> Vec<[f32;3]>{
+        let guides : Vec<[f32;4]> = frame.normal.iter().zip(frame.depth.iter()).map(|(normal,depth)| [normal[0],normal[1],normal[2],depth[0]]).collect();
+        return self.atrous.filter(&self.instance, &self.device, &self.physical_device, frame.width, frame.height, &guides, illumination, strength, super::denoise::ITERATIONS);
+    }
+    ///Uploads the scene for trace_pass, call it again whenever the spheres or materials change.
+    ///The error names the images that are traced with their mean color, the scene is uploaded either way.
+    pub fn set_scene(&mut self , scene : &super::scene::Scene) -> Result<(),String>{
+        let (instance,device,physical_device,device_features) = (&self.instance,&self.device,&self.physical_device,&self.device_features);
+        let (graphics_queue_family,compute_queue_family,upload,descriptor_allocator) = (self.graphics_queue_family,self.compute_queue_family,&self.upload,&mut self.descriptor_allocator);
+        let pass = self.trace.get_or_insert_with(|| trace::TracePass::new(instance, device, physical_device, device_features, graphics_queue_family, compute_queue_family, MAX_FRAMES_IN_FLIGHT, upload, descriptor_allocator));
+        return pass.set_scene(instance, device, physical_device, upload, scene);
+    }
+    ///Traces a pass on the compute queue with the camera and the scene of the last set_scene, without waiting for it.
+    ///Every frame in flight has a pass of its own, a pass is added to the tracer by the call that reuses its frame, which is what the result tells.
+    pub fn trace_pass(&mut self , camera : &super::camera::Camera , tracer : &mut super::tracer::Tracer) -> bool{
+        let pass = self.trace.as_mut().expect("Failed to find the gpu tracer, set_scene creates it.");
+        return pass.trace(&self.instance, &self.device, &self.physical_device, camera, tracer);
+    }
+    fn destroy_swapchain(&mut self){
         for &framebuffer in self.framebuffers.iter(){
             unsafe{self.device.destroy_framebuffer(framebuffer, None)};
         }
-        unsafe{self.device.destroy_render_pass(self.render_pass, None)};
         for &image_view in self.swapchain_image_views.iter(){
             unsafe{self.device.destroy_image_view(image_view, None)};
         }
         unsafe{self.swapchain_loader.destroy_swapchain(self.swapchain, None)};
+    }
+}
+impl Drop for Renderer{
+    fn drop(&mut self){
+        unsafe{self.device.device_wait_idle()}.expect("Oh no! The renderer crashed.");
+        for i in 0..MAX_FRAMES_IN_FLIGHT{
+            unsafe{self.device.destroy_semaphore(self.image_available_semaphores[i], None)};
+            unsafe{self.device.destroy_semaphore(self.render_finished_semaphores[i], None)};
+            unsafe{self.device.destroy_fence(self.in_flight_fences[i], None)};
+        }
+        unsafe{self.device.destroy_command_pool(self.command_pool, None)};
+        self.upload.destroy(&self.device);
+        self.overlay.destroy(&self.device);
+        self.atrous.destroy(&self.device);
+        if let Some(trace) = self.trace.as_mut(){
+            trace.destroy(&self.device);
+        }
+        if let Some(timestamps) = self.timestamps.as_mut(){
+            timestamps.destroy(&self.device);
+        }
+        self.descriptor_allocator.destroy(&self.device);
+        self.destroy_swapchain();
+        unsafe{self.device.destroy_render_pass(self.render_pass, None)};
         unsafe{self.device.destroy_device(None)};
         unsafe{self.surface_loader.destroy_surface(self.surface, None)};
         unsafe{self.instance.destroy_instance(None)};
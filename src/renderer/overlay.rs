@@ -0,0 +1,523 @@
+use ash::Device;
+use ash::Instance;
+use ash::version::DeviceV1_0;
+
+use super::memory;
+use super::commands;
+use super::texture;
+use super::descriptors::DescriptorLayout;
+use super::descriptors::DescriptorAllocator;
+use super::descriptors::LayoutBinding;
+
+//Rebuild the shaders with glslc shaders/ui.vert -o shaders/ui.vert.spv and glslc shaders/ui.frag -o shaders/ui.frag.spv.
+const VERTEX_SHADER : &[u8] = include_bytes!("../../shaders/ui.vert.spv");
+const FRAGMENT_SHADER : &[u8] = include_bytes!("../../shaders/ui.frag.spv");
+///Position and uv as two floats each followed by the premultiplied sRGB color as bytes, the layout of egui vertices.
+pub const VERTEX_SIZE : usize = 20;
+///Screen size in points followed by whether the swapchain encodes sRGB itself.
+const PUSH_CONSTANTS_SIZE : u32 = 12;
+
+///Whether the swapchain format converts linear shader output to sRGB or stores linear values, otherwise the shader encodes.
+///Hdr10 swapchains are not told apart, the overlay shows with the wrong brightness on them.
+pub fn is_linear_output(format : ash::vk::Format) -> bool{
+    return matches!(format, ash::vk::Format::B8G8R8A8_SRGB | ash::vk::Format::R8G8B8A8_SRGB | ash::vk::Format::A8B8G8R8_SRGB_PACK32 | ash::vk::Format::R16G16B16A16_SFLOAT);
+}
+///Packs egui vertices into the vertex buffer layout.
+pub fn vertex_bytes(vertices : &[egui::epaint::Vertex]) -> Vec<u8>{
+    let mut bytes = Vec::with_capacity(vertices.len() * VERTEX_SIZE);
+    for vertex in vertices.iter(){
+        for value in [vertex.pos.x,vertex.pos.y,vertex.uv.x,vertex.uv.y].iter(){
+            bytes.extend_from_slice(&value.to_ne_bytes());
+        }
+        bytes.extend_from_slice(&vertex.color.to_array());
+    }
+    return bytes;
+}
+///The clip rectangle in framebuffer pixels, None when nothing of it is inside the framebuffer.
+pub fn scissor(clip_rect : egui::Rect , pixels_per_point : f32 , extent : ash::vk::Extent2D) -> Option<ash::vk::Rect2D>{
+    let min_x = (clip_rect.min.x * pixels_per_point).round().clamp(0.0, extent.width as f32) as u32;
+    let min_y = (clip_rect.min.y * pixels_per_point).round().clamp(0.0, extent.height as f32) as u32;
+    let max_x = (clip_rect.max.x * pixels_per_point).round().clamp(0.0, extent.width as f32) as u32;
+    let max_y = (clip_rect.max.y * pixels_per_point).round().clamp(0.0, extent.height as f32) as u32;
+    if max_x <= min_x || max_y <= min_y{return None;}
+    return Some(ash::vk::Rect2D{
+        offset : ash::vk::Offset2D{x : min_x as i32, y : min_y as i32},
+        extent : ash::vk::Extent2D{width : max_x - min_x, height : max_y - min_y},
+    });
+}
+///Copies rgba texels of a width wide region into the texels of a larger image at the position.
+pub fn patch_texels(texels : &mut [u8] , width : usize , position : [usize;2] , region_width : usize , region : &[u8]){
+    for (row,region_row) in region.chunks_exact(region_width * 4).enumerate(){
+        let start = ((position[1] + row) * width + position[0]) * 4;
+        texels[start..start + region_row.len()].copy_from_slice(region_row);
+    }
+}
+fn image_texels(image : &egui::ImageData) -> Vec<u8>{
+    match image{
+        egui::ImageData::Color(image) => image.pixels.iter().flat_map(|color| color.to_array()).collect(),
+        egui::ImageData::Font(image) => image.srgba_pixels(None).flat_map(|color| color.to_array()).collect(),
+    }
+}
+///An egui texture, the texels are kept so partial updates can be applied before uploading it again.
+struct OverlayTexture{
+    id : egui::TextureId,
+    size : [usize;2],
+    texels : Vec<u8>,
+    image : ash::vk::Image,
+    memory : ash::vk::DeviceMemory,
+    view : ash::vk::ImageView,
+    set : ash::vk::DescriptorSet,
+}
+///A host visible buffer that grows to fit what is written into it.
+struct OverlayBuffer{
+    buffer : ash::vk::Buffer,
+    memory : ash::vk::DeviceMemory,
+    size : u64,
+    usage : ash::vk::BufferUsageFlags,
+}
+impl OverlayBuffer{
+    fn new(usage : ash::vk::BufferUsageFlags) -> Self{
+        return Self{buffer : ash::vk::Buffer::null(), memory : ash::vk::DeviceMemory::null(), size : 0, usage};
+    }
+    fn write(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , data : &[u8]){
+        if (data.len() as u64) > self.size{
+            self.destroy(device);
+            let size = (data.len() as u64).next_power_of_two();
+            let (buffer,memory) = memory::create_buffer(instance, device, physical_device, size, self.usage, ash::vk::MemoryPropertyFlags::HOST_VISIBLE | ash::vk::MemoryPropertyFlags::HOST_COHERENT);
+            self.buffer = buffer;
+            self.memory = memory;
+            self.size = size;
+        }
+        if !data.is_empty(){
+            memory::write_memory(device, &self.memory, data);
+        }
+    }
+    fn destroy(&mut self , device : &Device){
+        if self.buffer != ash::vk::Buffer::null(){
+            unsafe{device.destroy_buffer(self.buffer, None)};
+            unsafe{device.free_memory(self.memory, None)};
+        }
+        *self = Self::new(self.usage);
+    }
+}
+///One indexed draw of the overlay.
+struct OverlayDraw{
+    set : ash::vk::DescriptorSet,
+    scissor : ash::vk::Rect2D,
+    first_index : u32,
+    index_count : u32,
+    vertex_offset : i32,
+}
+///Draws egui output over the frame inside the render pass, with premultiplied alpha blending.
+///Each frame in flight has its own vertex and index buffers, textures are only changed once the device is idle.
+pub struct Overlay{
+    descriptor_layout : DescriptorLayout,
+    pipeline_layout : ash::vk::PipelineLayout,
+    pipeline : ash::vk::Pipeline,
+    sampler : ash::vk::Sampler,
+    textures : Vec<OverlayTexture>,
+    ///Sets of freed textures, reused for new ones as the allocator never frees sets.
+    free_sets : Vec<ash::vk::DescriptorSet>,
+    ///Textures egui freed, they are destroyed with the next texture update as frames in flight may still use them.
+    pending_free : Vec<egui::TextureId>,
+    vertex_buffers : Vec<OverlayBuffer>,
+    index_buffers : Vec<OverlayBuffer>,
+    primitives : Vec<egui::ClippedPrimitive>,
+    pixels_per_point : f32,
+    draws : Vec<OverlayDraw>,
+    linear_output : bool,
+}
+impl Overlay{
+    pub fn new(device : &Device , render_pass : ash::vk::RenderPass , format : ash::vk::Format , frames : usize) -> Self{
+        let descriptor_layout = DescriptorLayout::new(device, &[
+            LayoutBinding::new(0, ash::vk::DescriptorType::SAMPLED_IMAGE, ash::vk::ShaderStageFlags::FRAGMENT),
+            LayoutBinding::new(1, ash::vk::DescriptorType::SAMPLER, ash::vk::ShaderStageFlags::FRAGMENT),
+        ]);
+        let pipeline_layout = create_pipeline_layout(device, descriptor_layout.layout);
+        let pipeline = create_pipeline(device, render_pass, pipeline_layout);
+        return Self{
+            descriptor_layout,
+            pipeline_layout,
+            pipeline,
+            sampler : create_sampler(device),
+            textures : vec!(),
+            free_sets : vec!(),
+            pending_free : vec!(),
+            vertex_buffers : (0..frames).map(|_| OverlayBuffer::new(ash::vk::BufferUsageFlags::VERTEX_BUFFER)).collect(),
+            index_buffers : (0..frames).map(|_| OverlayBuffer::new(ash::vk::BufferUsageFlags::INDEX_BUFFER)).collect(),
+            primitives : vec!(),
+            pixels_per_point : 1.0,
+            draws : vec!(),
+            linear_output : is_linear_output(format),
+        };
+    }
+    ///Whether a texture update has to wait for the device to become idle.
+    pub fn needs_idle(&self , textures_delta : &egui::TexturesDelta) -> bool{
+        return !textures_delta.set.is_empty() || !self.pending_free.is_empty();
+    }
+    ///Applies texture changes and replaces what is drawn, the device must be idle when needs_idle returned true.
+    pub fn update(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , command_pool : &ash::vk::CommandPool , queue : ash::vk::Queue , descriptor_allocator : &mut DescriptorAllocator , textures_delta : &egui::TexturesDelta , primitives : Vec<egui::ClippedPrimitive> , pixels_per_point : f32){
+        for id in std::mem::take(&mut self.pending_free){
+            if let Some(index) = self.textures.iter().position(|texture| texture.id == id){
+                let texture = self.textures.swap_remove(index);
+                destroy_texture(device, &texture);
+                self.free_sets.push(texture.set);
+            }
+        }
+        for (id,delta) in textures_delta.set.iter(){
+            let region = image_texels(&delta.image);
+            let index = match (self.textures.iter().position(|texture| texture.id == *id),delta.pos){
+                (Some(index),Some(position)) => {
+                    let texture = &mut self.textures[index];
+                    patch_texels(&mut texture.texels, texture.size[0], position, delta.image.width(), &region);
+                    destroy_texture(device, texture);
+                    index
+                }
+                (Some(index),None) => {
+                    let texture = &mut self.textures[index];
+                    destroy_texture(device, texture);
+                    texture.size = delta.image.size();
+                    texture.texels = region;
+                    index
+                }
+                (None,_) => {
+                    let set = match self.free_sets.pop(){
+                        Some(set) => set,
+                        None => descriptor_allocator.allocate(device, &self.descriptor_layout),
+                    };
+                    self.textures.push(OverlayTexture{
+                        id : *id,
+                        size : delta.image.size(),
+                        texels : region,
+                        image : ash::vk::Image::null(),
+                        memory : ash::vk::DeviceMemory::null(),
+                        view : ash::vk::ImageView::null(),
+                        set,
+                    });
+                    self.textures.len() - 1
+                }
+            };
+            let texture = &mut self.textures[index];
+            upload_texture(instance, device, physical_device, command_pool, queue, texture);
+            let image_info = ash::vk::DescriptorImageInfo{sampler : ash::vk::Sampler::null(), image_view : texture.view, image_layout : ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL};
+            let sampler_info = ash::vk::DescriptorImageInfo{sampler : self.sampler, image_view : ash::vk::ImageView::null(), image_layout : ash::vk::ImageLayout::UNDEFINED};
+            self.descriptor_layout.write(device, texture.set, 0, 0, &[image_info]);
+            self.descriptor_layout.write(device, texture.set, 1, 0, &[sampler_info]);
+        }
+        self.pending_free.extend(textures_delta.free.iter().copied());
+        self.primitives = primitives;
+        self.pixels_per_point = pixels_per_point;
+    }
+    ///Writes the meshes into the buffers of the frame, its previous submission must have finished.
+    pub fn prepare(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , frame : usize , extent : ash::vk::Extent2D){
+        self.draws.clear();
+        let mut vertices = vec!();
+        let mut indices : Vec<u32> = vec!();
+        for primitive in self.primitives.iter(){
+            let mesh = match &primitive.primitive{
+                egui::epaint::Primitive::Mesh(mesh) => mesh,
+                //Paint callbacks need the integration to draw them, none are used.
+                egui::epaint::Primitive::Callback(_) => continue,
+            };
+            let set = match self.textures.iter().find(|texture| texture.id == mesh.texture_id){
+                Some(texture) => texture.set,
+                None => continue,
+            };
+            let scissor = match scissor(primitive.clip_rect, self.pixels_per_point, extent){
+                Some(scissor) => scissor,
+                None => continue,
+            };
+            if mesh.indices.is_empty(){continue;}
+            self.draws.push(OverlayDraw{set, scissor, first_index : indices.len() as u32, index_count : mesh.indices.len() as u32, vertex_offset : vertices.len() as i32});
+            vertices.extend_from_slice(&mesh.vertices);
+            indices.extend_from_slice(&mesh.indices);
+        }
+        if self.draws.is_empty(){return;}
+        self.vertex_buffers[frame].write(instance, device, physical_device, &vertex_bytes(&vertices));
+        self.index_buffers[frame].write(instance, device, physical_device, &indices.iter().flat_map(|index| index.to_ne_bytes()).collect::<Vec<u8>>());
+    }
+    ///Records the draws written by prepare, inside the render pass.
+    pub fn record(&self , device : &Device , command_buffer : ash::vk::CommandBuffer , frame : usize , extent : ash::vk::Extent2D){
+        if self.draws.is_empty(){return;}
+        let viewport = ash::vk::Viewport{x : 0.0, y : 0.0, width : extent.width as f32, height : extent.height as f32, min_depth : 0.0, max_depth : 1.0};
+        let mut push_constants = vec!();
+        push_constants.extend_from_slice(&(extent.width as f32 / self.pixels_per_point).to_ne_bytes());
+        push_constants.extend_from_slice(&(extent.height as f32 / self.pixels_per_point).to_ne_bytes());
+        push_constants.extend_from_slice(&(self.linear_output as u32).to_ne_bytes());
+        unsafe{
+            device.cmd_bind_pipeline(command_buffer, ash::vk::PipelineBindPoint::GRAPHICS, self.pipeline);
+            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            device.cmd_push_constants(command_buffer, self.pipeline_layout, ash::vk::ShaderStageFlags::VERTEX | ash::vk::ShaderStageFlags::FRAGMENT, 0, &push_constants);
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffers[frame].buffer], &[0]);
+            device.cmd_bind_index_buffer(command_buffer, self.index_buffers[frame].buffer, 0, ash::vk::IndexType::UINT32);
+        }
+        for draw in self.draws.iter(){
+            unsafe{
+                device.cmd_set_scissor(command_buffer, 0, &[draw.scissor]);
+                device.cmd_bind_descriptor_sets(command_buffer, ash::vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout, 0, &[draw.set], &[]);
+                device.cmd_draw_indexed(command_buffer, draw.index_count, 1, draw.first_index, draw.vertex_offset, 0);
+            }
+        }
+    }
+    pub fn destroy(&mut self , device : &Device){
+        for texture in self.textures.drain(..){
+            destroy_texture(device, &texture);
+        }
+        for buffer in self.vertex_buffers.iter_mut().chain(self.index_buffers.iter_mut()){
+            buffer.destroy(device);
+        }
+        unsafe{device.destroy_sampler(self.sampler, None)};
+        unsafe{device.destroy_pipeline(self.pipeline, None)};
+        unsafe{device.destroy_pipeline_layout(self.pipeline_layout, None)};
+        self.descriptor_layout.destroy(device);
+    }
+}
+///Uploads the texels into a new sRGB image, egui colors are sRGB encoded so sampling returns linear values.
+fn upload_texture(instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , command_pool : &ash::vk::CommandPool , queue : ash::vk::Queue , texture : &mut OverlayTexture){
+    let format = ash::vk::Format::R8G8B8A8_SRGB;
+    let extent = ash::vk::Extent2D{width : texture.size[0] as u32, height : texture.size[1] as u32};
+    let (buffer,buffer_memory) = memory::create_buffer(instance, device, physical_device, texture.texels.len() as u64, ash::vk::BufferUsageFlags::TRANSFER_SRC, ash::vk::MemoryPropertyFlags::HOST_VISIBLE | ash::vk::MemoryPropertyFlags::HOST_COHERENT);
+    memory::write_memory(device, &buffer_memory, &texture.texels);
+    let (image,image_memory) = memory::create_image(instance, device, physical_device, &extent, 1, format, ash::vk::ImageUsageFlags::TRANSFER_DST | ash::vk::ImageUsageFlags::SAMPLED);
+    commands::submit_once(device, command_pool, queue, |command_buffer|{
+        commands::record_upload(device, command_buffer, image, Some(buffer), &extent);
+        commands::record_mipmaps(device, command_buffer, image, &extent, 1);
+    });
+    unsafe{device.destroy_buffer(buffer, None)};
+    unsafe{device.free_memory(buffer_memory, None)};
+    texture.image = image;
+    texture.memory = image_memory;
+    texture.view = texture::create_image_view(device, image, format, 1);
+}
+fn destroy_texture(device : &Device , texture : &OverlayTexture){
+    if texture.image != ash::vk::Image::null(){
+        unsafe{device.destroy_image_view(texture.view, None)};
+        unsafe{device.destroy_image(texture.image, None)};
+        unsafe{device.free_memory(texture.memory, None)};
+    }
+}
+///A bilinear sampler clamping to the edge, egui texture options are not distinguished.
+fn create_sampler(device : &Device) -> ash::vk::Sampler{
+    let sampler_create_info = ash::vk::SamplerCreateInfo{
+        s_type : ash::vk::StructureType::SAMPLER_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::SamplerCreateFlags::empty(),
+        mag_filter : ash::vk::Filter::LINEAR,
+        min_filter : ash::vk::Filter::LINEAR,
+        mipmap_mode : ash::vk::SamplerMipmapMode::NEAREST,
+        address_mode_u : ash::vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        address_mode_v : ash::vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        address_mode_w : ash::vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        mip_lod_bias : 0.0,
+        anisotropy_enable : ash::vk::FALSE,
+        max_anisotropy : 1.0,
+        compare_enable : ash::vk::FALSE,
+        compare_op : ash::vk::CompareOp::ALWAYS,
+        min_lod : 0.0,
+        max_lod : 0.0,
+        border_color : ash::vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+        unnormalized_coordinates : ash::vk::FALSE,
+    };
+    return unsafe{device.create_sampler(&sampler_create_info, None)}.expect("Failed to create overlay sampler.");
+}
+fn create_shader_module(device : &Device , code : &[u8]) -> ash::vk::ShaderModule{
+    let code = ash::util::read_spv(&mut std::io::Cursor::new(code)).expect("Failed to read overlay shader.");
+    let shader_module_create_info = ash::vk::ShaderModuleCreateInfo{
+        s_type : ash::vk::StructureType::SHADER_MODULE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::ShaderModuleCreateFlags::empty(),
+        code_size : code.len() * 4,
+        p_code : code.as_ptr(),
+    };
+    return unsafe{device.create_shader_module(&shader_module_create_info, None)}.expect("Failed to create overlay shader module.");
+}
+fn create_pipeline_layout(device : &Device , descriptor_set_layout : ash::vk::DescriptorSetLayout) -> ash::vk::PipelineLayout{
+    let set_layouts = [descriptor_set_layout];
+    let push_constant_ranges = [ash::vk::PushConstantRange{
+        stage_flags : ash::vk::ShaderStageFlags::VERTEX | ash::vk::ShaderStageFlags::FRAGMENT,
+        offset : 0,
+        size : PUSH_CONSTANTS_SIZE,
+    }];
+    let pipeline_layout_create_info = ash::vk::PipelineLayoutCreateInfo{
+        s_type : ash::vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::PipelineLayoutCreateFlags::empty(),
+        set_layout_count : set_layouts.len() as u32,
+        p_set_layouts : set_layouts.as_ptr(),
+        push_constant_range_count : push_constant_ranges.len() as u32,
+        p_push_constant_ranges : push_constant_ranges.as_ptr(),
+    };
+    return unsafe{device.create_pipeline_layout(&pipeline_layout_create_info, None)}.expect("Failed to create overlay pipeline layout.");
+}
+///Triangles without culling or depth, blended with premultiplied alpha, viewport and scissor are set while recording.
+fn create_pipeline(device : &Device , render_pass : ash::vk::RenderPass , pipeline_layout : ash::vk::PipelineLayout) -> ash::vk::Pipeline{
+    let vertex_module = create_shader_module(device, VERTEX_SHADER);
+    let fragment_module = create_shader_module(device, FRAGMENT_SHADER);
+    let entry_point = std::ffi::CString::new("main").unwrap();
+    let stages = [
+        ash::vk::PipelineShaderStageCreateInfo{
+            s_type : ash::vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : ash::vk::PipelineShaderStageCreateFlags::empty(),
+            stage : ash::vk::ShaderStageFlags::VERTEX,
+            module : vertex_module,
+            p_name : entry_point.as_ptr(),
+            p_specialization_info : std::ptr::null(),
+        },
+        ash::vk::PipelineShaderStageCreateInfo{
+            s_type : ash::vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : ash::vk::PipelineShaderStageCreateFlags::empty(),
+            stage : ash::vk::ShaderStageFlags::FRAGMENT,
+            module : fragment_module,
+            p_name : entry_point.as_ptr(),
+            p_specialization_info : std::ptr::null(),
+        },
+    ];
+    let bindings = [ash::vk::VertexInputBindingDescription{binding : 0, stride : VERTEX_SIZE as u32, input_rate : ash::vk::VertexInputRate::VERTEX}];
+    let attributes = [
+        ash::vk::VertexInputAttributeDescription{location : 0, binding : 0, format : ash::vk::Format::R32G32_SFLOAT, offset : 0},
+        ash::vk::VertexInputAttributeDescription{location : 1, binding : 0, format : ash::vk::Format::R32G32_SFLOAT, offset : 8},
+        ash::vk::VertexInputAttributeDescription{location : 2, binding : 0, format : ash::vk::Format::R8G8B8A8_UNORM, offset : 16},
+    ];
+    let vertex_input_state = ash::vk::PipelineVertexInputStateCreateInfo{
+        s_type : ash::vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::PipelineVertexInputStateCreateFlags::empty(),
+        vertex_binding_description_count : bindings.len() as u32,
+        p_vertex_binding_descriptions : bindings.as_ptr(),
+        vertex_attribute_description_count : attributes.len() as u32,
+        p_vertex_attribute_descriptions : attributes.as_ptr(),
+    };
+    let input_assembly_state = ash::vk::PipelineInputAssemblyStateCreateInfo{
+        s_type : ash::vk::StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::PipelineInputAssemblyStateCreateFlags::empty(),
+        topology : ash::vk::PrimitiveTopology::TRIANGLE_LIST,
+        primitive_restart_enable : ash::vk::FALSE,
+    };
+    let viewport_state = ash::vk::PipelineViewportStateCreateInfo{
+        s_type : ash::vk::StructureType::PIPELINE_VIEWPORT_STATE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::PipelineViewportStateCreateFlags::empty(),
+        viewport_count : 1,
+        p_viewports : std::ptr::null(),
+        scissor_count : 1,
+        p_scissors : std::ptr::null(),
+    };
+    let rasterization_state = ash::vk::PipelineRasterizationStateCreateInfo{
+        s_type : ash::vk::StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::PipelineRasterizationStateCreateFlags::empty(),
+        depth_clamp_enable : ash::vk::FALSE,
+        rasterizer_discard_enable : ash::vk::FALSE,
+        polygon_mode : ash::vk::PolygonMode::FILL,
+        cull_mode : ash::vk::CullModeFlags::NONE,
+        front_face : ash::vk::FrontFace::COUNTER_CLOCKWISE,
+        depth_bias_enable : ash::vk::FALSE,
+        depth_bias_constant_factor : 0.0,
+        depth_bias_clamp : 0.0,
+        depth_bias_slope_factor : 0.0,
+        line_width : 1.0,
+    };
+    let multisample_state = ash::vk::PipelineMultisampleStateCreateInfo{
+        s_type : ash::vk::StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::PipelineMultisampleStateCreateFlags::empty(),
+        rasterization_samples : ash::vk::SampleCountFlags::TYPE_1,
+        sample_shading_enable : ash::vk::FALSE,
+        min_sample_shading : 0.0,
+        p_sample_mask : std::ptr::null(),
+        alpha_to_coverage_enable : ash::vk::FALSE,
+        alpha_to_one_enable : ash::vk::FALSE,
+    };
+    let blend_attachments = [ash::vk::PipelineColorBlendAttachmentState{
+        blend_enable : ash::vk::TRUE,
+        src_color_blend_factor : ash::vk::BlendFactor::ONE,
+        dst_color_blend_factor : ash::vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+        color_blend_op : ash::vk::BlendOp::ADD,
+        src_alpha_blend_factor : ash::vk::BlendFactor::ONE_MINUS_DST_ALPHA,
+        dst_alpha_blend_factor : ash::vk::BlendFactor::ONE,
+        alpha_blend_op : ash::vk::BlendOp::ADD,
+        color_write_mask : ash::vk::ColorComponentFlags::R | ash::vk::ColorComponentFlags::G | ash::vk::ColorComponentFlags::B | ash::vk::ColorComponentFlags::A,
+    }];
+    let color_blend_state = ash::vk::PipelineColorBlendStateCreateInfo{
+        s_type : ash::vk::StructureType::PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::PipelineColorBlendStateCreateFlags::empty(),
+        logic_op_enable : ash::vk::FALSE,
+        logic_op : ash::vk::LogicOp::COPY,
+        attachment_count : blend_attachments.len() as u32,
+        p_attachments : blend_attachments.as_ptr(),
+        blend_constants : [0.0;4],
+    };
+    let dynamic_states = [ash::vk::DynamicState::VIEWPORT,ash::vk::DynamicState::SCISSOR];
+    let dynamic_state = ash::vk::PipelineDynamicStateCreateInfo{
+        s_type : ash::vk::StructureType::PIPELINE_DYNAMIC_STATE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::PipelineDynamicStateCreateFlags::empty(),
+        dynamic_state_count : dynamic_states.len() as u32,
+        p_dynamic_states : dynamic_states.as_ptr(),
+    };
+    let pipeline_create_info = ash::vk::GraphicsPipelineCreateInfo{
+        s_type : ash::vk::StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::PipelineCreateFlags::empty(),
+        stage_count : stages.len() as u32,
+        p_stages : stages.as_ptr(),
+        p_vertex_input_state : &vertex_input_state,
+        p_input_assembly_state : &input_assembly_state,
+        p_tessellation_state : std::ptr::null(),
+        p_viewport_state : &viewport_state,
+        p_rasterization_state : &rasterization_state,
+        p_multisample_state : &multisample_state,
+        p_depth_stencil_state : std::ptr::null(),
+        p_color_blend_state : &color_blend_state,
+        p_dynamic_state : &dynamic_state,
+        layout : pipeline_layout,
+        render_pass,
+        subpass : 0,
+        base_pipeline_handle : ash::vk::Pipeline::null(),
+        base_pipeline_index : -1,
+    };
+    let pipeline = unsafe{device.create_graphics_pipelines(ash::vk::PipelineCache::null(), &[pipeline_create_info], None)}.map_err(|(_,error)| error).expect("Failed to create overlay pipeline.")[0];
+    unsafe{device.destroy_shader_module(vertex_module, None)};
+    unsafe{device.destroy_shader_module(fragment_module, None)};
+    return pipeline;
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn vertices(){
+        let vertex = egui::epaint::Vertex{pos : egui::pos2(1.0, 2.0), uv : egui::pos2(0.25, 0.5), color : egui::Color32::from_rgba_premultiplied(10, 20, 30, 40)};
+        let bytes = vertex_bytes(&[vertex,vertex]);
+        assert_eq!(bytes.len(), 2 * VERTEX_SIZE);
+        assert_eq!(&bytes[0..4], &1.0f32.to_ne_bytes());
+        assert_eq!(&bytes[12..16], &0.5f32.to_ne_bytes());
+        assert_eq!(&bytes[16..20], &[10,20,30,40]);
+        //egui lays out its vertices the same way.
+        assert_eq!(std::mem::size_of::<egui::epaint::Vertex>(), VERTEX_SIZE);
+    }
+    #[test]
+    fn scissors(){
+        let extent = ash::vk::Extent2D{width : 200, height : 100};
+        let rect = |x0,y0,x1,y1| egui::Rect::from_min_max(egui::pos2(x0, y0), egui::pos2(x1, y1));
+        assert_eq!(scissor(rect(10.0, 5.0, 20.0, 15.0), 2.0, extent), Some(ash::vk::Rect2D{offset : ash::vk::Offset2D{x : 20, y : 10}, extent : ash::vk::Extent2D{width : 20, height : 20}}));
+        //egui clips to an infinite rectangle when nothing clips a shape.
+        assert_eq!(scissor(egui::Rect::EVERYTHING, 1.0, extent), Some(ash::vk::Rect2D{offset : ash::vk::Offset2D{x : 0, y : 0}, extent}));
+        assert_eq!(scissor(rect(300.0, 0.0, 400.0, 50.0), 1.0, extent), None);
+    }
+    #[test]
+    fn texture_patches(){
+        let mut texels = vec!(0u8;4 * 3 * 4);
+        patch_texels(&mut texels, 4, [1,1], 2, &[1;2 * 2 * 4]);
+        let patched : Vec<bool> = texels.chunks(4).map(|texel| texel[0] == 1).collect();
+        assert_eq!(patched, [false,false,false,false,false,true,true,false,false,true,true,false]);
+        assert!(is_linear_output(ash::vk::Format::B8G8R8A8_SRGB));
+        assert!(is_linear_output(ash::vk::Format::R16G16B16A16_SFLOAT));
+        assert!(!is_linear_output(ash::vk::Format::B8G8R8A8_UNORM));
+    }
+}
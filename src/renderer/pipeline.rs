@@ -0,0 +1,55 @@
+use ash::Device;
+use ash::version::DeviceV1_0;
+
+///A layout with the sets in order and push constants of the given size, none when it is 0.
+///The name of the pass goes into the error messages.
+pub fn create_compute_pipeline_layout(device : &Device , set_layouts : &[ash::vk::DescriptorSetLayout] , push_constants_size : u32 , name : &str) -> ash::vk::PipelineLayout{
+    let push_constant_ranges : Vec<ash::vk::PushConstantRange> = Some(push_constants_size).filter(|&size| size > 0).map(|size| ash::vk::PushConstantRange{
+        stage_flags : ash::vk::ShaderStageFlags::COMPUTE,
+        offset : 0,
+        size,
+    }).into_iter().collect();
+    let pipeline_layout_create_info = ash::vk::PipelineLayoutCreateInfo{
+        s_type : ash::vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::PipelineLayoutCreateFlags::empty(),
+        set_layout_count : set_layouts.len() as u32,
+        p_set_layouts : set_layouts.as_ptr(),
+        push_constant_range_count : push_constant_ranges.len() as u32,
+        p_push_constant_ranges : push_constant_ranges.as_ptr(),
+    };
+    return unsafe{device.create_pipeline_layout(&pipeline_layout_create_info, None)}.unwrap_or_else(|error| panic!("Failed to create {} pipeline layout : {}.",name,error));
+}
+///The pipeline of a compute shader, the code is a spir-v module with a main entry point.
+pub fn create_compute_pipeline(device : &Device , pipeline_layout : ash::vk::PipelineLayout , shader : &[u8] , name : &str) -> ash::vk::Pipeline{
+    let code = ash::util::read_spv(&mut std::io::Cursor::new(shader)).unwrap_or_else(|error| panic!("Failed to read {} shader : {}.",name,error));
+    let shader_module_create_info = ash::vk::ShaderModuleCreateInfo{
+        s_type : ash::vk::StructureType::SHADER_MODULE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::ShaderModuleCreateFlags::empty(),
+        code_size : code.len() * 4,
+        p_code : code.as_ptr(),
+    };
+    let module = unsafe{device.create_shader_module(&shader_module_create_info, None)}.unwrap_or_else(|error| panic!("Failed to create {} shader module : {}.",name,error));
+    let entry_point = std::ffi::CString::new("main").unwrap();
+    let pipeline_create_info = ash::vk::ComputePipelineCreateInfo{
+        s_type : ash::vk::StructureType::COMPUTE_PIPELINE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::PipelineCreateFlags::empty(),
+        stage : ash::vk::PipelineShaderStageCreateInfo{
+            s_type : ash::vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : ash::vk::PipelineShaderStageCreateFlags::empty(),
+            stage : ash::vk::ShaderStageFlags::COMPUTE,
+            module,
+            p_name : entry_point.as_ptr(),
+            p_specialization_info : std::ptr::null(),
+        },
+        layout : pipeline_layout,
+        base_pipeline_handle : ash::vk::Pipeline::null(),
+        base_pipeline_index : -1,
+    };
+    let pipeline = unsafe{device.create_compute_pipelines(ash::vk::PipelineCache::null(), &[pipeline_create_info], None)}.map_err(|(_,error)| error).unwrap_or_else(|error| panic!("Failed to create {} pipeline : {}.",name,error))[0];
+    unsafe{device.destroy_shader_module(module, None)};
+    return pipeline;
+}
@@ -0,0 +1,113 @@
+use super::portability;
+use super::portability::InstancePortability;
+
+///The architecture and operating system the binary was built for, such as aarch64-macos.
+pub fn target() -> String{
+    return format!("{}-{}",std::env::consts::ARCH,std::env::consts::OS);
+}
+///The platform specific paths compiled into the renderer, the surfaces come from ash-window.
+///Portability enumeration is not listed, every target opts in at runtime when the loader offers it, see runtime_code_paths.
+pub fn code_paths() -> Vec<&'static str>{
+    let mut paths = vec!();
+    if cfg!(any(target_os = "linux",target_os = "dragonfly",target_os = "freebsd",target_os = "netbsd",target_os = "openbsd")){
+        paths.push("xlib, xcb and wayland surfaces");
+    }
+    if cfg!(target_os = "windows"){
+        paths.push("win32 surfaces");
+    }
+    if cfg!(any(target_os = "macos",target_os = "ios")){
+        paths.push("metal surfaces through MoltenVK");
+    }
+    if cfg!(target_os = "android"){
+        paths.push("android surfaces");
+    }
+    if paths.is_empty(){
+        paths.push("no window surfaces");
+    }
+    return paths;
+}
+
+///The paths the loader enables at runtime, MoltenVK on macos only shows up through portability enumeration.
+pub fn runtime_code_paths(portability : &InstancePortability) -> Vec<&'static str>{
+    if portability.flags.contains(portability::ENUMERATE_PORTABILITY){
+        return vec!("portability enumeration for implementations such as MoltenVK");
+    }
+    return vec!("native drivers only");
+}
+
+//Smoke tests for the target, they replace the old TargetTest.sh.
+//The vulkan tests return early on machines without a loader.
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::device;
+    use super::super::instance;
+
+    use ash::version::InstanceV1_0;
+
+    use std::ffi::CStr;
+
+    fn name(bytes : &'static [u8]) -> &'static CStr{
+        return CStr::from_bytes_with_nul(bytes).unwrap();
+    }
+    fn load_entry() -> Option<ash::Entry>{
+        match instance::load_entry(){
+            Ok(entry) => return Some(entry),
+            Err(error) => {
+                println!("Skipped on {}, {}",target(),error);
+                return None;
+            }
+        }
+    }
+    #[test]
+    fn runtime_portability(){
+        let molten_vk = [name(b"VK_KHR_surface\0"),name(b"VK_EXT_metal_surface\0"),portability::enumeration_extension_name(),ash::vk::KhrGetPhysicalDeviceProperties2Fn::name()];
+        assert_eq!(runtime_code_paths(&portability::instance_portability(&molten_vk, &[])), vec!("portability enumeration for implementations such as MoltenVK"));
+        let native = [name(b"VK_KHR_surface\0"),name(b"VK_KHR_xcb_surface\0")];
+        assert_eq!(runtime_code_paths(&portability::instance_portability(&native, &[])), vec!("native drivers only"));
+    }
+    #[test]
+    fn instance_creation(){
+        let entry = match load_entry(){
+            Some(entry) => entry,
+            None => return,
+        };
+        let portability = instance::get_instance_portability(&entry, &[]);
+        println!("{} compiles in : {}, enables at runtime : {}.",target(),code_paths().join(", "),runtime_code_paths(&portability).join(", "));
+        //MoltenVK is the vulkan driver on apple silicon and the loader only lists it through portability enumeration.
+        if cfg!(all(target_arch = "aarch64",target_os = "macos")){
+            assert_eq!(portability.flags, portability::ENUMERATE_PORTABILITY);
+        }
+        let instance = instance::create_instance(&entry, &super::super::AppInfo::default(), &[], false);
+        unsafe{instance.destroy_instance(None)};
+    }
+    #[test]
+    fn headless_device_selection(){
+        let entry = match load_entry(){
+            Some(entry) => entry,
+            None => return,
+        };
+        let instance = instance::create_instance(&entry, &super::super::AppInfo::default(), &[], false);
+        let physical_devices = unsafe{instance.enumerate_physical_devices()}.expect("Failed to enumerate devices.");
+        let selected = match device::choose_headless_device(&instance, None){
+            Some(choice) => choice.device,
+            None => {
+                println!("Skipped on {}, no device has a graphics queue.",target());
+                unsafe{instance.destroy_instance(None)};
+                return;
+            }
+        };
+        assert!(physical_devices.contains(&selected));
+        let portability_subset = device::get_device_features(&instance, &selected).portability_subset;
+        println!("{} selects {}, portability subset : {}.",target(),device::get_device_name(&instance, &selected),portability_subset);
+        if cfg!(all(target_arch = "aarch64",target_os = "macos")){
+            assert!(portability_subset);
+        }
+        //Every device with graphics has the queues the renderer asks for.
+        device::get_graphics_queue_family(&instance, &selected);
+        device::get_transfer_queue_family(&instance, &selected);
+        device::get_compute_queue_family(&instance, &selected);
+        assert_eq!(device::choose_headless_device(&instance, Some("0")).map(|choice| choice.device), physical_devices.first().copied());
+        unsafe{instance.destroy_instance(None)};
+    }
+}
@@ -0,0 +1,76 @@
+//Support for portability implementations such as MoltenVK, they only expose their devices when the instance opts in and every device has to enable VK_KHR_portability_subset.
+//ash 0.31 predates these extensions so the names and flag are declared here.
+//The choices only look at extension names so they can be tested against mocked lists.
+
+use ash::vk;
+
+use std::ffi::CStr;
+
+///Lets the loader enumerate portability implementations, VK_INSTANCE_CREATE_ENUMERATE_PORTABILITY_BIT_KHR.
+pub const ENUMERATE_PORTABILITY : vk::InstanceCreateFlags = vk::InstanceCreateFlags::from_raw(0x1);
+
+pub fn enumeration_extension_name() -> &'static CStr{
+    return CStr::from_bytes_with_nul(b"VK_KHR_portability_enumeration\0").unwrap();
+}
+pub fn subset_extension_name() -> &'static CStr{
+    return CStr::from_bytes_with_nul(b"VK_KHR_portability_subset\0").unwrap();
+}
+///The extensions and flags the instance adds to the requested ones.
+#[derive(Clone,Debug,PartialEq)]
+pub struct InstancePortability{
+    pub extensions : Vec<&'static CStr>,
+    pub flags : vk::InstanceCreateFlags,
+}
+///Opts in to portability implementations when the loader offers the enumeration extension, the subset extension needs physical device properties 2 on vulkan 1.0 loaders.
+pub fn instance_portability(available : &[&CStr] , requested : &[&CStr]) -> InstancePortability{
+    let mut portability = InstancePortability{extensions : vec!(), flags : vk::InstanceCreateFlags::empty()};
+    if !available.contains(&enumeration_extension_name()){
+        return portability;
+    }
+    for &extension in [enumeration_extension_name(),vk::KhrGetPhysicalDeviceProperties2Fn::name()].iter(){
+        if available.contains(&extension) && !requested.contains(&extension){
+            portability.extensions.push(extension);
+        }
+    }
+    portability.flags = ENUMERATE_PORTABILITY;
+    return portability;
+}
+///A device that advertises the subset extension is a portability implementation and must enable it.
+pub fn is_portability_subset(device_extensions : &[&CStr]) -> bool{
+    return device_extensions.contains(&subset_extension_name());
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn name(bytes : &'static [u8]) -> &'static CStr{
+        return CStr::from_bytes_with_nul(bytes).unwrap();
+    }
+    fn surface() -> &'static CStr{
+        return name(b"VK_KHR_surface\0");
+    }
+    #[test]
+    fn molten_vk_instance(){
+        let available = [surface(),name(b"VK_EXT_metal_surface\0"),enumeration_extension_name(),vk::KhrGetPhysicalDeviceProperties2Fn::name()];
+        let portability = instance_portability(&available, &[surface()]);
+        assert_eq!(portability.extensions, vec!(enumeration_extension_name(),vk::KhrGetPhysicalDeviceProperties2Fn::name()));
+        assert_eq!(portability.flags, ENUMERATE_PORTABILITY);
+        //Extensions that were already requested are not added twice.
+        let portability = instance_portability(&available, &[surface(),vk::KhrGetPhysicalDeviceProperties2Fn::name()]);
+        assert_eq!(portability.extensions, vec!(enumeration_extension_name()));
+    }
+    #[test]
+    fn native_instance(){
+        let available = [surface(),name(b"VK_KHR_xcb_surface\0"),vk::KhrGetPhysicalDeviceProperties2Fn::name()];
+        let portability = instance_portability(&available, &[surface()]);
+        assert!(portability.extensions.is_empty());
+        assert_eq!(portability.flags, vk::InstanceCreateFlags::empty());
+    }
+    #[test]
+    fn subset_devices(){
+        let swapchain = name(b"VK_KHR_swapchain\0");
+        assert!(is_portability_subset(&[swapchain,subset_extension_name()]));
+        assert!(!is_portability_subset(&[swapchain]));
+    }
+}
@@ -4,10 +4,10 @@ use ash::version::DeviceV1_0;
 pub fn create_render_pass(device : &Device , format : ash::vk::Format) -> ash::vk::RenderPass{
     let color_attachment = ash::vk::AttachmentDescription{
         flags : ash::vk::AttachmentDescriptionFlags::empty(),
-        format : format,
-        initial_layout : ash::vk::ImageLayout::UNDEFINED,
+        format,
+        initial_layout : ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
         final_layout : ash::vk::ImageLayout::PRESENT_SRC_KHR,
-        load_op : ash::vk::AttachmentLoadOp::CLEAR,
+        load_op : ash::vk::AttachmentLoadOp::LOAD,
         store_op : ash::vk::AttachmentStoreOp::STORE,
         stencil_load_op : ash::vk::AttachmentLoadOp::DONT_CARE,
         stencil_store_op : ash::vk::AttachmentStoreOp::DONT_CARE,
@@ -34,10 +34,10 @@ pub fn create_render_pass(device : &Device , format : ash::vk::Format) -> ash::v
         dependency_flags : ash::vk::DependencyFlags::empty(),
         src_subpass : ash::vk::SUBPASS_EXTERNAL,
         dst_subpass : 0,
-        src_stage_mask : ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        src_stage_mask : ash::vk::PipelineStageFlags::TRANSFER,
         dst_stage_mask : ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-        src_access_mask : ash::vk::AccessFlags::empty(),
-        dst_access_mask : ash::vk:: AccessFlags::COLOR_ATTACHMENT_WRITE,
+        src_access_mask : ash::vk::AccessFlags::TRANSFER_WRITE,
+        dst_access_mask : ash::vk::AccessFlags::COLOR_ATTACHMENT_READ | ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
     };
     let dependencies = [subpass_dependency];
     let render_pass_create_info = ash::vk::RenderPassCreateInfo{
@@ -0,0 +1,193 @@
+use ash::Device;
+use ash::Instance;
+use ash::version::DeviceV1_0;
+
+use super::memory;
+use super::commands;
+use super::pipeline;
+use super::descriptors::DescriptorLayout;
+use super::descriptors::DescriptorAllocator;
+use super::descriptors::LayoutBinding;
+use super::super::sampler;
+use super::super::sampler::SamplerKind;
+use super::super::sampler::SamplerSettings;
+
+//Rebuild the shader with glslc shaders/sampler.comp -o shaders/sampler.comp.spv, it includes shaders/sampler.glsl.
+const COMPUTE_SHADER : &[u8] = include_bytes!("../../shaders/sampler.comp.spv");
+///Sampler kind, seed, samples per pixel, width, height, first sample, samples and dimensions.
+const PUSH_CONSTANTS_SIZE : u32 = 32;
+///The workgroup size of the shader.
+const GROUP_SIZE : u32 = 64;
+///One vec2 per dimension.
+const DIMENSION_SIZE : u64 = 8;
+
+///The value of the SAMPLER_ defines in shaders/sampler.glsl.
+pub fn shader_kind(kind : SamplerKind) -> u32{
+    match kind{
+        SamplerKind::Random => 0,
+        SamplerKind::Sobol => 1,
+        SamplerKind::BlueNoise => 2,
+        SamplerKind::Stratified => 3,
+    }
+}
+///The push constants of one dispatch as the shader lays them out.
+pub fn push_constants(settings : &SamplerSettings , width : u32 , height : u32 , first_sample : u32 , samples : u32 , dimensions : u32) -> [u8;PUSH_CONSTANTS_SIZE as usize]{
+    let mut bytes = [0u8;PUSH_CONSTANTS_SIZE as usize];
+    let values = [shader_kind(settings.kind),settings.seed,settings.samples_per_pixel,width,height,first_sample,samples,dimensions];
+    for (chunk,value) in bytes.chunks_exact_mut(4).zip(values){
+        chunk.copy_from_slice(&value.to_ne_bytes());
+    }
+    return bytes;
+}
+///Runs the gpu port of the samplers in shaders/sampler.glsl, so the numbers the gpu draws can be checked against src/sampler.
+///The buffers are host visible, the pass waits for its fence before returning.
+pub struct SequencePass{
+    descriptor_layout : DescriptorLayout,
+    pipeline_layout : ash::vk::PipelineLayout,
+    pipeline : ash::vk::Pipeline,
+    queue : ash::vk::Queue,
+    command_pool : ash::vk::CommandPool,
+    command_buffer : ash::vk::CommandBuffer,
+    fence : ash::vk::Fence,
+    set : ash::vk::DescriptorSet,
+    ///The blue noise tile followed by the sequences.
+    buffers : Vec<ash::vk::Buffer>,
+    memories : Vec<ash::vk::DeviceMemory>,
+    size : u64,
+}
+impl SequencePass{
+    pub fn new(instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , queue_family : u32 , descriptor_allocator : &mut DescriptorAllocator) -> Self{
+        let descriptor_layout = DescriptorLayout::new(device, &[
+            LayoutBinding::new(0, ash::vk::DescriptorType::STORAGE_BUFFER, ash::vk::ShaderStageFlags::COMPUTE),
+            LayoutBinding::new(1, ash::vk::DescriptorType::STORAGE_BUFFER, ash::vk::ShaderStageFlags::COMPUTE),
+        ]);
+        let pipeline_layout = pipeline::create_compute_pipeline_layout(device, &[descriptor_layout.layout], PUSH_CONSTANTS_SIZE, "sampler");
+        let pipeline = pipeline::create_compute_pipeline(device, pipeline_layout, COMPUTE_SHADER, "sampler");
+        let command_pool = commands::create_command_pool(device, queue_family);
+        let command_buffer = commands::create_command_buffers(device, &command_pool, 1)[0];
+        let fence_create_info = ash::vk::FenceCreateInfo{
+            s_type : ash::vk::StructureType::FENCE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : ash::vk::FenceCreateFlags::empty(),
+        };
+        let fence = unsafe{device.create_fence(&fence_create_info, None)}.expect("Failed to create sampler fence.");
+        let set = descriptor_allocator.allocate(device, &descriptor_layout);
+        let tile : Vec<u8> = sampler::blue_noise_tile().iter().flat_map(|value| value.to_ne_bytes()).collect();
+        let (tile_buffer,tile_memory) = memory::create_buffer(instance, device, physical_device, tile.len() as u64, ash::vk::BufferUsageFlags::STORAGE_BUFFER, ash::vk::MemoryPropertyFlags::HOST_VISIBLE | ash::vk::MemoryPropertyFlags::HOST_COHERENT);
+        memory::write_memory(device, &tile_memory, &tile);
+        descriptor_layout.write(device, set, 0, 0, &[ash::vk::DescriptorBufferInfo{buffer : tile_buffer, offset : 0, range : tile.len() as u64}]);
+        return Self{
+            descriptor_layout,
+            pipeline_layout,
+            pipeline,
+            queue : unsafe{device.get_device_queue(queue_family, 0)},
+            command_pool,
+            command_buffer,
+            fence,
+            set,
+            buffers : vec!(tile_buffer),
+            memories : vec!(tile_memory),
+            size : 0,
+        };
+    }
+    ///The first dimensions of the samples of every pixel in a width by height block at the origin, ordered by pixel, then sample, then dimension.
+    pub fn sample(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , settings : &SamplerSettings , width : u32 , height : u32 , first_sample : u32 , samples : u32 , dimensions : u32) -> Vec<[f32;2]>{
+        let invocations = width * height * samples;
+        let size = invocations as u64 * dimensions as u64 * DIMENSION_SIZE;
+        if size == 0{return vec!();}
+        self.update_buffer(instance, device, physical_device, size);
+        let command_buffer = self.command_buffer;
+        commands::begin_command_buffer(device, command_buffer);
+        unsafe{
+            device.cmd_bind_pipeline(command_buffer, ash::vk::PipelineBindPoint::COMPUTE, self.pipeline);
+            device.cmd_bind_descriptor_sets(command_buffer, ash::vk::PipelineBindPoint::COMPUTE, self.pipeline_layout, 0, &[self.set], &[]);
+            device.cmd_push_constants(command_buffer, self.pipeline_layout, ash::vk::ShaderStageFlags::COMPUTE, 0, &push_constants(settings, width, height, first_sample, samples, dimensions));
+            device.cmd_dispatch(command_buffer, invocations.div_ceil(GROUP_SIZE), 1, 1);
+        }
+        commands::record_host_barrier(device, command_buffer);
+        unsafe{device.end_command_buffer(command_buffer)}.expect("Failed to record sampler command buffer.");
+        commands::submit_and_wait(device, self.queue, command_buffer, self.fence);
+        let bytes = memory::read_memory(device, &self.memories[1], size as usize);
+        return bytes.chunks_exact(DIMENSION_SIZE as usize).map(|pair| {
+            let value = |offset : usize| f32::from_ne_bytes([pair[offset],pair[offset + 1],pair[offset + 2],pair[offset + 3]]);
+            [value(0),value(4)]
+        }).collect();
+    }
+    ///Makes sure the sequence buffer has the size and points the set at it, the pass is idle between dispatches.
+    fn update_buffer(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , size : u64){
+        if self.size == size{return;}
+        self.destroy_sequences(device);
+        let (buffer,memory) = memory::create_buffer(instance, device, physical_device, size, ash::vk::BufferUsageFlags::STORAGE_BUFFER, ash::vk::MemoryPropertyFlags::HOST_VISIBLE | ash::vk::MemoryPropertyFlags::HOST_COHERENT);
+        self.buffers.push(buffer);
+        self.memories.push(memory);
+        self.descriptor_layout.write(device, self.set, 1, 0, &[ash::vk::DescriptorBufferInfo{buffer, offset : 0, range : size}]);
+        self.size = size;
+    }
+    fn destroy_sequences(&mut self , device : &Device){
+        while self.buffers.len() > 1{
+            unsafe{device.destroy_buffer(self.buffers.pop().unwrap(), None)};
+            unsafe{device.free_memory(self.memories.pop().unwrap(), None)};
+        }
+        self.size = 0;
+    }
+    ///The set is freed with the pools of the descriptor allocator.
+    pub fn destroy(&mut self , device : &Device){
+        self.destroy_sequences(device);
+        for (&buffer,&memory) in self.buffers.iter().zip(self.memories.iter()){
+            unsafe{device.destroy_buffer(buffer, None)};
+            unsafe{device.free_memory(memory, None)};
+        }
+        unsafe{device.destroy_fence(self.fence, None)};
+        unsafe{device.destroy_command_pool(self.command_pool, None)};
+        unsafe{device.destroy_pipeline(self.pipeline, None)};
+        unsafe{device.destroy_pipeline_layout(self.pipeline_layout, None)};
+        self.descriptor_layout.destroy(device);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::ComputeDevice;
+    use super::super::RendererSettings;
+    use super::super::AppInfo;
+
+    #[test]
+    fn push_constant_layout(){
+        let settings = SamplerSettings{kind : SamplerKind::Stratified, seed : 9, samples_per_pixel : 16};
+        let bytes = push_constants(&settings, 4, 3, 8, 2, 5);
+        let words : Vec<u32> = bytes.chunks_exact(4).map(|word| u32::from_ne_bytes([word[0],word[1],word[2],word[3]])).collect();
+        assert_eq!(words, vec!(3,9,16,4,3,8,2,5));
+        assert_eq!(sampler::SAMPLERS.map(shader_kind), [0,1,2,3]);
+    }
+    #[test]
+    fn shader_is_spirv(){
+        let code = ash::util::read_spv(&mut std::io::Cursor::new(COMPUTE_SHADER)).expect("Failed to read sampler shader.");
+        assert_eq!(code[0], 0x0723_0203);
+    }
+    //Returns early on machines without a vulkan device.
+    #[test]
+    fn matches_cpu_sequences(){
+        let settings = RendererSettings{present_mode : ash::vk::PresentModeKHR::FIFO, hdr : false, validation : false, device : None, app_info : AppInfo::default()};
+        let mut compute = match ComputeDevice::new(&settings){
+            Ok(compute) => compute,
+            Err(error) => {
+                println!("Skipped, {}",error);
+                return;
+            }
+        };
+        let (width,height,first_sample,samples,dimensions) = (9,7,5,6,8);
+        for &kind in sampler::SAMPLERS.iter(){
+            let settings = SamplerSettings{kind, seed : 11, samples_per_pixel : 4};
+            let cpu = sampler::Sampler::new(settings);
+            let mut expected = vec!();
+            for pixel in 0..width * height{
+                for index in first_sample..first_sample + samples{
+                    let mut stream = cpu.pixel(pixel % width, pixel / width, index);
+                    expected.extend((0..dimensions).map(|_| stream.next_2d()));
+                }
+            }
+            assert_eq!(compute.sample_sequences(&settings, width, height, first_sample, samples, dimensions), expected, "{} differs on the gpu",kind.name());
+        }
+    }
+}
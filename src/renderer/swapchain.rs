@@ -4,29 +4,141 @@ use ash::vk::SwapchainKHR;
 use ash::Instance;
 use ash::Device;
 use ash::version::DeviceV1_0;
+use ash::version::InstanceV1_0;
+
+///The order in which present modes are cycled through by the hotkey.
+pub const PRESENT_MODES : [ash::vk::PresentModeKHR;4] = [ash::vk::PresentModeKHR::IMMEDIATE,ash::vk::PresentModeKHR::MAILBOX,ash::vk::PresentModeKHR::FIFO,ash::vk::PresentModeKHR::FIFO_RELAXED];
 
 pub fn get_swapchain_extent(surface_loader : &ash::extensions::khr::Surface , surface : &ash::vk::SurfaceKHR , physical_device : &ash::vk::PhysicalDevice) -> ash::vk::Extent2D{
     let capabilites = unsafe{surface_loader.get_physical_device_surface_capabilities(*physical_device, *surface)}.expect("Failed to acquire surface capabilities.");
-    if capabilites.current_extent.width != std::u32::MAX{return capabilites.current_extent}else{return capabilites.max_image_extent};
+    return choose_extent(&capabilites);
+}
+///The surface size, a current extent of u32::MAX means the swapchain decides and the largest allowed size is used.
+pub fn choose_extent(capabilites : &ash::vk::SurfaceCapabilitiesKHR) -> ash::vk::Extent2D{
+    if capabilites.current_extent.width != u32::MAX{return capabilites.current_extent}else{return capabilites.max_image_extent};
+}
+///Surface formats in order of preference for sdr output, the srgb formats let the hardware do the gamma encoding.
+const SDR_SURFACE_FORMATS : [(ash::vk::Format,ash::vk::ColorSpaceKHR);5] = [
+    (ash::vk::Format::B8G8R8A8_SRGB,ash::vk::ColorSpaceKHR::SRGB_NONLINEAR),
+    (ash::vk::Format::R8G8B8A8_SRGB,ash::vk::ColorSpaceKHR::SRGB_NONLINEAR),
+    (ash::vk::Format::A8B8G8R8_SRGB_PACK32,ash::vk::ColorSpaceKHR::SRGB_NONLINEAR),
+    (ash::vk::Format::B8G8R8A8_UNORM,ash::vk::ColorSpaceKHR::SRGB_NONLINEAR),
+    (ash::vk::Format::R8G8B8A8_UNORM,ash::vk::ColorSpaceKHR::SRGB_NONLINEAR),
+];
+///Surface formats in order of preference for hdr output, these require VK_EXT_swapchain_colorspace.
+const HDR_SURFACE_FORMATS : [(ash::vk::Format,ash::vk::ColorSpaceKHR);4] = [
+    (ash::vk::Format::A2B10G10R10_UNORM_PACK32,ash::vk::ColorSpaceKHR::HDR10_ST2084_EXT),
+    (ash::vk::Format::A2R10G10B10_UNORM_PACK32,ash::vk::ColorSpaceKHR::HDR10_ST2084_EXT),
+    (ash::vk::Format::R16G16B16A16_SFLOAT,ash::vk::ColorSpaceKHR::HDR10_ST2084_EXT),
+    (ash::vk::Format::R16G16B16A16_SFLOAT,ash::vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT),
+];
+///Bytes per pixel of the swapchain formats the post process pass can encode frames in, None for every other format.
+pub fn format_size(format : ash::vk::Format) -> Option<usize>{
+    match format{
+        ash::vk::Format::B8G8R8A8_SRGB | ash::vk::Format::B8G8R8A8_UNORM => Some(4),
+        ash::vk::Format::R8G8B8A8_SRGB | ash::vk::Format::R8G8B8A8_UNORM => Some(4),
+        ash::vk::Format::A8B8G8R8_SRGB_PACK32 | ash::vk::Format::A8B8G8R8_UNORM_PACK32 => Some(4),
+        ash::vk::Format::A2B10G10R10_UNORM_PACK32 | ash::vk::Format::A2R10G10B10_UNORM_PACK32 => Some(4),
+        ash::vk::Format::R16G16B16A16_SFLOAT => Some(8),
+        _ => None,
+    }
 }
-pub fn get_swapchain_surface_format(surface_loader : &ash::extensions::khr::Surface , surface : &ash::vk::SurfaceKHR , physical_device : &ash::vk::PhysicalDevice) -> ash::vk::SurfaceFormatKHR{
+///The surface format to present in, an error when the surface offers no format frames can be encoded in.
+pub fn get_swapchain_surface_format(surface_loader : &ash::extensions::khr::Surface , surface : &ash::vk::SurfaceKHR , physical_device : &ash::vk::PhysicalDevice , hdr : bool) -> Result<ash::vk::SurfaceFormatKHR,String>{
     let formats = unsafe{surface_loader.get_physical_device_surface_formats(*physical_device, *surface)}.expect("Failed to acquire supported formats.");
-    for &format in formats.iter(){
-        if format.format == ash::vk::Format::B8G8R8A8_SRGB && format.color_space == ash::vk::ColorSpaceKHR::SRGB_NONLINEAR{ return format; }
+    return choose_surface_format(&formats, hdr).ok_or(format!("The surface offers no format frames can be encoded in, it supports {:?}.",formats));
+}
+///Picks the best ranked surface format, hdr formats are only considered when hdr is requested.
+///Unranked formats are only used when the post process pass can encode them, None when the surface offers none of those.
+pub fn choose_surface_format(formats : &[ash::vk::SurfaceFormatKHR] , hdr : bool) -> Option<ash::vk::SurfaceFormatKHR>{
+    let preferred = ash::vk::SurfaceFormatKHR{format : SDR_SURFACE_FORMATS[0].0, color_space : SDR_SURFACE_FORMATS[0].1};
+    //A single undefined entry means the surface has no preference.
+    if formats.is_empty() || (formats.len() == 1 && formats[0].format == ash::vk::Format::UNDEFINED){
+        return Some(preferred);
+    }
+    let ranked = if hdr{HDR_SURFACE_FORMATS.iter().chain(SDR_SURFACE_FORMATS.iter()).collect::<Vec<_>>()}else{SDR_SURFACE_FORMATS.iter().collect::<Vec<_>>()};
+    for &&(format,color_space) in ranked.iter(){
+        if let Some(&surface_format) = formats.iter().find(|f| f.format == format && f.color_space == color_space){
+            return Some(surface_format);
+        }
+    }
+    return formats.iter().find(|f| f.color_space == ash::vk::ColorSpaceKHR::SRGB_NONLINEAR && format_size(f.format).is_some()).copied();
+}
+pub fn is_hdr_color_space(color_space : ash::vk::ColorSpaceKHR) -> bool{
+    return color_space == ash::vk::ColorSpaceKHR::HDR10_ST2084_EXT || color_space == ash::vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT;
+}
+///Returns the modes to try for a requested present mode, best match first.
+///FIFO is always last since it is the only mode the spec guarantees, only immediate requests fall back to modes that may tear.
+pub fn present_mode_fallbacks(requested : ash::vk::PresentModeKHR) -> &'static [ash::vk::PresentModeKHR]{
+    match requested{
+        ash::vk::PresentModeKHR::IMMEDIATE => &[ash::vk::PresentModeKHR::IMMEDIATE,ash::vk::PresentModeKHR::MAILBOX,ash::vk::PresentModeKHR::FIFO_RELAXED,ash::vk::PresentModeKHR::FIFO],
+        ash::vk::PresentModeKHR::MAILBOX => &[ash::vk::PresentModeKHR::MAILBOX,ash::vk::PresentModeKHR::FIFO],
+        ash::vk::PresentModeKHR::FIFO_RELAXED => &[ash::vk::PresentModeKHR::FIFO_RELAXED,ash::vk::PresentModeKHR::FIFO],
+        _ => &[ash::vk::PresentModeKHR::FIFO],
+    }
+}
+pub fn choose_present_mode(requested : ash::vk::PresentModeKHR , available : &[ash::vk::PresentModeKHR]) -> ash::vk::PresentModeKHR{
+    for mode in present_mode_fallbacks(requested).iter(){
+        if available.contains(mode){return *mode;}
     }
-    return formats[1];
+    return ash::vk::PresentModeKHR::FIFO;
 }
-pub fn get_swapchain_present_mode(surface_loader : &ash::extensions::khr::Surface , surface : &ash::vk::SurfaceKHR , physical_device : &ash::vk::PhysicalDevice) -> ash::vk::PresentModeKHR{
+pub fn get_swapchain_present_mode(surface_loader : &ash::extensions::khr::Surface , surface : &ash::vk::SurfaceKHR , physical_device : &ash::vk::PhysicalDevice , requested : ash::vk::PresentModeKHR) -> ash::vk::PresentModeKHR{
     let present_modes = unsafe{surface_loader.get_physical_device_surface_present_modes(*physical_device, *surface)}.expect("Failed to acquire surface present modes.");
-    if present_modes.contains(&ash::vk::PresentModeKHR::MAILBOX){return ash::vk::PresentModeKHR::MAILBOX}else{return ash::vk::PresentModeKHR::FIFO};
+    return choose_present_mode(requested, &present_modes);
+}
+pub fn next_present_mode(mode : ash::vk::PresentModeKHR) -> ash::vk::PresentModeKHR{
+    let index = PRESENT_MODES.iter().position(|&m| m == mode).unwrap_or(0);
+    return PRESENT_MODES[(index + 1) % PRESENT_MODES.len()];
+}
+pub fn present_mode_name(mode : ash::vk::PresentModeKHR) -> &'static str{
+    match mode{
+        ash::vk::PresentModeKHR::IMMEDIATE => "Immediate",
+        ash::vk::PresentModeKHR::MAILBOX => "Mailbox",
+        ash::vk::PresentModeKHR::FIFO => "FIFO",
+        ash::vk::PresentModeKHR::FIFO_RELAXED => "FIFO relaxed",
+        _ => "Unknown",
+    }
+}
+pub fn parse_present_mode(name : &str) -> Option<ash::vk::PresentModeKHR>{
+    match name.to_lowercase().replace(&['-','_',' '][..], "").as_str(){
+        "immediate" | "off" => Some(ash::vk::PresentModeKHR::IMMEDIATE),
+        "mailbox" => Some(ash::vk::PresentModeKHR::MAILBOX),
+        "fifo" | "vsync" | "on" => Some(ash::vk::PresentModeKHR::FIFO),
+        "fiforelaxed" | "relaxed" | "adaptive" => Some(ash::vk::PresentModeKHR::FIFO_RELAXED),
+        _ => None,
+    }
+}
+///Scaling blits from an image in the swapchain format onto a swapchain image, linear filtering is used when supported.
+pub fn get_scaling_filter(instance : &Instance , physical_device : &ash::vk::PhysicalDevice , format : ash::vk::Format) -> Option<ash::vk::Filter>{
+    let features = unsafe{instance.get_physical_device_format_properties(*physical_device, format)}.optimal_tiling_features;
+    if !features.contains(ash::vk::FormatFeatureFlags::BLIT_SRC | ash::vk::FormatFeatureFlags::BLIT_DST){return None;}
+    if features.contains(ash::vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR){return Some(ash::vk::Filter::LINEAR);}
+    return Some(ash::vk::Filter::NEAREST);
 }
 pub fn get_min_image_count(surface_loader : &ash::extensions::khr::Surface , surface : &ash::vk::SurfaceKHR , physical_device : &ash::vk::PhysicalDevice) -> u32{
     let capabilites = unsafe{surface_loader.get_physical_device_surface_capabilities(*physical_device, *surface)}.expect("Failed to acquire surface capabilities.");
-    if capabilites.max_image_count >= capabilites.min_image_count+1{return capabilites.min_image_count+1}else{return capabilites.max_image_count};
+    return choose_image_count(&capabilites);
+}
+///One image more than the minimum so the cpu does not wait on the driver, a max_image_count of 0 means there is no upper limit.
+pub fn choose_image_count(capabilites : &ash::vk::SurfaceCapabilitiesKHR) -> u32{
+    let count = capabilites.min_image_count + 1;
+    if capabilites.max_image_count == 0{return count;}
+    return count.min(capabilites.max_image_count);
+}
+///Frames are copied into the swapchain images, surfaces that only allow rendering into them can not be used.
+pub fn check_image_usage(capabilites : &ash::vk::SurfaceCapabilitiesKHR) -> Result<(),String>{
+    if !capabilites.supported_usage_flags.contains(ash::vk::ImageUsageFlags::TRANSFER_DST){
+        return Err(format!("The surface does not support copying frames into the swapchain, it supports {:?}.",capabilites.supported_usage_flags));
+    }
+    return Ok(());
 }
-pub fn create_swapchain(instance : &Instance , device : &Device , surface : &ash::vk::SurfaceKHR , present_mode : &ash::vk::PresentModeKHR , extent : &ash::vk::Extent2D , format : &ash::vk::SurfaceFormatKHR , min_image_count : u32 , graphics_queue_family : u32 , presentation_queue_family : u32 , surface_loader : &ash::extensions::khr::Surface , physical_device : &ash::vk::PhysicalDevice) -> (Swapchain,SwapchainKHR){
+///Creates the swapchain, an error when the surface does not allow copying frames into its images.
+pub fn create_swapchain(instance : &Instance , device : &Device , surface : &ash::vk::SurfaceKHR , present_mode : &ash::vk::PresentModeKHR , extent : &ash::vk::Extent2D , format : &ash::vk::SurfaceFormatKHR , min_image_count : u32 , graphics_queue_family : u32 , presentation_queue_family : u32 , surface_loader : &ash::extensions::khr::Surface , physical_device : &ash::vk::PhysicalDevice) -> Result<(Swapchain,SwapchainKHR),String>{
     let capabilites = unsafe{surface_loader.get_physical_device_surface_capabilities(*physical_device, *surface)}.expect("Failed to acquire surface capabilities.");
+    check_image_usage(&capabilites)?;
     let swapchain_loader = Swapchain::new(instance , device);
+    let queue_family_indices = [graphics_queue_family,presentation_queue_family];
     let swapchain_create_info = ash::vk::SwapchainCreateInfoKHR{
         s_type : ash::vk::StructureType::SWAPCHAIN_CREATE_INFO_KHR,
         p_next : std::ptr::null(),
@@ -35,35 +147,35 @@ pub fn create_swapchain(instance : &Instance , device : &Device , surface : &ash
         old_swapchain : SwapchainKHR::null(),
         clipped : 1,
         image_array_layers : 1,
-        image_usage : ash::vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        image_usage : ash::vk::ImageUsageFlags::COLOR_ATTACHMENT | ash::vk::ImageUsageFlags::TRANSFER_DST,
         composite_alpha : ash::vk::CompositeAlphaFlagsKHR::OPAQUE,
         present_mode : *present_mode,
         image_extent : *extent,
         image_color_space : format.color_space,
         image_format : format.format,
-        min_image_count : min_image_count,
+        min_image_count,
         image_sharing_mode : if graphics_queue_family == presentation_queue_family {ash::vk::SharingMode::EXCLUSIVE}else{ash::vk::SharingMode::CONCURRENT},
         queue_family_index_count : if graphics_queue_family == presentation_queue_family {0}else{2},
-        p_queue_family_indices : if graphics_queue_family == presentation_queue_family{std::ptr::null()}else{[graphics_queue_family,presentation_queue_family].as_ptr()},
+        p_queue_family_indices : if graphics_queue_family == presentation_queue_family{std::ptr::null()}else{queue_family_indices.as_ptr()},
         pre_transform : capabilites.current_transform,
     };
     let swapchain = unsafe{swapchain_loader.create_swapchain(&swapchain_create_info, None)}.expect("Failed to create swapchain.");
-    return (swapchain_loader,swapchain);
+    return Ok((swapchain_loader,swapchain));
 }
 pub fn create_swapchain_images(swapchain_loader : &Swapchain , swapchain : &SwapchainKHR) -> Vec<ash::vk::Image>{
     return unsafe{swapchain_loader.get_swapchain_images(*swapchain)}.expect("Failed to acquire images from the swapchain.");
 }
-pub fn create_swapchain_image_views(images : &Vec<ash::vk::Image> , device : &Device , format : ash::vk::Format) -> Vec<ash::vk::ImageView>{
+pub fn create_swapchain_image_views(images : &[ash::vk::Image] , device : &Device , format : ash::vk::Format) -> Vec<ash::vk::ImageView>{
     let mut image_views = vec!();
     for &image in images.iter(){
         let image_view_create_info = ash::vk::ImageViewCreateInfo{
             s_type : ash::vk::StructureType::IMAGE_VIEW_CREATE_INFO,
             p_next : std::ptr::null(),
             flags : ash::vk::ImageViewCreateFlags::empty(),
-            image : image,
-            format : format,
+            image,
+            format,
             view_type : ash::vk::ImageViewType::TYPE_2D,
-            components : ash::vk::ComponentMapping{a:ash::vk::ComponentSwizzle::A,b:ash::vk::ComponentSwizzle::B,g:ash::vk::ComponentSwizzle::G,r:ash::vk::ComponentSwizzle::R},
+            components : ash::vk::ComponentMapping{a:ash::vk::ComponentSwizzle::IDENTITY,b:ash::vk::ComponentSwizzle::IDENTITY,g:ash::vk::ComponentSwizzle::IDENTITY,r:ash::vk::ComponentSwizzle::IDENTITY},
             subresource_range : ash::vk::ImageSubresourceRange{
                 aspect_mask : ash::vk::ImageAspectFlags::COLOR,
                 layer_count : 1,
@@ -75,4 +187,89 @@ pub fn create_swapchain_image_views(images : &Vec<ash::vk::Image> , device : &De
         image_views.push(unsafe{device.create_image_view(&image_view_create_info, None)}.expect("Failed to create image view for the swapchain."));
     }
     return image_views;
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use ash::vk::ColorSpaceKHR;
+    use ash::vk::Format;
+    use ash::vk::PresentModeKHR;
+
+    fn surface_format(format : Format , color_space : ColorSpaceKHR) -> ash::vk::SurfaceFormatKHR{
+        return ash::vk::SurfaceFormatKHR{format, color_space};
+    }
+    fn capabilities(min_image_count : u32 , max_image_count : u32) -> ash::vk::SurfaceCapabilitiesKHR{
+        return ash::vk::SurfaceCapabilitiesKHR{
+            min_image_count,
+            max_image_count,
+            current_extent : ash::vk::Extent2D{width : 1280, height : 720},
+            max_image_extent : ash::vk::Extent2D{width : 4096, height : 4096},
+            ..Default::default()
+        };
+    }
+    #[test]
+    fn image_count(){
+        assert_eq!(choose_image_count(&capabilities(2, 8)), 3);
+        assert_eq!(choose_image_count(&capabilities(3, 3)), 3);
+        //A max_image_count of 0 has no upper limit, it must not be taken as the count.
+        assert_eq!(choose_image_count(&capabilities(2, 0)), 3);
+        assert_eq!(choose_image_count(&capabilities(1, 0)), 2);
+    }
+    #[test]
+    fn extent(){
+        assert_eq!(choose_extent(&capabilities(2, 3)), ash::vk::Extent2D{width : 1280, height : 720});
+        let mut undefined = capabilities(2, 3);
+        undefined.current_extent = ash::vk::Extent2D{width : u32::MAX, height : u32::MAX};
+        assert_eq!(choose_extent(&undefined), ash::vk::Extent2D{width : 4096, height : 4096});
+    }
+    #[test]
+    fn image_usage(){
+        let mut usage = capabilities(2, 3);
+        usage.supported_usage_flags = ash::vk::ImageUsageFlags::COLOR_ATTACHMENT | ash::vk::ImageUsageFlags::TRANSFER_DST;
+        assert!(check_image_usage(&usage).is_ok());
+        //Surfaces that only allow rendering into the images are reported instead of aborting.
+        usage.supported_usage_flags = ash::vk::ImageUsageFlags::COLOR_ATTACHMENT;
+        assert!(check_image_usage(&usage).is_err());
+    }
+    #[test]
+    fn surface_formats(){
+        let desktop = [surface_format(Format::B8G8R8A8_UNORM, ColorSpaceKHR::SRGB_NONLINEAR),surface_format(Format::B8G8R8A8_SRGB, ColorSpaceKHR::SRGB_NONLINEAR),surface_format(Format::A2B10G10R10_UNORM_PACK32, ColorSpaceKHR::HDR10_ST2084_EXT)];
+        let format = |formats : &[ash::vk::SurfaceFormatKHR] , hdr : bool| choose_surface_format(formats, hdr).map(|f| f.format);
+        assert_eq!(format(&desktop, false), Some(Format::B8G8R8A8_SRGB));
+        assert_eq!(choose_surface_format(&desktop, true).map(|f| f.color_space), Some(ColorSpaceKHR::HDR10_ST2084_EXT));
+        assert_eq!(format(&desktop[..2], true), Some(Format::B8G8R8A8_SRGB));
+        //A surface without a preference, and one with only unranked formats.
+        assert_eq!(format(&[surface_format(Format::UNDEFINED, ColorSpaceKHR::SRGB_NONLINEAR)], false), Some(Format::B8G8R8A8_SRGB));
+        assert_eq!(format(&[], false), Some(Format::B8G8R8A8_SRGB));
+        let unranked = [surface_format(Format::R5G6B5_UNORM_PACK16, ColorSpaceKHR::SRGB_NONLINEAR),surface_format(Format::A2R10G10B10_UNORM_PACK32, ColorSpaceKHR::SRGB_NONLINEAR)];
+        assert_eq!(format(&unranked, false), Some(Format::A2R10G10B10_UNORM_PACK32));
+        //Formats frames can not be encoded in are never chosen.
+        assert_eq!(format(&unranked[..1], false), None);
+        assert_eq!(format(&[surface_format(Format::B8G8R8A8_UNORM, ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT)], false), None);
+    }
+    #[test]
+    fn format_sizes(){
+        for &(format,_) in SDR_SURFACE_FORMATS.iter().chain(HDR_SURFACE_FORMATS.iter()){
+            assert!(format_size(format).is_some(), "{:?}", format);
+        }
+        assert_eq!(format_size(Format::R16G16B16A16_SFLOAT), Some(8));
+        assert_eq!(format_size(Format::A2B10G10R10_UNORM_PACK32), Some(4));
+        assert_eq!(format_size(Format::R5G6B5_UNORM_PACK16), None);
+        assert_eq!(format_size(Format::R32G32B32A32_SFLOAT), None);
+    }
+    #[test]
+    fn present_modes(){
+        let all = [PresentModeKHR::IMMEDIATE,PresentModeKHR::MAILBOX,PresentModeKHR::FIFO,PresentModeKHR::FIFO_RELAXED];
+        for &mode in all.iter(){
+            assert_eq!(choose_present_mode(mode, &all), mode);
+        }
+        //Mobile and MoltenVK style surfaces often only offer fifo.
+        assert_eq!(choose_present_mode(PresentModeKHR::MAILBOX, &[PresentModeKHR::FIFO]), PresentModeKHR::FIFO);
+        //Mailbox never tears, so it does not fall back to immediate.
+        assert_eq!(choose_present_mode(PresentModeKHR::MAILBOX, &[PresentModeKHR::FIFO,PresentModeKHR::IMMEDIATE,PresentModeKHR::FIFO_RELAXED]), PresentModeKHR::FIFO);
+        assert_eq!(choose_present_mode(PresentModeKHR::IMMEDIATE, &[PresentModeKHR::FIFO,PresentModeKHR::FIFO_RELAXED]), PresentModeKHR::FIFO_RELAXED);
+        assert_eq!(choose_present_mode(PresentModeKHR::FIFO_RELAXED, &[PresentModeKHR::MAILBOX]), PresentModeKHR::FIFO);
+        assert_eq!(choose_present_mode(PresentModeKHR::FIFO, &[]), PresentModeKHR::FIFO);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,27 @@
+use ash::Device;
+use ash::version::DeviceV1_0;
+
+pub fn create_semaphores(device : &Device , count : usize) -> Vec<ash::vk::Semaphore>{
+    let semaphore_create_info = ash::vk::SemaphoreCreateInfo{
+        s_type : ash::vk::StructureType::SEMAPHORE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::SemaphoreCreateFlags::empty(),
+    };
+    let mut semaphores = vec!();
+    for _ in 0..count{
+        semaphores.push(unsafe{device.create_semaphore(&semaphore_create_info, None)}.expect("Failed to create semaphore."));
+    }
+    return semaphores;
+}
+pub fn create_fences(device : &Device , count : usize) -> Vec<ash::vk::Fence>{
+    let fence_create_info = ash::vk::FenceCreateInfo{
+        s_type : ash::vk::StructureType::FENCE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::FenceCreateFlags::SIGNALED,
+    };
+    let mut fences = vec!();
+    for _ in 0..count{
+        fences.push(unsafe{device.create_fence(&fence_create_info, None)}.expect("Failed to create fence."));
+    }
+    return fences;
+}
@@ -0,0 +1,295 @@
+use std::sync::Arc;
+
+use ash::Device;
+use ash::Instance;
+use ash::version::DeviceV1_0;
+use ash::version::InstanceV1_0;
+
+use image::GenericImageView;
+
+use super::memory;
+use super::commands;
+use super::device::DeviceFeatures;
+use super::descriptors::DescriptorLayout;
+use super::descriptors::DescriptorAllocator;
+use super::descriptors::LayoutBinding;
+use super::super::material::Image;
+use super::super::material::Texture as MaterialTexture;
+
+///Upper bound on the size of the texture array when descriptor indexing is supported.
+const MAX_BINDLESS_TEXTURES : u32 = 4096;
+///Without descriptor indexing every element of the array has to be written and the shaders read it with constant indices, so it is kept small.
+///Every device supports at least 16 sampled images per stage, shaders/trace.comp declares the same size.
+const MAX_BOUND_TEXTURES : u32 = 16;
+///Anisotropic filtering beyond this is rarely visible.
+const MAX_ANISOTROPY : f32 = 16.0;
+///The bindings of the texture array set.
+const SAMPLER_BINDING : u32 = 0;
+const TEXTURES_BINDING : u32 = 1;
+
+///A sampled device image with a full mip chain when the format allows generating one.
+pub struct Texture{
+    image : ash::vk::Image,
+    memory : ash::vk::DeviceMemory,
+    view : ash::vk::ImageView,
+}
+///Picks the format for the image and returns its texels in that format.
+///Color textures are sRGB encoded and 8 bit formats let the sampler decode them, 16 bit color is reduced to 8 bits as there are no 16 bit sRGB formats.
+pub fn texture_format(image : &image::DynamicImage , srgb : bool) -> (ash::vk::Format,Vec<u8>){
+    let sixteen_bit = matches!(image.color(),image::ColorType::L16 | image::ColorType::La16 | image::ColorType::Rgb16 | image::ColorType::Rgba16);
+    if sixteen_bit && !srgb{
+        return (ash::vk::Format::R16G16B16A16_UNORM,image.to_rgba16().into_raw().into_iter().flat_map(u16::to_ne_bytes).collect());
+    }
+    let format = if srgb{ash::vk::Format::R8G8B8A8_SRGB}else{ash::vk::Format::R8G8B8A8_UNORM};
+    return (format,image.to_rgba8().into_raw());
+}
+///The amount of levels needed to halve the extent down to a single texel.
+pub fn mip_levels(extent : &ash::vk::Extent2D) -> u32{
+    return 32 - extent.width.max(extent.height).max(1).leading_zeros();
+}
+///Whether mips of the format can be generated with linear blits.
+fn supports_mipmaps(instance : &Instance , physical_device : &ash::vk::PhysicalDevice , format : ash::vk::Format) -> bool{
+    let features = unsafe{instance.get_physical_device_format_properties(*physical_device, format)}.optimal_tiling_features;
+    return features.contains(ash::vk::FormatFeatureFlags::BLIT_SRC | ash::vk::FormatFeatureFlags::BLIT_DST | ash::vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR);
+}
+///A color view of the first mip levels of a 2d image.
+pub fn create_image_view(device : &Device , image : ash::vk::Image , format : ash::vk::Format , mip_levels : u32) -> ash::vk::ImageView{
+    let image_view_create_info = ash::vk::ImageViewCreateInfo{
+        s_type : ash::vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::ImageViewCreateFlags::empty(),
+        image,
+        view_type : ash::vk::ImageViewType::TYPE_2D,
+        format,
+        components : ash::vk::ComponentMapping{
+            r : ash::vk::ComponentSwizzle::IDENTITY,
+            g : ash::vk::ComponentSwizzle::IDENTITY,
+            b : ash::vk::ComponentSwizzle::IDENTITY,
+            a : ash::vk::ComponentSwizzle::IDENTITY,
+        },
+        subresource_range : ash::vk::ImageSubresourceRange{
+            aspect_mask : ash::vk::ImageAspectFlags::COLOR,
+            base_mip_level : 0,
+            level_count : mip_levels,
+            base_array_layer : 0,
+            layer_count : 1,
+        },
+    };
+    return unsafe{device.create_image_view(&image_view_create_info, None)}.expect("Failed to create texture image view.");
+}
+///Uploads the image through a staging buffer and generates its mips on the queue, which must support graphics for the blits.
+///The image is shared by the queue families so the compute queue can sample it without an ownership transfer.
+pub fn create_texture(instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , command_pool : &ash::vk::CommandPool , queue : ash::vk::Queue , image : &image::DynamicImage , srgb : bool , queue_families : &[u32]) -> Texture{
+    let (format,data) = texture_format(image, srgb);
+    let extent = ash::vk::Extent2D{width : image.width(), height : image.height()};
+    let mip_levels = if supports_mipmaps(instance, physical_device, format){mip_levels(&extent)}else{1};
+    let (buffer,buffer_memory) = memory::create_buffer(instance, device, physical_device, data.len() as u64, ash::vk::BufferUsageFlags::TRANSFER_SRC, ash::vk::MemoryPropertyFlags::HOST_VISIBLE | ash::vk::MemoryPropertyFlags::HOST_COHERENT);
+    memory::write_memory(device, &buffer_memory, &data);
+    let usage = ash::vk::ImageUsageFlags::TRANSFER_SRC | ash::vk::ImageUsageFlags::TRANSFER_DST | ash::vk::ImageUsageFlags::SAMPLED;
+    let (texture_image,texture_memory) = memory::create_shared_image(instance, device, physical_device, &extent, mip_levels, format, usage, queue_families);
+    commands::submit_once(device, command_pool, queue, |command_buffer|{
+        commands::record_upload(device, command_buffer, texture_image, Some(buffer), &extent);
+        commands::record_mipmaps(device, command_buffer, texture_image, &extent, mip_levels);
+    });
+    unsafe{device.destroy_buffer(buffer, None)};
+    unsafe{device.free_memory(buffer_memory, None)};
+    return Texture{
+        image : texture_image,
+        memory : texture_memory,
+        view : create_image_view(device, texture_image, format, mip_levels),
+    };
+}
+///Uploads the linear rgb texels of an environment map as a 32 bit float image without mips, the tracer reads it texel by texel.
+///Only transfer commands are recorded, so the queue can be a transfer only one.
+pub fn create_environment_texture(instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , command_pool : &ash::vk::CommandPool , queue : ash::vk::Queue , width : u32 , height : u32 , texels : &[[f32;3]] , queue_families : &[u32]) -> Texture{
+    let format = ash::vk::Format::R32G32B32A32_SFLOAT;
+    let data : Vec<u8> = texels.iter().flat_map(|texel| [texel[0],texel[1],texel[2],1.0]).flat_map(f32::to_ne_bytes).collect();
+    let extent = ash::vk::Extent2D{width, height};
+    let (buffer,buffer_memory) = memory::create_buffer(instance, device, physical_device, data.len() as u64, ash::vk::BufferUsageFlags::TRANSFER_SRC, ash::vk::MemoryPropertyFlags::HOST_VISIBLE | ash::vk::MemoryPropertyFlags::HOST_COHERENT);
+    memory::write_memory(device, &buffer_memory, &data);
+    let usage = ash::vk::ImageUsageFlags::TRANSFER_DST | ash::vk::ImageUsageFlags::SAMPLED;
+    let (texture_image,texture_memory) = memory::create_shared_image(instance, device, physical_device, &extent, 1, format, usage, queue_families);
+    commands::submit_once(device, command_pool, queue, |command_buffer|{
+        commands::record_upload(device, command_buffer, texture_image, Some(buffer), &extent);
+        commands::record_shader_read(device, command_buffer, texture_image);
+    });
+    unsafe{device.destroy_buffer(buffer, None)};
+    unsafe{device.free_memory(buffer_memory, None)};
+    return Texture{
+        image : texture_image,
+        memory : texture_memory,
+        view : create_image_view(device, texture_image, format, 1),
+    };
+}
+///The anisotropy of samplers on a device with the given limit, None without anisotropic filtering.
+fn sampler_anisotropy(max_anisotropy : Option<f32>) -> Option<f32>{
+    return max_anisotropy.map(|max_anisotropy| max_anisotropy.min(MAX_ANISOTROPY));
+}
+///A trilinear repeating sampler, anisotropic when the device supports it.
+pub fn create_sampler(device : &Device , max_anisotropy : Option<f32>) -> ash::vk::Sampler{
+    let anisotropy = sampler_anisotropy(max_anisotropy);
+    let sampler_create_info = ash::vk::SamplerCreateInfo{
+        s_type : ash::vk::StructureType::SAMPLER_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ash::vk::SamplerCreateFlags::empty(),
+        mag_filter : ash::vk::Filter::LINEAR,
+        min_filter : ash::vk::Filter::LINEAR,
+        mipmap_mode : ash::vk::SamplerMipmapMode::LINEAR,
+        address_mode_u : ash::vk::SamplerAddressMode::REPEAT,
+        address_mode_v : ash::vk::SamplerAddressMode::REPEAT,
+        address_mode_w : ash::vk::SamplerAddressMode::REPEAT,
+        mip_lod_bias : 0.0,
+        anisotropy_enable : if anisotropy.is_some(){ash::vk::TRUE}else{ash::vk::FALSE},
+        max_anisotropy : anisotropy.unwrap_or(1.0),
+        compare_enable : ash::vk::FALSE,
+        compare_op : ash::vk::CompareOp::ALWAYS,
+        min_lod : 0.0,
+        max_lod : ash::vk::LOD_CLAMP_NONE,
+        border_color : ash::vk::BorderColor::FLOAT_OPAQUE_BLACK,
+        unnormalized_coordinates : ash::vk::FALSE,
+    };
+    return unsafe{device.create_sampler(&sampler_create_info, None)}.expect("Failed to create sampler.");
+}
+impl Texture{
+    pub fn destroy(&self , device : &Device){
+        unsafe{device.destroy_image_view(self.view, None)};
+        unsafe{device.destroy_image(self.image, None)};
+        unsafe{device.free_memory(self.memory, None)};
+    }
+}
+///The size of the texture array for the amount of sampled images a shader stage may use.
+fn array_capacity(max_per_stage_sampled_images : u32 , bindless : bool) -> u32{
+    if bindless{
+        return max_per_stage_sampled_images.min(MAX_BINDLESS_TEXTURES);
+    }
+    return MAX_BOUND_TEXTURES;
+}
+///Every material image in one descriptor array next to the sampler, materials refer to images by their index in it.
+///With descriptor indexing the array is bindless, it is partially bound and can be updated while in use.
+///Otherwise it is small and unused elements point to the first texture, a white texel.
+///The images are decoded again from their files so they keep their own format, the mips are blitted on the graphics queue.
+pub struct TextureArray{
+    layout : DescriptorLayout,
+    set : ash::vk::DescriptorSet,
+    sampler : ash::vk::Sampler,
+    textures : Vec<Texture>,
+    ///The material images of the textures after the white one, equal images are uploaded once.
+    images : Vec<MaterialTexture>,
+    capacity : u32,
+    bindless : bool,
+    queue_families : Vec<u32>,
+    queue : ash::vk::Queue,
+    command_pool : ash::vk::CommandPool,
+}
+impl TextureArray{
+    ///The textures are read by shaders on the queue family of the user.
+    pub fn new(instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , device_features : &DeviceFeatures , graphics_queue_family : u32 , user_queue_family : u32 , descriptor_allocator : &mut DescriptorAllocator) -> Self{
+        let bindless = device_features.descriptor_indexing;
+        let limit = unsafe{instance.get_physical_device_properties(*physical_device)}.limits.max_per_stage_descriptor_sampled_images;
+        let capacity = array_capacity(limit, bindless);
+        let stages = ash::vk::ShaderStageFlags::COMPUTE | ash::vk::ShaderStageFlags::FRAGMENT;
+        let layout = DescriptorLayout::new(device, &[
+            LayoutBinding::new(SAMPLER_BINDING, ash::vk::DescriptorType::SAMPLER, stages),
+            LayoutBinding::array(TEXTURES_BINDING, ash::vk::DescriptorType::SAMPLED_IMAGE, capacity, stages, bindless),
+        ]);
+        let set = descriptor_allocator.allocate(device, &layout);
+        let mut queue_families = vec!(graphics_queue_family,user_queue_family);
+        queue_families.dedup();
+        let mut array = Self{
+            layout,
+            set,
+            sampler : create_sampler(device, device_features.max_anisotropy),
+            textures : vec!(),
+            images : vec!(),
+            capacity,
+            bindless,
+            queue_families,
+            queue : unsafe{device.get_device_queue(graphics_queue_family, 0)},
+            command_pool : commands::create_command_pool(device, graphics_queue_family),
+        };
+        array.layout.write(device, array.set, SAMPLER_BINDING, 0, &[ash::vk::DescriptorImageInfo{sampler : array.sampler, image_view : ash::vk::ImageView::null(), image_layout : ash::vk::ImageLayout::UNDEFINED}]);
+        let white = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([255;4])));
+        let white = create_texture(instance, device, physical_device, &array.command_pool, array.queue, &white, false, &array.queue_families);
+        let elements = if bindless{1}else{capacity};
+        array.layout.write(device, array.set, TEXTURES_BINDING, 0, &vec!(image_info(&white);elements as usize));
+        array.textures.push(white);
+        return array;
+    }
+    ///The index of the image in the array, it is uploaded the first time it is asked for.
+    ///An error when the array is full or the file can not be decoded again.
+    ///Without descriptor indexing the set must not be in use by the gpu.
+    pub fn index(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , image : &Arc<Image>) -> Result<u32,String>{
+        let texture = MaterialTexture::Image(image.clone());
+        if let Some(index) = self.images.iter().position(|other| *other == texture){
+            return Ok(index as u32 + 1);
+        }
+        let index = self.textures.len() as u32;
+        if index >= self.capacity{
+            return Err(format!("The texture array is full at {} textures, {} is not uploaded.",self.capacity,image.path.display()));
+        }
+        let decoded = image::open(&image.path).map_err(|error| format!("Failed to load texture {} : {}.",image.path.display(),error))?;
+        let uploaded = create_texture(instance, device, physical_device, &self.command_pool, self.queue, &decoded, image.srgb, &self.queue_families);
+        self.layout.write(device, self.set, TEXTURES_BINDING, index, &[image_info(&uploaded)]);
+        self.textures.push(uploaded);
+        self.images.push(texture);
+        return Ok(index);
+    }
+    pub fn layout(&self) -> ash::vk::DescriptorSetLayout{
+        return self.layout.layout;
+    }
+    pub fn set(&self) -> ash::vk::DescriptorSet{
+        return self.set;
+    }
+    pub fn is_bindless(&self) -> bool{
+        return self.bindless;
+    }
+    ///The set is freed with the pools of the descriptor allocator.
+    pub fn destroy(&mut self , device : &Device){
+        for texture in self.textures.drain(..){
+            texture.destroy(device);
+        }
+        unsafe{device.destroy_command_pool(self.command_pool, None)};
+        unsafe{device.destroy_sampler(self.sampler, None)};
+        self.layout.destroy(device);
+    }
+}
+pub fn image_info(texture : &Texture) -> ash::vk::DescriptorImageInfo{
+    return ash::vk::DescriptorImageInfo{sampler : ash::vk::Sampler::null(), image_view : texture.view, image_layout : ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL};
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn formats(){
+        let rgb8 = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(2, 1, image::Rgb([255,128,0])));
+        let (format,data) = texture_format(&rgb8, true);
+        assert_eq!(format, ash::vk::Format::R8G8B8A8_SRGB);
+        assert_eq!(data, vec!(255,128,0,255,255,128,0,255));
+        assert_eq!(texture_format(&rgb8, false).0, ash::vk::Format::R8G8B8A8_UNORM);
+        //Linear 16 bit data such as roughness keeps its precision, 16 bit color is reduced to 8 bit sRGB.
+        let gray16 = image::DynamicImage::ImageLuma16(image::ImageBuffer::from_pixel(1, 1, image::Luma([65535u16])));
+        let (format,data) = texture_format(&gray16, false);
+        assert_eq!(format, ash::vk::Format::R16G16B16A16_UNORM);
+        assert_eq!(data.len(), 8);
+        assert_eq!(texture_format(&gray16, true), (ash::vk::Format::R8G8B8A8_SRGB,vec!(255;4)));
+    }
+    #[test]
+    fn mip_chain(){
+        let extent = |width,height| ash::vk::Extent2D{width, height};
+        assert_eq!(mip_levels(&extent(1,1)), 1);
+        assert_eq!(mip_levels(&extent(256,256)), 9);
+        assert_eq!(mip_levels(&extent(300,20)), 9);
+        assert_eq!(mip_levels(&extent(0,0)), 1);
+    }
+    #[test]
+    fn sampler_and_array_limits(){
+        assert_eq!(sampler_anisotropy(None), None);
+        assert_eq!(sampler_anisotropy(Some(4.0)), Some(4.0));
+        assert_eq!(sampler_anisotropy(Some(64.0)), Some(MAX_ANISOTROPY));
+        assert_eq!(array_capacity(1_000_000, true), MAX_BINDLESS_TEXTURES);
+        assert_eq!(array_capacity(128, true), 128);
+        assert_eq!(array_capacity(1_000_000, false), MAX_BOUND_TEXTURES);
+    }
+}
@@ -0,0 +1,112 @@
+use ash::Device;
+use ash::version::DeviceV1_0;
+
+///The most scopes a frame can record, each uses a begin and an end query.
+const MAX_SCOPES : u32 = 8;
+
+///A gpu pass measured with timestamps, relative to the first timestamp of its frame.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct GpuScope{
+    pub name : &'static str,
+    pub begin : std::time::Duration,
+    pub end : std::time::Duration,
+}
+///The gpu scopes of one frame, available once the frame has finished on the gpu.
+#[derive(Clone,Debug,PartialEq)]
+pub struct GpuFrame{
+    ///When the frame was submitted, gpu and cpu clocks are not related so the scopes are placed after it.
+    pub submitted : std::time::Instant,
+    pub scopes : Vec<GpuScope>,
+}
+///The span between two timestamps, the counters only hold valid_bits bits and may wrap around in between.
+pub fn timestamp_duration(begin : u64 , end : u64 , valid_bits : u32 , period : f32) -> std::time::Duration{
+    let mask = if valid_bits >= 64{u64::MAX}else{(1u64 << valid_bits) - 1};
+    let ticks = (end & mask).wrapping_sub(begin & mask) & mask;
+    return std::time::Duration::from_nanos((ticks as f64 * period as f64).round() as u64);
+}
+///Timestamp query pools for each frame in flight, results are read back when the frame's fence has been waited on.
+pub struct TimestampQueries{
+    pools : Vec<ash::vk::QueryPool>,
+    ///The scopes written into each pool and when it was submitted, empty when nothing is waiting to be read.
+    pending : Vec<(Vec<&'static str>,std::time::Instant)>,
+    ///Nanoseconds per tick.
+    period : f32,
+    valid_bits : u32,
+}
+impl TimestampQueries{
+    ///Returns None when the queue family does not support timestamps.
+    pub fn new(device : &Device , frames : usize , period : f32 , valid_bits : u32) -> Option<Self>{
+        if valid_bits == 0 || period <= 0.0{return None;}
+        let query_pool_create_info = ash::vk::QueryPoolCreateInfo{
+            s_type : ash::vk::StructureType::QUERY_POOL_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : ash::vk::QueryPoolCreateFlags::empty(),
+            query_type : ash::vk::QueryType::TIMESTAMP,
+            query_count : MAX_SCOPES * 2,
+            pipeline_statistics : ash::vk::QueryPipelineStatisticFlags::empty(),
+        };
+        let pools = (0..frames).map(|_| unsafe{device.create_query_pool(&query_pool_create_info, None)}.expect("Failed to create timestamp query pool.")).collect();
+        return Some(Self{pools, pending : vec!((vec!(),std::time::Instant::now());frames), period, valid_bits});
+    }
+    ///Reads the scopes of the frame, its fence must have been waited on.
+    pub fn read(&mut self , device : &Device , frame : usize) -> Option<GpuFrame>{
+        let (names,submitted) = std::mem::replace(&mut self.pending[frame], (vec!(),std::time::Instant::now()));
+        if names.is_empty(){return None;}
+        let mut timestamps = vec!(0u64;names.len() * 2);
+        unsafe{device.get_query_pool_results(self.pools[frame], 0, timestamps.len() as u32, &mut timestamps, ash::vk::QueryResultFlags::TYPE_64)}.ok()?;
+        let first = timestamps[0];
+        let scopes = names.iter().enumerate().map(|(index,&name)| GpuScope{
+            name,
+            begin : timestamp_duration(first, timestamps[index * 2], self.valid_bits, self.period),
+            end : timestamp_duration(first, timestamps[index * 2 + 1], self.valid_bits, self.period),
+        }).collect();
+        return Some(GpuFrame{submitted, scopes});
+    }
+    ///Resets the frame's queries, record this before any scope of the frame.
+    pub fn record_reset(&mut self , device : &Device , command_buffer : ash::vk::CommandBuffer , frame : usize){
+        unsafe{device.cmd_reset_query_pool(command_buffer, self.pools[frame], 0, MAX_SCOPES * 2)};
+        self.pending[frame] = (vec!(),std::time::Instant::now());
+    }
+    ///Marks the frame as submitted, its results are read the next time the frame comes around.
+    pub fn submitted(&mut self , frame : usize){
+        self.pending[frame].1 = std::time::Instant::now();
+    }
+    pub fn destroy(&mut self , device : &Device){
+        for &pool in self.pools.iter(){
+            unsafe{device.destroy_query_pool(pool, None)};
+        }
+        self.pools.clear();
+    }
+}
+///Measures the commands recorded by the closure, they are recorded without timestamps when not profiling or past MAX_SCOPES.
+pub fn record_scope<F : FnOnce()>(queries : Option<&mut TimestampQueries> , device : &Device , command_buffer : ash::vk::CommandBuffer , frame : usize , name : &'static str , record : F){
+    let queries = match queries{
+        Some(queries) if (queries.pending[frame].0.len() as u32) < MAX_SCOPES => queries,
+        _ => {
+            record();
+            return;
+        }
+    };
+    let index = queries.pending[frame].0.len() as u32;
+    unsafe{device.cmd_write_timestamp(command_buffer, ash::vk::PipelineStageFlags::TOP_OF_PIPE, queries.pools[frame], index * 2)};
+    record();
+    unsafe{device.cmd_write_timestamp(command_buffer, ash::vk::PipelineStageFlags::BOTTOM_OF_PIPE, queries.pools[frame], index * 2 + 1)};
+    queries.pending[frame].0.push(name);
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn durations(){
+        assert_eq!(timestamp_duration(100, 350, 64, 1.0), std::time::Duration::from_nanos(250));
+        //Tick lengths that are not whole nanoseconds, such as on some mobile gpus.
+        assert_eq!(timestamp_duration(0, 1000, 64, 52.08), std::time::Duration::from_nanos(52080));
+        //A 36 bit counter that wrapped between the two timestamps.
+        let max = (1u64 << 36) - 1;
+        assert_eq!(timestamp_duration(max - 9, 10, 36, 1.0), std::time::Duration::from_nanos(20));
+        //Bits above the valid ones are undefined and ignored.
+        assert_eq!(timestamp_duration(0xFF00_0000_0000_0010, 0x0000_0000_0000_0020, 48, 1.0), std::time::Duration::from_nanos(16));
+    }
+}
@@ -0,0 +1,790 @@
+use std::sync::Arc;
+
+use ash::Device;
+use ash::Instance;
+use ash::version::DeviceV1_0;
+
+use cgmath::Vector3;
+
+use super::memory;
+use super::commands;
+use super::pipeline;
+use super::sequence;
+use super::device::DeviceFeatures;
+use super::texture;
+use super::texture::TextureArray;
+use super::upload::FrameUpload;
+use super::descriptors::DescriptorLayout;
+use super::descriptors::DescriptorAllocator;
+use super::descriptors::LayoutBinding;
+use super::super::sampler;
+use super::super::sampler::SamplerSettings;
+use super::super::camera::Camera;
+use super::super::scene::Scene;
+use super::super::material::Material;
+use super::super::material::MaterialKind;
+use super::super::material::Texture;
+use super::super::material::Image;
+use super::super::light::Light;
+use super::super::light::Emitter;
+use super::super::light::LightSampler;
+use super::super::light::Distribution1D;
+use super::super::tracer::Aov;
+use super::super::tracer::Tile;
+use super::super::tracer::Tracer;
+use super::super::tracer::TileSamples;
+
+//Rebuild the shaders with glslc shaders/trace.comp -o shaders/trace.comp.spv and glslc shaders/trace_bindless.comp -o shaders/trace_bindless.comp.spv.
+//Both include shaders/trace.glsl, which includes shaders/sampler.glsl, shaders/bsdf.glsl and shaders/lights.glsl.
+const COMPUTE_SHADER : &[u8] = include_bytes!("../../shaders/trace.comp.spv");
+///The same tracer reading the material images from a bindless array.
+const BINDLESS_COMPUTE_SHADER : &[u8] = include_bytes!("../../shaders/trace_bindless.comp.spv");
+///The workgroup size of the shader in both dimensions.
+const GROUP_SIZE : u32 = 8;
+///Six vec4 and twelve uints in the std140 layout of the uniform buffer.
+const PARAMETERS_SIZE : usize = 144;
+///The center, radius, material and emitter of a sphere.
+const SPHERE_WORDS : usize = 8;
+///The kind, index of refraction and the six textures of a material.
+const MATERIAL_WORDS : usize = 8;
+///The kind, image and scale of a texture followed by two vec4.
+const TEXTURE_WORDS : usize = 12;
+///The kinds of textures in shaders/trace.glsl.
+const TEXTURE_CONSTANT : u32 = 0;
+const TEXTURE_CHECKER : u32 = 1;
+const TEXTURE_IMAGE : u32 = 2;
+///The kind and sphere of an emitter, then its position with the cdf of LightSampler, its direction with the inner cosine and its intensity with the outer cosine.
+const EMITTER_WORDS : usize = 16;
+///The kinds of emitters in shaders/lights.glsl.
+const EMITTER_POINT : u32 = 0;
+const EMITTER_SPOT : u32 = 1;
+const EMITTER_DIRECTIONAL : u32 = 2;
+const EMITTER_SPHERE : u32 = 3;
+const EMITTER_ENVIRONMENT : u32 = 4;
+///The vec4 per pixel with only the radiance, with the first hit and with the lighting split as well.
+const RADIANCE_OUTPUTS : u32 = 1;
+const FIRST_HIT_OUTPUTS : u32 = 4;
+const LIGHTING_OUTPUTS : u32 = 8;
+///The bindings of the first set of shaders/trace.glsl, the buffers of the pass are stored in this order.
+///The second set is the texture array.
+const PARAMETERS : usize = 0;
+const BLUE_NOISE : usize = 1;
+const SPHERES : usize = 2;
+const MATERIALS : usize = 3;
+const TEXTURES : usize = 4;
+const SAMPLE_INDICES : usize = 5;
+const OUTPUTS : usize = 6;
+const EMITTERS : usize = 7;
+const ENVIRONMENT_DISTRIBUTION : usize = 8;
+const BINDINGS : usize = 9;
+///The environment map follows the buffers in the first set.
+const ENVIRONMENT : u32 = 9;
+
+///The spheres, materials, textures and emitters of a scene as the storage buffers of shaders/trace.glsl lay them out.
+///Equal materials are stored once, texture 0 is a constant zero for the parameters a kind of material does not use.
+///Images refer to their element of the texture array, those without one are stored as their mean color.
+///Emitters are those of LightSampler in the same order, spheres refer to theirs plus one and zero when they do not emit.
+#[derive(Clone,Debug,PartialEq)]
+pub struct SceneData{
+    pub spheres : Vec<u32>,
+    pub materials : Vec<u32>,
+    pub textures : Vec<u32>,
+    ///Scene::material_ids, the index of the material of every sphere.
+    pub material_ids : Vec<u32>,
+    pub emitters : Vec<u32>,
+    ///The emitter for the sky or environment map, None when it is black.
+    pub environment_emitter : Option<u32>,
+    pub sky : Vector3<f32>,
+    ///The rotation in radians and the intensity of the environment map, None when the sky is used.
+    pub environment : Option<[f32;2]>,
+    ///The distribution of rows of the environment map followed by the distribution within every row, see distribution_words.
+    pub environment_distribution : Vec<u32>,
+}
+impl SceneData{
+    ///Asks image_index for the element of the texture array holding each image.
+    pub fn new<F : FnMut(&Arc<Image>) -> Option<u32>>(scene : &Scene , mut image_index : F) -> Self{
+        let material_ids = scene.material_ids();
+        let lights = LightSampler::new(scene);
+        let mut data = Self{
+            spheres : vec!(),
+            materials : vec!(),
+            textures : vec!(),
+            material_ids : material_ids.clone(),
+            emitters : vec!(),
+            environment_emitter : lights.emitters().iter().position(|&emitter| emitter == Emitter::Environment).map(|index| index as u32),
+            sky : scene.sky,
+            environment : scene.environment.as_ref().map(|environment| [environment.rotation,environment.intensity]),
+            environment_distribution : vec!(),
+        };
+        for (&emitter,&cdf) in lights.emitters().iter().zip(lights.cdf().iter()){
+            data.push_emitter(scene, emitter, cdf);
+        }
+        if let Some(environment) = &scene.environment{
+            data.environment_distribution = distribution_words(environment.marginal());
+            for conditional in environment.conditionals(){
+                data.environment_distribution.extend(distribution_words(conditional));
+            }
+        }
+        data.push_texture(&Texture::value(0.0), &mut image_index);
+        for (index,(sphere,&id)) in scene.spheres.iter().zip(material_ids.iter()).enumerate(){
+            let emitter = lights.emitters().iter().position(|&emitter| emitter == Emitter::Sphere(index)).map_or(0, |emitter| emitter as u32 + 1);
+            data.spheres.extend([sphere.center.x.to_bits(),sphere.center.y.to_bits(),sphere.center.z.to_bits(),sphere.radius.to_bits(),id,emitter,0,0]);
+            if id as usize == data.materials.len() / MATERIAL_WORDS{
+                data.push_material(&sphere.material, &mut image_index);
+            }
+        }
+        return data;
+    }
+    pub fn sphere_count(&self) -> u32{
+        return (self.spheres.len() / SPHERE_WORDS) as u32;
+    }
+    pub fn emitter_count(&self) -> u32{
+        return (self.emitters.len() / EMITTER_WORDS) as u32;
+    }
+    fn push_emitter(&mut self , scene : &Scene , emitter : Emitter , cdf : f32){
+        let zero = Vector3::new(0.0,0.0,0.0);
+        let (kind,sphere,position,direction,intensity,cos_inner,cos_outer) = match emitter{
+            Emitter::Light(index) => match scene.lights[index]{
+                Light::Point{position,intensity} => (EMITTER_POINT,0,position,zero,intensity,0.0,0.0),
+                Light::Spot{position,direction,intensity,cos_inner,cos_outer} => (EMITTER_SPOT,0,position,direction,intensity,cos_inner,cos_outer),
+                Light::Directional{direction,irradiance} => (EMITTER_DIRECTIONAL,0,zero,direction,irradiance,0.0,0.0),
+            },
+            Emitter::Sphere(index) => (EMITTER_SPHERE,index as u32,zero,zero,zero,0.0,0.0),
+            Emitter::Environment => (EMITTER_ENVIRONMENT,0,zero,zero,zero,0.0,0.0),
+        };
+        self.emitters.extend([kind,sphere,0,0]);
+        self.emitters.extend([position.x.to_bits(),position.y.to_bits(),position.z.to_bits(),cdf.to_bits()]);
+        self.emitters.extend([direction.x.to_bits(),direction.y.to_bits(),direction.z.to_bits(),cos_inner.to_bits()]);
+        self.emitters.extend([intensity.x.to_bits(),intensity.y.to_bits(),intensity.z.to_bits(),cos_outer.to_bits()]);
+    }
+    fn push_material<F : FnMut(&Arc<Image>) -> Option<u32>>(&mut self , material : &Material , image_index : &mut F){
+        //The kind is the BSDF_ define of shaders/bsdf.glsl, then the color, roughness, metallic, specular and transmission textures.
+        let (kind,ior,parameters) : (u32,f32,[Option<&Texture>;5]) = match &material.kind{
+            MaterialKind::Lambert{albedo} => (0,1.0,[Some(albedo),None,None,None,None]),
+            MaterialKind::Conductor{color,roughness} => (1,1.0,[Some(color),Some(roughness),None,None,None]),
+            MaterialKind::Dielectric{ior,roughness,tint} => (2,*ior,[Some(tint),Some(roughness),None,None,None]),
+            MaterialKind::ThinDielectric{ior,tint} => (3,*ior,[Some(tint),None,None,None,None]),
+            MaterialKind::Principled{base_color,metallic,roughness,specular,transmission,ior} => (5,*ior,[Some(base_color),Some(roughness),Some(metallic),Some(specular),Some(transmission)]),
+        };
+        let emission = self.push_texture(&material.emission, image_index);
+        let textures : Vec<u32> = parameters.iter().map(|texture| texture.map_or(0, |texture| self.push_texture(texture, image_index))).collect();
+        self.materials.extend([kind,ior.to_bits(),emission]);
+        self.materials.extend(textures);
+    }
+    fn push_texture<F : FnMut(&Arc<Image>) -> Option<u32>>(&mut self , texture : &Texture , image_index : &mut F) -> u32{
+        let index = (self.textures.len() / TEXTURE_WORDS) as u32;
+        let zero = Vector3::new(0.0,0.0,0.0);
+        let (kind,element,scale,even,odd) = match texture{
+            Texture::Constant(value) => (TEXTURE_CONSTANT,0,0.0,*value,*value),
+            Texture::Checker{even,odd,scale} => (TEXTURE_CHECKER,0,*scale,*even,*odd),
+            Texture::Image(image) => match image_index(image){
+                Some(element) => (TEXTURE_IMAGE,element,0.0,zero,zero),
+                None => {
+                    let sum = image.texels.iter().fold(zero, |sum,texel| sum + Vector3::from(*texel));
+                    let mean = sum / image.texels.len().max(1) as f32;
+                    (TEXTURE_CONSTANT,0,0.0,mean,mean)
+                }
+            }
+        };
+        self.textures.extend([kind,element,scale.to_bits(),0]);
+        self.textures.extend([even.x.to_bits(),even.y.to_bits(),even.z.to_bits(),0]);
+        self.textures.extend([odd.x.to_bits(),odd.y.to_bits(),odd.z.to_bits(),0]);
+        return index;
+    }
+}
+///A distribution as shaders/lights.glsl reads it, the amount of buckets and the integral, then the function and the cdf.
+pub fn distribution_words(distribution : &Distribution1D) -> Vec<u32>{
+    let mut words = vec!(distribution.function().len() as u32,distribution.integral().to_bits());
+    words.extend(distribution.function().iter().chain(distribution.cdf().iter()).map(|value| value.to_bits()));
+    return words;
+}
+///The vec4 the shader writes per pixel for the enabled aovs.
+pub fn outputs(aovs : &[Aov]) -> u32{
+    if aovs.iter().any(|aov| aov.is_radiance()){return LIGHTING_OUTPUTS;}
+    if !aovs.is_empty(){return FIRST_HIT_OUTPUTS;}
+    return RADIANCE_OUTPUTS;
+}
+///The uniform buffer of one pass as the shader lays it out.
+pub fn parameters(camera : &Camera , scene : &SceneData , width : u32 , height : u32 , max_bounces : u32 , settings : &SamplerSettings , outputs : u32) -> [u8;PARAMETERS_SIZE]{
+    let (position,forward,right,up) = (camera.position,camera.forward(),camera.right(),camera.up());
+    let tan_half_fov = (camera.fov.to_radians() * 0.5).tan();
+    let aspect = width as f32 / height.max(1) as f32;
+    let sky = scene.sky;
+    let [rotation,intensity] = scene.environment.unwrap_or([0.0,0.0]);
+    let floats = [
+        position.x,position.y,position.z,tan_half_fov,
+        forward.x,forward.y,forward.z,camera.aperture,
+        right.x,right.y,right.z,camera.focus_distance,
+        up.x,up.y,up.z,aspect,
+        sky.x,sky.y,sky.z,0.0,
+        rotation,intensity,if scene.environment.is_some(){1.0}else{0.0},0.0,
+    ];
+    let environment_emitter = scene.environment_emitter.map_or(0, |index| index + 1);
+    let words = floats.iter().map(|value| value.to_bits()).chain([width,height,scene.sphere_count(),max_bounces,sequence::shader_kind(settings.kind),settings.seed,settings.samples_per_pixel,outputs,scene.emitter_count(),environment_emitter,0,0]);
+    let mut bytes = [0u8;PARAMETERS_SIZE];
+    for (chunk,word) in bytes.chunks_exact_mut(4).zip(words){
+        chunk.copy_from_slice(&word.to_ne_bytes());
+    }
+    return bytes;
+}
+///Turns what the shader wrote into one sample for every pixel of the frame.
+pub fn frame_samples(values : &[[f32;4]] , width : u32 , height : u32 , aovs : &[Aov] , material_ids : &[u32]) -> TileSamples{
+    let stride = outputs(aovs) as usize;
+    let pixels = values.chunks_exact(stride);
+    let mut samples = TileSamples{
+        tile : Tile{x : 0, y : 0, width, height},
+        radiance : pixels.clone().map(|pixel| [pixel[0][0],pixel[0][1],pixel[0][2]]).collect(),
+        aovs : aovs.iter().map(|&aov| (aov,Vec::with_capacity((width * height) as usize))).collect(),
+    };
+    for pixel in pixels{
+        for (aov,values) in samples.aovs.iter_mut(){
+            if pixel[0][3] == 0.0{
+                values.push(None);
+                continue;
+            }
+            let xyz = |index : usize| [pixel[index][0],pixel[index][1],pixel[index][2]];
+            let sphere = pixel[2][3] as usize - 1;
+            values.push(Some(match aov{
+                Aov::Albedo => xyz(1),
+                Aov::Normal => xyz(2),
+                Aov::Position => xyz(3),
+                Aov::Depth => [pixel[1][3];3],
+                Aov::ObjectId => [pixel[2][3];3],
+                Aov::MaterialId => [(material_ids[sphere] + 1) as f32;3],
+                Aov::DiffuseDirect => xyz(4),
+                Aov::DiffuseIndirect => xyz(5),
+                Aov::SpecularDirect => xyz(6),
+                Aov::SpecularIndirect => xyz(7),
+            }));
+        }
+    }
+    return samples;
+}
+///A pass submitted with the buffers of a frame whose samples are not in the tracer yet.
+#[derive(Clone,Debug,PartialEq)]
+struct PendingPass{
+    ///Tracer::generation when the pass was submitted, the samples are dropped when it changed since.
+    generation : u64,
+    width : u32,
+    height : u32,
+    aovs : Vec<Aov>,
+    material_ids : Vec<u32>,
+}
+///The descriptor set, buffers and command buffer of one pass in flight.
+struct TraceFrame{
+    set : ash::vk::DescriptorSet,
+    command_buffer : ash::vk::CommandBuffer,
+    fence : ash::vk::Fence,
+    ///One buffer per binding, null until it is first needed.
+    buffers : Vec<ash::vk::Buffer>,
+    memories : Vec<ash::vk::DeviceMemory>,
+    sizes : Vec<u64>,
+    pending : Option<PendingPass>,
+}
+impl TraceFrame{
+    ///Copies the data into the buffer of a binding.
+    fn write_buffer(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , layout : &DescriptorLayout , binding : usize , data : &[u8]){
+        self.update_buffer(instance, device, physical_device, layout, binding, data.len() as u64);
+        memory::write_memory(device, &self.memories[binding], data);
+    }
+    ///Makes sure the buffer of a binding has the size and points the set at it, the frame has no pass in flight.
+    fn update_buffer(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , layout : &DescriptorLayout , binding : usize , size : u64){
+        if self.sizes[binding] == size{return;}
+        self.destroy_buffer(device, binding);
+        let usage = if binding == PARAMETERS{ash::vk::BufferUsageFlags::UNIFORM_BUFFER}else{ash::vk::BufferUsageFlags::STORAGE_BUFFER};
+        let (buffer,memory) = memory::create_buffer(instance, device, physical_device, size, usage, ash::vk::MemoryPropertyFlags::HOST_VISIBLE | ash::vk::MemoryPropertyFlags::HOST_COHERENT);
+        layout.write(device, self.set, binding as u32, 0, &[ash::vk::DescriptorBufferInfo{buffer, offset : 0, range : size}]);
+        self.buffers[binding] = buffer;
+        self.memories[binding] = memory;
+        self.sizes[binding] = size;
+    }
+    fn destroy_buffer(&mut self , device : &Device , binding : usize){
+        if self.sizes[binding] == 0{return;}
+        unsafe{device.destroy_buffer(self.buffers[binding], None)};
+        unsafe{device.free_memory(self.memories[binding], None)};
+        self.sizes[binding] = 0;
+    }
+    ///Waits for the pass in flight and returns it.
+    fn wait(&mut self , device : &Device) -> Option<PendingPass>{
+        let pending = self.pending.take()?;
+        commands::wait_for_fence(device, self.fence);
+        return Some(pending);
+    }
+}
+///The passes in flight whose samples the tracer will still take, a new pass continues the sample indices after them.
+fn pending_samples(frames : &[TraceFrame] , tracer : &Tracer) -> u32{
+    return frames.iter().filter_map(|frame| frame.pending.as_ref()).filter(|pending| pending.generation == tracer.generation()).count() as u32;
+}
+///The path tracer on the compute queue, every trace adds one sample to each pixel of the tracer's accumulator.
+///Spheres are scanned linearly, lights are sampled like the cpu tracer samples them, with next event estimation weighted against bsdf sampling.
+///Every frame in flight has its own descriptor set and host visible buffers, so the next pass is written while the gpu traces the last one.
+pub struct TracePass{
+    descriptor_layout : DescriptorLayout,
+    pipeline_layout : ash::vk::PipelineLayout,
+    pipeline : ash::vk::Pipeline,
+    queue : ash::vk::Queue,
+    queue_family : u32,
+    command_pool : ash::vk::CommandPool,
+    ///The material images, uploaded by set_scene.
+    textures : TextureArray,
+    ///The environment map of the scene, a black texel until set_scene uploads one.
+    environment : texture::Texture,
+    ///The texels the environment was uploaded from.
+    environment_texels : Option<Arc<Vec<[f32;3]>>>,
+    frames : Vec<TraceFrame>,
+    ///The frame the next pass is traced with.
+    next_frame : usize,
+    ///The scene uploaded by set_scene.
+    scene : Option<SceneData>,
+}
+impl TracePass{
+    ///The material images are uploaded on the graphics queue, the environment map on the transfer queue of the upload, both are read on the queue of the tracer.
+    ///With a single frame every trace waits for its pass, with more a pass is added to the tracer by the trace that reuses its frame.
+    pub fn new(instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , device_features : &DeviceFeatures , graphics_queue_family : u32 , queue_family : u32 , frames : usize , upload : &FrameUpload , descriptor_allocator : &mut DescriptorAllocator) -> Self{
+        let mut bindings : Vec<LayoutBinding> = (0..BINDINGS as u32).map(|binding| {
+            let descriptor_type = if binding as usize == PARAMETERS{ash::vk::DescriptorType::UNIFORM_BUFFER}else{ash::vk::DescriptorType::STORAGE_BUFFER};
+            LayoutBinding::new(binding, descriptor_type, ash::vk::ShaderStageFlags::COMPUTE)
+        }).collect();
+        bindings.push(LayoutBinding::new(ENVIRONMENT, ash::vk::DescriptorType::SAMPLED_IMAGE, ash::vk::ShaderStageFlags::COMPUTE));
+        let descriptor_layout = DescriptorLayout::new(device, &bindings);
+        let textures = TextureArray::new(instance, device, physical_device, device_features, graphics_queue_family, queue_family, descriptor_allocator);
+        let pipeline_layout = pipeline::create_compute_pipeline_layout(device, &[descriptor_layout.layout,textures.layout()], 0, "tracer");
+        let shader = if textures.is_bindless(){BINDLESS_COMPUTE_SHADER}else{COMPUTE_SHADER};
+        let pipeline = pipeline::create_compute_pipeline(device, pipeline_layout, shader, "tracer");
+        let command_pool = commands::create_command_pool(device, queue_family);
+        let command_buffers = commands::create_command_buffers(device, &command_pool, frames as u32);
+        let sets = descriptor_allocator.allocate_per_frame(device, &descriptor_layout, frames);
+        let fence_create_info = ash::vk::FenceCreateInfo{
+            s_type : ash::vk::StructureType::FENCE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : ash::vk::FenceCreateFlags::empty(),
+        };
+        let frames : Vec<TraceFrame> = sets.into_iter().zip(command_buffers).map(|(set,command_buffer)| TraceFrame{
+            set,
+            command_buffer,
+            fence : unsafe{device.create_fence(&fence_create_info, None)}.expect("Failed to create tracer fence."),
+            buffers : vec!(ash::vk::Buffer::null();BINDINGS),
+            memories : vec!(ash::vk::DeviceMemory::null();BINDINGS),
+            sizes : vec!(0;BINDINGS),
+            pending : None,
+        }).collect();
+        let mut pass = Self{
+            descriptor_layout,
+            pipeline_layout,
+            pipeline,
+            queue : unsafe{device.get_device_queue(queue_family, 0)},
+            queue_family,
+            command_pool,
+            textures,
+            environment : upload.upload_environment(instance, device, physical_device, 1, 1, &[[0.0;3]], &[queue_family]),
+            environment_texels : None,
+            frames,
+            next_frame : 0,
+            scene : None,
+        };
+        let tile : Vec<u8> = sampler::blue_noise_tile().iter().flat_map(|value| value.to_ne_bytes()).collect();
+        pass.write_buffers(instance, device, physical_device, BLUE_NOISE, &tile);
+        pass.write_environment(device);
+        return pass;
+    }
+    ///Uploads the spheres, materials and images, call it again whenever they change, the camera is read by every trace.
+    ///Passes still in flight are waited for and dropped, they traced the old scene.
+    ///An environment map is uploaded on the transfer queue of the upload unless it is the one uploaded before, rotation and intensity are read by every trace.
+    ///The scene is always uploaded, the error names the images traced with their mean color as they could not be uploaded.
+    pub fn set_scene(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , upload : &FrameUpload , scene : &Scene) -> Result<(),String>{
+        for frame in self.frames.iter_mut(){
+            frame.wait(device);
+        }
+        if let Some(environment) = &scene.environment{
+            if !self.environment_texels.as_ref().is_some_and(|texels| Arc::ptr_eq(texels, environment.texels())){
+                let texture = upload.upload_environment(instance, device, physical_device, environment.width() as u32, environment.height() as u32, environment.texels(), &[self.queue_family]);
+                self.environment.destroy(device);
+                self.environment = texture;
+                self.environment_texels = Some(environment.texels().clone());
+                self.write_environment(device);
+            }
+        }
+        let mut errors : Vec<String> = vec!();
+        let textures = &mut self.textures;
+        let data = SceneData::new(scene, |image| match textures.index(instance, device, physical_device, image){
+            Ok(element) => Some(element),
+            Err(error) => {
+                if !errors.contains(&error){
+                    errors.push(error);
+                }
+                None
+            }
+        });
+        let bytes = |words : &[u32]| words.iter().flat_map(|word| word.to_ne_bytes()).collect::<Vec<u8>>();
+        //Empty buffers can not be bound, a scene without spheres still gets one.
+        let mut spheres = bytes(&data.spheres);
+        spheres.resize(spheres.len().max(SPHERE_WORDS * 4), 0);
+        self.write_buffers(instance, device, physical_device, SPHERES, &spheres);
+        self.write_buffers(instance, device, physical_device, MATERIALS, &bytes(&data.materials));
+        self.write_buffers(instance, device, physical_device, TEXTURES, &bytes(&data.textures));
+        let mut emitters = bytes(&data.emitters);
+        emitters.resize(emitters.len().max(EMITTER_WORDS * 4), 0);
+        self.write_buffers(instance, device, physical_device, EMITTERS, &emitters);
+        let mut distribution = bytes(&data.environment_distribution);
+        distribution.resize(distribution.len().max(4), 0);
+        self.write_buffers(instance, device, physical_device, ENVIRONMENT_DISTRIBUTION, &distribution);
+        self.scene = Some(data);
+        if errors.is_empty(){
+            return Ok(());
+        }
+        return Err(errors.join("\n"));
+    }
+    ///Traces a pass with the next frame after adding the pass that frame traced before to the tracer, a cpu pass in progress is abandoned.
+    ///Passes started before the tracer was reset, resized or given another aov are dropped.
+    ///Returns whether a pass was added, with a single frame the pass just traced is waited for and added.
+    pub fn trace(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , camera : &Camera , tracer : &mut Tracer) -> bool{
+        let frame = self.next_frame;
+        self.next_frame = (frame + 1) % self.frames.len();
+        let added = self.finish(device, frame, tracer);
+        self.submit(instance, device, physical_device, frame, camera, tracer);
+        if self.frames.len() > 1{
+            return added;
+        }
+        return self.finish(device, frame, tracer);
+    }
+    ///Writes the parameters of the next pass into the buffers of a frame and submits it.
+    fn submit(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , frame : usize , camera : &Camera , tracer : &Tracer){
+        let accumulator = tracer.accumulator();
+        let (width,height) = (accumulator.width(),accumulator.height());
+        if width == 0 || height == 0{return;}
+        let aovs = accumulator.enabled_aovs();
+        let outputs = outputs(&aovs);
+        let scene = self.scene.as_ref().expect("Failed to find the scene of the gpu tracer, set_scene uploads it.");
+        let parameters = parameters(camera, scene, width, height, tracer.max_bounces(), &tracer.sampler_settings(), outputs);
+        let pending = PendingPass{generation : tracer.generation(), width, height, aovs, material_ids : scene.material_ids.clone()};
+        let offset = pending_samples(&self.frames, tracer);
+        let sample_indices : Vec<u8> = (0..height).flat_map(|y| (0..width).map(move |x| (x,y))).flat_map(|(x,y)| (accumulator.pixel_samples(x, y) + offset).to_ne_bytes()).collect();
+        let layout = &self.descriptor_layout;
+        let trace_frame = &mut self.frames[frame];
+        trace_frame.write_buffer(instance, device, physical_device, layout, PARAMETERS, &parameters);
+        trace_frame.write_buffer(instance, device, physical_device, layout, SAMPLE_INDICES, &sample_indices);
+        trace_frame.update_buffer(instance, device, physical_device, layout, OUTPUTS, (width * height * outputs) as u64 * 16);
+        let command_buffer = trace_frame.command_buffer;
+        commands::begin_command_buffer(device, command_buffer);
+        unsafe{
+            device.cmd_bind_pipeline(command_buffer, ash::vk::PipelineBindPoint::COMPUTE, self.pipeline);
+            device.cmd_bind_descriptor_sets(command_buffer, ash::vk::PipelineBindPoint::COMPUTE, self.pipeline_layout, 0, &[trace_frame.set,self.textures.set()], &[]);
+            device.cmd_dispatch(command_buffer, width.div_ceil(GROUP_SIZE), height.div_ceil(GROUP_SIZE), 1);
+        }
+        commands::record_host_barrier(device, command_buffer);
+        unsafe{device.end_command_buffer(command_buffer)}.expect("Failed to record tracer command buffer.");
+        commands::submit(device, self.queue, command_buffer, trace_frame.fence);
+        trace_frame.pending = Some(pending);
+    }
+    ///Waits for the pass in flight on a frame and adds it to the tracer, returns whether there was one the tracer could take.
+    fn finish(&mut self , device : &Device , frame : usize , tracer : &mut Tracer) -> bool{
+        let trace_frame = &mut self.frames[frame];
+        let pending = match trace_frame.wait(device){
+            Some(pending) if pending.generation == tracer.generation() => pending,
+            _ => return false,
+        };
+        let output_size = trace_frame.sizes[OUTPUTS] as usize;
+        let bytes = memory::read_memory(device, &trace_frame.memories[OUTPUTS], output_size);
+        let values : Vec<[f32;4]> = bytes.chunks_exact(16).map(|value| {
+            let channel = |c : usize| f32::from_ne_bytes([value[c * 4],value[c * 4 + 1],value[c * 4 + 2],value[c * 4 + 3]]);
+            [channel(0),channel(1),channel(2),channel(3)]
+        }).collect();
+        let samples = frame_samples(&values, pending.width, pending.height, &pending.aovs, &pending.material_ids);
+        tracer.add_pass(&samples);
+        return true;
+    }
+    ///Copies the data into the buffer of a binding of every frame, none of them may have a pass in flight.
+    fn write_buffers(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , binding : usize , data : &[u8]){
+        let layout = &self.descriptor_layout;
+        for frame in self.frames.iter_mut(){
+            frame.write_buffer(instance, device, physical_device, layout, binding, data);
+        }
+    }
+    ///Points the set of every frame at the environment map, none of them may have a pass in flight.
+    fn write_environment(&self , device : &Device){
+        for frame in self.frames.iter(){
+            self.descriptor_layout.write(device, frame.set, ENVIRONMENT, 0, &[texture::image_info(&self.environment)]);
+        }
+    }
+    ///The sets are freed with the pools of the descriptor allocator, the device must be idle.
+    pub fn destroy(&mut self , device : &Device){
+        for frame in self.frames.iter_mut(){
+            for binding in 0..BINDINGS{
+                frame.destroy_buffer(device, binding);
+            }
+            unsafe{device.destroy_fence(frame.fence, None)};
+        }
+        self.textures.destroy(device);
+        self.environment.destroy(device);
+        unsafe{device.destroy_command_pool(self.command_pool, None)};
+        unsafe{device.destroy_pipeline(self.pipeline, None)};
+        unsafe{device.destroy_pipeline_layout(self.pipeline_layout, None)};
+        self.descriptor_layout.destroy(device);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::ComputeDevice;
+    use super::super::RendererSettings;
+    use super::super::AppInfo;
+    use super::super::super::scene::Sphere;
+    use super::super::super::light::Environment;
+
+    #[test]
+    fn scene_layout(){
+        let mut scene = Scene::default_scene();
+        scene.spheres.push(Sphere{center : Vector3::new(3.0,0.5,0.0), radius : 0.5, material : Material::lambert([0.8,0.2,0.2])});
+        scene.spheres[0].material.kind = MaterialKind::Lambert{albedo : Texture::Checker{even : Vector3::new(0.1,0.1,0.1), odd : Vector3::new(0.9,0.9,0.9), scale : 4.0}};
+        let data = SceneData::new(&scene, |_| None);
+        assert_eq!(data.sphere_count(), 6);
+        assert_eq!(data.material_ids, vec!(0,1,2,3,4,1));
+        //The last sphere shares the material of the second one.
+        assert_eq!(data.materials.len(), 5 * MATERIAL_WORDS);
+        assert_eq!(&data.spheres[5 * SPHERE_WORDS..6 * SPHERE_WORDS], &[3f32.to_bits(),0.5f32.to_bits(),0,0.5f32.to_bits(),1,0,0,0]);
+        //The floor's checker and its emission follow the zero texture.
+        assert_eq!(&data.materials[..MATERIAL_WORDS], &[0,1f32.to_bits(),1,2,0,0,0,0]);
+        assert_eq!(&data.textures[2 * TEXTURE_WORDS..3 * TEXTURE_WORDS], &[1,0,4f32.to_bits(),0,0.1f32.to_bits(),0.1f32.to_bits(),0.1f32.to_bits(),0,0.9f32.to_bits(),0.9f32.to_bits(),0.9f32.to_bits(),0]);
+        //The glass sphere keeps its index of refraction, its tint and roughness textures.
+        assert_eq!(&data.materials[3 * MATERIAL_WORDS..3 * MATERIAL_WORDS + 3], &[2,1.5f32.to_bits(),data.materials[3 * MATERIAL_WORDS + 2]]);
+        assert_ne!(data.materials[3 * MATERIAL_WORDS + 4], 0);
+        //The emissive sphere is the first emitter and the sky the second.
+        assert_eq!(data.emitter_count(), 2);
+        assert_eq!(data.spheres[4 * SPHERE_WORDS + 5], 1);
+        assert_eq!(data.spheres[SPHERE_WORDS + 5], 0);
+        assert_eq!(&data.emitters[..4], &[EMITTER_SPHERE,4,0,0]);
+        assert_eq!(data.emitters[EMITTER_WORDS], EMITTER_ENVIRONMENT);
+        assert_eq!(data.environment_emitter, Some(1));
+        assert_eq!(f32::from_bits(data.emitters[7]), LightSampler::new(&scene).cdf()[0]);
+        assert_eq!(f32::from_bits(data.emitters[EMITTER_WORDS + 7]), 1.0);
+    }
+    #[test]
+    fn light_layout(){
+        let mut scene = Scene::default_scene();
+        scene.lights.push(Light::spot(Vector3::new(0.0,3.0,0.0), Vector3::new(0.0,-1.0,0.0), Vector3::new(5.0,5.0,5.0), 20.0, 30.0));
+        scene.environment = Some(Environment::new(4, 2, vec!([1.0,1.0,1.0];8), 0.0, 1.0));
+        let data = SceneData::new(&scene, |_| None);
+        //Lights come before the emissive spheres and the environment.
+        assert_eq!(data.emitter_count(), 3);
+        let spot = &data.emitters[..EMITTER_WORDS];
+        assert_eq!(&spot[..4], &[EMITTER_SPOT,0,0,0]);
+        assert_eq!(f32::from_bits(spot[9]), -1.0);
+        assert!((f32::from_bits(spot[11]) - 20f32.to_radians().cos()).abs() < 1e-6);
+        assert!((f32::from_bits(spot[15]) - 30f32.to_radians().cos()).abs() < 1e-6);
+        assert_eq!(data.spheres[4 * SPHERE_WORDS + 5], 2);
+        assert_eq!(data.environment_emitter, Some(2));
+        //A row distribution of two buckets, then one of four buckets per row.
+        assert_eq!(data.environment_distribution.len(), (2 * 2 + 3) + 2 * (2 * 4 + 3));
+        assert_eq!(&data.environment_distribution[..2], &[2,scene.environment.as_ref().unwrap().marginal().integral().to_bits()]);
+        assert_eq!(data.environment_distribution[7], 4);
+        assert_eq!(f32::from_bits(data.environment_distribution[7 + 2 + 4 + 4]), 1.0);
+    }
+    #[test]
+    fn parameter_layout(){
+        let camera = Camera::look_at(Vector3::new(0.0,1.0,5.0), Vector3::new(0.0,1.0,0.0), 90.0);
+        let settings = SamplerSettings{kind : sampler::SamplerKind::Sobol, seed : 3, samples_per_pixel : 16};
+        let scene = SceneData{
+            spheres : vec!(0;5 * SPHERE_WORDS),
+            materials : vec!(),
+            textures : vec!(),
+            material_ids : vec!(),
+            emitters : vec!(0;3 * EMITTER_WORDS),
+            environment_emitter : Some(2),
+            sky : Vector3::new(0.2,0.3,0.5),
+            environment : Some([0.5,2.0]),
+            environment_distribution : vec!(),
+        };
+        let bytes = parameters(&camera, &scene, 64, 32, 8, &settings, 4);
+        let word = |index : usize| u32::from_ne_bytes([bytes[index * 4],bytes[index * 4 + 1],bytes[index * 4 + 2],bytes[index * 4 + 3]]);
+        let float = |index : usize| f32::from_bits(word(index));
+        assert_eq!([float(0),float(1),float(2)], [0.0,1.0,5.0]);
+        assert!((float(3) - 1.0).abs() < 1e-6);
+        assert_eq!(float(6), -1.0);
+        assert_eq!(float(11), 5.0);
+        assert_eq!(float(15), 2.0);
+        assert_eq!([float(16),float(17),float(18)], [0.2,0.3,0.5]);
+        assert_eq!([float(20),float(21),float(22)], [0.5,2.0,1.0]);
+        assert_eq!((24..36).map(word).collect::<Vec<u32>>(), vec!(64,32,5,8,1,3,16,4,3,3,0,0));
+    }
+    #[test]
+    fn frame_outputs(){
+        assert_eq!(outputs(&[]), 1);
+        assert_eq!(outputs(&[Aov::Depth,Aov::ObjectId]), 4);
+        assert_eq!(outputs(&[Aov::Albedo,Aov::SpecularIndirect]), 8);
+        let aovs = [Aov::Depth,Aov::MaterialId,Aov::Position];
+        //A hit on the second sphere and a ray that escaped.
+        let values = [[1.0,2.0,3.0,1.0],[0.5,0.5,0.5,4.0],[0.0,1.0,0.0,2.0],[7.0,8.0,9.0,0.0],[0.2,0.3,0.5,0.0],[0.0;4],[0.0;4],[0.0;4]];
+        let samples = frame_samples(&values, 2, 1, &aovs, &[0,3]);
+        assert_eq!(samples.tile, Tile{x : 0, y : 0, width : 2, height : 1});
+        assert_eq!(samples.radiance, vec!([1.0,2.0,3.0],[0.2,0.3,0.5]));
+        assert_eq!(samples.aovs[0].1, vec!(Some([4.0;3]),None));
+        assert_eq!(samples.aovs[1].1, vec!(Some([4.0;3]),None));
+        assert_eq!(samples.aovs[2].1, vec!(Some([7.0,8.0,9.0]),None));
+    }
+    #[test]
+    fn pending_pass_samples(){
+        let mut tracer = Tracer::new(8, 4);
+        let frame = |generation : Option<u64>| TraceFrame{
+            set : ash::vk::DescriptorSet::null(),
+            command_buffer : ash::vk::CommandBuffer::null(),
+            fence : ash::vk::Fence::null(),
+            buffers : vec!(),
+            memories : vec!(),
+            sizes : vec!(),
+            pending : generation.map(|generation| PendingPass{generation, width : 8, height : 4, aovs : vec!(), material_ids : vec!()}),
+        };
+        let current = tracer.generation();
+        assert_eq!(pending_samples(&[frame(None),frame(None)], &tracer), 0);
+        assert_eq!(pending_samples(&[frame(Some(current)),frame(None)], &tracer), 1);
+        assert_eq!(pending_samples(&[frame(Some(current)),frame(Some(current))], &tracer), 2);
+        //Passes from before a reset, resize or new aov do not count, they are dropped when their frame comes around.
+        tracer.reset();
+        assert_eq!(pending_samples(&[frame(Some(current)),frame(Some(tracer.generation()))], &tracer), 1);
+        let reset = tracer.generation();
+        tracer.resize(8, 4);
+        assert_eq!(tracer.generation(), reset);
+        tracer.resize(16, 4);
+        assert_ne!(tracer.generation(), reset);
+        let resized = tracer.generation();
+        tracer.enable_aov(Aov::Depth);
+        tracer.enable_aov(Aov::Depth);
+        assert_eq!(tracer.generation(), resized + 1);
+    }
+    #[test]
+    fn shader_is_spirv(){
+        let code = ash::util::read_spv(&mut std::io::Cursor::new(COMPUTE_SHADER)).expect("Failed to read tracer shader.");
+        assert_eq!(code[0], 0x0723_0203);
+    }
+    //Returns early on machines without a vulkan device.
+    #[test]
+    fn matches_cpu_first_hits(){
+        let settings = RendererSettings{present_mode : ash::vk::PresentModeKHR::FIFO, hdr : false, validation : false, device : None, app_info : AppInfo::default()};
+        let mut compute = match ComputeDevice::new(&settings){
+            Ok(compute) => compute,
+            Err(error) => {
+                println!("Skipped, {}",error);
+                return;
+            }
+        };
+        let scene = Scene::default_scene();
+        let aovs = [Aov::Albedo,Aov::Normal,Aov::Depth,Aov::ObjectId,Aov::MaterialId];
+        let mut cpu = Tracer::new(48, 32);
+        let mut gpu = Tracer::new(48, 32);
+        for &aov in aovs.iter(){
+            cpu.enable_aov(aov);
+            gpu.enable_aov(aov);
+        }
+        assert!(cpu.render_pass(&scene));
+        compute.set_scene(&scene).expect("Failed to upload the scene.");
+        compute.trace_pass(&scene.camera, &mut gpu);
+        assert_eq!(gpu.accumulator().samples(), 1);
+        //The camera rays are the same, so are the spheres they hit.
+        for &aov in aovs.iter(){
+            let expected = cpu.accumulator().resolve_aov(aov).unwrap();
+            let traced = gpu.accumulator().resolve_aov(aov).unwrap();
+            let differing = expected.iter().zip(traced.iter()).filter(|(a,b)| (0..3).any(|c| (a[c] - b[c]).abs() > 1e-3 * a[c].abs().max(1.0))).count();
+            //Pixels on silhouettes may land on either sphere.
+            assert!(differing * 100 <= expected.len(), "{} of {} pixels of the {} aov differ on the gpu",differing,expected.len(),aov.name());
+        }
+    }
+    //Returns early on machines without a vulkan device.
+    #[test]
+    fn matches_cpu_image_textures(){
+        let settings = RendererSettings{present_mode : ash::vk::PresentModeKHR::FIFO, hdr : false, validation : false, device : None, app_info : AppInfo::default()};
+        let mut compute = match ComputeDevice::new(&settings){
+            Ok(compute) => compute,
+            Err(error) => {
+                println!("Skipped, {}",error);
+                return;
+            }
+        };
+        //A smooth gradient, so the mips the gpu filters with stay close to the bilinear lookups of the cpu.
+        let path = std::env::temp_dir().join(format!("mport_trace_texture_{}.png",std::process::id()));
+        image::RgbImage::from_fn(32, 32, |x,y| image::Rgb([(x * 8) as u8,(y * 8) as u8,128])).save(&path).expect("Failed to save the test texture.");
+        let texture = Texture::load(&path, true).expect("Failed to load the test texture.");
+        std::fs::remove_file(&path).expect("Failed to remove the test texture.");
+        let mut scene = Scene::default_scene();
+        scene.spheres[1].material.kind = MaterialKind::Lambert{albedo : texture};
+        let mut cpu = Tracer::new(64, 48);
+        let mut gpu = Tracer::new(64, 48);
+        for tracer in [&mut cpu,&mut gpu]{
+            tracer.enable_aov(Aov::Albedo);
+            tracer.enable_aov(Aov::ObjectId);
+        }
+        assert!(cpu.render_pass(&scene));
+        compute.set_scene(&scene).expect("Failed to upload the test texture.");
+        compute.trace_pass(&scene.camera, &mut gpu);
+        let ids = cpu.accumulator().resolve_aov(Aov::ObjectId).unwrap();
+        let expected = cpu.accumulator().resolve_aov(Aov::Albedo).unwrap();
+        let traced = gpu.accumulator().resolve_aov(Aov::Albedo).unwrap();
+        let textured : Vec<usize> = (0..ids.len()).filter(|&pixel| ids[pixel][0] == 2.0).collect();
+        assert!(textured.len() > 100);
+        let error = textured.iter().map(|&pixel| (0..3).map(|c| (expected[pixel][c] - traced[pixel][c]).abs()).sum::<f32>()).sum::<f32>() / (3 * textured.len()) as f32;
+        assert!(error < 0.02, "The gpu albedo of the textured sphere is off by {} on average",error);
+    }
+    //Returns early on machines without a vulkan device.
+    #[test]
+    fn matches_cpu_environment(){
+        let settings = RendererSettings{present_mode : ash::vk::PresentModeKHR::FIFO, hdr : false, validation : false, device : None, app_info : AppInfo::default()};
+        let mut compute = match ComputeDevice::new(&settings){
+            Ok(compute) => compute,
+            Err(error) => {
+                println!("Skipped, {}",error);
+                return;
+            }
+        };
+        //Every texel differs, so a ray reading the wrong one shows.
+        let texels : Vec<[f32;3]> = (0..16 * 8).map(|index| [(index % 16) as f32 / 16.0,(index / 16) as f32 / 8.0,0.25]).collect();
+        let mut scene = Scene::default_scene();
+        scene.environment = Some(Environment::new(16, 8, texels, 30.0, 2.0));
+        let mut cpu = Tracer::new(64, 48);
+        let mut gpu = Tracer::new(64, 48);
+        for tracer in [&mut cpu,&mut gpu]{
+            tracer.enable_aov(Aov::ObjectId);
+        }
+        assert!(cpu.render_pass(&scene));
+        compute.set_scene(&scene).expect("Failed to upload the scene.");
+        compute.trace_pass(&scene.camera, &mut gpu);
+        //Camera rays that escape see the background straight away, with the same jitter on both.
+        let ids = cpu.accumulator().resolve_aov(Aov::ObjectId).unwrap();
+        let traced_ids = gpu.accumulator().resolve_aov(Aov::ObjectId).unwrap();
+        let expected = cpu.accumulator().resolve();
+        let traced = gpu.accumulator().resolve();
+        let background : Vec<usize> = (0..ids.len()).filter(|&pixel| ids[pixel][0] == 0.0 && traced_ids[pixel][0] == 0.0).collect();
+        assert!(background.len() > 100);
+        //Directions on a texel border may round to either side.
+        let differing = background.iter().filter(|&&pixel| (0..3).any(|c| (expected[pixel][c] - traced[pixel][c]).abs() > 1e-3)).count();
+        assert!(differing * 100 <= background.len(), "{} of {} background pixels differ on the gpu",differing,background.len());
+    }
+    //Returns early on machines without a vulkan device.
+    #[test]
+    fn matches_cpu_lighting(){
+        let settings = RendererSettings{present_mode : ash::vk::PresentModeKHR::FIFO, hdr : false, validation : false, device : None, app_info : AppInfo::default()};
+        let mut compute = match ComputeDevice::new(&settings){
+            Ok(compute) => compute,
+            Err(error) => {
+                println!("Skipped, {}",error);
+                return;
+            }
+        };
+        //Every kind of emitter, next event estimation picks each of them.
+        let texels : Vec<[f32;3]> = (0..16 * 8).map(|index| if index == 37{[50.0,40.0,30.0]}else{[0.2,0.3,0.5]}).collect();
+        let mut scene = Scene::default_scene();
+        scene.environment = Some(Environment::new(16, 8, texels, 45.0, 1.0));
+        scene.lights.push(Light::Point{position : Vector3::new(-2.0,2.0,1.0), intensity : Vector3::new(4.0,4.0,4.0)});
+        scene.lights.push(Light::spot(Vector3::new(1.5,3.0,1.0), Vector3::new(-0.3,-1.0,-0.2), Vector3::new(10.0,8.0,6.0), 15.0, 25.0));
+        scene.lights.push(Light::Directional{direction : Vector3::new(0.5,-1.0,-0.3), irradiance : Vector3::new(1.0,0.9,0.8)});
+        let mut cpu = Tracer::new(48, 32);
+        let mut gpu = Tracer::new(48, 32);
+        compute.set_scene(&scene).expect("Failed to upload the scene.");
+        for _ in 0..16{
+            assert!(cpu.render_pass(&scene));
+            compute.trace_pass(&scene.camera, &mut gpu);
+        }
+        //Both trace the same paths, only rounding differs, so the images agree closely on average.
+        let mean = |pixels : Vec<[f32;3]>| pixels.iter().map(|pixel| pixel[0] + pixel[1] + pixel[2]).sum::<f32>() / pixels.len() as f32;
+        let (expected,traced) = (mean(cpu.accumulator().resolve()),mean(gpu.accumulator().resolve()));
+        assert!((expected - traced).abs() < 0.02 * expected, "The gpu image averages {} where the cpu one averages {}",traced,expected);
+    }
+}
@@ -0,0 +1,236 @@
+use ash::Device;
+use ash::Instance;
+use ash::version::DeviceV1_0;
+use ash::version::InstanceV1_0;
+
+use super::memory;
+use super::commands;
+use super::sync;
+use super::texture;
+use super::super::tracer::TILE_SIZE;
+
+///The tiles of the frame whose bytes differ from the previous upload, the whole frame when there is nothing to compare against.
+///The tiles line up with the tiles of the tracer, so while the tonemapping stays the same only the tiles it finished show up.
+pub fn changed_regions(previous : &[u8] , frame : &[u8] , extent : ash::vk::Extent2D , pixel_size : usize) -> Vec<ash::vk::Rect2D>{
+    if previous.len() != frame.len(){
+        return vec!(ash::vk::Rect2D{offset : ash::vk::Offset2D{x : 0, y : 0}, extent});
+    }
+    let mut regions = vec!();
+    for y in (0..extent.height).step_by(TILE_SIZE as usize){
+        for x in (0..extent.width).step_by(TILE_SIZE as usize){
+            let region = ash::vk::Rect2D{offset : ash::vk::Offset2D{x : x as i32, y : y as i32}, extent : ash::vk::Extent2D{width : TILE_SIZE.min(extent.width - x), height : TILE_SIZE.min(extent.height - y)}};
+            let changed = (y..y + region.extent.height).any(|row| {
+                let start = (row as usize * extent.width as usize + x as usize) * pixel_size;
+                let end = start + region.extent.width as usize * pixel_size;
+                previous[start..end] != frame[start..end]
+            });
+            if changed{
+                regions.push(region);
+            }
+        }
+    }
+    return regions;
+}
+///Whether a queue family with the given minimum image transfer granularity may copy the region of an image of the extent.
+///A granularity of zero only allows whole images, otherwise offsets are multiples of it and so are extents that stop short of the edge.
+pub fn fits_granularity(region : &ash::vk::Rect2D , extent : ash::vk::Extent2D , granularity : ash::vk::Extent3D) -> bool{
+    if granularity.width == 0 || granularity.height == 0{
+        return region.offset.x == 0 && region.offset.y == 0 && region.extent == extent;
+    }
+    let fits = |offset : i32 , size : u32 , end : u32 , granularity : u32| {
+        offset as u32 % granularity == 0 && (size % granularity == 0 || offset as u32 + size == end)
+    };
+    return fits(region.offset.x, region.extent.width, extent.width, granularity.width) && fits(region.offset.y, region.extent.height, extent.height, granularity.height);
+}
+///Uploads the frames on the transfer queue into an image the graphics queue copies onto the swapchain.
+///The image keeps the previous frame, only the tiles that changed are copied, it stays in GENERAL and is shared by both queue families.
+pub struct FrameUpload{
+    queue : ash::vk::Queue,
+    command_pool : ash::vk::CommandPool,
+    command_buffers : Vec<ash::vk::CommandBuffer>,
+    ///Signaled when the upload of a frame in flight is done, the draw of that frame waits on it.
+    uploaded_semaphores : Vec<ash::vk::Semaphore>,
+    ///Signaled when the draw of a frame in flight has read the image, the next upload waits on it before overwriting tiles.
+    released_semaphores : Vec<ash::vk::Semaphore>,
+    ///The released semaphore that was signaled and not waited on yet.
+    pending_release : Option<usize>,
+    transfer_queue_family : u32,
+    queue_families : Vec<u32>,
+    staging_buffers : Vec<ash::vk::Buffer>,
+    staging_memories : Vec<ash::vk::DeviceMemory>,
+    staging_size : u64,
+    image : ash::vk::Image,
+    image_memory : ash::vk::DeviceMemory,
+    extent : ash::vk::Extent2D,
+    format : ash::vk::Format,
+    ///The min_image_transfer_granularity of the transfer queue family, tiles it can not copy fall back to a copy of the whole frame.
+    granularity : ash::vk::Extent3D,
+    ///What the image holds once the submitted uploads are done.
+    uploaded : Vec<u8>,
+}
+impl FrameUpload{
+    pub fn new(device : &Device , transfer_queue_family : u32 , graphics_queue_family : u32 , frames_in_flight : usize) -> Self{
+        let command_pool = commands::create_command_pool(device, transfer_queue_family);
+        let mut queue_families = vec!(transfer_queue_family,graphics_queue_family);
+        queue_families.dedup();
+        return Self{
+            queue : unsafe{device.get_device_queue(transfer_queue_family, 0)},
+            command_buffers : commands::create_command_buffers(device, &command_pool, frames_in_flight as u32),
+            command_pool,
+            uploaded_semaphores : sync::create_semaphores(device, frames_in_flight),
+            released_semaphores : sync::create_semaphores(device, frames_in_flight),
+            pending_release : None,
+            transfer_queue_family,
+            queue_families,
+            staging_buffers : vec!(),
+            staging_memories : vec!(),
+            staging_size : 0,
+            image : ash::vk::Image::null(),
+            image_memory : ash::vk::DeviceMemory::null(),
+            extent : ash::vk::Extent2D{width : 0, height : 0},
+            format : ash::vk::Format::UNDEFINED,
+            granularity : ash::vk::Extent3D{width : 0, height : 0, depth : 0},
+            uploaded : vec!(),
+        };
+    }
+    ///The image the frames are uploaded to, in GENERAL.
+    pub fn image(&self) -> ash::vk::Image{
+        return self.image;
+    }
+    ///Submits the upload of the changed tiles of the frame, the returned semaphore is signaled once they are in the image.
+    ///The draw that waits on it has to signal released so the next upload does not overwrite tiles it still reads.
+    pub fn upload(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , frame_index : usize , frame : &[u8] , extent : ash::vk::Extent2D , format : ash::vk::Format , pixel_size : usize) -> ash::vk::Semaphore{
+        self.update_image(instance, device, physical_device, extent, format);
+        self.update_staging_buffers(instance, device, physical_device, frame.len() as u64);
+        memory::write_memory(device, &self.staging_memories[frame_index], frame);
+        let mut regions = changed_regions(&self.uploaded, frame, extent, pixel_size);
+        if !regions.iter().all(|region| fits_granularity(region, extent, self.granularity)){
+            regions = vec!(ash::vk::Rect2D{offset : ash::vk::Offset2D{x : 0, y : 0}, extent});
+        }
+        let command_buffer = self.command_buffers[frame_index];
+        commands::begin_command_buffer(device, command_buffer);
+        if self.uploaded.is_empty(){
+            commands::record_transition(device, command_buffer, self.image, ash::vk::ImageLayout::UNDEFINED, ash::vk::ImageLayout::GENERAL, ash::vk::AccessFlags::empty(), ash::vk::AccessFlags::TRANSFER_WRITE);
+        }
+        commands::record_copy_regions(device, command_buffer, self.staging_buffers[frame_index], self.image, &extent, pixel_size, &regions);
+        unsafe{device.end_command_buffer(command_buffer)}.expect("Failed to record upload command buffer.");
+        let wait_semaphores : Vec<ash::vk::Semaphore> = self.pending_release.take().map(|index| self.released_semaphores[index]).into_iter().collect();
+        let wait_stages = [ash::vk::PipelineStageFlags::TRANSFER];
+        let signal_semaphores = [self.uploaded_semaphores[frame_index]];
+        let command_buffers = [command_buffer];
+        let submit_info = ash::vk::SubmitInfo{
+            s_type : ash::vk::StructureType::SUBMIT_INFO,
+            p_next : std::ptr::null(),
+            wait_semaphore_count : wait_semaphores.len() as u32,
+            p_wait_semaphores : wait_semaphores.as_ptr(),
+            p_wait_dst_stage_mask : wait_stages.as_ptr(),
+            command_buffer_count : command_buffers.len() as u32,
+            p_command_buffers : command_buffers.as_ptr(),
+            signal_semaphore_count : signal_semaphores.len() as u32,
+            p_signal_semaphores : signal_semaphores.as_ptr(),
+        };
+        unsafe{device.queue_submit(self.queue, &[submit_info], ash::vk::Fence::null())}.expect("Failed to submit upload command buffer.");
+        self.uploaded.clear();
+        self.uploaded.extend_from_slice(frame);
+        return self.uploaded_semaphores[frame_index];
+    }
+    ///Uploads the texels of an environment map on the transfer queue and waits for it, the image is shared with the queue families that read it.
+    pub fn upload_environment(&self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , width : u32 , height : u32 , texels : &[[f32;3]] , queue_families : &[u32]) -> texture::Texture{
+        let mut families = vec!(self.transfer_queue_family);
+        families.extend(queue_families.iter().filter(|&&family| family != self.transfer_queue_family));
+        return texture::create_environment_texture(instance, device, physical_device, &self.command_pool, self.queue, width, height, texels, &families);
+    }
+    ///The semaphore the draw of the frame signals once it has read the image.
+    pub fn released(&mut self , frame_index : usize) -> ash::vk::Semaphore{
+        self.pending_release = Some(frame_index);
+        return self.released_semaphores[frame_index];
+    }
+    ///Makes sure the image matches the frame, a new image is uploaded as a whole.
+    fn update_image(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , extent : ash::vk::Extent2D , format : ash::vk::Format){
+        if self.extent == extent && self.format == format{return;}
+        unsafe{device.device_wait_idle()}.expect("Failed to wait for the device to become idle.");
+        self.destroy_image(device);
+        let (image,image_memory) = memory::create_shared_image(instance, device, physical_device, &extent, 1, format, ash::vk::ImageUsageFlags::TRANSFER_DST | ash::vk::ImageUsageFlags::TRANSFER_SRC, &self.queue_families);
+        self.image = image;
+        self.image_memory = image_memory;
+        self.extent = extent;
+        self.format = format;
+        self.granularity = unsafe{instance.get_physical_device_queue_family_properties(*physical_device)}[self.transfer_queue_family as usize].min_image_transfer_granularity;
+    }
+    ///Makes sure every frame in flight has a staging buffer of the given size.
+    fn update_staging_buffers(&mut self , instance : &Instance , device : &Device , physical_device : &ash::vk::PhysicalDevice , size : u64){
+        if self.staging_size == size{return;}
+        unsafe{device.device_wait_idle()}.expect("Failed to wait for the device to become idle.");
+        self.destroy_staging_buffers(device);
+        for _ in 0..self.command_buffers.len(){
+            let (buffer,memory) = memory::create_buffer(instance, device, physical_device, size, ash::vk::BufferUsageFlags::TRANSFER_SRC, ash::vk::MemoryPropertyFlags::HOST_VISIBLE | ash::vk::MemoryPropertyFlags::HOST_COHERENT);
+            self.staging_buffers.push(buffer);
+            self.staging_memories.push(memory);
+        }
+        self.staging_size = size;
+    }
+    fn destroy_image(&mut self , device : &Device){
+        if self.image != ash::vk::Image::null(){
+            unsafe{device.destroy_image(self.image, None)};
+            unsafe{device.free_memory(self.image_memory, None)};
+        }
+        self.image = ash::vk::Image::null();
+        self.image_memory = ash::vk::DeviceMemory::null();
+        self.extent = ash::vk::Extent2D{width : 0, height : 0};
+        self.uploaded.clear();
+    }
+    fn destroy_staging_buffers(&mut self , device : &Device){
+        for (&buffer,&memory) in self.staging_buffers.iter().zip(self.staging_memories.iter()){
+            unsafe{device.destroy_buffer(buffer, None)};
+            unsafe{device.free_memory(memory, None)};
+        }
+        self.staging_buffers.clear();
+        self.staging_memories.clear();
+        self.staging_size = 0;
+    }
+    pub fn destroy(&mut self , device : &Device){
+        for (&uploaded,&released) in self.uploaded_semaphores.iter().zip(self.released_semaphores.iter()){
+            unsafe{device.destroy_semaphore(uploaded, None)};
+            unsafe{device.destroy_semaphore(released, None)};
+        }
+        unsafe{device.destroy_command_pool(self.command_pool, None)};
+        self.destroy_staging_buffers(device);
+        self.destroy_image(device);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn changed_tiles(){
+        let extent = ash::vk::Extent2D{width : 70, height : 40};
+        let frame = vec!(0u8;70 * 40 * 4);
+        let full = changed_regions(&[], &frame, extent, 4);
+        assert_eq!(full, vec!(ash::vk::Rect2D{offset : ash::vk::Offset2D{x : 0, y : 0}, extent}));
+        assert!(changed_regions(&frame, &frame, extent, 4).is_empty());
+        //One byte in the last pixel of the partial tile at the bottom right.
+        let mut changed = frame.clone();
+        changed[(39 * 70 + 69) * 4 + 3] = 1;
+        assert_eq!(changed_regions(&frame, &changed, extent, 4), vec!(ash::vk::Rect2D{offset : ash::vk::Offset2D{x : 64, y : 32}, extent : ash::vk::Extent2D{width : 6, height : 8}}));
+    }
+    #[test]
+    fn transfer_granularity(){
+        let extent = ash::vk::Extent2D{width : 70, height : 40};
+        let full = ash::vk::Rect2D{offset : ash::vk::Offset2D{x : 0, y : 0}, extent};
+        let inner = ash::vk::Rect2D{offset : ash::vk::Offset2D{x : 32, y : 0}, extent : ash::vk::Extent2D{width : 32, height : 32}};
+        let edge = ash::vk::Rect2D{offset : ash::vk::Offset2D{x : 64, y : 32}, extent : ash::vk::Extent2D{width : 6, height : 8}};
+        let granularity = |size : u32| ash::vk::Extent3D{width : size, height : size, depth : size};
+        for region in [full,inner,edge].iter(){
+            assert!(fits_granularity(region, extent, granularity(1)));
+            assert!(fits_granularity(region, extent, granularity(32)));
+        }
+        assert!(!fits_granularity(&edge, extent, granularity(64)));
+        assert!(!fits_granularity(&inner, extent, granularity(64)));
+        assert!(fits_granularity(&full, extent, granularity(64)));
+        //Transfer only queues without a granularity copy whole images.
+        assert!(fits_granularity(&full, extent, granularity(0)));
+        assert!(!fits_granularity(&inner, extent, granularity(0)));
+    }
+}
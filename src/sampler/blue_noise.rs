@@ -0,0 +1,139 @@
+use std::sync::OnceLock;
+
+use super::hash;
+
+///Side of the tileable blue noise texture, a power of two so coordinates wrap with a mask.
+pub const BLUE_NOISE_SIZE : u32 = 64;
+///Share of the pixels set in the initial binary pattern.
+const INITIAL_DENSITY : usize = 10;
+///exp(-1 / (2 * 1.5^2)), the gaussian the energy is filtered with is built from powers of it so it is the same on every platform.
+const FALLOFF : f32 = 0.800_737_4;
+
+///Ranks of a void and cluster tile, Ulichney 1993, scaled to 32 bit fractions.
+///The tile is generated on first use.
+pub fn blue_noise_tile() -> &'static [u32]{
+    static TILE : OnceLock<Vec<u32>> = OnceLock::new();
+    return TILE.get_or_init(|| {
+        let shift = 32 - (BLUE_NOISE_SIZE * BLUE_NOISE_SIZE).trailing_zeros();
+        return void_and_cluster(BLUE_NOISE_SIZE as usize).into_iter().map(|rank| rank << shift).collect();
+    });
+}
+///The gaussian energy every set pixel adds around itself on a torus.
+struct Energy{
+    size : usize,
+    ///Weight by toroidal distance along one axis.
+    falloff : Vec<f32>,
+    values : Vec<f32>,
+}
+impl Energy{
+    fn new(size : usize) -> Self{
+        let falloff = (0..size).map(|d| {
+            let distance = d.min(size - d);
+            let mut weight = 1.0;
+            for _ in 0..distance * distance{
+                weight *= FALLOFF;
+            }
+            weight
+        }).collect();
+        return Self{size, falloff, values : vec!(0.0;size * size)};
+    }
+    fn update(&mut self , pixel : usize , sign : f32){
+        let (px,py) = (pixel % self.size,pixel / self.size);
+        for y in 0..self.size{
+            let weight_y = self.falloff[(y + self.size - py) % self.size] * sign;
+            for x in 0..self.size{
+                self.values[y * self.size + x] += weight_y * self.falloff[(x + self.size - px) % self.size];
+            }
+        }
+    }
+    ///The set pixel with the most energy, the center of the tightest cluster.
+    fn tightest_cluster(&self , pattern : &[bool]) -> usize{
+        return self.extreme(pattern, true, |a,b| a > b);
+    }
+    ///The empty pixel with the least energy, the center of the largest void.
+    fn largest_void(&self , pattern : &[bool]) -> usize{
+        return self.extreme(pattern, false, |a,b| a < b);
+    }
+    fn extreme(&self , pattern : &[bool] , set : bool , better : impl Fn(f32,f32) -> bool) -> usize{
+        let mut best = None;
+        for (pixel,&value) in self.values.iter().enumerate(){
+            if pattern[pixel] == set && best.map_or(true, |best : usize| better(value, self.values[best])){
+                best = Some(pixel);
+            }
+        }
+        return best.expect("Failed to find a pixel in the blue noise pattern.");
+    }
+}
+///The rank of every pixel, pixels with ranks below n are spread as evenly as possible for every n.
+fn void_and_cluster(size : usize) -> Vec<u32>{
+    let count = size * size;
+    let mut energy = Energy::new(size);
+    let mut pattern = vec!(false;count);
+    //A random initial pattern, made uniform by moving the tightest cluster into the largest void until that changes nothing.
+    let mut state = 0;
+    let mut ones = 0;
+    while ones < count / INITIAL_DENSITY{
+        state = hash(state);
+        let pixel = state as usize % count;
+        if !pattern[pixel]{
+            pattern[pixel] = true;
+            energy.update(pixel, 1.0);
+            ones += 1;
+        }
+    }
+    loop{
+        let cluster = energy.tightest_cluster(&pattern);
+        pattern[cluster] = false;
+        energy.update(cluster, -1.0);
+        let void = energy.largest_void(&pattern);
+        pattern[void] = true;
+        energy.update(void, 1.0);
+        if void == cluster{
+            break;
+        }
+    }
+    let mut ranks = vec!(0;count);
+    //The initial pattern is ranked by removing clusters, the rest by filling voids.
+    let (initial_pattern,initial_energy) = (pattern.clone(),energy.values.clone());
+    for rank in (0..ones).rev(){
+        let cluster = energy.tightest_cluster(&pattern);
+        pattern[cluster] = false;
+        energy.update(cluster, -1.0);
+        ranks[cluster] = rank as u32;
+    }
+    pattern = initial_pattern;
+    energy.values = initial_energy;
+    for rank in ones..count{
+        let void = energy.largest_void(&pattern);
+        pattern[void] = true;
+        energy.update(void, 1.0);
+        ranks[void] = rank as u32;
+    }
+    return ranks;
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn ranks(){
+        let tile = blue_noise_tile();
+        let mut ranks : Vec<u32> = tile.iter().map(|rank| rank >> 20).collect();
+        ranks.sort_unstable();
+        assert_eq!(ranks, (0..BLUE_NOISE_SIZE * BLUE_NOISE_SIZE).collect::<Vec<u32>>());
+        //Thresholding keeps the points apart, neighbours rarely both fall below the same rank.
+        let size = BLUE_NOISE_SIZE as usize;
+        let threshold = (tile.len() / 8) as u32;
+        let mut neighbours = 0;
+        for y in 0..size{
+            for x in 0..size{
+                let below = |x : usize , y : usize| tile[(y % size) * size + x % size] >> 20 < threshold;
+                if below(x, y) && (below(x + 1, y) || below(x, y + 1)){
+                    neighbours += 1;
+                }
+            }
+        }
+        assert!(neighbours < tile.len() / 200, "{}",neighbours);
+    }
+}
@@ -0,0 +1,303 @@
+mod blue_noise;
+
+pub use blue_noise::blue_noise_tile;
+pub use blue_noise::BLUE_NOISE_SIZE;
+
+///How the random numbers of a path are generated.
+///Every sequence is built from 32 bit integer operations and only turned into floats at the end, so every platform draws the same numbers.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum SamplerKind{
+    ///Independent PCG numbers per pixel and sample.
+    Random,
+    ///Sobol (0,2) sequences with hash based Owen scrambling, Burley 2020, padded per pair of dimensions.
+    Sobol,
+    ///One scrambled Sobol sequence shared by every pixel, rotated per pixel by a blue noise tile, Georgiev and Fajardo 2016.
+    BlueNoise,
+    ///Correlated multi-jittered samples, Kensler 2013, stratified for a fixed number of samples per pixel.
+    Stratified,
+}
+pub const SAMPLERS : [SamplerKind;4] = [SamplerKind::Random,SamplerKind::Sobol,SamplerKind::BlueNoise,SamplerKind::Stratified];
+
+impl SamplerKind{
+    pub fn name(self) -> &'static str{
+        match self{
+            SamplerKind::Random => "Random",
+            SamplerKind::Sobol => "Sobol",
+            SamplerKind::BlueNoise => "Blue-noise",
+            SamplerKind::Stratified => "Stratified",
+        }
+    }
+    pub fn parse(name : &str) -> Option<Self>{
+        return SAMPLERS.iter().copied().find(|kind| kind.name().eq_ignore_ascii_case(name));
+    }
+    pub fn next(self) -> Self{
+        let index = SAMPLERS.iter().position(|&kind| kind == self).unwrap_or(0);
+        return SAMPLERS[(index + 1) % SAMPLERS.len()];
+    }
+}
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct SamplerSettings{
+    pub kind : SamplerKind,
+    ///Renders with the same seed and sampler draw the same numbers.
+    pub seed : u32,
+    ///The samples per pixel the stratified sampler divides the domain into, later samples start a new set of strata.
+    pub samples_per_pixel : u32,
+}
+impl Default for SamplerSettings{
+    fn default() -> Self{
+        return Self{kind : SamplerKind::Sobol, seed : 0, samples_per_pixel : 64};
+    }
+}
+///Creates the samplers of single pixels, it has no state so pixels can be traced in any order.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Sampler{
+    pub settings : SamplerSettings,
+}
+impl Sampler{
+    pub fn new(settings : SamplerSettings) -> Self{
+        return Self{settings};
+    }
+    ///The numbers of one sample of a pixel, sample_index counts the samples taken in the pixel so far.
+    pub fn pixel(&self , x : u32 , y : u32 , sample_index : u32) -> PixelSampler{
+        let seed = hash(self.settings.seed);
+        let pixel = hash(seed ^ hash(x ^ hash(y)));
+        return PixelSampler{
+            kind : self.settings.kind,
+            seed,
+            x,
+            y,
+            pixel,
+            index : sample_index,
+            dimension : 0,
+            samples_per_pixel : self.settings.samples_per_pixel.max(1),
+            state : hash(pixel ^ hash(sample_index)),
+        };
+    }
+}
+///The random numbers of one path, every call moves on to the next dimension.
+#[derive(Clone,Debug)]
+pub struct PixelSampler{
+    kind : SamplerKind,
+    seed : u32,
+    x : u32,
+    y : u32,
+    pixel : u32,
+    index : u32,
+    dimension : u32,
+    samples_per_pixel : u32,
+    state : u32,
+}
+impl PixelSampler{
+    ///A value in [0,1).
+    pub fn next_1d(&mut self) -> f32{
+        let [value,_] = self.next();
+        return to_float(value);
+    }
+    ///Two values in [0,1) that are stratified against each other.
+    pub fn next_2d(&mut self) -> [f32;2]{
+        let [x,y] = self.next();
+        return [to_float(x),to_float(y)];
+    }
+    fn next(&mut self) -> [u32;2]{
+        let dimension = self.dimension;
+        self.dimension += 1;
+        match self.kind{
+            SamplerKind::Random => return [self.random(),self.random()],
+            SamplerKind::Sobol => return sobol_2d(self.index, hash(self.pixel ^ hash(dimension))),
+            SamplerKind::BlueNoise => {
+                let [x,y] = sobol_2d(self.index, hash(self.seed ^ hash(dimension)));
+                let offset = hash(self.seed ^ hash(dimension ^ 0x68bc_21eb));
+                //Every dimension reads the tile at its own toroidal offset so the rotations are not correlated.
+                let rotation_x = blue_noise(self.x.wrapping_add(offset), self.y.wrapping_add(offset >> 16));
+                let rotation_y = blue_noise(self.x.wrapping_add(offset >> 8), self.y.wrapping_add(offset >> 24));
+                return [x.wrapping_add(rotation_x),y.wrapping_add(rotation_y)];
+            }
+            SamplerKind::Stratified => {
+                let round = self.index / self.samples_per_pixel;
+                let pattern = hash(self.pixel ^ hash(dimension ^ hash(round)));
+                return correlated_multi_jitter(self.index % self.samples_per_pixel, self.samples_per_pixel, pattern);
+            }
+        }
+    }
+    ///The RXS-M-XS output of a 32 bit PCG stream.
+    fn random(&mut self) -> u32{
+        self.state = self.state.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+        return rxs_m_xs(self.state);
+    }
+}
+fn blue_noise(x : u32 , y : u32) -> u32{
+    let mask = BLUE_NOISE_SIZE - 1;
+    return blue_noise_tile()[((y & mask) * BLUE_NOISE_SIZE + (x & mask)) as usize];
+}
+///Uses the upper 24 bits so the float is exact and below one.
+fn to_float(value : u32) -> f32{
+    return (value >> 8) as f32 * (1.0 / (1u32 << 24) as f32);
+}
+fn rxs_m_xs(state : u32) -> u32{
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277_803_737);
+    return (word >> 22) ^ word;
+}
+///Jarzynski and Olano's PCG hash.
+pub fn hash(value : u32) -> u32{
+    return rxs_m_xs(value.wrapping_mul(747_796_405).wrapping_add(2_891_336_453));
+}
+///The first two dimensions of the Sobol sequence as 32 bit fractions, the first is the van der Corput sequence.
+pub fn sobol(index : u32) -> [u32;2]{
+    let mut y = 0;
+    let mut direction = 1u32 << 31;
+    let mut i = index;
+    while i != 0{
+        if i & 1 != 0{
+            y ^= direction;
+        }
+        i >>= 1;
+        direction ^= direction >> 1;
+    }
+    return [index.reverse_bits(),y];
+}
+///A Sobol pair with a shuffled index and Owen scrambled values, the seed decorrelates pixels and dimensions.
+fn sobol_2d(index : u32 , seed : u32) -> [u32;2]{
+    let [x,y] = sobol(nested_uniform_scramble(index, seed));
+    return [nested_uniform_scramble(x, hash(seed ^ 0x2c1b_3c6d)),nested_uniform_scramble(y, hash(seed ^ 0x297a_2d39))];
+}
+///Owen scrambling of a 32 bit fraction, the bits are reversed so the hash only mixes bits into more significant ones.
+pub fn nested_uniform_scramble(value : u32 , seed : u32) -> u32{
+    return laine_karras_permutation(value.reverse_bits(), seed).reverse_bits();
+}
+///Nathan Vegdahl's improved Laine-Karras hash.
+fn laine_karras_permutation(mut x : u32 , seed : u32) -> u32{
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    return x;
+}
+///A permutation of 0..length chosen by the pattern, from Kensler's correlated multi-jittered sampling.
+fn permute(mut i : u32 , length : u32 , pattern : u32) -> u32{
+    let mut w = length - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop{
+        i ^= pattern;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= pattern >> 16;
+        i ^= (i & w) >> 4;
+        i ^= pattern >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= pattern >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | pattern >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < length{
+            return i.wrapping_add(pattern) % length;
+        }
+    }
+}
+///A fraction inside the stratum of count equal strata, jitter picks the position inside it.
+fn stratum(index : u32 , count : u32 , jitter : u32) -> u32{
+    let width = (u32::MAX / count).max(1);
+    return index * width + ((jitter as u64 * width as u64) >> 32) as u32;
+}
+///Sample of a columns by rows grid that is also stratified in count columns and count rows.
+fn correlated_multi_jitter(sample : u32 , count : u32 , pattern : u32) -> [u32;2]{
+    let mut columns = 1;
+    while columns * columns < count{
+        columns += 1;
+    }
+    let rows = count.div_ceil(columns);
+    let sample = permute(sample, count, pattern.wrapping_mul(0x5163_3e2d));
+    let (column,row) = (sample % columns,sample / columns);
+    let sub_x = permute(column, columns, pattern.wrapping_mul(0xa511_e9b3));
+    let sub_y = permute(row, rows, pattern.wrapping_mul(0x63d8_3595));
+    let jitter_x = hash(sample ^ pattern.wrapping_mul(0xa399_d265));
+    let jitter_y = hash(sample ^ pattern.wrapping_mul(0x711a_d6a5));
+    return [stratum(column * rows + sub_y, columns * rows, jitter_x),stratum(row * columns + sub_x, columns * rows, jitter_y)];
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn settings(kind : SamplerKind , seed : u32) -> Sampler{
+        return Sampler::new(SamplerSettings{kind, seed, samples_per_pixel : 16});
+    }
+    fn samples(sampler : &Sampler , x : u32 , y : u32 , count : u32 , dimension : u32) -> Vec<[f32;2]>{
+        return (0..count).map(|index| {
+            let mut pixel = sampler.pixel(x, y, index);
+            for _ in 0..dimension{
+                pixel.next_2d();
+            }
+            pixel.next_2d()
+        }).collect();
+    }
+    #[test]
+    fn names(){
+        for &kind in SAMPLERS.iter(){
+            assert_eq!(SamplerKind::parse(&kind.name().to_uppercase()), Some(kind));
+        }
+        assert_eq!(SamplerKind::parse("halton"), None);
+        assert_eq!(SamplerKind::Stratified.next(), SamplerKind::Random);
+    }
+    #[test]
+    fn seeded(){
+        for &kind in SAMPLERS.iter(){
+            let a = samples(&settings(kind, 7), 3, 5, 32, 2);
+            assert_eq!(a, samples(&settings(kind, 7), 3, 5, 32, 2), "{} is not deterministic",kind.name());
+            assert_ne!(a, samples(&settings(kind, 8), 3, 5, 32, 2), "{} ignores the seed",kind.name());
+            assert_ne!(a, samples(&settings(kind, 7), 4, 5, 32, 2), "{} repeats across pixels",kind.name());
+            assert!(a.iter().flatten().all(|&value| (0.0..1.0).contains(&value)));
+        }
+    }
+    #[test]
+    fn sobol_points(){
+        let points : Vec<[u32;2]> = (0..4).map(sobol).collect();
+        assert_eq!(points, vec!([0,0],[1 << 31,1 << 31],[1 << 30,3 << 30],[3 << 30,1 << 30]));
+        //Scrambling keeps the stratification, every 4x4, 2x8 and 8x2 grid of 16 points has one point per cell.
+        for &dimension in [0,3].iter(){
+            let points = samples(&settings(SamplerKind::Sobol, 1), 9, 2, 16, dimension);
+            for &(columns,rows) in [(4,4),(2,8),(8,2)].iter(){
+                let mut cells = vec!(0;16);
+                for point in points.iter(){
+                    cells[(point[1] * rows as f32) as usize * columns + (point[0] * columns as f32) as usize] += 1;
+                }
+                assert!(cells.iter().all(|&count| count == 1), "{} {}x{} : {:?}",dimension,columns,rows,cells);
+            }
+        }
+    }
+    #[test]
+    fn stratified(){
+        //Counts that are not square use a larger grid, 10 samples fall into distinct strata of a 4x3 grid.
+        for &(count,strata) in [(16,16),(10,12)].iter(){
+            let sampler = Sampler::new(SamplerSettings{kind : SamplerKind::Stratified, seed : 3, samples_per_pixel : count});
+            //A second round of samples is stratified on its own.
+            for round in 0..2{
+                let points : Vec<[f32;2]> = samples(&sampler, 1, 1, count * 2, 1).into_iter().skip((round * count) as usize).take(count as usize).collect();
+                for axis in 0..2{
+                    let mut strata : Vec<usize> = points.iter().map(|point| (point[axis] * strata as f32) as usize).collect();
+                    strata.sort_unstable();
+                    strata.dedup();
+                    assert_eq!(strata.len(), count as usize);
+                }
+            }
+        }
+    }
+    #[test]
+    fn random(){
+        let sampler = settings(SamplerKind::Random, 0);
+        let values : Vec<f32> = (0..4096).map(|index| sampler.pixel(index % 64, index / 64, 0).next_1d()).collect();
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        assert!((mean - 0.5).abs() < 0.02, "{}",mean);
+    }
+}
@@ -0,0 +1,273 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use cgmath::Vector3;
+
+use super::Scene;
+use super::Sphere;
+use super::super::camera::Camera;
+use super::super::material::Material;
+use super::super::material::MaterialKind;
+use super::super::material::Texture;
+use super::super::light::Light;
+use super::super::light::Environment;
+
+///The toml scene description, every table is optional and falls back to the default scene.
+#[derive(Deserialize,Debug)]
+#[serde(deny_unknown_fields)]
+struct SceneFile{
+    sky : Option<[f32;3]>,
+    environment : Option<EnvironmentFile>,
+    camera : Option<CameraFile>,
+    #[serde(default)]
+    sphere : Vec<SphereFile>,
+    #[serde(default)]
+    light : Vec<LightFile>,
+}
+#[derive(Deserialize,Debug)]
+#[serde(deny_unknown_fields)]
+struct CameraFile{
+    position : [f32;3],
+    target : [f32;3],
+    #[serde(default = "default_fov")]
+    fov : f32,
+    #[serde(default)]
+    aperture : f32,
+    focus_distance : Option<f32>,
+}
+///An equirectangular .hdr or .exr image replacing the sky.
+#[derive(Deserialize,Debug)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentFile{
+    ///Relative to the scene.
+    path : String,
+    ///Degrees around the up axis.
+    #[serde(default)]
+    rotation : f32,
+    #[serde(default = "default_intensity")]
+    intensity : f32,
+}
+#[derive(Deserialize,Debug)]
+#[serde(deny_unknown_fields)]
+struct SphereFile{
+    center : [f32;3],
+    radius : f32,
+    ///Shorthand for a lambert material, can not be combined with a material table.
+    albedo : Option<TextureFile>,
+    material : Option<MaterialFile>,
+    #[serde(default)]
+    emission : TextureFile,
+}
+#[derive(Deserialize,Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialFile{
+    Lambert{
+        #[serde(default = "default_albedo")]
+        albedo : TextureFile,
+    },
+    Conductor{
+        color : TextureFile,
+        #[serde(default)]
+        roughness : TextureFile,
+    },
+    Dielectric{
+        #[serde(default = "default_ior")]
+        ior : f32,
+        #[serde(default)]
+        roughness : TextureFile,
+        #[serde(default = "default_tint")]
+        tint : TextureFile,
+    },
+    ThinDielectric{
+        #[serde(default = "default_ior")]
+        ior : f32,
+        #[serde(default = "default_tint")]
+        tint : TextureFile,
+    },
+    Principled{
+        #[serde(default = "default_albedo")]
+        base_color : TextureFile,
+        #[serde(default)]
+        metallic : TextureFile,
+        #[serde(default = "default_roughness")]
+        roughness : TextureFile,
+        #[serde(default = "default_specular")]
+        specular : TextureFile,
+        #[serde(default)]
+        transmission : TextureFile,
+        #[serde(default = "default_ior")]
+        ior : f32,
+    },
+}
+///Lights are white unless given a color, the intensity scales it.
+#[derive(Deserialize,Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightFile{
+    Point{
+        position : [f32;3],
+        #[serde(default = "default_light_color")]
+        color : [f32;3],
+        #[serde(default = "default_intensity")]
+        intensity : f32,
+    },
+    Spot{
+        position : [f32;3],
+        target : [f32;3],
+        #[serde(default = "default_light_color")]
+        color : [f32;3],
+        #[serde(default = "default_intensity")]
+        intensity : f32,
+        ///Half angle of the cone in degrees.
+        #[serde(default = "default_spot_angle")]
+        angle : f32,
+        ///Half angle in degrees inside which the light is at full intensity, defaults to 80% of the angle.
+        inner_angle : Option<f32>,
+    },
+    Directional{
+        ///The way the light travels, from the light into the scene.
+        direction : [f32;3],
+        #[serde(default = "default_light_color")]
+        color : [f32;3],
+        #[serde(default = "default_intensity")]
+        intensity : f32,
+    },
+}
+///A number, a color, an image path relative to the scene or a checker pattern.
+#[derive(Deserialize,Debug)]
+#[serde(untagged)]
+enum TextureFile{
+    Value(f32),
+    Color([f32;3]),
+    Image{image : String},
+    Checker{
+        checker : [[f32;3];2],
+        #[serde(default = "default_checker_scale")]
+        scale : f32,
+    },
+}
+impl Default for TextureFile{
+    fn default() -> Self{
+        return TextureFile::Value(0.0);
+    }
+}
+fn default_fov() -> f32{
+    return 45.0;
+}
+fn default_albedo() -> TextureFile{
+    return TextureFile::Color([0.8,0.8,0.8]);
+}
+fn default_tint() -> TextureFile{
+    return TextureFile::Value(1.0);
+}
+fn default_roughness() -> TextureFile{
+    return TextureFile::Value(0.5);
+}
+fn default_specular() -> TextureFile{
+    return TextureFile::Value(0.5);
+}
+fn default_ior() -> f32{
+    return 1.5;
+}
+fn default_checker_scale() -> f32{
+    return 8.0;
+}
+fn default_light_color() -> [f32;3]{
+    return [1.0,1.0,1.0];
+}
+fn default_intensity() -> f32{
+    return 1.0;
+}
+fn default_spot_angle() -> f32{
+    return 30.0;
+}
+impl LightFile{
+    fn load(self) -> Result<Light,String>{
+        let light = match self{
+            LightFile::Point{position,color,intensity} => Light::Point{position : Vector3::from(position), intensity : Vector3::from(color) * intensity},
+            LightFile::Spot{position,target,color,intensity,angle,inner_angle} => {
+                if position == target{
+                    return Err(String::from("a spot light needs a target different from its position"));
+                }
+                let position = Vector3::from(position);
+                Light::spot(position, Vector3::from(target) - position, Vector3::from(color) * intensity, inner_angle.unwrap_or(angle * 0.8), angle)
+            }
+            LightFile::Directional{direction,color,intensity} => {
+                if direction == [0.0;3]{
+                    return Err(String::from("a directional light needs a direction"));
+                }
+                Light::Directional{direction : Vector3::from(direction), irradiance : Vector3::from(color) * intensity}
+            }
+        };
+        return Ok(light);
+    }
+}
+impl TextureFile{
+    ///Colors are stored sRGB encoded in images, scalar parameters are linear.
+    fn load(self , directory : &Path , srgb : bool) -> Result<Texture,String>{
+        match self{
+            TextureFile::Value(value) => Ok(Texture::value(value)),
+            TextureFile::Color(color) => Ok(Texture::color(color)),
+            TextureFile::Image{image} => Texture::load(&directory.join(image), srgb),
+            TextureFile::Checker{checker,scale} => Ok(Texture::Checker{even : Vector3::from(checker[0]), odd : Vector3::from(checker[1]), scale}),
+        }
+    }
+}
+impl MaterialFile{
+    fn load(self , directory : &Path) -> Result<MaterialKind,String>{
+        let kind = match self{
+            MaterialFile::Lambert{albedo} => MaterialKind::Lambert{albedo : albedo.load(directory, true)?},
+            MaterialFile::Conductor{color,roughness} => MaterialKind::Conductor{color : color.load(directory, true)?, roughness : roughness.load(directory, false)?},
+            MaterialFile::Dielectric{ior,roughness,tint} => MaterialKind::Dielectric{ior, roughness : roughness.load(directory, false)?, tint : tint.load(directory, true)?},
+            MaterialFile::ThinDielectric{ior,tint} => MaterialKind::ThinDielectric{ior, tint : tint.load(directory, true)?},
+            MaterialFile::Principled{base_color,metallic,roughness,specular,transmission,ior} => MaterialKind::Principled{
+                base_color : base_color.load(directory, true)?,
+                metallic : metallic.load(directory, false)?,
+                roughness : roughness.load(directory, false)?,
+                specular : specular.load(directory, false)?,
+                transmission : transmission.load(directory, false)?,
+                ior,
+            },
+        };
+        return Ok(kind);
+    }
+}
+impl SphereFile{
+    fn load(self , directory : &Path) -> Result<Sphere,String>{
+        let kind = match (self.albedo,self.material){
+            (Some(_),Some(_)) => return Err(String::from("a sphere can not have both an albedo and a material")),
+            (Some(albedo),None) => MaterialKind::Lambert{albedo : albedo.load(directory, true)?},
+            (None,Some(material)) => material.load(directory)?,
+            (None,None) => MaterialKind::Lambert{albedo : default_albedo().load(directory, true)?},
+        };
+        return Ok(Sphere{
+            center : Vector3::from(self.center),
+            radius : self.radius,
+            material : Material{kind, emission : self.emission.load(directory, false)?},
+        });
+    }
+}
+///Parses a toml scene, texture paths are relative to the directory.
+pub fn parse(source : &str , directory : &Path) -> Result<Scene,String>{
+    let file : SceneFile = toml::from_str(source).map_err(|error| error.to_string())?;
+    let mut scene = Scene::default_scene();
+    if let Some(sky) = file.sky{
+        scene.sky = Vector3::from(sky);
+    }
+    if let Some(environment) = file.environment{
+        scene.environment = Some(Environment::load(&directory.join(environment.path), environment.rotation, environment.intensity)?);
+    }
+    if let Some(camera) = file.camera{
+        let mut scene_camera = Camera::look_at(Vector3::from(camera.position), Vector3::from(camera.target), camera.fov);
+        scene_camera.aperture = camera.aperture;
+        if let Some(focus_distance) = camera.focus_distance{
+            scene_camera.focus_distance = focus_distance;
+        }
+        scene.camera = scene_camera;
+    }
+    if !file.sphere.is_empty(){
+        scene.spheres = file.sphere.into_iter().map(|sphere| sphere.load(directory)).collect::<Result<_,_>>()?;
+    }
+    scene.lights = file.light.into_iter().map(LightFile::load).collect::<Result<_,_>>()?;
+    return Ok(scene);
+}
@@ -0,0 +1,117 @@
+mod file;
+
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+
+use super::camera::Camera;
+use super::material::Material;
+use super::material::MaterialKind;
+use super::material::Texture;
+use super::light::Light;
+use super::light::Environment;
+
+pub struct Sphere{
+    pub center : Vector3<f32>,
+    pub radius : f32,
+    pub material : Material,
+}
+pub struct Scene{
+    pub spheres : Vec<Sphere>,
+    ///Point, spot and directional lights, emissive spheres and the sky or environment map light the scene as well.
+    pub lights : Vec<Light>,
+    ///Constant radiance from every direction, used when there is no environment map.
+    pub sky : Vector3<f32>,
+    pub environment : Option<Environment>,
+    pub camera : Camera,
+}
+impl Sphere{
+    ///Returns the distance along the ray to the nearest intersection in front of the origin.
+    pub fn intersect(&self , origin : Vector3<f32> , direction : Vector3<f32>) -> Option<f32>{
+        let oc = origin - self.center;
+        let b = oc.dot(direction);
+        let c = oc.dot(oc) - self.radius * self.radius;
+        let discriminant = b * b - c;
+        if discriminant < 0.0{return None;}
+        let root = discriminant.sqrt();
+        let t = -b - root;
+        if t > 1e-4{return Some(t);}
+        let t = -b + root;
+        if t > 1e-4{return Some(t);}
+        return None;
+    }
+    ///Texture coordinates of the point with the given outward normal, u wraps around the vertical axis.
+    pub fn uv(&self , normal : Vector3<f32>) -> [f32;2]{
+        return [0.5 + normal.x.atan2(-normal.z) / (2.0 * std::f32::consts::PI),0.5 + normal.y.clamp(-1.0, 1.0).asin() / std::f32::consts::PI];
+    }
+}
+impl Scene{
+    ///Returns the distance to the closest sphere along the ray and its index.
+    pub fn intersect(&self , origin : Vector3<f32> , direction : Vector3<f32>) -> Option<(f32,usize)>{
+        let mut closest : Option<(f32,usize)> = None;
+        for (index,sphere) in self.spheres.iter().enumerate(){
+            if let Some(t) = sphere.intersect(origin, direction){
+                if closest.map_or(true, |(closest_t,_)| t < closest_t){
+                    closest = Some((t,index));
+                }
+            }
+        }
+        return closest;
+    }
+    ///Whether anything blocks the ray before it travels the given distance.
+    pub fn occluded(&self , origin : Vector3<f32> , direction : Vector3<f32> , distance : f32) -> bool{
+        //Shortened a little so the light being tested does not block itself.
+        let distance = distance * (1.0 - 1e-4);
+        return self.spheres.iter().any(|sphere| sphere.intersect(origin, direction).is_some_and(|t| t < distance));
+    }
+    ///The radiance reaching rays that escape the scene.
+    pub fn background(&self , direction : Vector3<f32>) -> Vector3<f32>{
+        match &self.environment{
+            Some(environment) => return environment.radiance(direction),
+            None => return self.sky,
+        }
+    }
+    ///Numbers the distinct materials in the order they first appear, one id per sphere.
+    pub fn material_ids(&self) -> Vec<u32>{
+        let mut ids : Vec<u32> = vec!();
+        for (index,sphere) in self.spheres.iter().enumerate(){
+            let first = self.spheres[..index].iter().position(|other| other.material == sphere.material);
+            let id = match first{
+                Some(first) => ids[first],
+                None => ids.iter().max().map_or(0, |max| max + 1),
+            };
+            ids.push(id);
+        }
+        return ids;
+    }
+    ///Loads a built in scene by name or a toml scene file.
+    pub fn load(name : &str) -> Result<Self,String>{
+        if name == "default"{
+            return Ok(Self::default_scene());
+        }
+        let source = std::fs::read_to_string(name).map_err(|error| format!("Failed to read scene {} : {}.",name,error))?;
+        let directory = std::path::Path::new(name).parent().unwrap_or_else(|| std::path::Path::new(""));
+        return file::parse(&source, directory).map_err(|error| format!("Failed to parse scene {} : {}",name,error));
+    }
+    ///A small test scene, a bright emitter over a diffuse, a metal and a glass sphere.
+    pub fn default_scene() -> Self{
+        return Self{
+            spheres : vec!(
+                Sphere{center : Vector3::new(0.0,-1000.0,0.0), radius : 1000.0, material : Material::lambert([0.7,0.7,0.7])},
+                Sphere{center : Vector3::new(-1.2,0.6,0.0), radius : 0.6, material : Material::lambert([0.8,0.2,0.2])},
+                Sphere{center : Vector3::new(0.0,0.6,-0.6), radius : 0.6, material : Material{
+                    kind : MaterialKind::Conductor{color : Texture::color([0.95,0.75,0.35]), roughness : Texture::value(0.25)},
+                    emission : Texture::value(0.0),
+                }},
+                Sphere{center : Vector3::new(1.2,0.6,0.0), radius : 0.6, material : Material{
+                    kind : MaterialKind::Dielectric{ior : 1.5, roughness : Texture::value(0.0), tint : Texture::color([0.8,0.85,1.0])},
+                    emission : Texture::value(0.0),
+                }},
+                Sphere{center : Vector3::new(0.0,4.0,1.0), radius : 0.5, material : Material::emissive([40.0,36.0,30.0])},
+            ),
+            lights : vec!(),
+            sky : Vector3::new(0.2,0.3,0.5),
+            environment : None,
+            camera : Camera::look_at(Vector3::new(0.0,1.5,5.0), Vector3::new(0.0,0.5,0.0), 45.0),
+        };
+    }
+}
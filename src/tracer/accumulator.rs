@@ -0,0 +1,123 @@
+use super::Aov;
+use super::TileSamples;
+
+///Running sum of linear radiance per pixel, the average is the current estimate of the image.
+///Tiles are added as soon as they are traced, so pixels of an unfinished pass hold one sample more than the rest.
+pub struct Accumulator{
+    width : u32,
+    height : u32,
+    radiance : Vec<[f32;3]>,
+    aovs : Vec<(Aov,Vec<[f32;3]>)>,
+    ///Samples added to each pixel.
+    counts : Vec<u32>,
+    samples : u32,
+}
+impl Accumulator{
+    pub fn new(width : u32 , height : u32) -> Self{
+        return Self{
+            width,
+            height,
+            radiance : vec!([0.0;3];(width * height) as usize),
+            aovs : vec!(),
+            counts : vec!(0;(width * height) as usize),
+            samples : 0,
+        };
+    }
+    pub fn width(&self) -> u32{
+        return self.width;
+    }
+    pub fn height(&self) -> u32{
+        return self.height;
+    }
+    ///The amount of completed samples per pixel.
+    pub fn samples(&self) -> u32{
+        return self.samples;
+    }
+    ///The samples added to a single pixel, including those of an unfinished pass.
+    pub fn pixel_samples(&self , x : u32 , y : u32) -> u32{
+        return self.counts[(y * self.width + x) as usize];
+    }
+    pub fn reset(&mut self){
+        for pixel in self.radiance.iter_mut(){
+            *pixel = [0.0;3];
+        }
+        for (_,layer) in self.aovs.iter_mut(){
+            for pixel in layer.iter_mut(){
+                *pixel = [0.0;3];
+            }
+        }
+        for count in self.counts.iter_mut(){
+            *count = 0;
+        }
+        self.samples = 0;
+    }
+    pub fn resize(&mut self , width : u32 , height : u32){
+        if width != self.width || height != self.height{
+            let aovs = self.enabled_aovs();
+            *self = Self::new(width, height);
+            for aov in aovs{
+                self.enable_aov(aov);
+            }
+        }
+    }
+    ///Starts recording an aov, it only holds samples added after it was enabled so the accumulation is restarted.
+    pub fn enable_aov(&mut self , aov : Aov){
+        if !self.has_aov(aov){
+            self.aovs.push((aov,vec!([0.0;3];(self.width * self.height) as usize)));
+            self.reset();
+        }
+    }
+    pub fn has_aov(&self , aov : Aov) -> bool{
+        return self.aovs.iter().any(|(enabled,_)| *enabled == aov);
+    }
+    pub fn enabled_aovs(&self) -> Vec<Aov>{
+        return self.aovs.iter().map(|(aov,_)| *aov).collect();
+    }
+    ///Adds a sample to every pixel of the tile.
+    ///Ids are not averaged, they hold the value of the first sample so edges never blend two ids into a third.
+    pub fn add_tile(&mut self , samples : &TileSamples){
+        let tile = samples.tile;
+        for row in 0..tile.height{
+            for column in 0..tile.width{
+                let source = (row * tile.width + column) as usize;
+                let index = ((tile.y + row) * self.width + tile.x + column) as usize;
+                for c in 0..3{
+                    self.radiance[index][c] += samples.radiance[source][c];
+                }
+                for (aov,values) in samples.aovs.iter(){
+                    let value = match values[source]{
+                        Some(value) => value,
+                        None => continue,
+                    };
+                    if let Some((_,layer)) = self.aovs.iter_mut().find(|(enabled,_)| enabled == aov){
+                        if aov.is_id(){
+                            if self.counts[index] == 0{layer[index] = value;}
+                            continue;
+                        }
+                        for c in 0..3{
+                            layer[index][c] += value[c];
+                        }
+                    }
+                }
+                self.counts[index] += 1;
+            }
+        }
+    }
+    pub fn finish_pass(&mut self){
+        self.samples += 1;
+    }
+    ///Returns the averaged linear radiance, row major starting at the top left.
+    pub fn resolve(&self) -> Vec<[f32;3]>{
+        return self.average(&self.radiance);
+    }
+    ///Returns the averaged aov, or None when it is not recorded.
+    pub fn resolve_aov(&self , aov : Aov) -> Option<Vec<[f32;3]>>{
+        return self.aovs.iter().find(|(enabled,_)| *enabled == aov).map(|(_,layer)| if aov.is_id(){layer.clone()}else{self.average(layer)});
+    }
+    fn average(&self , layer : &[[f32;3]]) -> Vec<[f32;3]>{
+        return layer.iter().zip(self.counts.iter()).map(|(pixel,&count)| {
+            let scale = if count == 0{0.0}else{1.0 / count as f32};
+            [pixel[0] * scale,pixel[1] * scale,pixel[2] * scale]
+        }).collect();
+    }
+}
@@ -0,0 +1,96 @@
+///Auxiliary outputs recorded for every camera ray, the data aovs describe the first hit and the lighting aovs split the radiance reaching the camera.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Aov{
+    Albedo,
+    ///World space shading normal.
+    Normal,
+    ///World space position, zero where the ray escaped.
+    Position,
+    ///View space depth, zero where the ray escaped.
+    Depth,
+    ///One plus the index of the sphere, zero where the ray escaped.
+    ObjectId,
+    ///One plus the index of the material, spheres with equal materials share it.
+    MaterialId,
+    ///Light that reached the first hit straight from an emitter and left through the diffuse lobes.
+    DiffuseDirect,
+    ///Light that bounced at least once more before reaching the first hit, through the diffuse lobes.
+    DiffuseIndirect,
+    ///Direct light through the glossy, mirror and transmission lobes.
+    SpecularDirect,
+    SpecularIndirect,
+}
+pub const AOVS : [Aov;10] = [Aov::Albedo,Aov::Normal,Aov::Position,Aov::Depth,Aov::ObjectId,Aov::MaterialId,Aov::DiffuseDirect,Aov::DiffuseIndirect,Aov::SpecularDirect,Aov::SpecularIndirect];
+
+impl Aov{
+    pub fn name(self) -> &'static str{
+        match self{
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Position => "position",
+            Aov::Depth => "depth",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::DiffuseDirect => "diffuse_direct",
+            Aov::DiffuseIndirect => "diffuse_indirect",
+            Aov::SpecularDirect => "specular_direct",
+            Aov::SpecularIndirect => "specular_indirect",
+        }
+    }
+    pub fn parse(name : &str) -> Option<Self>{
+        return AOVS.iter().copied().find(|aov| aov.name().eq_ignore_ascii_case(name.trim()));
+    }
+    ///Lighting aovs hold linear radiance and sum up to the beauty without the emission seen directly.
+    pub fn is_radiance(self) -> bool{
+        return matches!(self, Aov::DiffuseDirect | Aov::DiffuseIndirect | Aov::SpecularDirect | Aov::SpecularIndirect);
+    }
+    pub fn is_id(self) -> bool{
+        return matches!(self, Aov::ObjectId | Aov::MaterialId);
+    }
+    ///The channel names in OpenEXR files, single channel aovs store the same value in all three components.
+    pub fn channels(self) -> &'static [&'static str]{
+        match self{
+            Aov::Albedo => &["albedo.R","albedo.G","albedo.B"],
+            Aov::Normal => &["normal.X","normal.Y","normal.Z"],
+            Aov::Position => &["position.X","position.Y","position.Z"],
+            Aov::Depth => &["depth.Z"],
+            Aov::ObjectId => &["object_id.ID"],
+            Aov::MaterialId => &["material_id.ID"],
+            Aov::DiffuseDirect => &["diffuse_direct.R","diffuse_direct.G","diffuse_direct.B"],
+            Aov::DiffuseIndirect => &["diffuse_indirect.R","diffuse_indirect.G","diffuse_indirect.B"],
+            Aov::SpecularDirect => &["specular_direct.R","specular_direct.G","specular_direct.B"],
+            Aov::SpecularIndirect => &["specular_indirect.R","specular_indirect.G","specular_indirect.B"],
+        }
+    }
+    ///Maps a data aov to display values in [0,1] for the viewer and 8 bit files, lighting aovs are returned unchanged to be tonemapped like the beauty.
+    pub fn preview(self , pixels : &[[f32;3]]) -> Vec<[f32;3]>{
+        match self{
+            Aov::Albedo => return pixels.iter().map(|pixel| [pixel[0].clamp(0.0, 1.0),pixel[1].clamp(0.0, 1.0),pixel[2].clamp(0.0, 1.0)]).collect(),
+            Aov::Normal => return pixels.iter().map(|pixel| [pixel[0] * 0.5 + 0.5,pixel[1] * 0.5 + 0.5,pixel[2] * 0.5 + 0.5]).collect(),
+            Aov::Position => {
+                //Scaled so the farthest coordinate reaches the edge of the range, the origin is middle grey.
+                let extent = pixels.iter().flat_map(|pixel| pixel.iter()).fold(0.0f32, |extent,c| extent.max(c.abs())).max(1e-6);
+                return pixels.iter().map(|pixel| [pixel[0] / extent * 0.5 + 0.5,pixel[1] / extent * 0.5 + 0.5,pixel[2] / extent * 0.5 + 0.5]).collect();
+            }
+            Aov::Depth => {
+                //Near surfaces are bright, escaped rays black.
+                let far = pixels.iter().fold(0.0f32, |far,pixel| far.max(pixel[0])).max(1e-6);
+                return pixels.iter().map(|pixel| if pixel[0] > 0.0{[1.0 - 0.9 * pixel[0] / far;3]}else{[0.0;3]}).collect();
+            }
+            Aov::ObjectId | Aov::MaterialId => return pixels.iter().map(|pixel| id_color(pixel[0])).collect(),
+            _ => return pixels.to_vec(),
+        }
+    }
+}
+///A saturated color for every id, zero stays black.
+///Hues step by the golden ratio so neighbouring ids are far apart on the color wheel.
+fn id_color(id : f32) -> [f32;3]{
+    let id = id.round() as u32;
+    if id == 0{return [0.0;3];}
+    let hue = (id as f32 * 0.618_034).fract() * 6.0;
+    let channel = |offset : f32| {
+        let k = (offset + hue) % 6.0;
+        return 0.9 - 0.7 * (k.min(4.0 - k)).clamp(0.0, 1.0);
+    };
+    return [channel(5.0),channel(3.0),channel(1.0)];
+}
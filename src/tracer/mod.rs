@@ -0,0 +1,468 @@
+mod accumulator;
+mod aov;
+mod tiles;
+
+pub use accumulator::Accumulator;
+pub use aov::Aov;
+pub use aov::AOVS;
+pub use tiles::Tile;
+pub use tiles::TileSamples;
+pub use tiles::TILE_SIZE;
+
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+use rayon::prelude::*;
+
+use cgmath::Vector3;
+use cgmath::InnerSpace;
+use cgmath::ElementWise;
+
+use super::scene::Scene;
+use super::bvh::Bvh4;
+use super::material::Frame;
+use super::light::LightSampler;
+use super::sampler::PixelSampler;
+use super::sampler::Sampler;
+use super::sampler::SamplerSettings;
+
+///Scenes with fewer spheres are scanned linearly, the bvh only pays off from about this many.
+const BVH_MIN_SPHERES : usize = 16;
+struct FirstHit{
+    albedo : Vector3<f32>,
+    normal : Vector3<f32>,
+    position : Vector3<f32>,
+    distance : f32,
+    sphere : usize,
+    lighting : Lighting,
+}
+///The radiance scattered towards the camera at the first hit, split by lobe and by whether the light came straight from an emitter.
+struct Lighting{
+    diffuse_direct : Vector3<f32>,
+    diffuse_indirect : Vector3<f32>,
+    specular_direct : Vector3<f32>,
+    specular_indirect : Vector3<f32>,
+}
+impl Lighting{
+    fn new() -> Self{
+        let zero = Vector3::new(0.0,0.0,0.0);
+        return Self{diffuse_direct : zero, diffuse_indirect : zero, specular_direct : zero, specular_indirect : zero};
+    }
+    ///Splits the contribution by the share of the diffuse lobes at the first hit.
+    fn add(&mut self , radiance : Vector3<f32> , diffuse_fraction : Vector3<f32> , direct : bool){
+        let diffuse = radiance.mul_element_wise(diffuse_fraction);
+        let specular = radiance - diffuse;
+        if direct{
+            self.diffuse_direct += diffuse;
+            self.specular_direct += specular;
+        } else{
+            self.diffuse_indirect += diffuse;
+            self.specular_indirect += specular;
+        }
+    }
+}
+///The tiles of a pass that are not traced yet, a tile skipped by a cancel stays in place and is handed out again before the later ones.
+///What the tiles need from the scene is gathered once when the pass starts and shared by all of its batches.
+struct Pass{
+    remaining : Vec<Tile>,
+    count : usize,
+    lights : LightSampler,
+    material_ids : Vec<u32>,
+    aovs : Vec<Aov>,
+}
+impl Pass{
+    fn new(width : u32 , height : u32 , scene : &Scene , aovs : Vec<Aov>) -> Self{
+        let remaining = tiles::tiles(width, height, TILE_SIZE);
+        return Self{count : remaining.len(), remaining, lights : LightSampler::new(scene), material_ids : scene.material_ids(), aovs};
+    }
+    ///The next count tiles that are not traced yet.
+    fn batch(&self , count : usize) -> Vec<Tile>{
+        return self.remaining[..self.remaining.len().min(count)].to_vec();
+    }
+    fn finish(&mut self , traced : &[Tile]){
+        self.remaining.retain(|tile| !traced.contains(tile));
+    }
+    fn progress(&self) -> f32{
+        return (self.count - self.remaining.len()) as f32 / self.count.max(1) as f32;
+    }
+}
+///What every tile of a batch shares.
+struct PassContext<'a>{
+    bvh : Option<&'a Bvh4>,
+    lights : &'a LightSampler,
+    material_ids : &'a [u32],
+    aovs : &'a [Aov],
+}
+impl PassContext<'_>{
+    fn intersect(&self , scene : &Scene , origin : Vector3<f32> , direction : Vector3<f32>) -> Option<(f32,usize)>{
+        match self.bvh{
+            Some(bvh) => return bvh.intersect(origin, direction),
+            None => return scene.intersect(origin, direction),
+        }
+    }
+    fn occluded(&self , scene : &Scene , origin : Vector3<f32> , direction : Vector3<f32> , distance : f32) -> bool{
+        match self.bvh{
+            Some(bvh) => return bvh.occluded(origin, direction, distance),
+            None => return scene.occluded(origin, direction, distance),
+        }
+    }
+}
+///Cpu path tracer, every pass adds one sample to each pixel of the accumulator.
+///A pass is split into tiles that are traced on all cores, a pass can be spread over several calls and tiles show up in the accumulator as soon as they are done.
+pub struct Tracer{
+    accumulator : Accumulator,
+    max_bounces : u32,
+    sampler : Sampler,
+    pass : Option<Pass>,
+    ///Built over the spheres of scenes with at least BVH_MIN_SPHERES when a pass needs it and kept until the next reset.
+    bvh : Option<Bvh4>,
+    cancel : Arc<AtomicBool>,
+    ///Counts the changes that make a pass started before them unfit for the accumulator.
+    generation : u64,
+}
+impl Tracer{
+    pub fn new(width : u32 , height : u32) -> Self{
+        return Self{
+            accumulator : Accumulator::new(width, height),
+            max_bounces : 8,
+            sampler : Sampler::new(SamplerSettings::default()),
+            pass : None,
+            bvh : None,
+            cancel : Arc::new(AtomicBool::new(false)),
+            generation : 0,
+        };
+    }
+    pub fn accumulator(&self) -> &Accumulator{
+        return &self.accumulator;
+    }
+    pub fn max_bounces(&self) -> u32{
+        return self.max_bounces;
+    }
+    pub fn sampler_settings(&self) -> SamplerSettings{
+        return self.sampler.settings;
+    }
+    ///Switching to other sequences discards the accumulated samples, they were drawn from the old ones.
+    pub fn set_sampler(&mut self , settings : SamplerSettings){
+        if settings != self.sampler.settings{
+            self.sampler = Sampler::new(settings);
+            self.reset();
+        }
+    }
+    pub fn enable_aov(&mut self , aov : Aov){
+        if !self.accumulator.has_aov(aov){
+            self.accumulator.enable_aov(aov);
+            self.pass = None;
+            self.generation += 1;
+        }
+    }
+    ///Discards the accumulated samples, call this whenever the scene or camera changes.
+    pub fn reset(&mut self){
+        self.accumulator.reset();
+        self.pass = None;
+        self.bvh = None;
+        self.generation += 1;
+    }
+    pub fn resize(&mut self , width : u32 , height : u32){
+        if width != self.accumulator.width() || height != self.accumulator.height(){
+            self.accumulator.resize(width, height);
+            self.pass = None;
+            self.generation += 1;
+        }
+    }
+    ///Changes on every reset, resize and newly enabled aov, a pass traced elsewhere is only added when it was started in the current generation.
+    pub fn generation(&self) -> u64{
+        return self.generation;
+    }
+    ///Setting the flag from any thread stops the pass in progress after the tiles being traced, it stays set until cleared.
+    pub fn cancel_flag(&self) -> Arc<AtomicBool>{
+        return self.cancel.clone();
+    }
+    pub fn is_cancelled(&self) -> bool{
+        return self.cancel.load(Ordering::Relaxed);
+    }
+    ///Abandons the pass in progress, the tiles traced so far keep their samples.
+    pub fn cancel_pass(&mut self){
+        self.pass = None;
+    }
+    ///The share of the tiles of the current pass that are traced.
+    pub fn pass_progress(&self) -> f32{
+        return self.pass.as_ref().map_or(0.0, Pass::progress);
+    }
+    ///Adds one sample to each pixel, returns false when the pass was cancelled.
+    pub fn render_pass(&mut self , scene : &Scene) -> bool{
+        while !self.render_tiles(scene, usize::MAX){
+            if self.is_cancelled(){
+                return false;
+            }
+        }
+        return true;
+    }
+    ///Traces the tiles of the current pass until it is finished or the budget is spent, the viewer uses this so a slow pass does not stall the window.
+    ///Returns whether the pass was finished, the next call continues where this one stopped.
+    pub fn render_for(&mut self , scene : &Scene , budget : Duration) -> bool{
+        let start = Instant::now();
+        //A couple of tiles per thread so threads that finish early can steal work.
+        let batch = rayon::current_num_threads() * 2;
+        loop{
+            if self.render_tiles(scene, batch){
+                return true;
+            }
+            if self.is_cancelled() || start.elapsed() >= budget{
+                return false;
+            }
+        }
+    }
+    ///Traces up to count tiles of the current pass in parallel and adds them to the accumulator, a pass is started when none is in progress.
+    ///Returns whether the pass was finished.
+    pub fn render_tiles(&mut self , scene : &Scene , count : usize) -> bool{
+        let (width,height) = (self.accumulator.width(),self.accumulator.height());
+        let accumulator = &self.accumulator;
+        let batch = self.pass.get_or_insert_with(|| Pass::new(width, height, scene, accumulator.enabled_aovs())).batch(count);
+        if self.bvh.is_none() && scene.spheres.len() >= BVH_MIN_SPHERES{
+            self.bvh = Some(Bvh4::new(&scene.spheres));
+        }
+        let pass = self.pass.as_ref().expect("Failed to keep the pass in progress.");
+        let context = PassContext{
+            bvh : self.bvh.as_ref(),
+            lights : &pass.lights,
+            material_ids : &pass.material_ids,
+            aovs : &pass.aovs,
+        };
+        let tracer = &*self;
+        let cancel = &*self.cancel;
+        //Rayon splits the batch over its threads and idle threads steal the tiles others have not started.
+        let traced : Vec<TileSamples> = batch.par_iter().filter_map(|tile| {
+            if cancel.load(Ordering::Relaxed){
+                return None;
+            }
+            return Some(tracer.trace_tile(scene, &context, *tile));
+        }).collect();
+        for samples in traced.iter(){
+            self.accumulator.add_tile(samples);
+        }
+        //Every tile is marked on its own, tiles a cancel skipped between traced ones are traced by the next call and the traced ones never twice.
+        let pass = self.pass.as_mut().expect("Failed to keep the pass in progress.");
+        pass.finish(&traced.iter().map(|samples| samples.tile).collect::<Vec<Tile>>());
+        if !pass.remaining.is_empty(){
+            return false;
+        }
+        self.pass = None;
+        self.accumulator.finish_pass();
+        return true;
+    }
+    ///Adds a pass traced elsewhere, the gpu tracer hands its passes over this way, a pass in progress is abandoned.
+    pub fn add_pass(&mut self , samples : &TileSamples){
+        self.pass = None;
+        self.accumulator.add_tile(samples);
+        self.accumulator.finish_pass();
+    }
+    ///One sample for each pixel of the tile, the sample index of a pixel is the number of samples it already holds.
+    fn trace_tile(&self , scene : &Scene , context : &PassContext , tile : Tile) -> TileSamples{
+        let width = self.accumulator.width();
+        let height = self.accumulator.height();
+        let forward = scene.camera.forward();
+        let aspect = width as f32 / height.max(1) as f32;
+        let mut samples = TileSamples{
+            tile,
+            radiance : Vec::with_capacity(tile.pixel_count()),
+            aovs : context.aovs.iter().map(|&aov| (aov,Vec::with_capacity(tile.pixel_count()))).collect(),
+        };
+        for y in tile.y..tile.y + tile.height{
+            for x in tile.x..tile.x + tile.width{
+                let mut sampler = self.sampler.pixel(x, y, self.accumulator.pixel_samples(x, y));
+                let [jitter_x,jitter_y] = sampler.next_2d();
+                let u = (x as f32 + jitter_x) / width as f32 * 2.0 - 1.0;
+                let v = 1.0 - (y as f32 + jitter_y) / height as f32 * 2.0;
+                let lens_sample = sampler.next_2d();
+                let ray = scene.camera.generate_ray(u, v, aspect, lens_sample);
+                let (radiance,first_hit) = self.trace(scene, context, &mut sampler, ray.origin, ray.direction);
+                samples.radiance.push([radiance.x,radiance.y,radiance.z]);
+                for (aov,values) in samples.aovs.iter_mut(){
+                    let value = first_hit.as_ref().map(|hit| {
+                        let lighting = &hit.lighting;
+                        let value = match aov{
+                            Aov::Albedo => hit.albedo,
+                            Aov::Normal => hit.normal,
+                            Aov::Position => hit.position,
+                            Aov::Depth => Vector3::new(1.0,1.0,1.0) * hit.distance * ray.direction.dot(forward),
+                            Aov::ObjectId => Vector3::new(1.0,1.0,1.0) * (hit.sphere + 1) as f32,
+                            Aov::MaterialId => Vector3::new(1.0,1.0,1.0) * (context.material_ids[hit.sphere] + 1) as f32,
+                            Aov::DiffuseDirect => lighting.diffuse_direct,
+                            Aov::DiffuseIndirect => lighting.diffuse_indirect,
+                            Aov::SpecularDirect => lighting.specular_direct,
+                            Aov::SpecularIndirect => lighting.specular_indirect,
+                        };
+                        [value.x,value.y,value.z]
+                    });
+                    values.push(value);
+                }
+            }
+        }
+        return samples;
+    }
+    ///Returns the radiance along the ray and the surface it hit first.
+    ///Every non specular hit samples a light directly, emission found by bsdf sampling is weighted against it with the power heuristic.
+    ///Light found by the first bounce is direct lighting of the first hit, anything found later is indirect.
+    ///Every bounce draws the same dimensions from the sampler whether it uses them or not, so dimensions line up across paths.
+    fn trace(&self , scene : &Scene , context : &PassContext , sampler : &mut PixelSampler , mut origin : Vector3<f32> , mut direction : Vector3<f32>) -> (Vector3<f32>,Option<FirstHit>){
+        let mut radiance = Vector3::new(0.0,0.0,0.0);
+        let mut throughput = Vector3::new(1.0,1.0,1.0);
+        let mut first_hit : Option<FirstHit> = None;
+        //The part of the first hit's bsdf the path continued through that was diffuse, zero for dirac samples.
+        let mut diffuse_fraction = Vector3::new(0.0,0.0,0.0);
+        //The density the last bounce was sampled with, None for camera rays and specular bounces which lights can not sample.
+        let mut bsdf_pdf : Option<f32> = None;
+        for bounce in 0..self.max_bounces{
+            let (t,sphere_index) = match context.intersect(scene, origin, direction){
+                Some(hit) => hit,
+                None => {
+                    let weight = bsdf_pdf.map_or(1.0, |pdf| power_heuristic(pdf, context.lights.environment_pdf(scene, direction)));
+                    let contribution = throughput.mul_element_wise(scene.background(direction)) * weight;
+                    radiance += contribution;
+                    if let Some(hit) = first_hit.as_mut(){
+                        hit.lighting.add(contribution, diffuse_fraction, bounce == 1);
+                    }
+                    break;
+                }
+            };
+            let sphere = &scene.spheres[sphere_index];
+            let previous = origin;
+            origin += direction * t;
+            let normal = (origin - sphere.center).normalize();
+            let uv = sphere.uv(normal);
+            let bsdf = sphere.material.bsdf(uv);
+            let emission = sphere.material.emission(uv);
+            if emission != Vector3::new(0.0,0.0,0.0){
+                let weight = bsdf_pdf.map_or(1.0, |pdf| power_heuristic(pdf, context.lights.sphere_pdf(scene, sphere_index, previous)));
+                let contribution = throughput.mul_element_wise(emission) * weight;
+                radiance += contribution;
+                if let Some(hit) = first_hit.as_mut(){
+                    hit.lighting.add(contribution, diffuse_fraction, bounce == 1);
+                }
+            }
+            if bounce == 0{
+                first_hit = Some(FirstHit{albedo : bsdf.albedo(), normal, position : origin, distance : t, sphere : sphere_index, lighting : Lighting::new()});
+            }
+            let frame = Frame::new(normal);
+            let wo = frame.to_local(-direction);
+            let light_choice = sampler.next_1d();
+            let light_sample = sampler.next_2d();
+            let lobe = sampler.next_1d();
+            let [direction_u,direction_v] = sampler.next_2d();
+            if let Some((light_index,probability)) = context.lights.choose(light_choice){
+                if let Some(light) = context.lights.sample(scene, light_index, origin, light_sample){
+                    let wi = frame.to_local(light.direction);
+                    let f = bsdf.eval(wo, wi) * wi.z.abs();
+                    if f != Vector3::new(0.0,0.0,0.0) && !context.occluded(scene, origin, light.direction, light.distance){
+                        let light_pdf = probability * light.pdf;
+                        let weight = if light.delta{1.0}else{power_heuristic(light_pdf, bsdf.pdf(wo, wi))};
+                        let contribution = throughput.mul_element_wise(f).mul_element_wise(light.radiance) * (weight / light_pdf);
+                        radiance += contribution;
+                        if let Some(hit) = first_hit.as_mut(){
+                            let fraction = if bounce == 0{bsdf.diffuse_fraction(wo, wi)}else{diffuse_fraction};
+                            hit.lighting.add(contribution, fraction, bounce == 0);
+                        }
+                    }
+                }
+            }
+            let sample = match bsdf.sample(wo, [lobe,direction_u,direction_v]){
+                Some(sample) => sample,
+                None => break,
+            };
+            if bounce == 0 && !sample.delta{
+                diffuse_fraction = bsdf.diffuse_fraction(wo, sample.wi);
+            }
+            throughput = throughput.mul_element_wise(sample.weight);
+            bsdf_pdf = if sample.delta{None}else{Some(sample.pdf)};
+            direction = frame.to_world(sample.wi).normalize();
+        }
+        return (radiance,first_hit);
+    }
+}
+///Veach's power heuristic with an exponent of two.
+fn power_heuristic(pdf : f32 , other_pdf : f32) -> f32{
+    let (a,b) = (pdf * pdf,other_pdf * other_pdf);
+    if a + b <= 0.0{return 0.0;}
+    return a / (a + b);
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn tiled_passes(){
+        let scene = Scene::default_scene();
+        let mut whole = Tracer::new(70, 40);
+        whole.enable_aov(Aov::ObjectId);
+        assert!(whole.render_pass(&scene));
+        //The same pass spread over several calls, tiles show up as soon as they are traced.
+        let mut tiled = Tracer::new(70, 40);
+        tiled.enable_aov(Aov::ObjectId);
+        assert!(!tiled.render_tiles(&scene, 2));
+        assert_eq!(tiled.accumulator().samples(), 0);
+        assert_eq!(tiled.pass_progress(), 2.0 / 6.0);
+        let first = tiles::tiles(70, 40, TILE_SIZE)[0];
+        assert_eq!(tiled.accumulator().pixel_samples(first.x, first.y), 1);
+        while !tiled.render_tiles(&scene, 3){}
+        assert_eq!(tiled.accumulator().samples(), 1);
+        assert_eq!(tiled.accumulator().resolve(), whole.accumulator().resolve());
+        assert_eq!(tiled.accumulator().resolve_aov(Aov::ObjectId), whole.accumulator().resolve_aov(Aov::ObjectId));
+    }
+    #[test]
+    fn bvh(){
+        let mut scene = Scene::default_scene();
+        for i in 0..20{
+            scene.spheres.push(crate::scene::Sphere{center : Vector3::new(i as f32 * 0.3 - 3.0,0.15,1.0), radius : 0.15, material : crate::material::Material::lambert([0.3,0.6,0.3])});
+        }
+        let mut tracer = Tracer::new(70, 40);
+        assert!(tracer.render_pass(&scene));
+        //The paths through the bvh are exactly the paths through the linear scan.
+        let lights = LightSampler::new(&scene);
+        let material_ids = scene.material_ids();
+        let context = |bvh| PassContext{bvh, lights : &lights, material_ids : &material_ids, aovs : &[]};
+        let bvh = tracer.bvh.as_ref().expect("Failed to build the bvh of a large scene.");
+        for tile in tiles::tiles(70, 40, TILE_SIZE){
+            assert_eq!(tracer.trace_tile(&scene, &context(Some(bvh)), tile).radiance, tracer.trace_tile(&scene, &context(None), tile).radiance);
+        }
+        tracer.reset();
+        assert!(tracer.bvh.is_none());
+        assert!(tracer.render_pass(&Scene::default_scene()));
+        assert!(tracer.bvh.is_none());
+    }
+    #[test]
+    fn cancel(){
+        let scene = Scene::default_scene();
+        let mut tracer = Tracer::new(70, 40);
+        tracer.cancel_flag().store(true, Ordering::Relaxed);
+        assert!(!tracer.render_pass(&scene));
+        assert!(!tracer.render_for(&scene, Duration::from_secs(1)));
+        assert_eq!(tracer.pass_progress(), 0.0);
+        tracer.cancel.store(false, Ordering::Relaxed);
+        tracer.render_tiles(&scene, 1);
+        //Abandoning a pass keeps the tiles that were traced.
+        tracer.cancel_pass();
+        assert_eq!(tracer.pass_progress(), 0.0);
+        assert_eq!(tracer.accumulator().samples(), 0);
+        assert!(tracer.accumulator().resolve().iter().any(|pixel| *pixel != [0.0;3]));
+        assert!(tracer.render_pass(&scene));
+        assert_eq!(tracer.accumulator().samples(), 1);
+    }
+    #[test]
+    fn cancel_gaps(){
+        let mut pass = Pass::new(70, 40, &Scene::default_scene(), vec!());
+        let tiles = pass.batch(usize::MAX);
+        assert_eq!(pass.count, 6);
+        //A cancel hit the second tile of the batch while the first and third were traced.
+        let batch = pass.batch(3);
+        pass.finish(&[batch[0],batch[2]]);
+        assert_eq!(pass.progress(), 2.0 / 6.0);
+        assert_eq!(pass.batch(2), vec!(tiles[1],tiles[3]));
+        pass.finish(&pass.batch(usize::MAX));
+        assert!(pass.remaining.is_empty());
+        assert_eq!(pass.progress(), 1.0);
+    }
+}
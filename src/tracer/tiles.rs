@@ -0,0 +1,59 @@
+use super::Aov;
+
+///Side of a tile in pixels, small enough that every core gets several tiles of a pass to balance the load.
+pub const TILE_SIZE : u32 = 32;
+
+///A rectangle of the image that is traced as one unit of work.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Tile{
+    pub x : u32,
+    pub y : u32,
+    pub width : u32,
+    pub height : u32,
+}
+impl Tile{
+    pub fn pixel_count(&self) -> usize{
+        return (self.width * self.height) as usize;
+    }
+}
+///Covers the image with tiles, ordered from the center outwards so the preview fills in where one usually looks first.
+pub fn tiles(width : u32 , height : u32 , size : u32) -> Vec<Tile>{
+    let mut tiles = vec!();
+    for y in (0..height).step_by(size as usize){
+        for x in (0..width).step_by(size as usize){
+            tiles.push(Tile{x, y, width : size.min(width - x), height : size.min(height - y)});
+        }
+    }
+    //Doubled coordinates keep the distance to the center exact.
+    let distance = |tile : &Tile| {
+        let dx = (2 * tile.x + tile.width) as i64 - width as i64;
+        let dy = (2 * tile.y + tile.height) as i64 - height as i64;
+        dx * dx + dy * dy
+    };
+    tiles.sort_by_key(distance);
+    return tiles;
+}
+///One sample for every pixel of a tile, row major within the tile.
+pub struct TileSamples{
+    pub tile : Tile,
+    pub radiance : Vec<[f32;3]>,
+    ///The enabled aovs, None where the camera ray escaped.
+    pub aovs : Vec<(Aov,Vec<Option<[f32;3]>>)>,
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn cover(){
+        let tiles = tiles(100, 50, 32);
+        assert_eq!(tiles.len(), 4 * 2);
+        assert_eq!(tiles.iter().map(Tile::pixel_count).sum::<usize>(), 100 * 50);
+        assert!(tiles.contains(&Tile{x : 96, y : 32, width : 4, height : 18}));
+        //The tiles around the center come first.
+        assert!(tiles[..2].iter().all(|tile| tile.x == 32 || tile.x == 64));
+        assert!(tiles.iter().all(|tile| tile.width > 0 && tile.height > 0));
+        assert!(super::tiles(0, 10, 32).is_empty());
+    }
+}
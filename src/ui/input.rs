@@ -0,0 +1,160 @@
+use winit::event::ElementState;
+use winit::event::ModifiersState;
+use winit::event::MouseButton;
+use winit::event::MouseScrollDelta;
+use winit::event::VirtualKeyCode;
+use winit::event::WindowEvent;
+
+///Keys egui uses for navigation, text editing and shortcuts.
+pub fn key(key : VirtualKeyCode) -> Option<egui::Key>{
+    let key = match key{
+        VirtualKeyCode::Down => egui::Key::ArrowDown,
+        VirtualKeyCode::Left => egui::Key::ArrowLeft,
+        VirtualKeyCode::Right => egui::Key::ArrowRight,
+        VirtualKeyCode::Up => egui::Key::ArrowUp,
+        VirtualKeyCode::Escape => egui::Key::Escape,
+        VirtualKeyCode::Tab => egui::Key::Tab,
+        VirtualKeyCode::Back => egui::Key::Backspace,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => egui::Key::Enter,
+        VirtualKeyCode::Space => egui::Key::Space,
+        VirtualKeyCode::Insert => egui::Key::Insert,
+        VirtualKeyCode::Delete => egui::Key::Delete,
+        VirtualKeyCode::Home => egui::Key::Home,
+        VirtualKeyCode::End => egui::Key::End,
+        VirtualKeyCode::PageUp => egui::Key::PageUp,
+        VirtualKeyCode::PageDown => egui::Key::PageDown,
+        VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => egui::Key::Minus,
+        VirtualKeyCode::Equals | VirtualKeyCode::NumpadAdd => egui::Key::Plus,
+        VirtualKeyCode::A => egui::Key::A,
+        VirtualKeyCode::C => egui::Key::C,
+        VirtualKeyCode::V => egui::Key::V,
+        VirtualKeyCode::X => egui::Key::X,
+        VirtualKeyCode::Y => egui::Key::Y,
+        VirtualKeyCode::Z => egui::Key::Z,
+        _ => return None,
+    };
+    return Some(key);
+}
+pub fn modifiers(state : ModifiersState) -> egui::Modifiers{
+    return egui::Modifiers{
+        alt : state.alt(),
+        ctrl : state.ctrl(),
+        shift : state.shift(),
+        mac_cmd : cfg!(target_os = "macos") && state.logo(),
+        command : if cfg!(target_os = "macos"){state.logo()}else{state.ctrl()},
+    };
+}
+pub fn pointer_button(button : MouseButton) -> Option<egui::PointerButton>{
+    match button{
+        MouseButton::Left => Some(egui::PointerButton::Primary),
+        MouseButton::Right => Some(egui::PointerButton::Secondary),
+        MouseButton::Middle => Some(egui::PointerButton::Middle),
+        MouseButton::Other(_) => None,
+    }
+}
+///Turns winit window events into egui events, positions are converted from physical pixels to points.
+#[derive(Clone,Debug,Default)]
+pub struct Input{
+    pub events : Vec<egui::Event>,
+    pub pointer : egui::Pos2,
+    pub modifiers : egui::Modifiers,
+    ///Whether the window has the keyboard focus.
+    pub focused : bool,
+}
+impl Input{
+    pub fn handle(&mut self , event : &WindowEvent , pixels_per_point : f32){
+        match event{
+            WindowEvent::CursorMoved{position,..} => {
+                self.pointer = egui::pos2(position.x as f32 / pixels_per_point, position.y as f32 / pixels_per_point);
+                self.events.push(egui::Event::PointerMoved(self.pointer));
+            }
+            WindowEvent::CursorLeft{..} => self.events.push(egui::Event::PointerGone),
+            WindowEvent::MouseInput{state,button,..} => {
+                if let Some(button) = pointer_button(*button){
+                    self.events.push(egui::Event::PointerButton{pos : self.pointer, button, pressed : *state == ElementState::Pressed, modifiers : self.modifiers});
+                }
+            }
+            WindowEvent::MouseWheel{delta,..} => {
+                let (unit,delta) = match delta{
+                    MouseScrollDelta::LineDelta(x,y) => (egui::MouseWheelUnit::Line,egui::vec2(*x, *y)),
+                    MouseScrollDelta::PixelDelta(position) => (egui::MouseWheelUnit::Point,egui::vec2(position.x as f32, position.y as f32) / pixels_per_point),
+                };
+                self.events.push(egui::Event::MouseWheel{unit, delta, modifiers : self.modifiers});
+            }
+            WindowEvent::ModifiersChanged(state) => self.modifiers = modifiers(*state),
+            //Control characters are sent as keys, text only gets printable characters.
+            WindowEvent::ReceivedCharacter(character) if !(character.is_control() || self.modifiers.ctrl || self.modifiers.mac_cmd) => {
+                self.events.push(egui::Event::Text(character.to_string()));
+            }
+            WindowEvent::KeyboardInput{input,..} => {
+                if let Some(key) = input.virtual_keycode.and_then(key){
+                    let pressed = input.state == ElementState::Pressed;
+                    if pressed && self.modifiers.command{
+                        match key{
+                            egui::Key::C => self.events.push(egui::Event::Copy),
+                            egui::Key::X => self.events.push(egui::Event::Cut),
+                            _ => {}
+                        }
+                    }
+                    self.events.push(egui::Event::Key{key, physical_key : None, pressed, repeat : false, modifiers : self.modifiers});
+                }
+            }
+            WindowEvent::Focused(focused) => {
+                self.focused = *focused;
+                self.events.push(egui::Event::WindowFocused(*focused));
+            }
+            _ => {}
+        }
+    }
+    ///The input of the next egui frame, the events are handed over.
+    pub fn take(&mut self , screen_size : egui::Vec2 , pixels_per_point : f32 , time : f64) -> egui::RawInput{
+        let mut raw_input = egui::RawInput{
+            screen_rect : Some(egui::Rect::from_min_size(egui::Pos2::ZERO, screen_size)),
+            time : Some(time),
+            modifiers : self.modifiers,
+            events : std::mem::take(&mut self.events),
+            focused : self.focused,
+            ..Default::default()
+        };
+        raw_input.viewports.entry(egui::ViewportId::ROOT).or_default().native_pixels_per_point = Some(pixels_per_point);
+        return raw_input;
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[allow(deprecated)]
+    #[test]
+    fn events(){
+        let device_id = unsafe{winit::event::DeviceId::dummy()};
+        let mut input = Input::default();
+        input.handle(&WindowEvent::CursorMoved{device_id, position : winit::dpi::PhysicalPosition::new(200.0, 100.0), modifiers : ModifiersState::empty()}, 2.0);
+        input.handle(&WindowEvent::ModifiersChanged(ModifiersState::SHIFT), 2.0);
+        input.handle(&WindowEvent::MouseInput{device_id, state : ElementState::Pressed, button : MouseButton::Left, modifiers : ModifiersState::empty()}, 2.0);
+        input.handle(&WindowEvent::ReceivedCharacter('a'), 2.0);
+        input.handle(&WindowEvent::ReceivedCharacter('\u{8}'), 2.0);
+        input.handle(&WindowEvent::MouseWheel{device_id, delta : MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition::new(0.0, 30.0)), phase : winit::event::TouchPhase::Moved, modifiers : ModifiersState::empty()}, 2.0);
+        let shift = egui::Modifiers{shift : true, ..Default::default()};
+        assert_eq!(input.events, vec!(
+            egui::Event::PointerMoved(egui::pos2(100.0, 50.0)),
+            egui::Event::PointerButton{pos : egui::pos2(100.0, 50.0), button : egui::PointerButton::Primary, pressed : true, modifiers : shift},
+            egui::Event::Text(String::from("a")),
+            egui::Event::MouseWheel{unit : egui::MouseWheelUnit::Point, delta : egui::vec2(0.0, 15.0), modifiers : shift},
+        ));
+        let raw_input = input.take(egui::vec2(400.0, 300.0), 2.0, 1.5);
+        assert_eq!(raw_input.events.len(), 4);
+        assert!(input.events.is_empty());
+        assert_eq!(raw_input.screen_rect, Some(egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(400.0, 300.0))));
+        assert_eq!(raw_input.viewports[&egui::ViewportId::ROOT].native_pixels_per_point, Some(2.0));
+    }
+    #[test]
+    fn keys(){
+        assert_eq!(key(VirtualKeyCode::Back), Some(egui::Key::Backspace));
+        assert_eq!(key(VirtualKeyCode::NumpadEnter), Some(egui::Key::Enter));
+        assert_eq!(key(VirtualKeyCode::F1), None);
+        assert!(modifiers(ModifiersState::CTRL).ctrl);
+        assert_eq!(pointer_button(MouseButton::Other(4)), None);
+    }
+}
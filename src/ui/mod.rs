@@ -0,0 +1,304 @@
+mod input;
+
+pub use input::Input;
+
+use cgmath::Vector3;
+
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+use super::camera::Camera;
+use super::denoise::DenoiseSettings;
+use super::material::MaterialKind;
+use super::material::Texture;
+use super::post::Exposure;
+use super::post::PostSettings;
+use super::post::TONEMAPPERS;
+use super::profiler::Profiler;
+use super::profiler::RollingStats;
+use super::renderer;
+use super::scene::Sphere;
+
+///What the renderer needs to draw one overlay frame.
+pub struct OverlayFrame{
+    pub textures_delta : egui::TexturesDelta,
+    pub primitives : Vec<egui::ClippedPrimitive>,
+    pub pixels_per_point : f32,
+}
+///The debug overlay of the viewer, feeds window input to egui while visible.
+pub struct DebugUi{
+    pub visible : bool,
+    context : egui::Context,
+    input : Input,
+    start : std::time::Instant,
+}
+impl DebugUi{
+    pub fn new(visible : bool) -> Self{
+        return Self{visible, context : egui::Context::default(), input : Input{focused : true, ..Default::default()}, start : std::time::Instant::now()};
+    }
+    ///Returns true when the event was consumed by the overlay, such as clicks on a panel or typing into a field.
+    pub fn handle_window_event(&mut self , window : &Window , event : &WindowEvent) -> bool{
+        if !self.visible{return false;}
+        self.input.handle(event, window.scale_factor() as f32);
+        match event{
+            WindowEvent::MouseInput{..} | WindowEvent::MouseWheel{..} => return self.context.wants_pointer_input(),
+            WindowEvent::KeyboardInput{..} | WindowEvent::ReceivedCharacter(_) => return self.context.wants_keyboard_input(),
+            _ => return false,
+        }
+    }
+    ///Builds the next overlay frame, textures egui already uploaded are kept by the renderer.
+    pub fn run<F : FnMut(&egui::Context)>(&mut self , window : &Window , build : F) -> OverlayFrame{
+        let pixels_per_point = window.scale_factor() as f32;
+        let size = window.inner_size();
+        let raw_input = self.input.take(egui::vec2(size.width as f32, size.height as f32) / pixels_per_point, pixels_per_point, self.start.elapsed().as_secs_f64());
+        let output = self.context.run(raw_input, build);
+        let primitives = self.context.tessellate(output.shapes, output.pixels_per_point);
+        return OverlayFrame{textures_delta : output.textures_delta, primitives, pixels_per_point : output.pixels_per_point};
+    }
+}
+///What an edit in the debug window changed.
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct DebugChanges{
+    ///The camera or a material changed, accumulation has to restart.
+    pub scene : bool,
+    pub camera : bool,
+    pub denoiser : bool,
+    pub profiling : Option<bool>,
+    pub present_mode : Option<ash::vk::PresentModeKHR>,
+}
+impl DebugChanges{
+    ///Combines the changes of several passes over the window.
+    pub fn merge(&mut self , other : DebugChanges){
+        self.scene |= other.scene;
+        self.camera |= other.camera;
+        self.denoiser |= other.denoiser;
+        self.profiling = other.profiling.or(self.profiling);
+        self.present_mode = other.present_mode.or(self.present_mode);
+    }
+}
+///The state the debug window shows and edits.
+pub struct DebugView<'a>{
+    pub device_info : &'a [String],
+    ///Time between frames in milliseconds.
+    pub frame_times : &'a RollingStats,
+    pub profiler : &'a Profiler,
+    pub samples : u32,
+    pub camera : &'a mut Camera,
+    pub spheres : &'a mut [Sphere],
+    pub post : &'a mut PostSettings,
+    pub denoise : &'a mut DenoiseSettings,
+    pub requested_present_mode : ash::vk::PresentModeKHR,
+    pub present_mode : ash::vk::PresentModeKHR,
+}
+impl<'a> DebugView<'a>{
+    pub fn show(&mut self , context : &egui::Context) -> DebugChanges{
+        let mut changes = DebugChanges::default();
+        egui::Window::new("Debug").default_width(320.0).show(context, |ui|{
+            egui::CollapsingHeader::new("Device").show(ui, |ui|{
+                for line in self.device_info.iter().filter(|line| !line.is_empty()){
+                    ui.label(line.as_str());
+                }
+            });
+            egui::CollapsingHeader::new("Timings").default_open(true).show(ui, |ui|{
+                let mean = self.frame_times.mean();
+                ui.label(format!("Frame : {:.2} ms ({:.1} fps), {:.2} min, {:.2} max.",mean,if mean > 0.0{1000.0 / mean}else{0.0},self.frame_times.min(),self.frame_times.max()));
+                ui.label(format!("Samples per pixel : {}.",self.samples));
+                let mut profiling = self.profiler.enabled;
+                if ui.checkbox(&mut profiling, "Profile scopes").changed(){
+                    changes.profiling = Some(profiling);
+                }
+                for (name,track,stats) in self.profiler.scopes(){
+                    ui.label(format!("{} {} : {:.3} ms mean, {:.3} max.",track.name(),name,stats.mean(),stats.max()));
+                }
+            });
+            egui::CollapsingHeader::new("Camera").show(ui, |ui|{
+                changes.camera = camera_controls(ui, self.camera);
+            });
+            egui::CollapsingHeader::new("Materials").show(ui, |ui|{
+                for (index,sphere) in self.spheres.iter_mut().enumerate(){
+                    egui::CollapsingHeader::new(format!("Sphere {} : {}",index,kind_name(&sphere.material.kind))).show(ui, |ui|{
+                        changes.scene |= material_controls(ui, index, &mut sphere.material.kind, &mut sphere.material.emission);
+                    });
+                }
+            });
+            egui::CollapsingHeader::new("Post").show(ui, |ui|{
+                post_controls(ui, self.post);
+                let mut denoise = *self.denoise;
+                ui.checkbox(&mut denoise.enabled, "Denoise");
+                ui.add(egui::Slider::new(&mut denoise.strength, super::denoise::MIN_STRENGTH..=super::denoise::MAX_STRENGTH).text("Strength"));
+                ui.checkbox(&mut denoise.compute, "Filter on the gpu");
+                changes.denoiser = denoise != *self.denoise;
+                *self.denoise = denoise;
+            });
+            egui::CollapsingHeader::new("Presentation").show(ui, |ui|{
+                let mut present_mode = self.requested_present_mode;
+                egui::ComboBox::from_label("Present mode").selected_text(renderer::present_mode_name(present_mode)).show_ui(ui, |ui|{
+                    for &mode in renderer::PRESENT_MODES.iter(){
+                        ui.selectable_value(&mut present_mode, mode, renderer::present_mode_name(mode));
+                    }
+                });
+                ui.label(format!("Using : {}.",renderer::present_mode_name(self.present_mode)));
+                if present_mode != self.requested_present_mode{
+                    changes.present_mode = Some(present_mode);
+                }
+            });
+        });
+        changes.scene |= changes.camera;
+        return changes;
+    }
+}
+fn kind_name(kind : &MaterialKind) -> &'static str{
+    match kind{
+        MaterialKind::Lambert{..} => "lambert",
+        MaterialKind::Conductor{..} => "conductor",
+        MaterialKind::Dielectric{..} => "dielectric",
+        MaterialKind::ThinDielectric{..} => "thin dielectric",
+        MaterialKind::Principled{..} => "principled",
+    }
+}
+fn drag(ui : &mut egui::Ui , label : &str , value : &mut f32 , speed : f32 , range : std::ops::RangeInclusive<f32>) -> bool{
+    return ui.horizontal(|ui|{
+        ui.label(label);
+        ui.add(egui::DragValue::new(value).speed(speed).range(range)).changed()
+    }).inner;
+}
+fn drag_vector(ui : &mut egui::Ui , label : &str , vector : &mut Vector3<f32> , speed : f32 , range : std::ops::RangeInclusive<f32>) -> bool{
+    return ui.horizontal(|ui|{
+        ui.label(label);
+        let mut changed = false;
+        for value in [&mut vector.x,&mut vector.y,&mut vector.z]{
+            changed |= ui.add(egui::DragValue::new(value).speed(speed).range(range.clone())).changed();
+        }
+        changed
+    }).inner;
+}
+///Returns true when the camera was edited, the ranges match what the camera controller allows.
+pub fn camera_controls(ui : &mut egui::Ui , camera : &mut Camera) -> bool{
+    let mut changed = drag_vector(ui, "Position", &mut camera.position, 0.05, f32::MIN..=f32::MAX);
+    let mut yaw = camera.yaw.to_degrees();
+    let mut pitch = camera.pitch.to_degrees();
+    if drag(ui, "Yaw", &mut yaw, 0.5, -360.0..=360.0){
+        camera.yaw = yaw.to_radians();
+        changed = true;
+    }
+    if drag(ui, "Pitch", &mut pitch, 0.5, -89.0..=89.0){
+        camera.pitch = pitch.to_radians();
+        changed = true;
+    }
+    changed |= drag(ui, "Field of view", &mut camera.fov, 0.5, 5.0..=150.0);
+    changed |= drag(ui, "Aperture", &mut camera.aperture, 0.005, 0.0..=10.0);
+    changed |= drag(ui, "Focus distance", &mut camera.focus_distance, 0.05, 0.01..=f32::MAX);
+    return changed;
+}
+///Edits constant and checker textures, image textures are only named.
+fn texture_controls(ui : &mut egui::Ui , label : &str , texture : &mut Texture , color : bool) -> bool{
+    let edit = |ui : &mut egui::Ui , label : &str , value : &mut Vector3<f32>| -> bool{
+        if color{
+            let mut rgb = [value.x,value.y,value.z];
+            let changed = ui.horizontal(|ui|{
+                ui.label(label);
+                ui.color_edit_button_rgb(&mut rgb).changed()
+            }).inner;
+            *value = Vector3::from(rgb);
+            return changed;
+        }
+        let mut scalar = value.x;
+        let changed = drag(ui, label, &mut scalar, 0.01, 0.0..=1.0);
+        *value = Vector3::new(scalar,scalar,scalar);
+        return changed;
+    };
+    match texture{
+        Texture::Constant(value) => return edit(ui, label, value),
+        Texture::Checker{even,odd,scale} => {
+            let mut changed = edit(ui, &format!("{} even",label), even);
+            changed |= edit(ui, &format!("{} odd",label), odd);
+            changed |= drag(ui, &format!("{} scale",label), scale, 0.1, 0.01..=1000.0);
+            return changed;
+        }
+        Texture::Image(image) => {
+            ui.label(format!("{} : {}",label,image.path.display()));
+            return false;
+        }
+    }
+}
+///Returns true when a parameter of the material was edited.
+pub fn material_controls(ui : &mut egui::Ui , index : usize , kind : &mut MaterialKind , emission : &mut Texture) -> bool{
+    ui.push_id(index, |ui|{
+        let mut changed = match kind{
+            MaterialKind::Lambert{albedo} => texture_controls(ui, "Albedo", albedo, true),
+            MaterialKind::Conductor{color,roughness} => texture_controls(ui, "Color", color, true) | texture_controls(ui, "Roughness", roughness, false),
+            MaterialKind::Dielectric{ior,roughness,tint} => drag(ui, "Ior", ior, 0.01, 1.0..=3.0) | texture_controls(ui, "Roughness", roughness, false) | texture_controls(ui, "Tint", tint, true),
+            MaterialKind::ThinDielectric{ior,tint} => drag(ui, "Ior", ior, 0.01, 1.0..=3.0) | texture_controls(ui, "Tint", tint, true),
+            MaterialKind::Principled{base_color,metallic,roughness,specular,transmission,ior} => {
+                texture_controls(ui, "Base color", base_color, true)
+                    | texture_controls(ui, "Metallic", metallic, false)
+                    | texture_controls(ui, "Roughness", roughness, false)
+                    | texture_controls(ui, "Specular", specular, false)
+                    | texture_controls(ui, "Transmission", transmission, false)
+                    | drag(ui, "Ior", ior, 0.01, 1.0..=3.0)
+            }
+        };
+        //Emission is radiance and not limited to [0,1].
+        match emission{
+            Texture::Constant(value) => changed |= drag_vector(ui, "Emission", value, 0.05, 0.0..=f32::MAX),
+            emission => changed |= texture_controls(ui, "Emission", emission, true),
+        }
+        changed
+    }).inner
+}
+///Edits the tonemapper, exposure and white balance, none of them need accumulation to restart.
+pub fn post_controls(ui : &mut egui::Ui , settings : &mut PostSettings){
+    egui::ComboBox::from_label("Tonemapper").selected_text(settings.tonemapper.name()).show_ui(ui, |ui|{
+        for &tonemapper in TONEMAPPERS.iter(){
+            ui.selectable_value(&mut settings.tonemapper, tonemapper, tonemapper.name());
+        }
+    });
+    let (mut auto,mut stops) = match settings.exposure{
+        Exposure::Manual(stops) => (false,stops),
+        Exposure::Auto(compensation) => (true,compensation),
+    };
+    ui.checkbox(&mut auto, "Auto exposure");
+    drag(ui, if auto{"Compensation"}else{"Exposure"}, &mut stops, 0.05, -20.0..=20.0);
+    settings.exposure = if auto{Exposure::Auto(stops)}else{Exposure::Manual(stops)};
+    drag(ui, "Temperature", &mut settings.temperature, 10.0, 1000.0..=40000.0);
+    drag(ui, "Tint", &mut settings.tint, 0.01, -1.0..=1.0);
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::material::Material;
+
+    #[test]
+    fn debug_window(){
+        let context = egui::Context::default();
+        let mut camera = Camera::look_at(Vector3::new(0.0,0.0,5.0), Vector3::new(0.0,0.0,0.0), 45.0);
+        let mut spheres = vec!(Sphere{center : Vector3::new(0.0,0.0,0.0), radius : 1.0, material : Material::lambert([0.5,0.5,0.5])});
+        let mut post = PostSettings::default();
+        let mut denoise = DenoiseSettings::default();
+        let mut frame_times = RollingStats::default();
+        frame_times.push(16.0);
+        let profiler = Profiler::new(false);
+        let device_info = vec!(String::from("Using device : test."));
+        let mut changes = DebugChanges::default();
+        let mut run = || context.run(egui::RawInput::default(), |context| changes.merge(DebugView{
+            device_info : &device_info,
+            frame_times : &frame_times,
+            profiler : &profiler,
+            samples : 4,
+            camera : &mut camera,
+            spheres : &mut spheres,
+            post : &mut post,
+            denoise : &mut denoise,
+            requested_present_mode : ash::vk::PresentModeKHR::MAILBOX,
+            present_mode : ash::vk::PresentModeKHR::FIFO,
+        }.show(context)));
+        //Windows are sized on their first frame and only drawn from the second.
+        let first = run();
+        let second = run();
+        assert_eq!(first.textures_delta.set.len(), 1);
+        assert!(!context.tessellate(second.shapes, second.pixels_per_point).is_empty());
+        //Nothing was clicked, so nothing changed.
+        assert_eq!(changes, DebugChanges::default());
+    }
+}
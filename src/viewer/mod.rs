@@ -0,0 +1,344 @@
+//!The interactive viewer, traces on the cpu or on the gpu compute queue and shows the accumulation through the renderer.
+
+use std::path::PathBuf;
+
+use super::renderer;
+use super::tracer;
+use super::post;
+use super::output;
+use super::camera;
+use super::window;
+use super::denoise;
+use super::profiler;
+use super::ui;
+use super::config::Config;
+use super::scene::Scene;
+
+use winit::event_loop::EventLoop;
+use winit::event_loop::ControlFlow;
+use winit::event::Event;
+use winit::event::WindowEvent;
+use winit::event::DeviceEvent;
+use winit::event::ElementState;
+use winit::event::VirtualKeyCode;
+use winit::event::ModifiersState;
+
+///How long the viewer traces per frame, a pass that takes longer is continued next frame and its finished tiles are shown meanwhile.
+const TRACE_BUDGET : std::time::Duration = std::time::Duration::from_millis(30);
+
+///What the viewer needs besides the configuration.
+#[derive(Clone,Debug,Default)]
+pub struct ViewOptions{
+    ///Scene file or built in scene name, the default scene when unset.
+    pub scene : Option<String>,
+    ///Aovs recorded from the start, O cycles through them.
+    pub aovs : Vec<tracer::Aov>,
+    ///Profile from the start and write a Chrome trace to this file on exit.
+    pub profile : Option<PathBuf>,
+}
+
+///Opens the interactive viewer, only returns when the scene can not be loaded or the window can not be presented to since the event loop exits the process.
+pub fn run(config : &Config , options : &ViewOptions) -> Result<(),String>{
+    let mut scene = Scene::load(options.scene.as_deref().unwrap_or("default"))?;
+    let event_loop = EventLoop::new();
+    let (width,height) = config.window_size();
+    let mut fullscreen = config.fullscreen();
+    let settings = config.renderer_settings();
+    let window = window::create_window(&event_loop, &settings.app_info.name, width, height, fullscreen);
+    let mut renderer = renderer::Renderer::new(&window, &settings)?;
+    if let Some(fallback) = renderer.device_fallback(){
+        println!("{}",fallback);
+    }
+    let gpu_tracing = config.gpu_tracing();
+    if gpu_tracing{
+        if let Err(error) = renderer.set_scene(&scene){
+            println!("{}",error);
+        }
+    }
+    let mut render_scale = config.render_scale();
+    if render_scale != 1.0 && !renderer.supports_scaling(){
+        println!("The swapchain format can not be scaled, rendering at the window resolution.");
+        render_scale = 1.0;
+    }
+    let mut modifiers = ModifiersState::empty();
+    let mut title_stats = window::TitleStats::new(&settings.app_info.name);
+    let mut camera_controller = camera::CameraController::new(&scene.camera);
+    let mut last_frame = std::time::Instant::now();
+    let mut tracer = tracer::Tracer::new(renderer.swapchain_extent().width, renderer.swapchain_extent().height);
+    tracer.set_sampler(config.sampler_settings());
+    for &aov in options.aovs.iter(){
+        tracer.enable_aov(aov);
+    }
+    let mut post_process = post::PostProcess::new(config.post_settings());
+    let mut denoiser = denoise::Denoiser::new(config.denoise_settings());
+    if denoiser.settings.enabled{
+        denoise::enable_guides(&mut tracer);
+    }
+    let profile_path = options.profile.clone();
+    let mut profiler = profiler::Profiler::new(profile_path.is_some());
+    renderer.set_profiling(profiler.enabled);
+    let mut debug_ui = ui::DebugUi::new(false);
+    let mut frame_times = profiler::RollingStats::default();
+    //The aov shown instead of the beauty.
+    let mut shown_aov : Option<tracer::Aov> = None;
+    let mut first_loop = true;
+    event_loop.run(move |event,_,control_flow|{
+        *control_flow = ControlFlow::Poll;
+        if first_loop{
+            first_loop = false;
+            renderer.show_create_info();
+        }
+        match event{
+            Event::WindowEvent{
+                event : WindowEvent::CloseRequested,
+                ..
+            } => {
+                *control_flow = ControlFlow::Exit;
+                if let Some(path) = &profile_path{
+                    for gpu_frame in renderer.take_gpu_frames(){
+                        profiler.add_gpu_frame(&gpu_frame);
+                    }
+                    print!("{}",profiler.report());
+                    match profiler.save_chrome_trace(path){
+                        Ok(()) => println!("Saved {}.",path.display()),
+                        Err(error) => println!("{}",error),
+                    }
+                }
+            }
+            Event::WindowEvent{
+                event : WindowEvent::KeyboardInput{input,..},
+                ..
+            } if input.state == ElementState::Pressed && input.virtual_keycode == Some(VirtualKeyCode::F1) => {
+                debug_ui.visible = !debug_ui.visible;
+                if !debug_ui.visible{
+                    renderer.update_overlay(&egui::TexturesDelta::default(), vec!(), 1.0);
+                }
+            }
+            //The overlay sees every window event while visible and keeps those meant for its panels.
+            Event::WindowEvent{
+                ref event,
+                ..
+            } if debug_ui.handle_window_event(&window, event) => {}
+            Event::WindowEvent{
+                event : WindowEvent::Resized(_),
+                ..
+            } => {
+                renderer.resize();
+            }
+            //The suggested size keeps the logical window size, only the swapchain has to follow.
+            Event::WindowEvent{
+                event : WindowEvent::ScaleFactorChanged{..},
+                ..
+            } => {
+                renderer.resize();
+            }
+            Event::WindowEvent{
+                event : WindowEvent::ModifiersChanged(state),
+                ..
+            } => {
+                modifiers = state;
+            }
+            Event::WindowEvent{
+                ref event,
+                ..
+            } if camera_controller.handle_window_event(event) => {}
+            Event::DeviceEvent{
+                event : ref device_event @ DeviceEvent::MouseMotion{..},
+                ..
+            } => {
+                camera_controller.handle_device_event(device_event);
+            }
+            Event::WindowEvent{
+                event : WindowEvent::KeyboardInput{input,..},
+                ..
+            } if input.state == ElementState::Pressed && input.virtual_keycode == Some(VirtualKeyCode::V) => {
+                renderer.set_present_mode(renderer::next_present_mode(renderer.requested_present_mode()));
+                println!("Requested present mode : {}, using : {}.",renderer::present_mode_name(renderer.requested_present_mode()),renderer::present_mode_name(renderer.present_mode()));
+            }
+            Event::WindowEvent{
+                event : WindowEvent::KeyboardInput{input,..},
+                ..
+            } if input.state == ElementState::Pressed && (input.virtual_keycode == Some(VirtualKeyCode::F11) || (input.virtual_keycode == Some(VirtualKeyCode::Return) && modifiers.alt())) => {
+                let mode = if input.virtual_keycode == Some(VirtualKeyCode::F11){window::Fullscreen::Borderless}else{window::Fullscreen::Exclusive};
+                fullscreen = if fullscreen == mode{window::Fullscreen::Windowed}else{mode};
+                window::set_fullscreen(&window, fullscreen);
+                renderer.resize();
+            }
+            Event::WindowEvent{
+                event : WindowEvent::KeyboardInput{input,..},
+                ..
+            } if input.state == ElementState::Pressed && matches!(input.virtual_keycode, Some(VirtualKeyCode::F5) | Some(VirtualKeyCode::F6)) => {
+                if !renderer.supports_scaling(){
+                    println!("The swapchain format can not be scaled.");
+                    return;
+                }
+                let step = if input.virtual_keycode == Some(VirtualKeyCode::F5){-0.25}else{0.25};
+                render_scale = (render_scale + step).clamp(window::MIN_RENDER_SCALE, window::MAX_RENDER_SCALE);
+                println!("Render scale : {}.",render_scale);
+            }
+            Event::WindowEvent{
+                event : WindowEvent::KeyboardInput{input,..},
+                ..
+            } if input.state == ElementState::Pressed && input.virtual_keycode == Some(VirtualKeyCode::O) => {
+                shown_aov = match shown_aov{
+                    None => Some(tracer::AOVS[0]),
+                    Some(aov) => tracer::AOVS.iter().position(|&other| other == aov).and_then(|index| tracer::AOVS.get(index + 1)).copied(),
+                };
+                if let Some(aov) = shown_aov{
+                    tracer.enable_aov(aov);
+                }
+                println!("Showing : {}.",shown_aov.map_or("beauty", |aov| aov.name()));
+            }
+            Event::WindowEvent{
+                event : WindowEvent::KeyboardInput{input,..},
+                ..
+            } if input.state == ElementState::Pressed && input.virtual_keycode == Some(VirtualKeyCode::P) => {
+                profiler.enabled = !profiler.enabled;
+                renderer.set_profiling(profiler.enabled);
+                if profiler.enabled{
+                    println!("Profiling{}.",if renderer.supports_timestamps(){""}else{", the gpu has no timestamps"});
+                } else{
+                    print!("{}",profiler.report());
+                }
+            }
+            Event::WindowEvent{
+                event : WindowEvent::KeyboardInput{input,..},
+                ..
+            } if input.state == ElementState::Pressed && matches!(input.virtual_keycode, Some(VirtualKeyCode::N) | Some(VirtualKeyCode::F7) | Some(VirtualKeyCode::F8)) => {
+                let settings = &mut denoiser.settings;
+                match input.virtual_keycode{
+                    Some(VirtualKeyCode::N) => settings.enabled = !settings.enabled,
+                    Some(VirtualKeyCode::F7) => settings.strength = (settings.strength - 0.25).max(denoise::MIN_STRENGTH),
+                    _ => settings.strength = (settings.strength + 0.25).min(denoise::MAX_STRENGTH),
+                }
+                if settings.enabled{
+                    denoise::enable_guides(&mut tracer);
+                }
+                println!("Denoiser : {}, strength : {}.",if denoiser.settings.enabled{"on"}else{"off"},denoiser.settings.strength);
+            }
+            Event::WindowEvent{
+                event : WindowEvent::KeyboardInput{input,..},
+                ..
+            } if input.state == ElementState::Pressed => {
+                let exposure = post_process.exposure();
+                let settings = &mut post_process.settings;
+                match input.virtual_keycode{
+                    Some(VirtualKeyCode::R) => {
+                        tracer.reset();
+                        denoiser.reset();
+                    }
+                    Some(VirtualKeyCode::F12) => {
+                        let path = format!("mport_{}spp.exr",tracer.accumulator().samples());
+                        let options = output::OutputOptions{precision : output::Precision::Full, post : post_process.settings};
+                        let beauty = denoiser.process(tracer.accumulator(), &scene.camera);
+                        match output::save(std::path::Path::new(&path), tracer.accumulator(), &beauty, &options){
+                            Ok(()) => println!("Saved {}.",path),
+                            Err(error) => println!("{}",error),
+                        }
+                        return;
+                    }
+                    Some(VirtualKeyCode::T) => settings.tonemapper = settings.tonemapper.next(),
+                    Some(VirtualKeyCode::E) => settings.exposure = match settings.exposure{
+                        post::Exposure::Manual(_) => post::Exposure::Auto(0.0),
+                        post::Exposure::Auto(_) => post::Exposure::Manual(exposure),
+                    },
+                    Some(VirtualKeyCode::Equals) | Some(VirtualKeyCode::NumpadAdd) => settings.exposure = match settings.exposure{
+                        post::Exposure::Manual(stops) => post::Exposure::Manual(stops + 0.5),
+                        post::Exposure::Auto(compensation) => post::Exposure::Auto(compensation + 0.5),
+                    },
+                    Some(VirtualKeyCode::Minus) | Some(VirtualKeyCode::NumpadSubtract) => settings.exposure = match settings.exposure{
+                        post::Exposure::Manual(stops) => post::Exposure::Manual(stops - 0.5),
+                        post::Exposure::Auto(compensation) => post::Exposure::Auto(compensation - 0.5),
+                    },
+                    _ => return,
+                }
+                println!("Tonemapper : {}, exposure : {:?}.",post_process.settings.tonemapper.name(),post_process.settings.exposure);
+            }
+            Event::MainEventsCleared => {
+                let now = std::time::Instant::now();
+                let delta_time = (now - last_frame).as_secs_f32();
+                last_frame = now;
+                frame_times.push(delta_time * 1000.0);
+                if camera_controller.update(&mut scene.camera, delta_time){
+                    tracer.reset();
+                }
+                if debug_ui.visible{
+                    let device_info = renderer.create_info();
+                    let (requested_present_mode,present_mode) = (renderer.requested_present_mode(),renderer.present_mode());
+                    let samples = tracer.accumulator().samples();
+                    let mut changes = ui::DebugChanges::default();
+                    let overlay = debug_ui.run(&window, |context| changes.merge(ui::DebugView{
+                        device_info : &device_info,
+                        frame_times : &frame_times,
+                        profiler : &profiler,
+                        samples,
+                        camera : &mut scene.camera,
+                        spheres : &mut scene.spheres,
+                        post : &mut post_process.settings,
+                        denoise : &mut denoiser.settings,
+                        requested_present_mode,
+                        present_mode,
+                    }.show(context)));
+                    renderer.update_overlay(&overlay.textures_delta, overlay.primitives, overlay.pixels_per_point);
+                    if changes.camera{
+                        camera_controller.reset(&scene.camera);
+                    }
+                    if changes.scene{
+                        tracer.reset();
+                        denoiser.reset();
+                        if gpu_tracing{
+                            if let Err(error) = renderer.set_scene(&scene){
+                                println!("{}",error);
+                            }
+                        }
+                    }
+                    if changes.denoiser && denoiser.settings.enabled{
+                        denoise::enable_guides(&mut tracer);
+                    }
+                    if let Some(profiling) = changes.profiling{
+                        profiler.enabled = profiling;
+                        renderer.set_profiling(profiling);
+                    }
+                    if let Some(present_mode) = changes.present_mode{
+                        renderer.set_present_mode(present_mode);
+                    }
+                }
+                let extent = renderer.swapchain_extent();
+                let render_extent = window::render_extent(extent, render_scale);
+                tracer.resize(render_extent.width, render_extent.height);
+                //The tracer adds its samples straight into the accumulator, so accumulation is part of the trace scope.
+                //A gpu pass is traced while the frames after it are drawn, its samples show up once its frame comes around again.
+                let traced = profiler.scope("trace", || {
+                    if !gpu_tracing{
+                        return tracer.render_for(&scene, TRACE_BUDGET);
+                    }
+                    return renderer.trace_pass(&scene.camera, &mut tracer);
+                });
+                if traced{
+                    title_stats.add_pass(render_extent);
+                }
+                let format = renderer.swapchain_format();
+                let frame = match shown_aov.and_then(|aov| tracer.accumulator().resolve_aov(aov).map(|pixels| (aov,pixels))){
+                    Some((aov,pixels)) if !aov.is_radiance() => profiler.scope("tonemap", || post_process.encode(&aov.preview(&pixels), format)),
+                    Some((_,pixels)) => profiler.scope("tonemap", || post_process.process(&pixels, format)),
+                    None => {
+                        let beauty = if denoiser.settings.compute{
+                            profiler.scope("denoise", || denoiser.process_with(tracer.accumulator(), &scene.camera, |frame,illumination,strength| renderer.filter_illumination(frame, illumination, strength)))
+                        }else{
+                            profiler.scope("denoise", || denoiser.process(tracer.accumulator(), &scene.camera))
+                        };
+                        profiler.scope("tonemap", || post_process.process(&beauty, format))
+                    }
+                };
+                //The swapchain is only created with formats the post process pass can encode.
+                let frame = frame.expect("Failed to encode the frame in the swapchain format.");
+                profiler.scope("draw", || renderer.draw_frame(&frame, render_extent));
+                for gpu_frame in renderer.take_gpu_frames(){
+                    profiler.add_gpu_frame(&gpu_frame);
+                }
+                title_stats.update(&window, extent, render_extent, tracer.accumulator().samples());
+            }
+            _ => {}
+        }
+    })
+}
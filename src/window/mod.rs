@@ -0,0 +1,107 @@
+use winit::dpi::LogicalSize;
+use winit::event_loop::EventLoop;
+use winit::window::Window;
+use winit::window::WindowBuilder;
+
+///How often the title statistics are refreshed.
+const TITLE_INTERVAL : f32 = 0.5;
+pub const MIN_RENDER_SCALE : f32 = 0.25;
+pub const MAX_RENDER_SCALE : f32 = 2.0;
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Fullscreen{
+    Windowed,
+    ///A window covering the current monitor, switching is instant and other windows can overlap it.
+    Borderless,
+    ///Takes over the display with the largest and fastest video mode of the current monitor.
+    Exclusive,
+}
+pub const FULLSCREEN_MODES : [Fullscreen;3] = [Fullscreen::Windowed,Fullscreen::Borderless,Fullscreen::Exclusive];
+
+impl Fullscreen{
+    pub fn name(self) -> &'static str{
+        match self{
+            Fullscreen::Windowed => "windowed",
+            Fullscreen::Borderless => "borderless",
+            Fullscreen::Exclusive => "exclusive",
+        }
+    }
+    pub fn parse(name : &str) -> Option<Self>{
+        return FULLSCREEN_MODES.iter().copied().find(|mode| mode.name().eq_ignore_ascii_case(name.trim()));
+    }
+}
+///Creates the viewer window, the size is in logical pixels so it looks the same on hidpi displays.
+pub fn create_window(event_loop : &EventLoop<()> , title : &str , width : u32 , height : u32 , fullscreen : Fullscreen) -> Window{
+    let window = WindowBuilder::new()
+        .with_title(title)
+        .with_inner_size(LogicalSize::new(width, height))
+        .build(event_loop)
+        .expect("Failed to create window.");
+    set_fullscreen(&window, fullscreen);
+    return window;
+}
+pub fn set_fullscreen(window : &Window , mode : Fullscreen){
+    let fullscreen = match mode{
+        Fullscreen::Windowed => None,
+        Fullscreen::Borderless => Some(winit::window::Fullscreen::Borderless(window.current_monitor())),
+        Fullscreen::Exclusive => {
+            let video_mode = window.current_monitor().and_then(|monitor| monitor.video_modes().max_by_key(|mode| (mode.size().width * mode.size().height,mode.refresh_rate(),mode.bit_depth())));
+            match video_mode{
+                Some(video_mode) => Some(winit::window::Fullscreen::Exclusive(video_mode)),
+                None => {
+                    println!("No exclusive video mode is available, using borderless fullscreen.");
+                    Some(winit::window::Fullscreen::Borderless(window.current_monitor()))
+                }
+            }
+        }
+    };
+    window.set_fullscreen(fullscreen);
+}
+///The size the tracer renders at, the swapchain scales it to the window.
+pub fn render_extent(extent : ash::vk::Extent2D , render_scale : f32) -> ash::vk::Extent2D{
+    return ash::vk::Extent2D{
+        width : ((extent.width as f32 * render_scale).round() as u32).max(1),
+        height : ((extent.height as f32 * render_scale).round() as u32).max(1),
+    };
+}
+///Counts the samples traced since the last title update and writes throughput and resolution to the title.
+pub struct TitleStats{
+    title : String,
+    last_update : std::time::Instant,
+    passes : u32,
+    samples : u64,
+}
+impl TitleStats{
+    ///The title is shown in front of the statistics.
+    pub fn new(title : &str) -> Self{
+        return Self{
+            title : String::from(title),
+            last_update : std::time::Instant::now(),
+            passes : 0,
+            samples : 0,
+        };
+    }
+    ///Records one pass over the render extent.
+    pub fn add_pass(&mut self , render_extent : ash::vk::Extent2D){
+        self.passes += 1;
+        self.samples += render_extent.width as u64 * render_extent.height as u64;
+    }
+    pub fn update(&mut self , window : &Window , window_extent : ash::vk::Extent2D , render_extent : ash::vk::Extent2D , accumulated : u32){
+        let elapsed = self.last_update.elapsed().as_secs_f32();
+        if elapsed < TITLE_INTERVAL{return;}
+        let title = format!(
+            "{} - {}x{} (render {}x{}, hidpi {:.2}x) - {:.1} spp/s, {:.2} Msamples/s - {} spp",
+            self.title,
+            window_extent.width,window_extent.height,
+            render_extent.width,render_extent.height,
+            window.scale_factor(),
+            self.passes as f32 / elapsed,
+            self.samples as f32 / elapsed / 1e6,
+            accumulated,
+        );
+        window.set_title(&title);
+        self.last_update = std::time::Instant::now();
+        self.passes = 0;
+        self.samples = 0;
+    }
+}
@@ -1,3 +0,0 @@
-fn main() {
-	println!("Succes Zero errors and warings where found!");
-}
\ No newline at end of file
@@ -1,4 +0,0 @@
-fn main() {
-	println!("Succes Zero errors and warings where found!");
-	//unimplemented!();
-}
\ No newline at end of file
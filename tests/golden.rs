@@ -0,0 +1,234 @@
+#![allow(clippy::needless_return)]
+//Golden image regression tests, every test renders a scene with the mport binary and compares it against tests/golden/<name>.exr.
+//Run with MPORT_UPDATE_GOLDEN=1 to replace the golden images after an intended change, then review the new images before committing them.
+//Only the cpu tracer is covered, the vulkan renderer presents the cpu output and has no headless path to compare yet.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+use exr::prelude::*;
+
+const SIZE : &str = "96x54";
+const SPP : u32 = 64;
+///Images are compared at a quarter of the resolution where the noise is low enough to see a shift in brightness of a few percent.
+const DOWNSAMPLE : usize = 4;
+///Channels are compressed with c / (1 + |c|) before comparing, so highlights do not dominate and every aov shares the same scale.
+const MAX_RMSE : f32 = 0.01;
+///Structural similarity of the compressed beauty luminance.
+const MIN_SSIM : f32 = 0.98;
+///The name, scene and samples per pixel of the beauty renders, dark scenes need more samples to get below the tolerance.
+const SCENES : [(&str,&str,u32);4] = [("default","default",SPP),("materials","materials.toml",SPP),("lights","lights.toml",SPP * 4),("environment","environment.toml",SPP)];
+///Side of the square windows the ssim is averaged over.
+const SSIM_WINDOW : usize = 7;
+
+///The channels of an OpenEXR file by name.
+struct Channels{
+    width : usize,
+    height : usize,
+    channels : Vec<(String,Vec<f32>)>,
+}
+impl Channels{
+    fn read(path : &Path) -> Channels{
+        let image = read_all_flat_layers_from_file(path).unwrap_or_else(|error| panic!("Failed to read {} : {}.",path.display(),error));
+        let layer = &image.layer_data[0];
+        let mut channels : Vec<(String,Vec<f32>)> = layer.channel_data.list.iter().map(|channel| (channel.name.to_string(),channel.sample_data.values_as_f32().collect())).collect();
+        channels.sort_by(|a,b| a.0.cmp(&b.0));
+        return Channels{width : layer.size.width(), height : layer.size.height(), channels};
+    }
+    ///Averages blocks of pixels, the noise of a path traced image drops with the block size while regressions in shading remain.
+    fn downsample(&self , factor : usize) -> Channels{
+        let (width,height) = (self.width / factor,self.height / factor);
+        let channels = self.channels.iter().map(|(name,values)| {
+            let mut blocks = vec!(0.0;width * height);
+            for y in 0..height * factor{
+                for x in 0..width * factor{
+                    blocks[(y / factor) * width + x / factor] += values[y * self.width + x] / (factor * factor) as f32;
+                }
+            }
+            (name.clone(),blocks)
+        }).collect();
+        return Channels{width, height, channels};
+    }
+    fn names(&self) -> Vec<&str>{
+        return self.channels.iter().map(|(name,_)| name.as_str()).collect();
+    }
+    fn get(&self , name : &str) -> &[f32]{
+        return &self.channels.iter().find(|(channel,_)| channel == name).unwrap_or_else(|| panic!("Missing channel {}.",name)).1;
+    }
+    ///Compressed luminance of the beauty.
+    fn luminance(&self) -> Vec<f32>{
+        let (r,g,b) = (self.get("R"),self.get("G"),self.get("B"));
+        return (0..r.len()).map(|i| compress(0.2126 * r[i] + 0.7152 * g[i] + 0.0722 * b[i])).collect();
+    }
+}
+fn golden_dir() -> PathBuf{
+    return Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden");
+}
+fn output_dir() -> PathBuf{
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&dir).expect("Failed to create the output directory.");
+    return dir;
+}
+///Renders headlessly into the target directory, isolated from the user config and MPORT_* variables so only the arguments matter.
+fn render(name : &str , scene : &str , spp : u32 , args : &[&str]) -> PathBuf{
+    let output = output_dir().join(format!("{}.exr",name));
+    let home = output_dir().join("home");
+    let scene = if scene == "default"{String::from(scene)}else{golden_dir().join("scenes").join(scene).display().to_string()};
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mport"));
+    command.arg("render").arg(&scene).arg("--size").arg(SIZE).arg("--spp").arg(spp.to_string()).arg("--half").arg("-o").arg(&output).args(args);
+    command.env("HOME", &home).env("XDG_CONFIG_HOME", &home).env("APPDATA", &home);
+    for (key,_) in std::env::vars().filter(|(key,_)| key.starts_with("MPORT_")){
+        command.env_remove(key);
+    }
+    let result = command.output().expect("Failed to run mport.");
+    assert!(result.status.success(), "Rendering {} failed : {}",name,String::from_utf8_lossy(&result.stderr));
+    return output;
+}
+///Renders the scene and compares every channel with the golden image, a diff image is written next to the render on failure.
+fn check(name : &str , scene : &str , spp : u32 , args : &[&str]){
+    let actual_path = render(name, scene, spp, args);
+    let golden_path = golden_dir().join(format!("{}.exr",name));
+    if std::env::var("MPORT_UPDATE_GOLDEN").is_ok_and(|value| value == "1"){
+        std::fs::copy(&actual_path, &golden_path).expect("Failed to update the golden image.");
+        return;
+    }
+    assert!(golden_path.is_file(), "There is no golden image {}, run the tests with MPORT_UPDATE_GOLDEN=1 to create it.",golden_path.display());
+    compare(name, &actual_path, &golden_path);
+}
+///Compares every channel of a render with a golden image within the tolerances.
+fn compare(name : &str , actual_path : &Path , golden_path : &Path){
+    let actual = Channels::read(actual_path);
+    let golden = Channels::read(golden_path);
+    assert_eq!((actual.width,actual.height), (golden.width,golden.height), "{} changed size",name);
+    assert_eq!(actual.names(), golden.names(), "{} changed channels",name);
+    let (small_actual,small_golden) = (actual.downsample(DOWNSAMPLE),golden.downsample(DOWNSAMPLE));
+    let mut failures = vec!();
+    for ((channel,a),(_,b)) in small_actual.channels.iter().zip(small_golden.channels.iter()){
+        let error = rmse(a, b);
+        if error > MAX_RMSE{
+            failures.push(format!("{} rmse {:.4} > {}",channel,error,MAX_RMSE));
+        }
+    }
+    let similarity = ssim(small_actual.width, small_actual.height, &small_actual.luminance(), &small_golden.luminance());
+    if similarity < MIN_SSIM{
+        failures.push(format!("ssim {:.4} < {}",similarity,MIN_SSIM));
+    }
+    if !failures.is_empty(){
+        let diff_path = output_dir().join(format!("{}.diff.png",name));
+        write_diff(&diff_path, &actual, &golden);
+        panic!("{} differs from {} : {}. Render : {}, diff : {}.",name,golden_path.display(),failures.join(", "),actual_path.display(),diff_path.display());
+    }
+}
+fn compress(c : f32) -> f32{
+    return c / (1.0 + c.abs());
+}
+fn rmse(a : &[f32] , b : &[f32]) -> f32{
+    let sum : f32 = a.iter().zip(b.iter()).map(|(a,b)| (compress(*a) - compress(*b)).powi(2)).sum();
+    return (sum / a.len().max(1) as f32).sqrt();
+}
+///Mean structural similarity over all windows, after Wang et al. 2004.
+fn ssim(width : usize , height : usize , a : &[f32] , b : &[f32]) -> f32{
+    const C1 : f32 = 0.01 * 0.01;
+    const C2 : f32 = 0.03 * 0.03;
+    let mut total = 0.0;
+    let mut windows = 0;
+    for y in 0..=height.saturating_sub(SSIM_WINDOW){
+        for x in 0..=width.saturating_sub(SSIM_WINDOW){
+            let pixels = (y..(y + SSIM_WINDOW).min(height)).flat_map(|y| (x..(x + SSIM_WINDOW).min(width)).map(move |x| y * width + x)).collect::<Vec<_>>();
+            let n = pixels.len() as f32;
+            let mean_a = pixels.iter().map(|&i| a[i]).sum::<f32>() / n;
+            let mean_b = pixels.iter().map(|&i| b[i]).sum::<f32>() / n;
+            let variance_a = pixels.iter().map(|&i| (a[i] - mean_a).powi(2)).sum::<f32>() / n;
+            let variance_b = pixels.iter().map(|&i| (b[i] - mean_b).powi(2)).sum::<f32>() / n;
+            let covariance = pixels.iter().map(|&i| (a[i] - mean_a) * (b[i] - mean_b)).sum::<f32>() / n;
+            total += (2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2) / ((mean_a * mean_a + mean_b * mean_b + C1) * (variance_a + variance_b + C2));
+            windows += 1;
+        }
+    }
+    return total / windows.max(1) as f32;
+}
+///The largest compressed difference over all channels per pixel, red where the render is brighter and blue where it is darker, amplified 4 times.
+fn write_diff(path : &Path , actual : &Channels , golden : &Channels){
+    let mut pixels = vec!(0u8;actual.width * actual.height * 3);
+    for ((_,a),(_,b)) in actual.channels.iter().zip(golden.channels.iter()){
+        for (index,(a,b)) in a.iter().zip(b.iter()).enumerate(){
+            let difference = compress(*a) - compress(*b);
+            let channel = if difference > 0.0{0}else{2};
+            let value = &mut pixels[index * 3 + channel];
+            *value = (*value).max((difference.abs() * 4.0 * 255.0).min(255.0) as u8);
+        }
+    }
+    image::save_buffer(path, &pixels, actual.width as u32, actual.height as u32, image::ColorType::Rgb8).expect("Failed to write the diff image.");
+}
+
+#[test]
+fn default_scene(){
+    let (name,scene,spp) = SCENES[0];
+    check(name, scene, spp, &["--aovs","albedo,normal,depth,object_id"]);
+}
+#[test]
+fn materials(){
+    let (name,scene,spp) = SCENES[1];
+    check(name, scene, spp, &[]);
+}
+#[test]
+fn lights(){
+    let (name,scene,spp) = SCENES[2];
+    check(name, scene, spp, &[]);
+}
+#[test]
+fn environment(){
+    let (name,scene,spp) = SCENES[3];
+    check(name, scene, spp, &[]);
+}
+#[test]
+fn denoised(){
+    //Few samples so the result depends on the filter rather than on convergence.
+    check("denoised", "default", 4, &["--denoise"]);
+}
+///Every sampler converges to the same image, the golden images are rendered with the sobol sampler.
+#[test]
+fn samplers(){
+    let (name,scene,spp) = SCENES[1];
+    for sampler in ["random","blue-noise","stratified"].iter(){
+        let actual_path = render(&format!("{}.{}",name,sampler), scene, spp, &["--sampler",sampler,"--seed","5"]);
+        compare(&format!("{}.{}",name,sampler), &actual_path, &golden_dir().join(format!("{}.exr",name)));
+    }
+}
+///The same seed renders the same image, another seed renders other noise.
+#[test]
+fn seeded(){
+    let read = |name : &str , seed : &str| Channels::read(&render(name, "materials.toml", 4, &["--sampler","stratified","--seed",seed])).channels;
+    let first = read("seeded.first", "3");
+    assert_eq!(first, read("seeded.second", "3"));
+    assert_ne!(first, read("seeded.other", "4"));
+}
+#[test]
+fn metrics(){
+    let a = (0..64).map(|i| (i % 8) as f32 / 8.0).collect::<Vec<_>>();
+    assert_eq!(rmse(&a, &a), 0.0);
+    assert!((ssim(8, 8, &a, &a) - 1.0).abs() < 1e-5);
+    let darker = a.iter().map(|c| c * 0.5).collect::<Vec<_>>();
+    assert!(rmse(&a, &darker) > 0.05);
+    assert!(ssim(8, 8, &a, &darker) < 0.95);
+}
+///A tolerance that lets a 10% change in brightness pass would not catch much.
+#[test]
+fn tolerances_catch_brightness_shift(){
+    let golden = Channels::read(&golden_dir().join("default.exr")).downsample(DOWNSAMPLE);
+    let red = golden.get("R");
+    let brighter = red.iter().map(|c| c * 1.1).collect::<Vec<_>>();
+    assert!(rmse(red, &brighter) > MAX_RMSE);
+}
+///Prints how far renders of the scenes stray from a render with four times the samples, the tolerances have to stay above this.
+#[test]
+#[ignore]
+fn noise_levels(){
+    for &(name,scene,spp) in SCENES.iter(){
+        let noisy = Channels::read(&render(&format!("{}.noisy",name), scene, spp, &[])).downsample(DOWNSAMPLE);
+        let converged = Channels::read(&render(&format!("{}.converged",name), scene, spp * 4, &[])).downsample(DOWNSAMPLE);
+        let error = noisy.channels.iter().zip(converged.channels.iter()).map(|((_,a),(_,b))| rmse(a, b)).fold(0.0f32, f32::max);
+        println!("{} : rmse {:.4}, ssim {:.4}",name,error,ssim(noisy.width, noisy.height, &noisy.luminance(), &converged.luminance()));
+    }
+}